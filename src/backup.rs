@@ -0,0 +1,1812 @@
+//! Backup and restore, independent of the UI.
+//!
+//! Everything here takes an opened `Repo`, a `Target` and a passphrase, and blocks; the UI calls
+//! the `*_async` variants. Nothing here touches `Ui` or iced, so it can be driven from scripts or
+//! tests as well.
+use crate::*;
+use rdedup_lib::DecryptHandle;
+use sha2::{Digest, Sha256};
+use std::ffi::OsString;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::process::{Command as Process, Stdio};
+use std::sync::{
+    atomic::{AtomicBool, AtomicU64, Ordering},
+    Arc, Condvar, Mutex,
+};
+
+/// Implementation of the installed `tar`. They name and support `--format`s differently.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TarImpl {
+    Gnu,
+    /// libarchive's bsdtar, the default `tar` on macOS and the BSDs
+    Bsd,
+    /// E.g. busybox, which has no `--format`
+    Unknown,
+}
+
+lazy_static::lazy_static! {
+    /// Implementation of the installed `tar`, detected once from `tar --version`
+    pub static ref TAR: TarImpl = detect_tar();
+}
+
+fn detect_tar() -> TarImpl {
+    let version = match Process::new("tar").arg("--version").output() {
+        Ok(output) => String::from_utf8_lossy(&output.stdout).into_owned(),
+        Err(_) => return TarImpl::Unknown,
+    };
+    if version.contains("GNU tar") {
+        TarImpl::Gnu
+    } else if version.contains("bsdtar") {
+        TarImpl::Bsd
+    } else {
+        TarImpl::Unknown
+    }
+}
+
+/// Arguments for the `tar` process that archives one source of `target` to stdout.
+///
+/// Each source gets its own `tar` process, so that options like `--one-file-system` can be set per
+/// source. The archives are concatenated into one snapshot, which therefore has to be extracted
+/// with `--ignore-zeros`.
+pub fn tar_args(target: &Target, source: &Source) -> Option<Vec<OsString>> {
+    let path = source.path.as_ref()?;
+    let mut args: Vec<OsString> = vec!["--create".into(), "--file=-".into()];
+    // Checked by `verify_target`; without support, tar writes its default format
+    if let Some(format) = target.tar_format.tar_name(*TAR) {
+        args.push(format!("--format={}", format).into());
+    }
+    if source.one_file_system {
+        args.push("--one-file-system".into());
+    }
+    // bsdtar detects holes by itself
+    if target.handle_sparse && *TAR == TarImpl::Gnu {
+        args.push("--sparse".into());
+    }
+    // Each `tar` compresses its own archive. Concatenated gzip and zstd streams decompress as one.
+    if let Some(compression) = target.pre_compress {
+        args.push(compression.tar_option().into());
+    }
+    for exclude in &target.excludes {
+        args.push(format!("--exclude={}", exclude).into());
+    }
+    for preset in &target.exclude_presets {
+        args.extend(preset.options(*TAR).iter().map(OsString::from));
+        for pattern in preset.patterns() {
+            args.push(format!("--exclude={}", pattern).into());
+        }
+    }
+    if target.exclude_hidden {
+        // Scoped to the source, so that a source which is itself hidden is still included.
+        // (`*` matches `/` in tar exclude patterns)
+        let escaped = escape_glob(&path.display().to_string());
+        let escaped = escaped.trim_end_matches('/');
+        args.push(format!("--exclude={}/.*", escaped).into());
+        args.push(format!("--exclude={}/*/.*", escaped).into());
+    }
+    args.push("--".into());
+    args.push(path.into());
+    Some(args)
+}
+
+/// `target` with `repo_excludes` added to its excludes, leaving out duplicates. This is what is
+/// backed up.
+pub fn with_repo_excludes(target: &Target, repo_excludes: &[String]) -> Target {
+    let mut excludes = repo_excludes.to_vec();
+    for exclude in &target.excludes {
+        if !excludes.contains(exclude) {
+            excludes.push(exclude.clone());
+        }
+    }
+    Target {
+        excludes,
+        ..target.clone()
+    }
+}
+
+/// The `tar` commands that a backup of `target` runs, one per source, quoted for a POSIX shell.
+/// Their outputs are concatenated into the snapshot.
+pub fn tar_commands(target: &Target) -> Vec<String> {
+    target
+        .sources
+        .iter()
+        .filter_map(|source| tar_args(target, source))
+        .map(|args| {
+            std::iter::once("tar".to_string())
+                .chain(args.iter().map(|arg| shell_quote(&arg.to_string_lossy())))
+                .collect::<Vec<_>>()
+                .join(" ")
+        })
+        .collect()
+}
+
+/// Quote `arg` for a POSIX shell, if needed
+fn shell_quote(arg: &str) -> String {
+    let safe = |c: char| c.is_ascii_alphanumeric() || "-_=/.,:@+%".contains(c);
+    if !arg.is_empty() && arg.chars().all(safe) {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', "'\\''"))
+    }
+}
+
+/// The output of several `tar` processes, run one after another, as one stream.
+///
+/// Memory use is bounded regardless of the size of the archived files: `read` copies straight from
+/// the pipe into the caller's buffer, without buffering of its own. When `Repo::write` falls
+/// behind, the pipe fills up and `tar` blocks on writing to it, which is the back-pressure that
+/// keeps a huge member from being read into memory. Don't wrap the pipe in anything that reads
+/// ahead without a bound.
+struct TarStream {
+    /// Sources (for messages) and arguments of the processes that are yet to be spawned
+    pending: std::collections::VecDeque<(String, Vec<OsString>)>,
+    current: Option<(String, std::process::Child)>,
+    /// Problems that don't fail the backup. Shared, since `Repo::write` takes the stream.
+    warnings: Arc<Mutex<Vec<String>>>,
+    priority: Priority,
+    /// Hashes the archive, when checksums are enabled. Shared for the same reason as `warnings`.
+    hasher: Option<Arc<Mutex<Sha256>>>,
+    /// Fails the stream once set (see `BackupOptions::cancel`)
+    cancel: Arc<AtomicBool>,
+    /// Size of the archive so far. Shared for the same reason as `warnings`.
+    bytes: Arc<AtomicU64>,
+}
+impl TarStream {
+    fn new(
+        target: &Target,
+        priority: Priority,
+        warnings: Arc<Mutex<Vec<String>>>,
+        hasher: Option<Arc<Mutex<Sha256>>>,
+        cancel: Arc<AtomicBool>,
+        bytes: Arc<AtomicU64>,
+    ) -> Self {
+        Self {
+            priority,
+            hasher,
+            cancel,
+            bytes,
+            pending: target
+                .sources
+                .iter()
+                .filter_map(|source| {
+                    let path = source.path.as_ref()?.display().to_string();
+                    Some((path, tar_args(target, source)?))
+                })
+                .collect(),
+            current: None,
+            warnings,
+        }
+    }
+    /// Wait for the current process, failing unless it exited successfully.
+    fn finish_current(&mut self) -> std::io::Result<()> {
+        if let Some((source, mut child)) = self.current.take() {
+            let status = child.wait()?;
+            match status.code() {
+                Some(0) => (),
+                // Exit code 1 means that some files changed while being archived
+                Some(1) => self.warnings.lock().unwrap().push(format!(
+                    "{}: some files changed while being archived",
+                    source
+                )),
+                _ => {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::Other,
+                        format!("tar failed on {}: {}", source, status),
+                    ))
+                }
+            }
+        }
+        Ok(())
+    }
+}
+impl Read for TarStream {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        loop {
+            if self.cancel.load(Ordering::Relaxed) {
+                return Err(std::io::Error::new(std::io::ErrorKind::Other, "Cancelled"));
+            }
+            if let Some((_, ref mut child)) = self.current {
+                let n = child.stdout.as_mut().unwrap().read(buf)?;
+                if n > 0 {
+                    if let Some(ref hasher) = self.hasher {
+                        hasher.lock().unwrap().update(&buf[..n]);
+                    }
+                    self.bytes.fetch_add(n as u64, Ordering::Relaxed);
+                    return Ok(n);
+                }
+                self.finish_current()?;
+            }
+            match self.pending.pop_front() {
+                Some((source, args)) => {
+                    let mut tar = Process::new("tar");
+                    tar.args(args).stdout(Stdio::piped());
+                    if self.priority == Priority::Background {
+                        lower_child_priority(&mut tar);
+                    }
+                    self.current = Some((source, tar.spawn()?));
+                }
+                None => return Ok(0),
+            }
+        }
+    }
+}
+impl Drop for TarStream {
+    fn drop(&mut self) {
+        if let Some((_, ref mut child)) = self.current {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+/// How `run_backup` runs
+#[derive(Clone, Debug)]
+pub struct BackupOptions {
+    pub priority: Priority,
+    /// Compute a SHA-256 of the archive (see `BackupDone::sha256`)
+    pub checksum: bool,
+    /// Refuse to back up when the disk of the repo home (`.0`) has less free space than `.1` bytes
+    pub min_free_space: Option<(PathBuf, u64)>,
+    /// `RepoConfig::repo_excludes` of the repo
+    pub repo_excludes: Vec<String>,
+    /// Set to stop the backup. The incomplete snapshot is removed again.
+    pub cancel: Arc<AtomicBool>,
+    /// Name of the snapshot to write, if chosen beforehand (see `Target::interrupted_backup`).
+    /// Otherwise it is named after the target and the current time.
+    pub snapshot_name: Option<String>,
+    /// `RepoConfig::read_only` of the repo; refuses to back up
+    pub read_only: bool,
+    /// Read the new snapshot back after writing it (see `BackupDone::verified`)
+    pub verify: bool,
+}
+
+/// Priority of the work of a backup, relative to the rest of the machine
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Priority {
+    Normal,
+    /// Let other programs go first, so that the machine stays responsive during a backup. Applies
+    /// to `tar`, and on Linux also to the threads reading and writing the repo.
+    Background,
+}
+
+/// Nice value of background work on Unix
+#[cfg(unix)]
+const BACKGROUND_NICE: libc::c_int = 10;
+
+#[cfg(unix)]
+fn lower_child_priority(command: &mut Process) {
+    use std::os::unix::process::CommandExt;
+    // Safety: `nice` is async-signal-safe. Failure is harmless; tar then runs at normal priority.
+    unsafe {
+        command.pre_exec(|| {
+            libc::nice(BACKGROUND_NICE);
+            Ok(())
+        });
+    }
+}
+#[cfg(windows)]
+fn lower_child_priority(command: &mut Process) {
+    use std::os::windows::process::CommandExt;
+    const BELOW_NORMAL_PRIORITY_CLASS: u32 = 0x0000_4000;
+    command.creation_flags(BELOW_NORMAL_PRIORITY_CLASS);
+}
+#[cfg(not(any(unix, windows)))]
+fn lower_child_priority(_command: &mut Process) {}
+
+/// Run `f` on a new thread with background priority. On Linux, the nice value is per thread and
+/// inherited by threads spawned from it, so this covers rdedup's worker threads. A priority can't
+/// be raised again without privileges, hence the dedicated thread rather than a pooled one.
+/// Elsewhere, `f` runs at normal priority (only `tar` is lowered).
+fn in_background<T: Send>(f: impl FnOnce() -> T + Send) -> T {
+    std::thread::scope(|scope| {
+        scope
+            .spawn(|| {
+                #[cfg(target_os = "linux")]
+                // Safety: no preconditions. Failure just keeps the normal priority.
+                unsafe {
+                    libc::nice(BACKGROUND_NICE);
+                }
+                f()
+            })
+            .join()
+            .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+    })
+}
+
+fn escape_glob(path: &str) -> String {
+    let mut escaped = String::with_capacity(path.len());
+    for c in path.chars() {
+        if matches!(c, '*' | '?' | '[' | ']' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Run a hook command of `target` with `sh -c`.
+///
+/// The command is written by the user, so it is run through the shell on purpose (to allow pipes
+/// etc). Nothing is interpolated into it; information is passed in environment variables instead:
+/// `BUP_TARGET` (name of the target) and `BUP_RESULT` (`ok` or `failed`, only for the post hook).
+fn run_hook(hook: &str, target: &Target, result: Option<&str>, log: &Logger) -> anyhow::Result<()> {
+    info!(log, "Running hook {:?}", hook);
+    let mut command = Process::new("sh");
+    command.arg("-c").arg(hook).env("BUP_TARGET", &target.name);
+    if let Some(result) = result {
+        command.env("BUP_RESULT", result);
+    }
+    let output = command
+        .output()
+        .with_context(|| format!("Running hook {:?}", hook))?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if !stdout.trim().is_empty() {
+        info!(log, "Hook stdout: {}", stdout.trim_end());
+    }
+    if !stderr.trim().is_empty() {
+        info!(log, "Hook stderr: {}", stderr.trim_end());
+    }
+    if !output.status.success() {
+        anyhow::bail!("Hook {:?} failed: {}", hook, output.status);
+    }
+    Ok(())
+}
+
+/// What a backup would archive, when it exceeds `Target::max_files` or `Target::max_total_bytes`,
+/// or when previewed. Counting stops at the first exceeded limit, so these are lower bounds then.
+#[derive(Debug, Clone, Copy)]
+pub struct OverLimit {
+    pub files: u64,
+    pub bytes: u64,
+}
+impl OverLimit {
+    /// Whether this exceeds a limit of `target`, rather than just previewing its backup
+    pub fn exceeds(&self, target: &Target) -> bool {
+        target.max_files.map_or(false, |max| self.files > max)
+            || target.max_total_bytes.map_or(false, |max| self.bytes > max)
+    }
+}
+
+/// Visit the metadata of each file and folder that a backup of `target` would include, until
+/// `visit` returns false.
+///
+/// Excludes are applied like tar does, except for the `--exclude-caches` of presets, so slightly
+/// more may be visited than tar archives.
+fn walk_target(target: &Target, mut visit: impl FnMut(&std::fs::Metadata) -> bool) {
+    let patterns: Vec<&str> = target
+        .excludes
+        .iter()
+        .map(|exclude| exclude.as_str())
+        .chain(
+            target
+                .exclude_presets
+                .iter()
+                .flat_map(|preset| preset.patterns().iter().copied()),
+        )
+        .collect();
+    let mut stack: Vec<(PathBuf, bool)> = target
+        .sources
+        .iter()
+        .filter_map(|source| Some((source.path.clone()?, true)))
+        .collect();
+    while let Some((path, is_source)) = stack.pop() {
+        if patterns
+            .iter()
+            .any(|pattern| exclude::matches(pattern, &path.to_string_lossy()))
+        {
+            continue;
+        }
+        let hidden = path
+            .file_name()
+            .map_or(false, |name| name.to_string_lossy().starts_with('.'));
+        if target.exclude_hidden && hidden && !is_source {
+            continue;
+        }
+        let metadata = match std::fs::symlink_metadata(&path) {
+            Ok(metadata) => metadata,
+            // Unreadable; tar skips it too
+            Err(_) => continue,
+        };
+        if !visit(&metadata) {
+            return;
+        }
+        if metadata.is_dir() {
+            if let Ok(entries) = std::fs::read_dir(&path) {
+                stack.extend(entries.flatten().map(|entry| (entry.path(), false)));
+            }
+        }
+    }
+}
+
+/// Whether the file takes much less space on disk than its size, i.e. is mostly holes. Only
+/// large files count, since small files often take less space due to compression by the file
+/// system.
+#[cfg(unix)]
+fn is_sparse(metadata: &std::fs::Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    metadata.len() >= 1 << 26 && metadata.blocks() * 512 < metadata.len() / 2
+}
+#[cfg(not(unix))]
+fn is_sparse(_metadata: &std::fs::Metadata) -> bool {
+    false
+}
+
+/// Outcome of `check_limits`
+#[derive(Debug, Clone, Copy)]
+pub struct DryRun {
+    /// Set when the backup should be confirmed first: it exceeds a limit, or is previewed
+    pub over_limit: Option<OverLimit>,
+    /// Large sparse files found, when the target doesn't handle sparse files (see
+    /// `Target::handle_sparse`). A lower bound if counting stopped at an exceeded limit.
+    pub sparse_files: u64,
+}
+
+/// Dry run of a backup of `target`: count the files and bytes under its sources, and check them
+/// against its limits. No `DryRun::over_limit` if within the limits, or if it has none, unless
+/// `preview`, which counts everything.
+pub fn check_limits(target: &Target, preview: bool) -> DryRun {
+    let mut dry_run = DryRun {
+        over_limit: None,
+        sparse_files: 0,
+    };
+    if !preview && target.max_files.is_none() && target.max_total_bytes.is_none() {
+        return dry_run;
+    }
+    let mut count = OverLimit { files: 0, bytes: 0 };
+    walk_target(target, |metadata| {
+        count.files += 1;
+        if !metadata.is_dir() {
+            count.bytes += metadata.len();
+            if !target.handle_sparse && is_sparse(metadata) {
+                dry_run.sparse_files += 1;
+            }
+        }
+        if count.exceeds(target) {
+            dry_run.over_limit = Some(count);
+        }
+        dry_run.over_limit.is_none()
+    });
+    if preview && dry_run.over_limit.is_none() {
+        dry_run.over_limit = Some(count);
+    }
+    dry_run
+}
+
+/// `check_limits` on a blocking thread
+pub async fn check_limits_async(target: Target, preview: bool) -> Result<DryRun, String> {
+    tokio::task::spawn_blocking(move || check_limits(&target, preview))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Files under the sources of a target modified since its last backup (see `changed_since`)
+#[derive(Debug, Clone, Copy)]
+pub struct Changes {
+    pub files: u64,
+    pub bytes: u64,
+}
+
+/// Quick estimate of what the next backup of `target` adds: the files modified after `since`, by
+/// their modification time. Deleted files and unchanged files that moved aren't noticed.
+pub fn changed_since(target: &Target, since: DateTime<Utc>) -> Changes {
+    let since = std::time::SystemTime::from(since);
+    let mut changes = Changes { files: 0, bytes: 0 };
+    walk_target(target, |metadata| {
+        let modified = metadata
+            .modified()
+            .map_or(true, |modified| modified > since);
+        if !metadata.is_dir() && modified {
+            changes.files += 1;
+            changes.bytes += metadata.len();
+        }
+        true
+    });
+    changes
+}
+
+/// `changed_since` on a blocking thread
+pub async fn changed_since_async(target: Target, since: DateTime<Utc>) -> Result<Changes, String> {
+    tokio::task::spawn_blocking(move || changed_since(&target, since))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// A backup that succeeded, possibly with warnings
+#[derive(Debug, Clone)]
+pub struct BackupDone {
+    pub snapshot: PreviousSnapshot,
+    pub warnings: Vec<String>,
+    /// SHA-256 of the archive as written, if requested (hex)
+    pub sha256: Option<String>,
+    /// With `BackupOptions::verify`: the number of files listed by reading the snapshot back, or
+    /// why that failed. The snapshot stays in the repo either way.
+    pub verified: Option<Result<usize, String>>,
+}
+
+/// Archive the sources of `target` with `tar` and write the archive to `repo` as a new snapshot,
+/// surrounded by the hooks of the target.
+pub fn run_backup(
+    repo: &Repo,
+    target: &Target,
+    passphrase: String,
+    options: BackupOptions,
+    log: &Logger,
+) -> anyhow::Result<BackupDone> {
+    if options.read_only {
+        anyhow::bail!("The repo is read-only");
+    }
+    if let Some((ref home, min)) = options.min_free_space {
+        let space = rdedup::space(home)?;
+        if space.available < min {
+            anyhow::bail!(
+                "Only {} free on the disk of the repo; backups are refused below {}",
+                format_bytes(space.available),
+                format_bytes(min)
+            );
+        }
+    }
+    let target = &with_repo_excludes(target, &options.repo_excludes);
+    if let Some(ref hook) = target.pre_hook {
+        run_hook(hook, target, None, log).context("Pre-backup hook")?;
+    }
+    let verify = options.verify;
+    let cancel = options.cancel.clone();
+    let result = match options.priority {
+        Priority::Normal => write_snapshot(repo, target, passphrase.clone(), options, log),
+        Priority::Background => {
+            in_background(|| write_snapshot(repo, target, passphrase.clone(), options, log))
+        }
+    };
+    // Like a test restore: every chunk is decrypted and the archive is read through by `tar`
+    let result = result.map(|mut done| {
+        if verify {
+            let progress = Default::default();
+            let verified =
+                test_restore(repo, &done.snapshot.name, passphrase, progress, cancel, log)
+                    .map_err(|e| format!("{:#}", e));
+            if let Err(ref e) = verified {
+                error!(log, "Reading back {} failed: {}", done.snapshot.name, e);
+            }
+            done.verified = Some(verified);
+        }
+        done
+    });
+    if let Some(ref hook) = target.post_hook {
+        let status = if result.is_ok() { "ok" } else { "failed" };
+        if let Err(e) = run_hook(hook, target, Some(status), log) {
+            error!(log, "Post-backup hook: {:#}", e);
+        }
+    }
+    result
+}
+
+fn write_snapshot(
+    repo: &Repo,
+    target: &Target,
+    passphrase: String,
+    options: BackupOptions,
+    log: &Logger,
+) -> anyhow::Result<BackupDone> {
+    let timestamp = Utc::now();
+    let name = options
+        .snapshot_name
+        .clone()
+        .unwrap_or_else(|| rdedup::snapshot_name(&target.name, timestamp));
+    info!(log, "Backup {:?} to {:?}", target.name, name);
+    for command in tar_commands(target) {
+        debug!(log, "Archiving with: {}", command);
+    }
+
+    let encrypt = repo
+        .unlock_encrypt(&move || Ok(passphrase.clone()))
+        .context("Unlocking repo")?;
+
+    // Streams the archive; see `TarStream` for why this doesn't buffer whole files
+    let warnings = Arc::new(Mutex::new(Vec::new()));
+    let hasher = options
+        .checksum
+        .then(|| Arc::new(Mutex::new(Sha256::new())));
+    let bytes = Arc::new(AtomicU64::new(0));
+    if let Err(e) = repo.write(
+        &name,
+        TarStream::new(
+            target,
+            options.priority,
+            warnings.clone(),
+            hasher.clone(),
+            options.cancel.clone(),
+            bytes.clone(),
+        ),
+        &encrypt,
+    ) {
+        if let Err(e) = repo.rm(&name) {
+            error!(
+                log,
+                "Could not remove incomplete snapshot {:?}: {}", name, e
+            );
+        }
+        return Err(e).context("Writing snapshot");
+    }
+    let warnings = std::mem::take(&mut *warnings.lock().unwrap());
+    let sha256 = hasher.map(|hasher| {
+        let hasher = std::mem::replace(&mut *hasher.lock().unwrap(), Sha256::new());
+        format!("{:x}", hasher.finalize())
+    });
+    // As it will be listed, with the second precision of the name
+    let timestamp = rdedup::parse_snapshot_name(&name)
+        .map(|(_, time)| time)
+        .unwrap_or(timestamp);
+    Ok(BackupDone {
+        snapshot: PreviousSnapshot {
+            name,
+            timestamp,
+            label: None,
+            bytes: Some(bytes.load(Ordering::Relaxed) as usize),
+        },
+        warnings,
+        sha256,
+        verified: None,
+    })
+}
+
+/// `run_backup` on a blocking thread
+pub async fn run_backup_async(
+    repo: Repo,
+    target: Target,
+    passphrase: String,
+    options: BackupOptions,
+    log: Logger,
+) -> Result<BackupDone, BupError> {
+    tokio::task::spawn_blocking(move || run_backup(&repo, &target, passphrase, options, &log))
+        .await?
+        .map_err(BupError::backup)
+}
+
+/// What an interrupted backup left behind (see `discard_interrupted`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interrupted {
+    /// The snapshot was never written
+    NotWritten,
+    /// The snapshot was written, but did not verify, and was removed
+    Removed,
+    /// The snapshot was written completely, just before the interruption
+    Complete,
+}
+
+/// Clean up after a backup to snapshot `name` that was interrupted, e.g. because the app
+/// crashed. rdedup writes the name of a snapshot last, so a listed snapshot is normally complete;
+/// it is verified anyway, and removed if it is not, so that it isn't mistaken for a backup.
+pub fn discard_interrupted(
+    repo: &Repo,
+    name: &str,
+    passphrase: String,
+    log: &Logger,
+) -> anyhow::Result<Interrupted> {
+    let names = repo.list_names().context("Listing snapshots")?;
+    if !names.iter().any(|n| n == name) {
+        return Ok(Interrupted::NotWritten);
+    }
+    match verify_snapshot(repo, name, None, passphrase) {
+        Ok(()) => Ok(Interrupted::Complete),
+        Err(e) => {
+            info!(
+                log,
+                "Removing incomplete snapshot {:?} of an interrupted backup: {:#}", name, e
+            );
+            repo.rm(name)
+                .with_context(|| format!("Removing incomplete snapshot {:?}", name))?;
+            Ok(Interrupted::Removed)
+        }
+    }
+}
+
+/// `discard_interrupted` on a blocking thread
+pub async fn discard_interrupted_async(
+    repo: Repo,
+    name: String,
+    passphrase: String,
+    log: Logger,
+) -> Result<Interrupted, String> {
+    tokio::task::spawn_blocking(move || discard_interrupted(&repo, &name, passphrase, &log))
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| format!("{:#}", e))
+}
+
+/// Progress of a running restore or tar export, shared between its thread and the UI
+#[derive(Debug, Clone, Default)]
+pub struct RestoreProgress {
+    /// Number of members in the snapshot, if it could be counted
+    pub total_files: Option<usize>,
+    /// Number of members extracted so far
+    pub files: usize,
+    /// Bytes of the archive extracted so far
+    pub bytes: u64,
+    pub current_file: String,
+}
+
+/// Passes the archive on to `tar`, counting bytes, and fails once `cancel` is set.
+struct ProgressWriter<W> {
+    inner: W,
+    progress: Arc<Mutex<RestoreProgress>>,
+    cancel: Arc<AtomicBool>,
+}
+impl<W: Write> Write for ProgressWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if self.cancel.load(Ordering::Relaxed) {
+            // Not `Interrupted`, which `write_all` retries
+            return Err(std::io::Error::new(std::io::ErrorKind::Other, "Cancelled"));
+        }
+        let n = self.inner.write(buf)?;
+        self.progress.lock().unwrap().bytes += n as u64;
+        Ok(n)
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Hashes what is written to it
+struct HashWriter(Sha256);
+impl Write for HashWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.update(buf);
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// How many restores and verifications of a repo may read from it at once (see
+/// `RepoConfig::read_threads`)
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ReadLimit {
+    pub repo: Uuid,
+    pub max: usize,
+}
+
+lazy_static::lazy_static! {
+    /// Reads running per repo, and notified whenever one finishes (see `read_slot`)
+    static ref READS: (Mutex<HashMap<Uuid, usize>>, Condvar) =
+        (Mutex::new(HashMap::new()), Condvar::new());
+}
+
+/// One of the reads allowed by a `ReadLimit`, freed when dropped
+pub struct ReadSlot(Uuid);
+impl Drop for ReadSlot {
+    fn drop(&mut self) {
+        let (reads, finished) = &*READS;
+        if let Some(running) = reads.lock().unwrap().get_mut(&self.0) {
+            *running -= 1;
+        }
+        finished.notify_all();
+    }
+}
+
+/// Wait until fewer than `limit.max` reads of the repo are running, so that restores and
+/// verifications don't thrash a slow disk. Gives up once `cancel` is set meanwhile.
+///
+/// Each restore or verification takes a single slot, and doesn't wait for anything else while
+/// holding it, so waiting can't deadlock.
+pub fn read_slot(limit: ReadLimit, cancel: &AtomicBool) -> anyhow::Result<ReadSlot> {
+    let (reads, finished) = &*READS;
+    let mut running = reads.lock().unwrap();
+    loop {
+        if cancel.load(Ordering::Relaxed) {
+            anyhow::bail!("Cancelled");
+        }
+        let count = running.entry(limit.repo).or_default();
+        if *count < limit.max.max(1) {
+            *count += 1;
+            return Ok(ReadSlot(limit.repo));
+        }
+        // Also wakes up now and then to notice `cancel`
+        running = finished
+            .wait_timeout(running, Duration::from_millis(200))
+            .unwrap()
+            .0;
+    }
+}
+
+/// Read snapshot `name` in full and compare its SHA-256 with `expected` (see
+/// `BackupOptions::checksum`). A mismatch means that the snapshot no longer is what was backed up.
+fn verify_checksum(
+    repo: &Repo,
+    name: &str,
+    expected: &str,
+    decrypt: &DecryptHandle,
+) -> anyhow::Result<()> {
+    let mut writer = HashWriter(Sha256::new());
+    repo.read(name, &mut writer, decrypt)
+        .context("Reading snapshot")?;
+    let actual = format!("{:x}", writer.0.finalize());
+    if actual != expected {
+        anyhow::bail!(
+            "Checksum mismatch: the snapshot may be corrupted (expected {}, got {})",
+            expected,
+            actual
+        );
+    }
+    Ok(())
+}
+
+/// Verify snapshot `name` only, not the whole repo: `Repo::verify` reads just the chunks the
+/// snapshot refers to and checks their digests. Then, if a checksum was stored for it, compare
+/// that too.
+pub fn verify_snapshot(
+    repo: &Repo,
+    name: &str,
+    expected: Option<&str>,
+    passphrase: String,
+) -> anyhow::Result<()> {
+    let decrypt = repo
+        .unlock_decrypt(&move || Ok(passphrase.clone()))
+        .context("Unlocking repo")?;
+    let results = repo.verify(name, &decrypt).context("Verifying chunks")?;
+    if let Some((_, error)) = results.errors.first() {
+        anyhow::bail!(
+            "{} of the {} chunks of the snapshot are corrupted, e.g.: {}",
+            results.errors.len(),
+            results.scanned,
+            error
+        );
+    }
+    if let Some(expected) = expected {
+        verify_checksum(repo, name, expected, &decrypt)?;
+    }
+    Ok(())
+}
+
+/// `verify_snapshot` on a blocking thread, once `reads` allows
+pub async fn verify_snapshot_async(
+    repo: Repo,
+    name: String,
+    expected: Option<String>,
+    passphrase: String,
+    reads: ReadLimit,
+) -> Result<(), String> {
+    tokio::task::spawn_blocking(move || {
+        let _slot = read_slot(reads, &AtomicBool::new(false))?;
+        verify_snapshot(&repo, &name, expected.as_deref(), passphrase)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+    .map_err(|e| format!("{:#}", e))
+}
+
+/// Keeps the first bytes written to it, then fails, to stop `Repo::read` early
+struct Head(Vec<u8>);
+const HEAD_LEN: usize = 4;
+impl Write for Head {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if self.0.len() >= HEAD_LEN {
+            return Err(std::io::Error::new(std::io::ErrorKind::Other, "Got head"));
+        }
+        let n = buf.len().min(HEAD_LEN - self.0.len());
+        self.0.extend_from_slice(&buf[..n]);
+        Ok(n)
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Compression of snapshot `name` (see `Target::pre_compress`), from its first bytes
+fn snapshot_compression(
+    repo: &Repo,
+    name: &str,
+    decrypt: &DecryptHandle,
+) -> anyhow::Result<Option<Compression>> {
+    let mut head = Head(Vec::new());
+    // Fails on purpose once the head is read; a real failure leaves the head short
+    let read = repo.read(name, &mut head, decrypt);
+    if head.0.len() < HEAD_LEN {
+        read.context(ReadFailed)?;
+    }
+    Ok(Compression::from_magic(&head.0))
+}
+
+/// Members of snapshot `name`, as listed by `tar --list`. Folders end with `/`.
+pub fn list_members(repo: &Repo, name: &str, passphrase: String) -> anyhow::Result<Vec<String>> {
+    let decrypt = repo
+        .unlock_decrypt(&move || Ok(passphrase.clone()))
+        .context("Unlocking repo")?;
+    let compression = snapshot_compression(repo, name, &decrypt)?;
+    let mut tar = Process::new("tar")
+        .args(&["--list", "--ignore-zeros", "--file=-"])
+        .args(compression.map(|c| c.tar_option()))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .context("Spawning tar")?;
+    let stdout = tar.stdout.take().context("tar stdout")?;
+    let lister =
+        std::thread::spawn(move || BufReader::new(stdout).lines().flatten().collect::<Vec<_>>());
+
+    let mut stdin = tar.stdin.take().context("tar stdin")?;
+    let read = repo.read(name, &mut stdin, &decrypt);
+    drop(stdin);
+    let members = lister
+        .join()
+        .map_err(|_| anyhow::anyhow!("Listing files panicked"))?;
+    tar.wait().context("Waiting for tar")?;
+    read.context("Reading snapshot")?;
+    Ok(members)
+}
+
+/// `list_members` on a blocking thread
+pub async fn list_members_async(
+    repo: Repo,
+    name: String,
+    passphrase: String,
+) -> Result<Vec<String>, String> {
+    tokio::task::spawn_blocking(move || list_members(&repo, &name, passphrase))
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| format!("{:#}", e))
+}
+
+/// What to do with chosen files that already exist in the destination of a restore
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Collision {
+    Overwrite,
+    /// Keep the existing files
+    Skip,
+    /// Restore into a new folder named after the snapshot, like a full restore
+    NewFolder,
+}
+
+/// What `restore` extracts
+#[derive(Debug, Clone)]
+pub enum RestoreSelection {
+    /// The whole snapshot, into a new folder named after it
+    All,
+    /// Only these members (folders with everything below them), into the destination itself
+    /// unless `collision` says otherwise
+    Members {
+        members: Vec<String>,
+        collision: Collision,
+    },
+}
+
+/// Count the members of a snapshot with `tar --list`. Reads through the whole snapshot.
+fn count_files(
+    repo: &Repo,
+    name: &str,
+    compression: Option<Compression>,
+    decrypt: &DecryptHandle,
+) -> anyhow::Result<usize> {
+    let mut tar = Process::new("tar")
+        .args(&["--list", "--ignore-zeros", "--file=-"])
+        .args(compression.map(|c| c.tar_option()))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .context("Spawning tar")?;
+    let stdout = tar.stdout.take().context("tar stdout")?;
+    let counter = std::thread::spawn(move || BufReader::new(stdout).lines().count());
+
+    let mut stdin = tar.stdin.take().context("tar stdin")?;
+    let read = repo.read(name, &mut stdin, decrypt);
+    drop(stdin);
+    let count = counter
+        .join()
+        .map_err(|_| anyhow::anyhow!("Counting files panicked"))?;
+    tar.wait().context("Waiting for tar")?;
+    read.context("Reading snapshot")?;
+    Ok(count)
+}
+
+/// Extract snapshot `name` into the new directory `<dest>/<name>`, reporting progress. A selection
+/// of members is extracted into `dest` itself, unless its collision is `Collision::NewFolder`.
+///
+/// If extracting into a new directory fails or is cancelled via `cancel`, the directory is removed
+/// again. Returns the directory that was restored to.
+#[allow(clippy::too_many_arguments)]
+pub fn restore(
+    repo: &Repo,
+    name: &str,
+    dest: &Path,
+    selection: RestoreSelection,
+    passphrase: String,
+    progress: Arc<Mutex<RestoreProgress>>,
+    cancel: Arc<AtomicBool>,
+    log: &Logger,
+) -> anyhow::Result<PathBuf> {
+    info!(log, "Restore {:?} to {:?}", name, dest);
+    let decrypt = repo
+        .unlock_decrypt(&move || Ok(passphrase.clone()))
+        .context("Unlocking repo")?;
+
+    let compression = snapshot_compression(repo, name, &decrypt)?;
+    let (members, collision) = match selection {
+        RestoreSelection::All => {
+            let total_files = match count_files(repo, name, compression, &decrypt) {
+                Ok(count) => Some(count),
+                Err(e) => {
+                    error!(log, "Could not count files of {:?}: {:#}", name, e);
+                    None
+                }
+            };
+            progress.lock().unwrap().total_files = total_files;
+            (Vec::new(), Collision::NewFolder)
+        }
+        // The caller knows the total from the listed members
+        RestoreSelection::Members { members, collision } => (members, collision),
+    };
+    let mut options = Vec::new();
+    match (collision, *TAR) {
+        (Collision::NewFolder, _) => (),
+        (Collision::Overwrite, TarImpl::Bsd) => (), // the default of bsdtar
+        (Collision::Overwrite, _) => options.push("--overwrite"),
+        (Collision::Skip, TarImpl::Bsd) => options.push("--keep-old-files"),
+        (Collision::Skip, _) => options.push("--skip-old-files"),
+    }
+    if collision != Collision::NewFolder {
+        return extract(
+            repo,
+            name,
+            dest,
+            &options,
+            &members,
+            compression,
+            &decrypt,
+            progress,
+            cancel,
+        )
+        .map(|()| dest.to_path_buf());
+    }
+
+    let dir = dest.join(name);
+    std::fs::create_dir(&dir).with_context(|| format!("Creating {}", dir.display()))?;
+    let result = extract(
+        repo,
+        name,
+        &dir,
+        &options,
+        &members,
+        compression,
+        &decrypt,
+        progress,
+        cancel,
+    );
+    if result.is_err() {
+        if let Err(e) = std::fs::remove_dir_all(&dir) {
+            error!(log, "Could not clean up {:?}: {}", dir, e);
+        }
+    }
+    result.map(|()| dir)
+}
+
+/// Extract `members` of snapshot `name` (all if empty) into `dir`
+#[allow(clippy::too_many_arguments)]
+fn extract(
+    repo: &Repo,
+    name: &str,
+    dir: &Path,
+    options: &[&str],
+    members: &[String],
+    compression: Option<Compression>,
+    decrypt: &DecryptHandle,
+    progress: Arc<Mutex<RestoreProgress>>,
+    cancel: Arc<AtomicBool>,
+) -> anyhow::Result<()> {
+    let mut tar = Process::new("tar")
+        .args(&["--extract", "--ignore-zeros", "--verbose", "--file=-"])
+        .args(compression.map(|c| c.tar_option()))
+        .args(options)
+        .arg("--directory")
+        .arg(dir)
+        .arg("--")
+        .args(members)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .context("Spawning tar")?;
+
+    // `--verbose` prints each member as it is extracted
+    let stdout = tar.stdout.take().context("tar stdout")?;
+    let lister = {
+        let progress = progress.clone();
+        std::thread::spawn(move || {
+            for line in BufReader::new(stdout).lines().flatten() {
+                let mut progress = progress.lock().unwrap();
+                progress.files += 1;
+                progress.current_file = line;
+            }
+        })
+    };
+
+    let mut writer = ProgressWriter {
+        inner: tar.stdin.take().context("tar stdin")?,
+        progress,
+        cancel: cancel.clone(),
+    };
+    let read = repo.read(name, &mut writer, decrypt);
+    drop(writer);
+    let status = tar.wait().context("Waiting for tar")?;
+    let _ = lister.join();
+
+    if cancel.load(Ordering::Relaxed) {
+        anyhow::bail!("Cancelled");
+    }
+    read.context(ReadFailed)?;
+    if !status.success() {
+        anyhow::bail!("tar failed: {}", status);
+    }
+    Ok(())
+}
+
+/// Context of errors reading a snapshot from the repo, as opposed to writing it out
+#[derive(Debug)]
+struct ReadFailed;
+impl std::fmt::Display for ReadFailed {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "Reading snapshot")
+    }
+}
+
+/// Whether the repo failed to deliver the snapshot (e.g. a corrupted or missing chunk), which
+/// another copy of the repo may not. A broken pipe means that `tar` stopped reading instead.
+fn is_read_failure(e: &anyhow::Error) -> bool {
+    e.downcast_ref::<ReadFailed>().is_some()
+        && !e
+            .chain()
+            .filter_map(|cause| cause.downcast_ref::<std::io::Error>())
+            .any(|e| e.kind() == std::io::ErrorKind::BrokenPipe)
+}
+
+/// Open the copy of a repo at `home` (see `duplicate::copies`), checking that it has snapshot
+/// `name`
+pub fn open_copy(home: &Path, name: &str, log: &Logger) -> anyhow::Result<Repo> {
+    let repo = rdedup::open_existing(home, log.clone())?;
+    let names = repo.list_names().context("Listing snapshots")?;
+    if !names.iter().any(|n| n == name) {
+        anyhow::bail!("{} does not have {}", home.display(), name);
+    }
+    Ok(repo)
+}
+
+/// `open_copy` of each of `homes` on a blocking thread, to choose one to restore from
+pub async fn check_copies_async(
+    homes: Vec<PathBuf>,
+    name: String,
+    log: Logger,
+) -> Vec<Result<(), String>> {
+    let n = homes.len();
+    tokio::task::spawn_blocking(move || {
+        homes
+            .iter()
+            .map(|home| {
+                open_copy(home, &name, &log)
+                    .map(|_| ())
+                    .map_err(|e| format!("{:#}", e))
+            })
+            .collect()
+    })
+    .await
+    .unwrap_or_else(|e| vec![Err(e.to_string()); n])
+}
+
+/// `restore` from the first of `homes` (the repo and its copies) that has the snapshot. Should
+/// reading it fail midway, the restore starts over from the next one that has it.
+///
+/// Not with `Collision::Skip`: a file left incomplete by the failed attempt would be kept.
+#[allow(clippy::too_many_arguments)]
+pub fn restore_from_copies(
+    homes: &[PathBuf],
+    name: &str,
+    dest: &Path,
+    selection: RestoreSelection,
+    passphrase: String,
+    progress: Arc<Mutex<RestoreProgress>>,
+    cancel: Arc<AtomicBool>,
+    log: &Logger,
+) -> anyhow::Result<PathBuf> {
+    let fail_over = !matches!(
+        selection,
+        RestoreSelection::Members {
+            collision: Collision::Skip,
+            ..
+        }
+    );
+    let mut last_error = None;
+    for (i, home) in homes.iter().enumerate() {
+        let repo = match open_copy(home, name, log) {
+            Ok(repo) => repo,
+            Err(e) => {
+                info!(log, "Not restoring from {:?}: {:#}", home, e);
+                last_error = Some(e);
+                continue;
+            }
+        };
+        if i > 0 {
+            let mut progress = progress.lock().unwrap();
+            progress.files = 0;
+            progress.bytes = 0;
+        }
+        let result = restore(
+            &repo,
+            name,
+            dest,
+            selection.clone(),
+            passphrase.clone(),
+            progress.clone(),
+            cancel.clone(),
+            log,
+        );
+        match result {
+            Err(e) if fail_over && is_read_failure(&e) && i + 1 < homes.len() => {
+                error!(
+                    log,
+                    "Restoring from {:?} failed, failing over to the next copy: {:#}", home, e
+                );
+                last_error = Some(e);
+            }
+            result => return result,
+        }
+    }
+    Err(last_error.unwrap_or_else(|| anyhow::anyhow!("No repo to restore from")))
+}
+
+/// `restore_from_copies` on a blocking thread, once `reads` allows
+#[allow(clippy::too_many_arguments)]
+pub async fn restore_async(
+    homes: Vec<PathBuf>,
+    name: String,
+    dest: PathBuf,
+    selection: RestoreSelection,
+    passphrase: String,
+    progress: Arc<Mutex<RestoreProgress>>,
+    cancel: Arc<AtomicBool>,
+    reads: ReadLimit,
+    log: Logger,
+) -> Result<PathBuf, String> {
+    tokio::task::spawn_blocking(move || {
+        let _slot = read_slot(reads, &cancel)?;
+        restore_from_copies(
+            &homes, &name, &dest, selection, passphrase, progress, cancel, &log,
+        )
+    })
+    .await
+    .map_err(|e| e.to_string())?
+    .map_err(|e| format!("{:#}", e))
+}
+
+/// Read snapshot `name` in full through `tar --list` without writing any files, to check that
+/// it can be restored: every chunk is read, decrypted and decompressed, and the archive is
+/// parsed. Returns the number of members.
+pub fn test_restore(
+    repo: &Repo,
+    name: &str,
+    passphrase: String,
+    progress: Arc<Mutex<RestoreProgress>>,
+    cancel: Arc<AtomicBool>,
+    log: &Logger,
+) -> anyhow::Result<usize> {
+    info!(log, "Test restore of {:?}", name);
+    let decrypt = repo
+        .unlock_decrypt(&move || Ok(passphrase.clone()))
+        .context("Unlocking repo")?;
+    let compression = snapshot_compression(repo, name, &decrypt)?;
+    let mut tar = Process::new("tar")
+        .args(&["--list", "--ignore-zeros", "--file=-"])
+        .args(compression.map(|c| c.tar_option()))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .context("Spawning tar")?;
+
+    let stdout = tar.stdout.take().context("tar stdout")?;
+    let lister = {
+        let progress = progress.clone();
+        std::thread::spawn(move || {
+            for line in BufReader::new(stdout).lines().flatten() {
+                let mut progress = progress.lock().unwrap();
+                progress.files += 1;
+                progress.current_file = line;
+            }
+        })
+    };
+
+    let mut writer = ProgressWriter {
+        inner: tar.stdin.take().context("tar stdin")?,
+        progress: progress.clone(),
+        cancel: cancel.clone(),
+    };
+    let read = repo.read(name, &mut writer, &decrypt);
+    drop(writer);
+    let status = tar.wait().context("Waiting for tar")?;
+    let _ = lister.join();
+
+    if cancel.load(Ordering::Relaxed) {
+        anyhow::bail!("Cancelled");
+    }
+    let progress = progress.lock().unwrap();
+    read.with_context(|| format!("Reading snapshot failed at byte {}", progress.bytes))?;
+    if !status.success() {
+        anyhow::bail!(
+            "tar could not read the archive ({}), after {} files",
+            status,
+            progress.files
+        );
+    }
+    Ok(progress.files)
+}
+
+/// `test_restore` on a blocking thread, once `reads` allows
+pub async fn test_restore_async(
+    repo: Repo,
+    name: String,
+    passphrase: String,
+    progress: Arc<Mutex<RestoreProgress>>,
+    cancel: Arc<AtomicBool>,
+    reads: ReadLimit,
+    log: Logger,
+) -> Result<usize, String> {
+    tokio::task::spawn_blocking(move || {
+        let _slot = read_slot(reads, &cancel)?;
+        test_restore(&repo, &name, passphrase, progress, cancel, &log)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+    .map_err(|e| format!("{:#}", e))
+}
+
+/// Write snapshot `name` as a plain tar file into the folder `dest`, gzipped if `gzip`. A snapshot
+/// that was compressed before deduplication (see `Target::pre_compress`) is written as it is.
+///
+/// The file is not encrypted. If writing it fails or is cancelled via `cancel`, it is removed
+/// again. Returns the file.
+#[allow(clippy::too_many_arguments)]
+pub fn export_tar(
+    repo: &Repo,
+    name: &str,
+    dest: &Path,
+    gzip: bool,
+    passphrase: String,
+    progress: Arc<Mutex<RestoreProgress>>,
+    cancel: Arc<AtomicBool>,
+    log: &Logger,
+) -> anyhow::Result<PathBuf> {
+    info!(log, "Export {:?} to {:?}", name, dest);
+    let decrypt = repo
+        .unlock_decrypt(&move || Ok(passphrase.clone()))
+        .context("Unlocking repo")?;
+
+    let compression = snapshot_compression(repo, name, &decrypt)?;
+    let extension = match (compression, gzip) {
+        (Some(Compression::Gzip), _) | (None, true) => "tar.gz",
+        (Some(Compression::Zstd), _) => "tar.zst",
+        (None, false) => "tar",
+    };
+    let path = dest.join(format!("{}.{}", name, extension));
+    let file = std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&path)
+        .with_context(|| format!("Creating {}", path.display()))?;
+    let gzip = gzip && compression.is_none();
+    let result = write_export(repo, name, file, gzip, &decrypt, progress, cancel);
+    if result.is_err() {
+        if let Err(e) = std::fs::remove_file(&path) {
+            error!(log, "Could not clean up {:?}: {}", path, e);
+        }
+    }
+    result.map(|()| path)
+}
+
+/// Write snapshot `name` to `file`, through `gzip` if `gzip`
+fn write_export(
+    repo: &Repo,
+    name: &str,
+    file: std::fs::File,
+    gzip: bool,
+    decrypt: &DecryptHandle,
+    progress: Arc<Mutex<RestoreProgress>>,
+    cancel: Arc<AtomicBool>,
+) -> anyhow::Result<()> {
+    if !gzip {
+        let mut writer = ProgressWriter {
+            inner: std::io::BufWriter::new(file),
+            progress,
+            cancel: cancel.clone(),
+        };
+        let read = repo.read(name, &mut writer, decrypt);
+        if cancel.load(Ordering::Relaxed) {
+            anyhow::bail!("Cancelled");
+        }
+        read.context("Reading snapshot")?;
+        return writer.flush().context("Writing file");
+    }
+    let mut gzip = Process::new("gzip")
+        .arg("--stdout")
+        .stdin(Stdio::piped())
+        .stdout(file)
+        .spawn()
+        .context("Spawning gzip")?;
+    let mut writer = ProgressWriter {
+        inner: gzip.stdin.take().context("gzip stdin")?,
+        progress,
+        cancel: cancel.clone(),
+    };
+    let read = repo.read(name, &mut writer, decrypt);
+    drop(writer);
+    let status = gzip.wait().context("Waiting for gzip")?;
+
+    if cancel.load(Ordering::Relaxed) {
+        anyhow::bail!("Cancelled");
+    }
+    read.context("Reading snapshot")?;
+    if !status.success() {
+        anyhow::bail!("gzip failed: {}", status);
+    }
+    Ok(())
+}
+
+/// `export_tar` on a blocking thread
+#[allow(clippy::too_many_arguments)]
+pub async fn export_tar_async(
+    repo: Repo,
+    name: String,
+    dest: PathBuf,
+    gzip: bool,
+    passphrase: String,
+    progress: Arc<Mutex<RestoreProgress>>,
+    cancel: Arc<AtomicBool>,
+    log: Logger,
+) -> Result<PathBuf, String> {
+    tokio::task::spawn_blocking(move || {
+        export_tar(
+            &repo, &name, &dest, gzip, passphrase, progress, cancel, &log,
+        )
+    })
+    .await
+    .map_err(|e| e.to_string())?
+    .map_err(|e| format!("{:#}", e))
+}
+
+/// Sends everything written to it to a `ChannelReader`
+struct ChannelWriter(std::sync::mpsc::SyncSender<Vec<u8>>);
+impl Write for ChannelWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0
+            .send(buf.to_vec())
+            .map_err(|_| std::io::Error::new(std::io::ErrorKind::BrokenPipe, "Reader gone"))?;
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Reads what was written to a `ChannelWriter`, until it is dropped
+struct ChannelReader {
+    receiver: std::sync::mpsc::Receiver<Vec<u8>>,
+    buf: Vec<u8>,
+    pos: usize,
+}
+impl Read for ChannelReader {
+    fn read(&mut self, out: &mut [u8]) -> std::io::Result<usize> {
+        while self.pos == self.buf.len() {
+            match self.receiver.recv() {
+                Ok(buf) => {
+                    self.buf = buf;
+                    self.pos = 0;
+                }
+                // Writer done
+                Err(_) => return Ok(0),
+            }
+        }
+        let n = out.len().min(self.buf.len() - self.pos);
+        out[..n].copy_from_slice(&self.buf[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+/// Give snapshot `name` the label `label`. Returns the new name.
+///
+/// rdedup has no rename, so the snapshot is read and written again under the new name, after which
+/// the old name is removed. The data itself is deduplicated, so this costs time but hardly any space.
+pub fn rename_snapshot(
+    repo: &Repo,
+    name: &str,
+    label: &str,
+    passphrase: String,
+    log: &Logger,
+) -> anyhow::Result<String> {
+    rdedup::verify_label(label).map_err(anyhow::Error::msg)?;
+    let new_name = rdedup::with_label(name, label)
+        .with_context(|| format!("{:?} was not created by bup", name))?;
+    let names = repo.list_names().context("Listing snapshots")?;
+    if names.contains(&new_name) {
+        anyhow::bail!("A snapshot named {:?} already exists", new_name);
+    }
+    info!(log, "Rename {:?} to {:?}", name, new_name);
+
+    let decrypt = {
+        let passphrase = passphrase.clone();
+        repo.unlock_decrypt(&move || Ok(passphrase.clone()))
+            .context("Unlocking repo")?
+    };
+    let encrypt = repo
+        .unlock_encrypt(&move || Ok(passphrase.clone()))
+        .context("Unlocking repo")?;
+
+    // Bounded, so that memory use doesn't depend on the size of the snapshot
+    let (sender, receiver) = std::sync::mpsc::sync_channel(16);
+    let reader = {
+        let repo = repo.clone();
+        let name = name.to_string();
+        std::thread::spawn(move || repo.read(&name, &mut ChannelWriter(sender), &decrypt))
+    };
+    let write = repo.write(
+        &new_name,
+        ChannelReader {
+            receiver,
+            buf: Vec::new(),
+            pos: 0,
+        },
+        &encrypt,
+    );
+    let read = reader
+        .join()
+        .map_err(|_| anyhow::anyhow!("Reading snapshot panicked"))?;
+    if let Err(e) = read
+        .context("Reading snapshot")
+        .and(write.context("Writing snapshot"))
+    {
+        if let Err(e) = repo.rm(&new_name) {
+            error!(
+                log,
+                "Could not remove incomplete snapshot {:?}: {}", new_name, e
+            );
+        }
+        return Err(e);
+    }
+    repo.rm(name).context("Removing old name")?;
+    Ok(new_name)
+}
+
+/// `rename_snapshot` on a blocking thread
+pub async fn rename_snapshot_async(
+    repo: Repo,
+    name: String,
+    label: String,
+    passphrase: String,
+    log: Logger,
+) -> Result<String, String> {
+    tokio::task::spawn_blocking(move || rename_snapshot(&repo, &name, &label, passphrase, &log))
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| format!("{:#}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PASSPHRASE: &str = "test passphrase";
+
+    /// A new directory under the temp dir, removed again when dropped
+    struct TempDir(PathBuf);
+    impl TempDir {
+        fn new() -> Self {
+            let dir = std::env::temp_dir().join(format!("bup-test-{}", Uuid::new_v4()));
+            std::fs::create_dir_all(&dir).unwrap();
+            TempDir(dir)
+        }
+        /// A new (empty) directory within
+        fn dir(&self, name: &str) -> PathBuf {
+            let dir = self.0.join(name);
+            std::fs::create_dir(&dir).unwrap();
+            dir
+        }
+    }
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn logger() -> Logger {
+        Logger::root(slog::Discard, slog::o!())
+    }
+
+    fn new_repo(home: &Path) -> Repo {
+        rdedup::init(
+            home,
+            RepoSettings::default(),
+            PASSPHRASE.to_string(),
+            logger(),
+        )
+        .unwrap()
+    }
+
+    fn target(source: &Path) -> Target {
+        Target {
+            name: "test".to_string(),
+            sources: vec![Source {
+                path: Some(source.to_path_buf()),
+                one_file_system: false,
+            }],
+            ..Default::default()
+        }
+    }
+
+    fn options() -> BackupOptions {
+        BackupOptions {
+            priority: Priority::Normal,
+            checksum: false,
+            min_free_space: None,
+            repo_excludes: Vec::new(),
+            cancel: Default::default(),
+            snapshot_name: None,
+            read_only: false,
+            verify: false,
+        }
+    }
+
+    /// Create a file of `len` bytes that is a single hole
+    fn sparse_file(path: &Path, len: u64) {
+        std::fs::File::create(path).unwrap().set_len(len).unwrap();
+    }
+
+    /// Resident set size of this process
+    #[cfg(target_os = "linux")]
+    fn rss() -> u64 {
+        let status = std::fs::read_to_string("/proc/self/status").unwrap();
+        let kib = status
+            .lines()
+            .find_map(|line| line.strip_prefix("VmRSS:"))
+            .and_then(|value| {
+                value
+                    .trim()
+                    .trim_end_matches("kB")
+                    .trim()
+                    .parse::<u64>()
+                    .ok()
+            })
+            .unwrap();
+        kib << 10
+    }
+
+    /// Back up a folder, find its snapshot among the names of the repo and restore it
+    #[test]
+    #[cfg(unix)]
+    fn backup_list_restore() {
+        let temp = TempDir::new();
+        let (home, source, dest) = (temp.dir("repo"), temp.dir("source"), temp.dir("dest"));
+        std::fs::create_dir(source.join("sub")).unwrap();
+        let files: Vec<(&str, Vec<u8>)> = vec![
+            ("a.txt", b"hello".to_vec()),
+            ("sub/b.bin", (0..=255u8).cycle().take(3 << 20).collect()),
+        ];
+        for (path, content) in &files {
+            std::fs::write(source.join(path), content).unwrap();
+        }
+        let repo = new_repo(&home);
+
+        let done = run_backup(
+            &repo,
+            &target(&source),
+            PASSPHRASE.to_string(),
+            options(),
+            &logger(),
+        )
+        .unwrap();
+        let names = repo.list_names().unwrap();
+        assert_eq!(names, vec![done.snapshot.name.clone()]);
+        let snapshots = rdedup::snapshots_of(&names, "test");
+        assert_eq!(snapshots.len(), 1);
+        assert_eq!(snapshots[0].name, done.snapshot.name);
+
+        let dir = restore(
+            &repo,
+            &done.snapshot.name,
+            &dest,
+            RestoreSelection::All,
+            PASSPHRASE.to_string(),
+            Default::default(),
+            Default::default(),
+            &logger(),
+        )
+        .unwrap();
+        assert_eq!(dir, dest.join(&done.snapshot.name));
+        // tar stores the source by its absolute path, without the leading `/`
+        let restored = dir.join(source.strip_prefix("/").unwrap());
+        for (path, content) in &files {
+            let read = std::fs::read(restored.join(path)).unwrap();
+            assert!(read == *content, "{} differs after restoring", path);
+        }
+    }
+
+    /// With `handle_sparse`, the holes of a sparse file are archived as holes rather than zeros.
+    /// Measured on the archive, since rdedup would deduplicate the zeros in the repo anyway.
+    #[test]
+    #[cfg(unix)]
+    fn sparse_file_is_archived_small() {
+        use std::io::{Seek, SeekFrom};
+        if *TAR == TarImpl::Unknown {
+            return;
+        }
+        let temp = TempDir::new();
+        let (home, source) = (temp.dir("repo"), temp.dir("source"));
+        let path = source.join("disk.img");
+        sparse_file(&path, 1 << 30);
+        let mut file = std::fs::OpenOptions::new().write(true).open(&path).unwrap();
+        file.seek(SeekFrom::Start(1 << 29)).unwrap();
+        file.write_all(&[1; 1 << 16]).unwrap();
+        drop(file);
+        let apparent = std::fs::metadata(&path).unwrap().len();
+        // The file system may not support holes
+        if !is_sparse(&std::fs::metadata(&path).unwrap()) {
+            return;
+        }
+        let repo = new_repo(&home);
+
+        // Suggested by the dry run, until handled
+        let target = target(&source);
+        assert_eq!(check_limits(&target, true).sparse_files, 1);
+        let target = Target {
+            handle_sparse: true,
+            ..target
+        };
+        assert_eq!(check_limits(&target, true).sparse_files, 0);
+
+        let done =
+            run_backup(&repo, &target, PASSPHRASE.to_string(), options(), &logger()).unwrap();
+        let archived = done.snapshot.bytes.unwrap() as u64;
+        assert!(
+            archived < apparent / 100,
+            "Archived {} of a {} sparse file",
+            format_bytes(archived),
+            format_bytes(apparent)
+        );
+    }
+
+    /// The archive of a file much larger than memory should stream through, not be buffered
+    #[test]
+    #[ignore = "archives 4 GiB of zeros, run with --ignored"]
+    #[cfg(target_os = "linux")]
+    fn large_file_streams() {
+        let temp = TempDir::new();
+        let (home, source) = (temp.dir("repo"), temp.dir("source"));
+        sparse_file(&source.join("disk.img"), 4 << 30);
+        let repo = new_repo(&home);
+
+        let before = rss();
+        let done = Arc::new(AtomicBool::new(false));
+        let sampler = {
+            let done = done.clone();
+            std::thread::spawn(move || {
+                let mut peak = 0;
+                while !done.load(Ordering::Relaxed) {
+                    peak = peak.max(rss());
+                    std::thread::sleep(Duration::from_millis(50));
+                }
+                peak
+            })
+        };
+        let written = write_snapshot(
+            &repo,
+            &target(&source),
+            PASSPHRASE.to_string(),
+            options(),
+            &logger(),
+        );
+        done.store(true, Ordering::Relaxed);
+        let peak = sampler.join().unwrap();
+
+        let written = written.unwrap();
+        assert!(written.snapshot.bytes.unwrap() as u64 > 4 << 30);
+        let grown = peak.saturating_sub(before);
+        assert!(
+            grown < 512 << 20,
+            "Memory grew by {} while archiving",
+            format_bytes(grown)
+        );
+    }
+}
@@ -0,0 +1,272 @@
+//! Copy settings of one repo to the other repos, for users with many repos set up alike
+use super::*;
+
+#[derive(Debug, Clone)]
+pub enum BulkEditMessage {
+    SetSetting(RepoSetting, bool),
+    /// Include the repo with this id, or leave it out
+    SetRepo(Uuid, bool),
+
+    // Meant for outside
+    /// Apply button pressed
+    Apply,
+    /// Back button pressed
+    Back,
+}
+
+/// A setting of `RepoConfig` that lives only in the config of bup, and can thus be changed for
+/// existing repos. The encryption and chunking of a repo are fixed when it is created.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RepoSetting {
+    GcGrace,
+    LowSpace,
+    CriticalSpace,
+    Checksums,
+    ReadThreads,
+    RepoExcludes,
+    /// `notifiers` and `notify_on_success`
+    Notifications,
+}
+impl RepoSetting {
+    pub const ALL: [RepoSetting; 7] = [
+        RepoSetting::GcGrace,
+        RepoSetting::LowSpace,
+        RepoSetting::CriticalSpace,
+        RepoSetting::Checksums,
+        RepoSetting::ReadThreads,
+        RepoSetting::RepoExcludes,
+        RepoSetting::Notifications,
+    ];
+    pub fn name(self) -> &'static str {
+        match self {
+            RepoSetting::GcGrace => "Compaction grace period",
+            RepoSetting::LowSpace => "Low space warning",
+            RepoSetting::CriticalSpace => "Critical space limit",
+            RepoSetting::Checksums => "Snapshot checksums",
+            RepoSetting::ReadThreads => "Restores at the same time",
+            RepoSetting::RepoExcludes => "Repo excludes",
+            RepoSetting::Notifications => "Notifications",
+        }
+    }
+    /// The setting in `repo`, for showing
+    pub fn value(self, repo: &RepoConfig) -> String {
+        match self {
+            RepoSetting::GcGrace => format_duration(chrono::Duration::seconds(
+                repo.gc_grace_secs.min(i64::MAX as u64) as i64,
+            )),
+            RepoSetting::LowSpace => format_bytes(repo.low_space_bytes),
+            RepoSetting::CriticalSpace => format_bytes(repo.critical_space_bytes),
+            RepoSetting::Checksums => if repo.checksums { "on" } else { "off" }.to_string(),
+            RepoSetting::ReadThreads => repo.read_threads.to_string(),
+            RepoSetting::RepoExcludes if repo.repo_excludes.is_empty() => "none".to_string(),
+            RepoSetting::RepoExcludes => repo.repo_excludes.join(", "),
+            RepoSetting::Notifications => format!(
+                "{} notifiers{}",
+                repo.notifiers.len(),
+                if repo.notify_on_success {
+                    ", also on success"
+                } else {
+                    ""
+                }
+            ),
+        }
+    }
+    pub fn same(self, a: &RepoConfig, b: &RepoConfig) -> bool {
+        match self {
+            RepoSetting::GcGrace => a.gc_grace_secs == b.gc_grace_secs,
+            RepoSetting::LowSpace => a.low_space_bytes == b.low_space_bytes,
+            RepoSetting::CriticalSpace => a.critical_space_bytes == b.critical_space_bytes,
+            RepoSetting::Checksums => a.checksums == b.checksums,
+            RepoSetting::ReadThreads => a.read_threads == b.read_threads,
+            RepoSetting::RepoExcludes => a.repo_excludes == b.repo_excludes,
+            RepoSetting::Notifications => {
+                a.notifiers == b.notifiers && a.notify_on_success == b.notify_on_success
+            }
+        }
+    }
+    pub fn copy(self, from: &RepoConfig, to: &mut RepoConfig) {
+        match self {
+            RepoSetting::GcGrace => to.gc_grace_secs = from.gc_grace_secs,
+            RepoSetting::LowSpace => to.low_space_bytes = from.low_space_bytes,
+            RepoSetting::CriticalSpace => to.critical_space_bytes = from.critical_space_bytes,
+            RepoSetting::Checksums => to.checksums = from.checksums,
+            RepoSetting::ReadThreads => to.read_threads = from.read_threads,
+            RepoSetting::RepoExcludes => to.repo_excludes = from.repo_excludes.clone(),
+            RepoSetting::Notifications => {
+                to.notifiers = from.notifiers.clone();
+                to.notify_on_success = from.notify_on_success;
+            }
+        }
+    }
+}
+
+/// Copying the chosen settings of the selected repo to the other repos
+#[derive(Default)]
+pub struct BulkEdit {
+    /// Settings to copy
+    pub settings: Vec<RepoSetting>,
+    /// Repos opted out of the copy
+    pub skipped: HashSet<Uuid>,
+    /// Number of repos changed by the last apply
+    applied: Option<usize>,
+
+    s_apply: button::State,
+    s_back: button::State,
+    s_scrollable: scrollable::State,
+}
+impl BulkEdit {
+    /// Repos the settings of the selected repo may be copied to. Not demo repos, which are
+    /// deleted on exit.
+    fn others(config: &Config) -> impl Iterator<Item = &RepoConfig> {
+        let selected = config.selected_repo().map(|repo| repo.id);
+        config
+            .repos
+            .values()
+            .filter(move |repo| Some(repo.id) != selected && !repo.ephemeral)
+    }
+    /// What applying would change: "setting: old -> new" per setting that differs
+    fn changes(&self, from: &RepoConfig, to: &RepoConfig) -> Vec<String> {
+        self.settings
+            .iter()
+            .filter(|setting| !setting.same(from, to))
+            .map(|setting| {
+                format!(
+                    "{}: {} -> {}",
+                    setting.name(),
+                    setting.value(to),
+                    setting.value(from)
+                )
+            })
+            .collect()
+    }
+    /// Copy the settings to the repos that are not skipped. Returns the number of repos changed.
+    pub fn apply(&mut self, config: &mut Config) -> usize {
+        let from = match config.selected_repo() {
+            Some(repo) => repo.clone(),
+            None => return 0,
+        };
+        let ids: Vec<Uuid> = Self::others(config)
+            .filter(|repo| !self.skipped.contains(&repo.id))
+            .filter(|repo| !self.changes(&from, repo).is_empty())
+            .map(|repo| repo.id)
+            .collect();
+        for id in &ids {
+            if let Some(repo) = config.repos.get_mut(id) {
+                for setting in &self.settings {
+                    setting.copy(&from, repo);
+                }
+            }
+        }
+        self.applied = Some(ids.len());
+        ids.len()
+    }
+    pub fn view(&mut self, config: &Config) -> Element<'_, BulkEditMessage> {
+        let from = match config.selected_repo() {
+            Some(repo) => repo,
+            None => return Text::new("No repo selected").into(),
+        };
+        let mut settings = Column::new().spacing(4).push(h3("Settings to copy"));
+        for &setting in RepoSetting::ALL.iter() {
+            settings = settings.push(
+                Checkbox::new(
+                    self.settings.contains(&setting),
+                    format!("{} ({})", setting.name(), setting.value(from)),
+                    move |b| BulkEditMessage::SetSetting(setting, b),
+                )
+                .size(TEXT_SIZE - 4)
+                .text_size(TEXT_SIZE - 4),
+            );
+        }
+
+        let mut repos = Column::new().spacing(8).push(h3("Repos"));
+        let mut n_changed = 0;
+        for repo in Self::others(config) {
+            let included = !self.skipped.contains(&repo.id);
+            let changes = self.changes(from, repo);
+            if included && !changes.is_empty() {
+                n_changed += 1;
+            }
+            let id = repo.id;
+            let mut column = Column::new().spacing(2).push(
+                Checkbox::new(included, repo.name.clone(), move |b| {
+                    BulkEditMessage::SetRepo(id, b)
+                })
+                .size(TEXT_SIZE)
+                .text_size(TEXT_SIZE),
+            );
+            if changes.is_empty() {
+                column = column.push(Text::new("No changes").size(TEXT_SIZE - 4));
+            }
+            for change in changes {
+                let color = if included {
+                    Color::from_rgb(0.8, 0.8, 0.8)
+                } else {
+                    Color::from_rgb(0.5, 0.5, 0.5)
+                };
+                column = column.push(Text::new(change).size(TEXT_SIZE - 4).color(color));
+            }
+            repos = repos.push(column);
+        }
+
+        let mut apply = Button::new(
+            &mut self.s_apply,
+            Text::new(format!("APPLY TO {} REPOS", n_changed)).size(TEXT_SIZE - 4),
+        )
+        .padding(8)
+        .style(style::Button::Primary);
+        if n_changed > 0 {
+            apply = apply.on_press(BulkEditMessage::Apply);
+        }
+        let mut column = Column::new()
+            .padding(20)
+            .spacing(20)
+            .push(h3(format!("Copy settings of {} to other repos", from.name)))
+            .push(
+                Text::new(
+                    "Only settings of bup are copied. The encryption and chunking of a repo are \
+                     fixed when it is created, and stay as they are.",
+                )
+                .size(TEXT_SIZE - 4),
+            )
+            .push(settings)
+            .push(repos)
+            .push(
+                Row::new()
+                    .spacing(10)
+                    .push(
+                        Button::new(&mut self.s_back, Text::new("BACK").size(TEXT_SIZE - 4))
+                            .padding(8)
+                            .style(style::Button::Text)
+                            .on_press(BulkEditMessage::Back),
+                    )
+                    .push(apply),
+            );
+        if let Some(applied) = self.applied {
+            column = column.push(Text::new(format!("Changed {} repos", applied)));
+        }
+        let x = Container::new(column)
+            .style(style::DialogContainer)
+            .width(Length::Fill)
+            .max_width(1000)
+            .height(Length::Shrink);
+        Scrollable::new(&mut self.s_scrollable).push(x).into()
+    }
+    pub fn update(&mut self, message: BulkEditMessage) {
+        match message {
+            BulkEditMessage::SetSetting(setting, true) => {
+                if !self.settings.contains(&setting) {
+                    self.settings.push(setting);
+                }
+            }
+            BulkEditMessage::SetSetting(setting, false) => self.settings.retain(|s| *s != setting),
+            BulkEditMessage::SetRepo(id, true) => {
+                self.skipped.remove(&id);
+            }
+            BulkEditMessage::SetRepo(id, false) => {
+                self.skipped.insert(id);
+            }
+            BulkEditMessage::Apply | BulkEditMessage::Back => return,
+        }
+        self.applied = None;
+    }
+}
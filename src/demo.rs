@@ -0,0 +1,57 @@
+//! A throwaway repo in the temp dir, for trying out the app without touching real data.
+use crate::*;
+
+const DEMO_DIR_PREFIX: &str = "bup-demo-";
+
+/// Create a demo repo with one target, backing up a small generated folder.
+pub fn create_demo(passphrase: String, log: Logger) -> anyhow::Result<(RepoConfig, Repo)> {
+    let id = Uuid::new_v4();
+    let dir = std::env::temp_dir().join(format!("{}{}", DEMO_DIR_PREFIX, id));
+    let home = dir.join("repo");
+    let sample = dir.join("sample");
+
+    std::fs::create_dir_all(&home).context("Creating demo repo dir")?;
+    std::fs::create_dir_all(sample.join("notes")).context("Creating demo sample dir")?;
+    std::fs::write(sample.join("hello.txt"), "Hello from bup!\n")?;
+    for i in 0..10 {
+        std::fs::write(
+            sample.join("notes").join(format!("note-{}.txt", i)),
+            format!("Note number {}\n", i).repeat(100),
+        )?;
+    }
+
+    let repo = init_repo(&home, passphrase, log)?;
+    let config = RepoConfig {
+        id,
+        name: "Demo".to_string(),
+        home,
+        targets: vec![Target {
+            id: Uuid::new_v4(),
+            repo: id,
+            name: "Sample".to_string(),
+            sources: vec![Source {
+                path: Some(sample),
+                ..Default::default()
+            }],
+            ..Default::default()
+        }],
+        gc_grace_secs: DEFAULT_GC_GRACE_SECS,
+        ephemeral: true,
+        ..Default::default()
+    };
+    Ok((config, repo))
+}
+
+/// Delete everything created by `create_demo`
+pub fn remove_demo(repo: &RepoConfig) -> anyhow::Result<()> {
+    let dir = repo.home.parent().context("Demo repo has no parent dir")?;
+    let is_demo_dir = dir
+        .file_name()
+        .and_then(|name| name.to_str())
+        .map(|name| name.starts_with(DEMO_DIR_PREFIX))
+        .unwrap_or(false);
+    if !is_demo_dir {
+        anyhow::bail!("{} is not a demo dir", dir.display());
+    }
+    std::fs::remove_dir_all(dir).with_context(|| format!("Removing {}", dir.display()))
+}
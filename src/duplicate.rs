@@ -0,0 +1,566 @@
+//! Copying a repo to the destinations of its `Duplication`s, or once to a new home when moving
+//! it (see `relocate`)
+use crate::*;
+use std::io::ErrorKind;
+use std::process::{Command as Process, Stdio};
+
+/// Delay before the first retry. Doubles with every attempt.
+pub const RETRY_BASE_DELAY: Duration = Duration::from_secs(2);
+/// Upper bound of the delay between attempts
+pub const RETRY_MAX_DELAY: Duration = Duration::from_secs(5 * 60);
+
+/// Copy the repo at `home` to the destination of `kind`, on behalf of the target named `target`.
+/// Returns the disk that was written to (the program, for a `DuplicationKind::Command`).
+///
+/// The repo is mirrored into a folder of the same name in the destination folder. rdedup never
+/// modifies a file once written, so files that exist with the same size are skipped, which makes
+/// repeated runs cheap. Files are written under a temporary name and renamed, so an interrupted
+/// copy leaves no truncated files behind, and the next run continues the temporary file. Files
+/// removed from the repo (by gc) are not removed from the copy.
+pub fn duplicate(
+    home: &Path,
+    kind: &DuplicationKind,
+    target: &str,
+    log: &Logger,
+) -> anyhow::Result<PathBuf> {
+    let disk = match kind {
+        DuplicationKind::Disk { path } => {
+            if !path.is_dir() {
+                // Likely an unmounted disk; don't create the mount point
+                anyhow::bail!("{} is not available", path.display());
+            }
+            path
+        }
+        DuplicationKind::DiskRotation { paths, last_used } => {
+            pick_disk(paths, last_used.as_deref())
+                .with_context(|| format!("None of {} is available", kind))?
+        }
+        DuplicationKind::Command { program, args } => {
+            run_command(home, program, args, target, log)?;
+            return Ok(PathBuf::from(program));
+        }
+        DuplicationKind::Sftp { .. } => {
+            run_rsync(home, kind, log)?;
+            return Ok(PathBuf::from(remote_dest(kind)));
+        }
+    };
+    // A folder per repo, so that several repos can share a disk
+    let dest = disk.join(home.file_name().context("Repo home has no name")?);
+    info!(log, "Duplicate {:?} to {:?}", home, dest);
+    mirror_dir(home, &dest)?;
+    Ok(disk.clone())
+}
+
+/// Homes of the copies of `repo` that the duplications of its targets made, where they exist (e.g.
+/// their disk is mounted). They are complete repos, up to their last run.
+pub fn copies(repo: &RepoConfig) -> Vec<PathBuf> {
+    let name = match repo.home.file_name() {
+        Some(name) => name,
+        None => return Vec::new(),
+    };
+    let mut copies = Vec::new();
+    for duplication in repo.targets.iter().flat_map(|target| &target.duplication) {
+        for disk in duplication.kind.paths() {
+            let home = disk.join(name);
+            if !disk.as_os_str().is_empty() && home.is_dir() && !copies.contains(&home) {
+                copies.push(home);
+            }
+        }
+    }
+    copies
+}
+
+/// Failure of the program of a `DuplicationKind::Command`. Worth retrying, since such programs
+/// usually upload over a network.
+#[derive(Debug)]
+struct CommandFailed {
+    program: String,
+    status: std::process::ExitStatus,
+}
+impl std::fmt::Display for CommandFailed {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{} failed: {}", self.program, self.status)
+    }
+}
+impl std::error::Error for CommandFailed {}
+
+/// `arg` with `$NAME` and `${NAME}` of `vars` replaced by their values
+fn expand(arg: &str, vars: &[(&str, &str)]) -> String {
+    let mut arg = arg.to_string();
+    for (name, value) in vars {
+        arg = arg
+            .replace(&format!("${{{}}}", name), value)
+            .replace(&format!("${}", name), value);
+    }
+    arg
+}
+
+/// Run the program of a `DuplicationKind::Command`, logging its output
+fn run_command(
+    home: &Path,
+    program: &str,
+    args: &[String],
+    target: &str,
+    log: &Logger,
+) -> anyhow::Result<()> {
+    let names = rdedup::open_existing(home, log.clone())?
+        .list_names()
+        .context("Listing snapshots")?;
+    let snapshot = rdedup::snapshots_of(&names, target)
+        .into_iter()
+        .next()
+        .map(|snapshot| snapshot.name)
+        .unwrap_or_default();
+    let home_text = home.to_string_lossy();
+    let vars = [
+        ("BUP_REPO_HOME", &*home_text),
+        ("BUP_TARGET", target),
+        ("BUP_SNAPSHOT", &*snapshot),
+    ];
+    let args: Vec<String> = args.iter().map(|arg| expand(arg, &vars)).collect();
+    info!(log, "Running {:?} {:?}", program, args);
+    let output = Process::new(program)
+        .args(&args)
+        .env("BUP_REPO_HOME", home)
+        .env("BUP_TARGET", target)
+        .env("BUP_SNAPSHOT", &snapshot)
+        .stdin(Stdio::null())
+        .output()
+        .with_context(|| format!("Running {:?}", program))?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if !stdout.trim().is_empty() {
+        info!(log, "{} stdout: {}", program, stdout.trim_end());
+    }
+    if !stderr.trim().is_empty() {
+        info!(log, "{} stderr: {}", program, stderr.trim_end());
+    }
+    if !output.status.success() {
+        Err(CommandFailed {
+            program: program.to_string(),
+            status: output.status,
+        })?;
+    }
+    Ok(())
+}
+
+/// Where a `DuplicationKind::Sftp` copies to, as `user@host:path`. Empty for other kinds.
+pub fn remote_dest(kind: &DuplicationKind) -> String {
+    match kind {
+        DuplicationKind::Sftp {
+            host, user, path, ..
+        } if user.is_empty() => format!("{}:{}", host, path),
+        DuplicationKind::Sftp {
+            host, user, path, ..
+        } => format!("{}@{}:{}", user, host, path),
+        _ => String::new(),
+    }
+}
+
+/// Options of `ssh` for `kind`. It never prompts: without a usable key it fails instead.
+fn ssh_options(kind: &DuplicationKind) -> Vec<String> {
+    let mut options: Vec<String> = ["-o", "BatchMode=yes", "-o", "ConnectTimeout=30"]
+        .iter()
+        .map(|option| option.to_string())
+        .collect();
+    if let DuplicationKind::Sftp {
+        port: Some(port), ..
+    } = kind
+    {
+        options.extend(vec!["-p".to_string(), port.to_string()]);
+    }
+    options
+}
+
+/// `rsync` exit codes of network trouble: socket I/O (10), protocol stream (12), timeouts (30, 35)
+const RSYNC_TRANSIENT: [i32; 4] = [10, 12, 30, 35];
+
+/// Mirror the repo at `home` into a folder of the same name on the host of the
+/// `DuplicationKind::Sftp` `kind`, like `mirror_dir` does on a disk. rsync skips the files that
+/// exist with the same size, and keeps partial files of an interrupted run to continue them.
+fn run_rsync(home: &Path, kind: &DuplicationKind, log: &Logger) -> anyhow::Result<()> {
+    let dest = format!("{}/", remote_dest(kind));
+    info!(log, "Duplicate {:?} to {:?}", home, dest);
+    let output = Process::new("rsync")
+        .args(&["--recursive", "--times", "--size-only", "--partial", "-e"])
+        .arg(format!("ssh {}", ssh_options(kind).join(" ")))
+        // Without a trailing slash, so that rsync creates the folder of the repo
+        .arg(home)
+        .arg(&dest)
+        .stdin(Stdio::null())
+        .output()
+        .context("Running rsync; is it installed?")?;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if !stderr.trim().is_empty() {
+        info!(log, "rsync stderr: {}", stderr.trim_end());
+    }
+    if !output.status.success() {
+        if output
+            .status
+            .code()
+            .map_or(false, |code| RSYNC_TRANSIENT.contains(&code))
+        {
+            Err(CommandFailed {
+                program: "rsync".to_string(),
+                status: output.status,
+            })?;
+        }
+        // E.g. "Permission denied (publickey)" from ssh
+        anyhow::bail!("rsync failed: {}", stderr.trim());
+    }
+    Ok(())
+}
+
+/// Check that the host of the `DuplicationKind::Sftp` `kind` can be logged in to with a key, and
+/// that its folder exists and can be written to
+pub fn test_remote(kind: &DuplicationKind) -> anyhow::Result<()> {
+    let (host, user, path) = match kind {
+        DuplicationKind::Sftp {
+            host, user, path, ..
+        } => (host, user, path),
+        _ => anyhow::bail!("Not an SSH duplication"),
+    };
+    if host.trim().is_empty() || path.trim().is_empty() {
+        anyhow::bail!("No host or folder set");
+    }
+    let login = if user.is_empty() {
+        host.clone()
+    } else {
+        format!("{}@{}", user, host)
+    };
+    // Run by the remote shell, so the path is quoted for it
+    let quoted = format!("'{}'", path.replace('\'', "'\\''"));
+    let script = format!("test -d {0} && test -w {0}", quoted);
+    let output = Process::new("ssh")
+        .args(ssh_options(kind))
+        .arg(&login)
+        .arg(&script)
+        .stdin(Stdio::null())
+        .output()
+        .context("Running ssh; is it installed?")?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        match stderr.trim() {
+            // The login worked, `test` failed
+            "" => anyhow::bail!("{} is not a folder that can be written to", path),
+            stderr => anyhow::bail!("{}", stderr),
+        }
+    }
+    Ok(())
+}
+
+/// `test_remote` on a blocking thread
+pub async fn test_remote_async(kind: DuplicationKind) -> Result<(), String> {
+    tokio::task::spawn_blocking(move || test_remote(&kind))
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| format!("{:#}", e))
+}
+
+/// The disk of a rotation to write to: a mounted one (see `is_mounted`), preferring one other
+/// than `last_used` so that writes are spread over the disks.
+pub fn pick_disk<'a>(paths: &'a [PathBuf], last_used: Option<&Path>) -> Option<&'a PathBuf> {
+    let mut available = paths.iter().filter(|path| is_mounted(path));
+    let first = available.next()?;
+    if Some(first.as_path()) != last_used {
+        return Some(first);
+    }
+    Some(available.next().unwrap_or(first))
+}
+
+/// Whether the folder `path` is on a mounted disk: it or a folder above it is on another device
+/// than its parent. The empty mount point left behind by an unplugged disk is a folder too, but
+/// on the root file system, which a rotation must not fill.
+#[cfg(unix)]
+pub fn is_mounted(path: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    let path = match std::fs::canonicalize(path) {
+        Ok(path) if path.is_dir() => path,
+        _ => return false,
+    };
+    let device = |path: &Path| std::fs::metadata(path).map(|metadata| metadata.dev()).ok();
+    path.ancestors()
+        .zip(path.ancestors().skip(1))
+        .any(|(dir, parent)| match (device(dir), device(parent)) {
+            (Some(dir), Some(parent)) => dir != parent,
+            _ => false,
+        })
+}
+#[cfg(not(unix))]
+pub fn is_mounted(path: &Path) -> bool {
+    path.is_dir()
+}
+
+fn mirror_dir(from: &Path, to: &Path) -> anyhow::Result<()> {
+    std::fs::create_dir_all(to).with_context(|| format!("Creating {}", to.display()))?;
+    for entry in std::fs::read_dir(from).with_context(|| format!("Reading {}", from.display()))? {
+        let entry = entry?;
+        let dest = to.join(entry.file_name());
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            mirror_dir(&entry.path(), &dest)?;
+        } else if file_type.is_file() {
+            let len = entry.metadata()?.len();
+            if std::fs::metadata(&dest)
+                .map(|m| m.len() == len)
+                .unwrap_or(false)
+            {
+                continue;
+            }
+            let tmp = to.join(format!(".{}.bup-tmp", entry.file_name().to_string_lossy()));
+            copy_resuming(&entry.path(), &tmp, len)
+                .with_context(|| format!("Copying to {}", tmp.display()))?;
+            std::fs::rename(&tmp, &dest)
+                .with_context(|| format!("Renaming to {}", dest.display()))?;
+        }
+    }
+    Ok(())
+}
+
+/// Copy `from` (of length `len`) to `to`. A partial `to` left by an interrupted run is appended
+/// to rather than copied again, which is safe because rdedup never modifies a written file.
+fn copy_resuming(from: &Path, to: &Path, len: u64) -> std::io::Result<()> {
+    use std::io::{Seek, SeekFrom};
+    let mut dest = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(to)?;
+    let mut done = dest.metadata()?.len();
+    if done > len {
+        dest.set_len(0)?;
+        done = 0;
+    }
+    let mut source = std::fs::File::open(from)?;
+    source.seek(SeekFrom::Start(done))?;
+    std::io::copy(&mut source, &mut dest)?;
+    dest.sync_all()
+}
+
+/// Whether a failure may go away by itself, so that trying again makes sense. Errors like a full
+/// disk (ENOSPC), missing permissions or a missing destination are not. A failed program of a
+/// `DuplicationKind::Command` is.
+pub fn is_transient(e: &anyhow::Error) -> bool {
+    if e.chain().any(|cause| cause.is::<CommandFailed>()) {
+        return true;
+    }
+    e.chain()
+        .filter_map(|cause| cause.downcast_ref::<std::io::Error>())
+        .any(|e| {
+            matches!(
+                e.kind(),
+                ErrorKind::TimedOut
+                    | ErrorKind::Interrupted
+                    | ErrorKind::WouldBlock
+                    | ErrorKind::ConnectionReset
+                    | ErrorKind::ConnectionAborted
+                    | ErrorKind::BrokenPipe
+            )
+        })
+}
+
+/// `duplicate`, retried with exponential backoff on transient errors, up to
+/// `duplication.max_attempts` attempts in total.
+pub fn duplicate_with_retry(
+    home: &Path,
+    duplication: &Duplication,
+    target: &str,
+    log: &Logger,
+) -> Result<PathBuf, String> {
+    if let Some(started) = duplication.in_progress {
+        info!(
+            log,
+            "Resuming previous duplication to {} (started {})", duplication.kind, started
+        );
+    }
+    let max_attempts = duplication.max_attempts.max(1);
+    let mut delay = RETRY_BASE_DELAY;
+    let mut attempt = 1;
+    loop {
+        match duplicate(home, &duplication.kind, target, log) {
+            Ok(disk) => return Ok(disk),
+            Err(e) if attempt < max_attempts && is_transient(&e) => {
+                info!(
+                    log,
+                    "Duplication to {} failed (attempt {}/{}), retrying in {:?}: {:#}",
+                    duplication.kind,
+                    attempt,
+                    max_attempts,
+                    delay,
+                    e
+                );
+                std::thread::sleep(delay);
+                delay = (delay * 2).min(RETRY_MAX_DELAY);
+                attempt += 1;
+            }
+            Err(e) => {
+                error!(log, "Duplication to {} failed: {:#}", duplication.kind, e);
+                return Err(if attempt > 1 {
+                    format!("Failed after {} attempts: {:#}", attempt, e)
+                } else {
+                    format!("{:#}", e)
+                });
+            }
+        }
+    }
+}
+
+/// `duplicate_with_retry` on a blocking thread
+pub async fn duplicate_async(
+    home: PathBuf,
+    duplication: Duplication,
+    target: String,
+    log: Logger,
+) -> Result<PathBuf, BupError> {
+    tokio::task::spawn_blocking(move || duplicate_with_retry(&home, &duplication, &target, &log))
+        .await?
+        .map_err(BupError::Duplication)
+}
+
+/// Relative paths and sizes of the files under `dir`, skipping leftover temporary files
+fn file_sizes(dir: &Path) -> anyhow::Result<BTreeMap<PathBuf, u64>> {
+    let mut sizes = BTreeMap::new();
+    let mut stack = vec![dir.to_path_buf()];
+    while let Some(current) = stack.pop() {
+        for entry in
+            std::fs::read_dir(&current).with_context(|| format!("Reading {}", current.display()))?
+        {
+            let entry = entry?;
+            let file_type = entry.file_type()?;
+            if file_type.is_dir() {
+                stack.push(entry.path());
+            } else if file_type.is_file()
+                && !entry.file_name().to_string_lossy().ends_with(".bup-tmp")
+            {
+                let path = entry.path();
+                let relative = path.strip_prefix(dir).unwrap_or(&path).to_path_buf();
+                sizes.insert(relative, entry.metadata()?.len());
+            }
+        }
+    }
+    Ok(sizes)
+}
+
+/// Copy the repo at `from` to the new home `to`, once, for moving it to another disk. `to` must
+/// not exist yet or be an empty folder. The copy is verified by comparing the files and their
+/// sizes, then by opening it, checking `passphrase` and comparing the snapshot names. `from` is
+/// left as it is. Returns the repo opened at `to`.
+pub fn relocate(from: &Path, to: &Path, passphrase: String, log: &Logger) -> anyhow::Result<Repo> {
+    let from_abs = from
+        .canonicalize()
+        .with_context(|| format!("{} is not available", from.display()))?;
+    let to_abs = match to.canonicalize() {
+        Ok(to) => to,
+        // Not created yet; compare via its parent
+        Err(_) => to
+            .parent()
+            .and_then(|parent| parent.canonicalize().ok())
+            .and_then(|parent| Some(parent.join(to.file_name()?)))
+            .with_context(|| format!("The folder of {} does not exist", to.display()))?,
+    };
+    if to_abs.starts_with(&from_abs) || from_abs.starts_with(&to_abs) {
+        anyhow::bail!("The new location must be outside of the repo, and the repo outside of it");
+    }
+    if to.exists() {
+        let mut entries =
+            std::fs::read_dir(to).with_context(|| format!("{} is not a folder", to.display()))?;
+        if entries.next().is_some() {
+            anyhow::bail!("{} is not empty", to.display());
+        }
+    }
+
+    info!(log, "Relocate {:?} to {:?}", from, to);
+    mirror_dir(from, to)?;
+    let (expected, copied) = (file_sizes(from)?, file_sizes(to)?);
+    if expected != copied {
+        anyhow::bail!(
+            "The copy differs from the repo ({} files, {} copied)",
+            expected.len(),
+            copied.len()
+        );
+    }
+    let original = rdedup::open_existing(from, log.clone())?;
+    let repo = rdedup::open_existing(to, log.clone()).context("Opening the copy")?;
+    rdedup::check_passphrase(&repo, passphrase).context("Unlocking the copy")?;
+    let mut names = (original.list_names()?, repo.list_names()?);
+    names.0.sort();
+    names.1.sort();
+    if names.0 != names.1 {
+        anyhow::bail!("The copy has other snapshots than the repo");
+    }
+    Ok(repo)
+}
+
+/// `relocate` on a blocking thread
+pub async fn relocate_async(
+    from: PathBuf,
+    to: PathBuf,
+    passphrase: String,
+    log: Logger,
+) -> Result<Redacted<Repo>, String> {
+    tokio::task::spawn_blocking(move || relocate(&from, &to, passphrase, &log))
+        .await
+        .map_err(|e| e.to_string())?
+        .map(Redacted)
+        .map_err(|e| format!("{:#}", e))
+}
+
+/// Delete the old home of a relocated repo
+pub async fn remove_old_home_async(home: PathBuf) -> Result<(), String> {
+    tokio::task::spawn_blocking(move || {
+        std::fs::remove_dir_all(&home).map_err(|e| format!("Deleting {}: {}", home.display(), e))
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Check that `disk` can be duplicated to: it is a folder and a file can be written there
+pub fn test_disk(disk: &Path) -> anyhow::Result<()> {
+    if disk.as_os_str().is_empty() {
+        anyhow::bail!("No folder chosen");
+    }
+    if !disk.is_dir() {
+        anyhow::bail!("{} is not available", disk.display());
+    }
+    let probe = disk.join(format!(".bup-probe-{}", std::process::id()));
+    let written: anyhow::Result<()> = try {
+        std::fs::write(&probe, b"bup").context("Writing a test file")?;
+        if std::fs::read(&probe).context("Reading the test file")? != b"bup" {
+            Err(anyhow::anyhow!("The test file reads back differently"))?;
+        }
+    };
+    // Clean up even if reading failed
+    let removed = std::fs::remove_file(&probe).context("Deleting the test file");
+    written
+        .and(removed)
+        .with_context(|| format!("Testing {}", disk.display()))
+}
+
+/// `test_disk` for each of `disks` on a blocking thread
+pub async fn test_disks_async(disks: Vec<PathBuf>) -> Vec<Result<(), String>> {
+    let n = disks.len();
+    tokio::task::spawn_blocking(move || {
+        disks
+            .iter()
+            .map(|disk| test_disk(disk).map_err(|e| format!("{:#}", e)))
+            .collect()
+    })
+    .await
+    .unwrap_or_else(|e| vec![Err(e.to_string()); n])
+}
+
+/// Space on the disk of the duplication destination `disk`, or `None` if it isn't there (e.g. an
+/// unmounted disk)
+pub fn disk_space(disk: &Path) -> anyhow::Result<Option<rdedup::Space>> {
+    if !disk.is_dir() {
+        return Ok(None);
+    }
+    rdedup::space(disk).map(Some)
+}
+
+/// `disk_space` on a blocking thread, since it may hang on network disks
+pub async fn disk_space_async(disk: PathBuf) -> Result<Option<rdedup::Space>, String> {
+    tokio::task::spawn_blocking(move || disk_space(&disk))
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| format!("{:#}", e))
+}
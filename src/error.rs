@@ -0,0 +1,57 @@
+//! Errors of bup, kept apart by kind until they are shown to the user.
+//!
+//! The variants carry the message of the underlying error rather than the error itself, since
+//! results travel in iced messages, which must be `Clone`.
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BupError {
+    /// A target that can't be saved or backed up as it is
+    InvalidTarget(String),
+    /// A repo that can't be saved as it is
+    InvalidRepo(String),
+    /// Opening or creating an rdedup repo failed
+    RepoOpen(String),
+    Backup(String),
+    Duplication(String),
+    Io(String),
+    /// The background task doing the work failed, e.g. by panicking
+    Task(String),
+}
+impl BupError {
+    /// The message, without the kind of error
+    pub fn message(&self) -> &str {
+        match self {
+            BupError::InvalidTarget(message)
+            | BupError::InvalidRepo(message)
+            | BupError::RepoOpen(message)
+            | BupError::Backup(message)
+            | BupError::Duplication(message)
+            | BupError::Io(message)
+            | BupError::Task(message) => message,
+        }
+    }
+    pub fn backup(e: anyhow::Error) -> Self {
+        BupError::Backup(format!("{:#}", e))
+    }
+    pub fn repo_open(e: anyhow::Error) -> Self {
+        BupError::RepoOpen(format!("{:#}", e))
+    }
+}
+impl fmt::Display for BupError {
+    /// Only the message; where it is shown tells what failed
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message())
+    }
+}
+impl std::error::Error for BupError {}
+impl From<std::io::Error> for BupError {
+    fn from(e: std::io::Error) -> Self {
+        BupError::Io(e.to_string())
+    }
+}
+impl From<tokio::task::JoinError> for BupError {
+    fn from(e: tokio::task::JoinError) -> Self {
+        BupError::Task(e.to_string())
+    }
+}
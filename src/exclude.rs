@@ -0,0 +1,146 @@
+//! Matching of exclude patterns the way `tar --exclude` does, for previewing them.
+//!
+//! tar's defaults for `--exclude` are `--wildcards`, `--wildcards-match-slash` and
+//! `--no-anchored`: `*`, `?` and `[...]` are wildcards, `*` also matches `/`, and a pattern
+//! matches a path if it matches the whole path or any part of it that starts after a `/`.
+use crate::*;
+
+/// Stop scanning after this many files, so that a preview of a huge source stays quick
+pub const MAX_PREVIEW_SCANNED: usize = 100_000;
+/// Number of matched paths listed in a preview
+pub const PREVIEW_SAMPLE: usize = 20;
+
+/// Check that `pattern` is a well-formed exclude pattern
+pub fn validate(pattern: &str) -> Result<(), String> {
+    if pattern.is_empty() {
+        return Err("No exclude should be empty".to_string());
+    }
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                if chars.next().is_none() {
+                    return Err(format!("Exclude {:?} ends with a lone '\\'", pattern));
+                }
+            }
+            '[' => {
+                if !chars.any(|c| c == ']') {
+                    return Err(format!("Exclude {:?} has an unclosed '['", pattern));
+                }
+            }
+            _ => (),
+        }
+    }
+    Ok(())
+}
+
+/// Whether tar would exclude `path` because of `pattern`
+pub fn matches(pattern: &str, path: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let path: Vec<char> = path.chars().collect();
+    std::iter::once(0)
+        .chain(
+            path.iter()
+                .enumerate()
+                .filter(|(_, c)| **c == '/')
+                .map(|(i, _)| i + 1),
+        )
+        .any(|start| glob(&pattern, &path[start..]))
+}
+
+/// `fnmatch` without `FNM_PATHNAME`, so `*` matches `/`
+fn glob(pattern: &[char], text: &[char]) -> bool {
+    match pattern.split_first() {
+        None => text.is_empty(),
+        Some(('*', rest)) => (0..=text.len()).any(|i| glob(rest, &text[i..])),
+        Some(('?', rest)) => !text.is_empty() && glob(rest, &text[1..]),
+        Some(('[', rest)) => {
+            let end = match rest.iter().skip(1).position(|c| *c == ']') {
+                Some(end) => end + 1,
+                // Not a class; a literal '['
+                None => return text.first() == Some(&'[') && glob(rest, &text[1..]),
+            };
+            let (class, rest) = (&rest[..end], &rest[end + 1..]);
+            match text.first() {
+                Some(c) if in_class(class, *c) => glob(rest, &text[1..]),
+                _ => false,
+            }
+        }
+        Some(('\\', rest)) if !rest.is_empty() => {
+            text.first() == Some(&rest[0]) && glob(&rest[1..], &text[1..])
+        }
+        Some((c, rest)) => text.first() == Some(c) && glob(rest, &text[1..]),
+    }
+}
+
+fn in_class(class: &[char], c: char) -> bool {
+    let (negated, class) = match class.split_first() {
+        Some(('!', rest)) | Some(('^', rest)) => (true, rest),
+        _ => (false, class),
+    };
+    let mut found = false;
+    let mut i = 0;
+    while i < class.len() {
+        if i + 2 < class.len() && class[i + 1] == '-' {
+            found |= class[i] <= c && c <= class[i + 2];
+            i += 3;
+        } else {
+            found |= class[i] == c;
+            i += 1;
+        }
+    }
+    found != negated
+}
+
+/// What an exclude pattern matches within the sources of a target
+#[derive(Debug, Clone, Default)]
+pub struct ExcludePreview {
+    /// The first few matched paths
+    pub sample: Vec<PathBuf>,
+    /// Number of matched paths. A matched directory counts once; tar skips its contents.
+    pub count: usize,
+    pub scanned: usize,
+    /// Whether scanning stopped at `MAX_PREVIEW_SCANNED`
+    pub capped: bool,
+}
+
+/// Walk `sources`, applying just `pattern`
+pub fn preview(sources: &[PathBuf], pattern: &str) -> Result<ExcludePreview, String> {
+    validate(pattern)?;
+    let mut preview = ExcludePreview::default();
+    let mut stack: Vec<PathBuf> = sources.to_vec();
+    while let Some(path) = stack.pop() {
+        if preview.scanned >= MAX_PREVIEW_SCANNED {
+            preview.capped = true;
+            break;
+        }
+        preview.scanned += 1;
+        if matches(pattern, &path.to_string_lossy()) {
+            preview.count += 1;
+            if preview.sample.len() < PREVIEW_SAMPLE {
+                preview.sample.push(path);
+            }
+            continue;
+        }
+        let is_dir = std::fs::symlink_metadata(&path)
+            .map(|m| m.is_dir())
+            .unwrap_or(false);
+        if is_dir {
+            // Unreadable directories are skipped, like tar does (with a warning)
+            if let Ok(entries) = std::fs::read_dir(&path) {
+                stack.extend(entries.flatten().map(|entry| entry.path()));
+            }
+        }
+    }
+    Ok(preview)
+}
+
+/// `preview` on a blocking thread
+pub async fn preview_async(
+    sources: Vec<PathBuf>,
+    pattern: String,
+) -> Result<ExcludePreview, String> {
+    tokio::task::spawn_blocking(move || preview(&sources, &pattern))
+        .await
+        .map_err(|e| e.to_string())?
+}
@@ -0,0 +1,54 @@
+//! Bundles of exclude patterns for directories that are rarely worth backing up.
+//!
+//! Targets store which presets are enabled rather than the patterns, so that the patterns can be
+//! improved here and apply to existing targets.
+use crate::*;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ExcludePreset {
+    General,
+    Rust,
+    Node,
+    Python,
+}
+impl ExcludePreset {
+    pub const ALL: [ExcludePreset; 4] = [
+        ExcludePreset::General,
+        ExcludePreset::Rust,
+        ExcludePreset::Node,
+        ExcludePreset::Python,
+    ];
+    /// Patterns passed to `tar` via `--exclude`. Without a `/`, they match a file name anywhere.
+    pub fn patterns(&self) -> &'static [&'static str] {
+        match self {
+            ExcludePreset::General => &[".cache", ".thumbnails", "*.tmp", "*.swp", ".Trash-*"],
+            // See `options`: a pattern like `target` would also hit unrelated directories
+            ExcludePreset::Rust => &[],
+            ExcludePreset::Node => &["node_modules", ".npm", ".yarn/cache"],
+            ExcludePreset::Python => &["__pycache__", "*.pyc", ".venv", ".tox", ".mypy_cache"],
+        }
+    }
+    /// Extra `tar` options, for presets that can't be expressed as patterns. Only GNU tar has
+    /// them; bsdtar refuses unknown options.
+    pub fn options(&self, tar: backup::TarImpl) -> &'static [&'static str] {
+        match (self, tar) {
+            // Cargo marks `target` directories with a `CACHEDIR.TAG`
+            (ExcludePreset::Rust, backup::TarImpl::Gnu) => &["--exclude-caches"],
+            _ => &[],
+        }
+    }
+    /// Whether the preset excludes anything with `tar`
+    pub fn supported(&self, tar: backup::TarImpl) -> bool {
+        !self.patterns().is_empty() || !self.options(tar).is_empty()
+    }
+}
+impl std::fmt::Display for ExcludePreset {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ExcludePreset::General => write!(f, "General (caches, temp files)"),
+            ExcludePreset::Rust => write!(f, "Rust (target)"),
+            ExcludePreset::Node => write!(f, "Node (node_modules)"),
+            ExcludePreset::Python => write!(f, "Python (__pycache__, venvs)"),
+        }
+    }
+}
@@ -0,0 +1,252 @@
+//! Export the config for use on another machine, with paths translated by prefix rewrites
+use super::*;
+
+/// File name of the exported config, in the chosen folder
+pub const EXPORT_FILE_NAME: &str = "bup-config-export.json";
+
+#[derive(Debug, Clone)]
+pub enum ExportMessage {
+    NewRewrite,
+    SetFrom(usize, String),
+    SetTo(usize, String),
+    DelRewrite(usize),
+    Dest(path::Message),
+    Export,
+
+    // Meant for outside
+    /// Back button pressed
+    Back,
+}
+
+/// Replace the prefix `from` by `to`
+#[derive(Default)]
+pub struct Rewrite {
+    pub from: String,
+    pub to: String,
+
+    s_from: text_input::State,
+    s_to: text_input::State,
+    s_delete: button::State,
+}
+
+#[derive(Default)]
+pub struct ExportWizard {
+    pub rewrites: Vec<Rewrite>,
+    pub dest: Option<PathBuf>,
+    result: Option<Result<PathBuf, String>>,
+
+    s_new_rewrite: button::State,
+    s_dest: FilePicker,
+    s_export: button::State,
+    s_back: button::State,
+    s_scrollable: scrollable::State,
+}
+
+/// Apply the first rewrite whose `from` is a prefix of `path`
+pub fn rewrite_path(path: &Path, rewrites: &[(PathBuf, PathBuf)]) -> PathBuf {
+    for (from, to) in rewrites {
+        if let Ok(rest) = path.strip_prefix(from) {
+            return to.join(rest);
+        }
+    }
+    path.to_path_buf()
+}
+
+/// All machine-specific paths of `config`: repo homes, sources and duplication destinations
+pub fn config_paths_mut(config: &mut Config) -> Vec<&mut PathBuf> {
+    let mut paths = Vec::new();
+    for repo in config.repos.values_mut() {
+        paths.push(&mut repo.home);
+        for target in &mut repo.targets {
+            for source in &mut target.sources {
+                if let Some(ref mut path) = source.path {
+                    paths.push(path);
+                }
+            }
+            for duplication in &mut target.duplication {
+                paths.extend(duplication.kind.paths_mut());
+            }
+        }
+    }
+    paths
+}
+
+/// Copy of `config` with all its paths rewritten.
+/// Fails if a rewritten path is not absolute.
+pub fn rewrite_config(config: &Config, rewrites: &[(PathBuf, PathBuf)]) -> Result<Config, String> {
+    let mut config = config.clone();
+    for path in config_paths_mut(&mut config) {
+        let rewritten = rewrite_path(path, rewrites);
+        if !rewritten.is_absolute() {
+            return Err(format!(
+                "{} would become {}, which is not absolute",
+                path.display(),
+                rewritten.display()
+            ));
+        }
+        *path = rewritten;
+    }
+    Ok(config)
+}
+
+impl ExportWizard {
+    fn rewrites(&self) -> Vec<(PathBuf, PathBuf)> {
+        self.rewrites
+            .iter()
+            .filter(|rewrite| !rewrite.from.is_empty())
+            .map(|rewrite| (PathBuf::from(&rewrite.from), PathBuf::from(&rewrite.to)))
+            .collect()
+    }
+    pub fn view(&mut self, config: &Config) -> Element<'_, ExportMessage> {
+        let rewrites = self.rewrites();
+        // Preview of what each path becomes
+        let mut preview = Column::new().spacing(4).push(h3("Paths"));
+        let mut config_copy = config.clone();
+        for path in config_paths_mut(&mut config_copy) {
+            let rewritten = rewrite_path(path, &rewrites);
+            let (note, color) = if !rewritten.is_absolute() {
+                (" (not absolute!)", Color::from_rgb(0.8, 0.2, 0.2))
+            } else if !rewritten.exists() {
+                // Fine if it exists on the other machine
+                (" (does not exist here)", Color::from_rgb(0.9, 0.6, 0.2))
+            } else {
+                ("", Color::from_rgb(0.8, 0.8, 0.8))
+            };
+            preview = preview.push(
+                Text::new(format!(
+                    "{} -> {}{}",
+                    path.display(),
+                    rewritten.display(),
+                    note
+                ))
+                .size(TEXT_SIZE - 4)
+                .color(color),
+            );
+        }
+
+        let mut rows = Column::new().spacing(4);
+        for (i, rewrite) in self.rewrites.iter_mut().enumerate() {
+            rows = rows.push(
+                Row::new()
+                    .spacing(10)
+                    .push(
+                        TextInput::new(
+                            &mut rewrite.s_from,
+                            "From prefix",
+                            &rewrite.from,
+                            move |s| ExportMessage::SetFrom(i, s),
+                        )
+                        .style(style::TextInput)
+                        .size(TEXT_SIZE),
+                    )
+                    .push(Text::new("->"))
+                    .push(
+                        TextInput::new(&mut rewrite.s_to, "To prefix", &rewrite.to, move |s| {
+                            ExportMessage::SetTo(i, s)
+                        })
+                        .style(style::TextInput)
+                        .size(TEXT_SIZE),
+                    )
+                    .push(
+                        Button::new(&mut rewrite.s_delete, Icon::Delete.text())
+                            .on_press(ExportMessage::DelRewrite(i))
+                            .padding(0)
+                            .style(style::Button::Icon {
+                                hover_color: Color::from_rgb(0.7, 0.2, 0.2),
+                            }),
+                    ),
+            );
+        }
+
+        let mut column = Column::new()
+            .padding(20)
+            .spacing(20)
+            .push(h3("Export config for another machine"))
+            .push(
+                Row::new().spacing(20).push(h3("Path rewrites")).push(
+                    Button::new(&mut self.s_new_rewrite, Icon::New.text())
+                        .padding(BUTTON_PAD)
+                        .style(style::Button::Icon {
+                            hover_color: Color::WHITE,
+                        })
+                        .on_press(ExportMessage::NewRewrite),
+                ),
+            )
+            .push(rows)
+            .push(preview)
+            .push(
+                Row::new()
+                    .spacing(8)
+                    .push(Text::new("Export to folder:"))
+                    .push(
+                        self.s_dest
+                            .view(self.dest.as_deref(), TEXT_SIZE)
+                            .map(ExportMessage::Dest),
+                    ),
+            )
+            .push(
+                Row::new()
+                    .spacing(10)
+                    .push(
+                        Button::new(&mut self.s_back, Text::new("BACK").size(TEXT_SIZE - 4))
+                            .padding(8)
+                            .style(style::Button::Text)
+                            .on_press(ExportMessage::Back),
+                    )
+                    .push(
+                        Button::new(&mut self.s_export, Text::new("EXPORT").size(TEXT_SIZE - 4))
+                            .padding(8)
+                            .style(style::Button::Primary)
+                            .on_press(ExportMessage::Export),
+                    ),
+            );
+        match self.result {
+            Some(Ok(ref path)) => {
+                column = column.push(Text::new(format!("Exported to {}", path.display())))
+            }
+            Some(Err(ref error)) => {
+                column = column.push(
+                    Text::new(format!("Error: {}", error)).color(Color::from_rgb(0.5, 0.0, 0.0)),
+                )
+            }
+            None => (),
+        }
+        let x = Container::new(column)
+            .style(style::DialogContainer)
+            .width(Length::Fill)
+            .max_width(1000)
+            .height(Length::Shrink);
+        Scrollable::new(&mut self.s_scrollable).push(x).into()
+    }
+    pub fn update(&mut self, message: ExportMessage, config: &Config) -> Command<ExportMessage> {
+        match message {
+            ExportMessage::NewRewrite => self.rewrites.push(Default::default()),
+            ExportMessage::SetFrom(i, from) => self.rewrites[i].from = from,
+            ExportMessage::SetTo(i, to) => self.rewrites[i].to = to,
+            ExportMessage::DelRewrite(i) => {
+                self.rewrites.remove(i);
+            }
+            ExportMessage::Dest(msg) => {
+                if let path::Message::Path(ref path) = msg {
+                    self.dest = Some(path.clone());
+                }
+                return self.s_dest.update(msg).map(ExportMessage::Dest);
+            }
+            ExportMessage::Export => {
+                let result: Result<PathBuf, String> = try {
+                    let dest = self.dest.as_ref().ok_or("Choose a folder to export to")?;
+                    let exported = rewrite_config(config, &self.rewrites())?;
+                    let json =
+                        serde_json::to_string_pretty(&exported).map_err(|e| e.to_string())?;
+                    let path = dest.join(EXPORT_FILE_NAME);
+                    std::fs::write(&path, json)
+                        .map_err(|e| format!("Cannot write {}: {}", path.display(), e))?;
+                    path
+                };
+                self.result = Some(result);
+            }
+            ExportMessage::Back => (),
+        }
+        Command::none()
+    }
+}
@@ -0,0 +1,91 @@
+//! Backing up without the GUI, for system timers (see `timer`): `bup --backup <target id>`
+use crate::*;
+
+/// Environment variable with the app passphrase, used when it is not remembered in the keyring
+pub const PASSPHRASE_VAR: &str = "BUP_PASSPHRASE";
+
+/// Back up the target with id `target_id` and record the result in the config.
+///
+/// A GUI running at the same time may overwrite the recorded result when it saves the config;
+/// the snapshot itself is not affected.
+pub fn backup(target_id: Uuid) -> anyhow::Result<()> {
+    let log = log::logger();
+    let mut config = Config::load().context("Could not load the config file")?;
+    log::configure(config.log_level, config.log_file_location);
+    let (repo_id, index) = config
+        .repos
+        .values()
+        .find_map(|repo| {
+            let index = repo.targets.iter().position(|t| t.id == target_id)?;
+            Some((repo.id, index))
+        })
+        .with_context(|| format!("No target with id {}", target_id))?;
+    let repo_config = &config.repos[&repo_id];
+    let target = repo_config.targets[index].clone();
+    if repo_config.read_only {
+        info!(log, "Not backing up {}: the repo is read-only", target.name);
+        return Ok(());
+    }
+    verify_target(&target)?;
+
+    let passphrase = match std::env::var(PASSPHRASE_VAR) {
+        Ok(passphrase) => passphrase,
+        Err(_) if config.remember_passphrase => {
+            keychain::load(&log).context("The passphrase is not in the keyring")?
+        }
+        Err(_) => anyhow::bail!(
+            "The passphrase is not remembered. Remember it in bup, or set {}.",
+            PASSPHRASE_VAR
+        ),
+    };
+    let repo = rdedup::open_existing(&repo_config.home, log.clone())?;
+    rdedup::check_passphrase(&repo, passphrase.clone())?;
+
+    info!(log, "Backing up {}", target.name);
+    let mut options = config.backup_options(repo_id);
+    let name = rdedup::snapshot_name(&target.name, Utc::now());
+    options.snapshot_name = Some(name.clone());
+    // Cleaned up by the GUI if this process doesn't get to record the result
+    config.repos.get_mut(&repo_id).unwrap().targets[index].interrupted_backup = Some(name);
+    config.save()?;
+    let started = Instant::now();
+    let result =
+        backup::run_backup(&repo, &target, passphrase, options, &log).map_err(BupError::backup);
+    let entry = history::Entry::backup(repo_id, target.name.clone(), &result, Some(started));
+    history::append(&entry, &log);
+    let repo_config = &config.repos[&repo_id];
+    if notify::wanted(&entry, repo_config.notify_on_success) {
+        if let Err(e) = notify::notify(&repo_config.notifiers, &entry) {
+            error!(log, "Sending notifications failed: {}", e);
+        }
+    }
+
+    let repo_config = config.repos.get_mut(&repo_id).unwrap();
+    if let Some(target) = repo_config.targets.get_mut(index) {
+        target.last_result = Some(LastResult::new(&result));
+        target.interrupted_backup = None;
+        if let Ok(ref done) = result {
+            target.record_size(&done.snapshot);
+        }
+    }
+    if let Ok(backup::BackupDone {
+        ref snapshot,
+        sha256: Some(ref sha256),
+        ..
+    }) = result
+    {
+        repo_config
+            .snapshot_checksums
+            .insert(snapshot.name.clone(), sha256.clone());
+    }
+    if let Ok(ref done) = result {
+        record_verification(repo_config, done);
+    }
+    config.save()?;
+    let done = result?;
+    for warning in &done.warnings {
+        info!(log, "Warning: {}", warning);
+    }
+    info!(log, "Backup done: {}", done.snapshot.name);
+    Ok(())
+}
@@ -0,0 +1,187 @@
+//! At-a-glance summary of whether the backups of a repo are working
+use crate::*;
+
+/// A target whose newest snapshot is older than this is flagged
+pub const MAX_BACKUP_AGE_DAYS: i64 = 7;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Ok,
+    Warning,
+    Error,
+}
+impl Level {
+    pub fn color(&self) -> Color {
+        match self {
+            Level::Ok => Color::from_rgb(0.2, 0.7, 0.2),
+            Level::Warning => Color::from_rgb(0.9, 0.6, 0.2),
+            Level::Error => Color::from_rgb(0.8, 0.2, 0.2),
+        }
+    }
+}
+impl std::fmt::Display for Level {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Level::Ok => write!(f, "HEALTHY"),
+            Level::Warning => write!(f, "WARNING"),
+            Level::Error => write!(f, "ERROR"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Signal {
+    pub level: Level,
+    pub description: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct RepoHealth {
+    pub signals: Vec<Signal>,
+    /// When the signals were computed
+    pub computed: DateTime<Utc>,
+}
+impl RepoHealth {
+    /// The worst level among the signals
+    pub fn level(&self) -> Level {
+        self.signals
+            .iter()
+            .map(|signal| signal.level)
+            .max()
+            .unwrap_or(Level::Ok)
+    }
+}
+
+/// Compute the health of a repo. Lists all snapshots and measures the free space of the disks, so
+/// it should not run on the UI thread.
+pub fn check(repo: &Repo, config: &RepoConfig) -> RepoHealth {
+    let now = Utc::now();
+    let mut signals = Vec::new();
+    let mut signal = |level, description: String| signals.push(Signal { level, description });
+
+    match repo.list_names() {
+        Ok(names) => {
+            for target in &config.targets {
+                let newest = names
+                    .iter()
+                    .filter_map(|name| rdedup::parse_snapshot_name(name))
+                    .filter(|(target_name, _)| *target_name == target.name)
+                    .map(|(_, time)| time)
+                    .max();
+                match newest {
+                    None => signal(
+                        Level::Error,
+                        format!("{} has never been backed up", target.name),
+                    ),
+                    Some(time) => {
+                        let age = now - time;
+                        let level = if age > chrono::Duration::days(MAX_BACKUP_AGE_DAYS) {
+                            Level::Warning
+                        } else {
+                            Level::Ok
+                        };
+                        signal(
+                            level,
+                            format!("{}: last backup {} ago", target.name, format_duration(age)),
+                        );
+                    }
+                }
+            }
+        }
+        Err(e) => signal(Level::Error, format!("Cannot list snapshots: {}", e)),
+    }
+
+    for (name, error) in &config.failed_verifications {
+        signal(
+            Level::Error,
+            format!("{} could not be read back: {}", name, error),
+        );
+    }
+
+    match rdedup::space(&config.home) {
+        Ok(space) if space.available < config.critical_space_bytes => signal(
+            Level::Error,
+            format!(
+                "Only {} free on the disk of the repo, backups are refused",
+                format_bytes(space.available)
+            ),
+        ),
+        Ok(space) if space.available < config.low_space_bytes => signal(
+            Level::Warning,
+            format!(
+                "Only {} free on the disk of the repo",
+                format_bytes(space.available)
+            ),
+        ),
+        Ok(_) => (),
+        Err(e) => signal(Level::Warning, format!("{:#}", e)),
+    }
+
+    for target in &config.targets {
+        for duplication in &target.duplication {
+            let available = match duplication.kind {
+                DuplicationKind::Disk { ref path } => Some(path.is_dir()),
+                // One disk being mounted is enough
+                DuplicationKind::DiskRotation { ref paths, .. } => {
+                    Some(duplicate::pick_disk(paths, None).is_some())
+                }
+                // No disk to look for; `last_error` tells whether they are reachable
+                DuplicationKind::Command { .. } | DuplicationKind::Sftp { .. } => None,
+            };
+            if available == Some(false) {
+                signal(
+                    Level::Warning,
+                    format!("{}: {} is not available", target.name, duplication.kind),
+                );
+            }
+            let rotation = matches!(duplication.kind, DuplicationKind::DiskRotation { .. });
+            for disk in duplication.kind.paths() {
+                // An unplugged disk of a rotation would measure the root file system
+                if rotation && !duplicate::is_mounted(disk) {
+                    continue;
+                }
+                match duplicate::disk_space(disk) {
+                    Ok(Some(space)) if space.available < config.low_space_bytes => signal(
+                        Level::Warning,
+                        format!(
+                            "{}: only {} free on {}",
+                            target.name,
+                            format_bytes(space.available),
+                            disk.display()
+                        ),
+                    ),
+                    Ok(_) => (),
+                    Err(e) => signal(Level::Warning, format!("{}: {:#}", target.name, e)),
+                }
+            }
+            if let Some(ref error) = duplication.last_error {
+                signal(
+                    Level::Warning,
+                    format!(
+                        "{}: last run of {} failed: {}",
+                        target.name, duplication.kind, error
+                    ),
+                );
+            }
+        }
+    }
+
+    RepoHealth {
+        signals,
+        computed: now,
+    }
+}
+
+/// `check` on a blocking thread
+pub async fn check_async(repo: Repo, config: RepoConfig) -> RepoHealth {
+    let computed = Utc::now();
+    tokio::task::spawn_blocking(move || check(&repo, &config))
+        .await
+        .unwrap_or_else(|e| RepoHealth {
+            signals: vec![Signal {
+                level: Level::Error,
+                description: format!("Health check failed: {}", e),
+            }],
+            computed,
+        })
+}
@@ -0,0 +1,95 @@
+//! Audit log of finished backups, duplications and restores for external tooling: one JSON
+//! object per line in `history.jsonl` in the data dir. Only ever appended to.
+use crate::*;
+use std::io::Write;
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Action {
+    Backup,
+    Duplication,
+    Restore,
+}
+
+/// One line of the log
+#[derive(Debug, Clone, Serialize)]
+pub struct Entry {
+    /// When it finished
+    pub time: DateTime<Utc>,
+    pub repo: Uuid,
+    pub target: String,
+    pub action: Action,
+    /// Size of the snapshot written or read, if known
+    pub bytes: Option<u64>,
+    pub duration_secs: Option<f64>,
+    /// `ok`, `warnings` or `failed`
+    pub result: &'static str,
+    pub error: Option<String>,
+    pub warnings: Vec<String>,
+}
+impl Entry {
+    pub fn new(action: Action, repo: Uuid, target: String, result: Result<(), String>) -> Self {
+        Self {
+            time: Utc::now(),
+            repo,
+            target,
+            action,
+            bytes: None,
+            duration_secs: None,
+            result: if result.is_ok() { "ok" } else { "failed" },
+            error: result.err(),
+            warnings: Vec::new(),
+        }
+    }
+    pub fn backup(
+        repo: Uuid,
+        target: String,
+        result: &Result<backup::BackupDone, BupError>,
+        started: Option<Instant>,
+    ) -> Self {
+        let mut entry = Entry::new(
+            Action::Backup,
+            repo,
+            target,
+            result.as_ref().map(|_| ()).map_err(|e| e.to_string()),
+        );
+        if let Ok(done) = result {
+            entry.bytes = done.snapshot.bytes.map(|bytes| bytes as u64);
+            entry.warnings = done.warnings.clone();
+            if !done.warnings.is_empty() {
+                entry.result = "warnings";
+            }
+            // Written, but not restorable as far as anyone can tell
+            if let Some(Err(ref e)) = done.verified {
+                entry.result = "failed";
+                entry.error = Some(format!("Reading the snapshot back failed: {}", e));
+            }
+        }
+        entry.duration_secs = started.map(|started| started.elapsed().as_secs_f64());
+        entry
+    }
+}
+
+pub fn history_path() -> PathBuf {
+    data_dir().join("history.jsonl")
+}
+
+/// Append `entry` as one line, flushed to disk before returning. Failures are only logged, so
+/// that they never fail the recorded operation.
+pub fn append(entry: &Entry, log: &Logger) {
+    let path = history_path();
+    let result: anyhow::Result<()> = try {
+        let mut line = serde_json::to_string(entry)?;
+        line.push('\n');
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)?;
+        // One write per line, so that concurrent writers (e.g. `bup --backup`) don't interleave
+        file.write_all(line.as_bytes())?;
+        file.sync_data()?;
+    };
+    if let Err(e) = result {
+        error!(log, "Appending to {}: {:#}", path.display(), e);
+    }
+}
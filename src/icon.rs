@@ -10,6 +10,19 @@ pub enum Icon {
     New,
     Settings,
     Repo,
+    Ok,
+    Warning,
+    Failed,
+    Lock,
+    Unlock,
+    /// Of a collapsed section
+    Expand,
+    /// Of an expanded section
+    Collapse,
+    /// Show a hidden passphrase
+    Reveal,
+    /// Hide a shown passphrase
+    Conceal,
 }
 impl Icon {
     pub fn text(&self) -> Text {
@@ -38,6 +51,15 @@ impl Display for Icon {
                 Icon::New => '\u{f44d}', // TODO
                 Icon::Settings => '\u{f992}',
                 Icon::Repo => '\u{f401}',
+                Icon::Ok => '\u{f00c}',
+                Icon::Warning => '\u{f071}',
+                Icon::Failed => '\u{f057}',
+                Icon::Lock => '\u{f023}',
+                Icon::Unlock => '\u{f09c}',
+                Icon::Expand => '\u{f0da}',
+                Icon::Collapse => '\u{f0d7}',
+                Icon::Reveal => '\u{f06e}',
+                Icon::Conceal => '\u{f070}',
             }
         )
     }
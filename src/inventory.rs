@@ -0,0 +1,204 @@
+//! Export the list of snapshots of a repo to a CSV file, for record keeping outside of bup
+use super::*;
+use backup::RestoreProgress;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, Mutex,
+};
+
+#[derive(Debug, Clone)]
+pub enum InventoryMessage {
+    Dest(path::Message),
+    /// Refresh the shown progress of a running export
+    Progress,
+    Finished(Result<PathBuf, String>),
+    /// Cancel the running export
+    Cancel,
+    OpenDest,
+
+    // Meant for outside
+    /// Export button pressed
+    Start,
+    /// Back button pressed
+    Back,
+}
+
+/// Writing the snapshot list of the selected repo as a CSV file into a folder of choice
+#[derive(Default)]
+pub struct Inventory {
+    pub dest: Option<PathBuf>,
+    /// Written by the exporting thread. `files` counts the snapshots measured so far.
+    progress: Arc<Mutex<RestoreProgress>>,
+    /// Copy of `progress`, refreshed on `InventoryMessage::Progress`
+    shown: RestoreProgress,
+    cancel: Arc<AtomicBool>,
+    running: bool,
+    result: Option<Result<PathBuf, String>>,
+    open_error: Option<String>,
+
+    s_dest: FilePicker,
+    s_start: button::State,
+    s_cancel: button::State,
+    s_back: button::State,
+    s_open_dest: button::State,
+}
+impl Inventory {
+    pub fn is_running(&self) -> bool {
+        self.running
+    }
+    /// Start exporting in the background
+    pub fn start(
+        &mut self,
+        repo: Repo,
+        config: RepoConfig,
+        passphrase: String,
+    ) -> Command<InventoryMessage> {
+        let dest = match self.dest {
+            Some(ref dest) => dest.clone(),
+            None => {
+                self.result = Some(Err("Choose a folder to export to".to_string()));
+                return Command::none();
+            }
+        };
+        self.progress = Default::default();
+        self.shown = Default::default();
+        self.cancel = Default::default();
+        self.running = true;
+        self.result = None;
+        self.open_error = None;
+        Command::perform(
+            rdedup::export_inventory_async(
+                repo,
+                config,
+                dest,
+                passphrase,
+                self.progress.clone(),
+                self.cancel.clone(),
+            ),
+            InventoryMessage::Finished,
+        )
+    }
+    pub fn view(&mut self, repo_name: &str) -> Element<'_, InventoryMessage> {
+        let mut column = Column::new()
+            .padding(20)
+            .spacing(20)
+            .push(h3(format!("Export the snapshot list of {}", repo_name)))
+            .push(help(
+                "Writes a CSV file with the name, target, group, time, label and size of every \
+                 snapshot. Measuring the size reads the index of each snapshot, so this can take \
+                 a while for a large repo.",
+            ))
+            .push(
+                Row::new()
+                    .spacing(8)
+                    .push(Text::new("Export to folder:"))
+                    .push(
+                        self.s_dest
+                            .view(self.dest.as_deref(), TEXT_SIZE)
+                            .map(InventoryMessage::Dest),
+                    ),
+            );
+
+        if self.running {
+            let counted = match self.shown.total_files {
+                Some(total) => format!("{} / {} snapshots measured", self.shown.files, total),
+                None => "Listing snapshots...".to_string(),
+            };
+            column = column
+                .push(Text::new(counted))
+                .push(Text::new(self.shown.current_file.clone()).size(TEXT_SIZE - 4))
+                .push(
+                    Button::new(&mut self.s_cancel, Text::new("CANCEL").size(TEXT_SIZE - 4))
+                        .padding(8)
+                        .style(style::Button::Text)
+                        .on_press(InventoryMessage::Cancel),
+                );
+        } else {
+            column = column.push(
+                Row::new()
+                    .spacing(10)
+                    .push(
+                        Button::new(&mut self.s_back, Text::new("BACK").size(TEXT_SIZE - 4))
+                            .padding(8)
+                            .style(style::Button::Text)
+                            .on_press(InventoryMessage::Back),
+                    )
+                    .push(
+                        Button::new(&mut self.s_start, Text::new("EXPORT").size(TEXT_SIZE - 4))
+                            .padding(8)
+                            .style(style::Button::Primary)
+                            .on_press(InventoryMessage::Start),
+                    ),
+            );
+        }
+        match self.result {
+            Some(Ok(ref file)) => {
+                column = column.push(
+                    Row::new()
+                        .spacing(10)
+                        .push(Text::new(format!(
+                            "Exported {} snapshots to {}",
+                            self.shown.files,
+                            file.display()
+                        )))
+                        .push(
+                            Button::new(
+                                &mut self.s_open_dest,
+                                Text::new("OPEN FOLDER").size(TEXT_SIZE - 4),
+                            )
+                            .padding(8)
+                            .style(style::Button::Text)
+                            .on_press(InventoryMessage::OpenDest),
+                        ),
+                )
+            }
+            Some(Err(ref error)) => {
+                column = column.push(
+                    Text::new(format!("Error: {}", error)).color(Color::from_rgb(0.5, 0.0, 0.0)),
+                )
+            }
+            None => (),
+        }
+        if let Some(ref error) = self.open_error {
+            column = column
+                .push(Text::new(format!("Error: {}", error)).color(Color::from_rgb(0.5, 0.0, 0.0)));
+        }
+        Container::new(column)
+            .style(style::DialogContainer)
+            .width(Length::Fill)
+            .max_width(1000)
+            .height(Length::Shrink)
+            .into()
+    }
+    pub fn update(&mut self, message: InventoryMessage) -> Command<InventoryMessage> {
+        match message {
+            InventoryMessage::Dest(msg) => {
+                if let path::Message::Path(ref path) = msg {
+                    self.dest = Some(path.clone());
+                }
+                return self.s_dest.update(msg).map(InventoryMessage::Dest);
+            }
+            InventoryMessage::Progress => self.shown = self.progress.lock().unwrap().clone(),
+            InventoryMessage::Finished(result) => {
+                self.shown = self.progress.lock().unwrap().clone();
+                self.running = false;
+                self.result = Some(result);
+            }
+            InventoryMessage::Cancel => self.cancel.store(true, Ordering::Relaxed),
+            InventoryMessage::OpenDest => {
+                if let Some(ref dest) = self.dest {
+                    self.open_error = open_in_file_manager(dest).err().map(|e| {
+                        if e.kind() == std::io::ErrorKind::NotFound {
+                            "no file manager found".to_string()
+                        } else {
+                            e.to_string()
+                        }
+                    });
+                }
+            }
+            InventoryMessage::Start => (),
+            InventoryMessage::Back => (),
+        }
+        Command::none()
+    }
+}
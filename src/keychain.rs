@@ -0,0 +1,33 @@
+//! Remembering the app passphrase in the system keyring (Secret Service, Keychain, Credential
+//! Manager). The passphrase is never written to the config; only whether it is remembered.
+use crate::*;
+
+const SERVICE: &str = "bup";
+const USER: &str = "app-passphrase";
+
+fn entry() -> ::keyring::Entry {
+    ::keyring::Entry::new(SERVICE, USER)
+}
+
+/// The remembered passphrase. `None` if there is none, or the keyring is unavailable.
+pub fn load(log: &Logger) -> Option<String> {
+    match entry().get_password() {
+        Ok(passphrase) => Some(passphrase),
+        Err(e) => {
+            info!(log, "No passphrase from keyring: {}", e);
+            None
+        }
+    }
+}
+
+pub fn store(passphrase: &str) -> anyhow::Result<()> {
+    entry()
+        .set_password(passphrase)
+        .context("Storing passphrase in keyring")
+}
+
+pub fn forget() -> anyhow::Result<()> {
+    entry()
+        .delete_password()
+        .context("Removing passphrase from keyring")
+}
@@ -1,20 +1,142 @@
 use chrono::Duration;
 use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
 use slog::*;
 use slog_async::*;
 use slog_term::*;
 use std::{
     io::{self, Write},
     path::PathBuf,
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
 };
 
+/// Least severe level that is logged
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LogLevel {
+    Error,
+    Warn,
+    Info,
+    Debug,
+}
+impl LogLevel {
+    pub const ALL: [LogLevel; 4] = [
+        LogLevel::Error,
+        LogLevel::Warn,
+        LogLevel::Info,
+        LogLevel::Debug,
+    ];
+    fn slog_level(self) -> Level {
+        match self {
+            LogLevel::Error => Level::Error,
+            LogLevel::Warn => Level::Warning,
+            LogLevel::Info => Level::Info,
+            LogLevel::Debug => Level::Debug,
+        }
+    }
+}
+impl Default for LogLevel {
+    fn default() -> Self {
+        LogLevel::Info
+    }
+}
+impl std::fmt::Display for LogLevel {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            LogLevel::Error => write!(f, "Errors"),
+            LogLevel::Warn => write!(f, "Warnings"),
+            LogLevel::Info => write!(f, "Info"),
+            LogLevel::Debug => write!(f, "Debug"),
+        }
+    }
+}
+
+/// Settings applied by the drain of `logger`, so that they can change while it is in use. The level
+/// is stored as its index in `LogLevel::ALL`.
+static LEVEL: AtomicUsize = AtomicUsize::new(LogLevel::Info as usize);
+static FILE_LOCATION: AtomicBool = AtomicBool::new(false);
+
+/// Log records of `level` and more severe, with their source location if `file_location`
+pub fn configure(level: LogLevel, file_location: bool) {
+    LEVEL.store(level as usize, Ordering::Relaxed);
+    FILE_LOCATION.store(file_location, Ordering::Relaxed);
+}
+fn level() -> LogLevel {
+    LogLevel::ALL[LEVEL.load(Ordering::Relaxed)]
+}
+
+/// Environment variable with log levels per module, overriding the configured level, e.g.
+/// `BUP_LOG=warn,backup=debug`. Modules are matched by their path, with or without the leading
+/// `bup::`.
+pub const LOG_VAR: &str = "BUP_LOG";
+
+/// A directive of `LOG_VAR`: the least severe level logged in `module` (everywhere if `None`)
+struct Directive {
+    module: Option<String>,
+    level: Level,
+}
+
+lazy_static! {
+    static ref DIRECTIVES: Vec<Directive> = std::env::var(LOG_VAR)
+        .map(|spec| parse_directives(&spec))
+        .unwrap_or_default();
+}
+
+/// Parse `LOG_VAR`. Invalid directives are reported on stderr and skipped, since there is no
+/// logger yet.
+fn parse_directives(spec: &str) -> Vec<Directive> {
+    let mut directives = Vec::new();
+    for directive in spec.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+        let (module, level) = match directive.rsplit_once('=') {
+            Some((module, level)) => (Some(module.trim()), level.trim()),
+            None => (None, directive),
+        };
+        match level.parse::<Level>() {
+            Ok(level) => directives.push(Directive {
+                module: module.map(|module| module.trim_start_matches("bup::").to_string()),
+                level,
+            }),
+            Err(_) => eprintln!("Ignoring invalid {} directive {:?}", LOG_VAR, directive),
+        }
+    }
+    directives
+}
+
+/// Whether `record` is logged: by the directive of `LOG_VAR` for the most specific module
+/// containing it, or else by the configured level
+fn enabled(record: &Record) -> bool {
+    let module = record.module();
+    let module = module.strip_prefix("bup::").unwrap_or(module);
+    let best = DIRECTIVES
+        .iter()
+        .filter(|directive| match directive.module {
+            Some(ref prefix) => {
+                module == prefix.as_str()
+                    || module
+                        .strip_prefix(prefix.as_str())
+                        .map_or(false, |rest| rest.starts_with("::"))
+            }
+            None => true,
+        })
+        .max_by_key(|directive| {
+            directive
+                .module
+                .as_ref()
+                .map_or(0, |prefix| prefix.len() + 1)
+        });
+    let least_severe = match best {
+        Some(directive) => directive.level,
+        None => level().slog_level(),
+    };
+    record.level().is_at_least(least_severe)
+}
+
 pub fn logger() -> Logger {
     let decorator = TermDecorator::new().build();
     let drain = FullFormat::new(decorator)
         .use_custom_header_print(print_msg_header)
         .build()
         .fuse();
-    let drain = Filter::new(drain, |record| record.tag().is_empty()).fuse();
+    let drain = Filter::new(drain, |record| record.tag().is_empty() && enabled(record)).fuse();
     let drain = Async::new(drain).build().fuse();
     Logger::root(drain, o!())
 }
@@ -37,7 +159,7 @@ pub fn print_msg_header(
     rd.start_level()?;
     write!(rd, "{}", record.level().as_short_str())?;
 
-    if use_file_location {
+    if use_file_location || FILE_LOCATION.load(Ordering::Relaxed) {
         rd.start_location()?;
         write!(
             rd,
@@ -51,7 +173,12 @@ pub fn print_msg_header(
     rd.start_whitespace()?;
     write!(rd, " ")?;
 
-    rd.start_msg()?;
+    // Errors and warnings stand out in the color of their level (red and yellow)
+    if record.level().is_at_least(Level::Warning) {
+        rd.start_level()?;
+    } else {
+        rd.start_msg()?;
+    }
     let mut count_rd = CountingWriter::new(&mut rd);
     write!(count_rd, "{}", record.msg())?;
     Ok(count_rd.count() != 0)
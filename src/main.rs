@@ -4,36 +4,75 @@ use argon2::{
     password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
     Argon2,
 };
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Local, NaiveTime, TimeZone, Utc};
 use iced::alignment::{Horizontal, Vertical};
-use iced::{button, pick_list, scrollable, text_input};
+use iced::{button, pick_list, scrollable, text_input, tooltip};
 use iced::{Application, Color, Command, Font, Length, Settings, Subscription};
-use iced::{Button, Column, Container, Element, PickList, Row, Scrollable, Text, TextInput};
+use iced::{
+    Button, Checkbox, Column, Container, Element, PickList, ProgressBar, Row, Scrollable, Text,
+    TextInput, Tooltip,
+};
 use indexmap::IndexMap;
 use itertools::izip;
 use rdedup_lib::Repo;
 use serde::{Deserialize, Serialize};
-use slog::{error, info, Logger};
+use slog::{debug, error, info, Logger};
 use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
     path::{Path, PathBuf},
-    sync::atomic::AtomicBool,
+    sync::{atomic::AtomicBool, Arc, Mutex},
     time::{Duration, Instant},
 };
 use url::Url;
 use uuid::Uuid;
 
+mod backup;
+mod bulk_edit;
+mod demo;
+mod duplicate;
+mod error;
+mod exclude;
+mod exclude_preset;
+mod export;
 mod ext;
+mod headless;
+mod health;
+mod history;
 mod icon;
+mod inventory;
+mod keychain;
 mod log;
+mod notify;
+mod palette;
 mod path;
 mod rdedup;
+mod reconcile;
+mod repo_editor;
+mod restore;
+mod size_chart;
+mod snapshot_list;
+mod split;
 mod style;
+mod tar_export;
 mod target_editor;
+mod timer;
 mod util;
 
+pub use bulk_edit::*;
+pub use error::BupError;
+pub use exclude_preset::ExcludePreset;
+pub use export::*;
 pub use ext::*;
 pub use icon::Icon;
+pub use inventory::*;
+pub use palette::*;
 pub use path::FilePicker;
+pub use reconcile::*;
+pub use repo_editor::*;
+pub use restore::*;
+pub use snapshot_list::*;
+pub use split::*;
+pub use tar_export::*;
 pub use target_editor::*;
 pub use util::*;
 
@@ -43,6 +82,33 @@ pub const BUTTON_PAD: u16 = 2;
 
 pub type RepoSettings = rdedup_lib::settings::Repo;
 
+/// Default grace period passed to `Repo::gc`. Chunks that became unreferenced less than this long
+/// ago are kept, so that an in-flight write that still references them is not corrupted.
+pub const DEFAULT_GC_GRACE_SECS: u64 = 24 * 60 * 60;
+
+/// How long an estimate of the changes since the last backup of a target is reused
+pub const CHANGES_MAX_AGE: Duration = Duration::from_secs(10 * 60);
+
+/// Defaults of `RepoConfig::low_space_bytes` and `RepoConfig::critical_space_bytes`
+pub const DEFAULT_LOW_SPACE_BYTES: u64 = 10 << 30;
+pub const DEFAULT_CRITICAL_SPACE_BYTES: u64 = 1 << 30;
+/// Default and maximum of `RepoConfig::read_threads`
+pub const DEFAULT_READ_THREADS: usize = 2;
+pub const MAX_READ_THREADS: usize = 8;
+/// Number of deleted targets that can be restored with `Message::UndoDelete`
+pub const UNDO_DEPTH: usize = 10;
+/// Number of snapshot sizes kept per target (see `Target::size_history`)
+pub const SIZE_HISTORY_LEN: usize = 50;
+
+/// How often the free space on the disk of the selected repo is checked
+pub const FREE_SPACE_INTERVAL: Duration = Duration::from_secs(60);
+/// Changes to the config not saved explicitly are written at most this often, so that they
+/// survive the process being killed
+pub const AUTOSAVE_INTERVAL: Duration = Duration::from_secs(5);
+/// Difference between the wall clock and the monotonic clock across one tick that counts as a
+/// clock jump (see `Ui::check_clock`)
+pub const CLOCK_JUMP: Duration = Duration::from_secs(60);
+
 lazy_static::lazy_static! {
     pub static ref SHOULD_EXIT: AtomicBool = AtomicBool::new(false);
 }
@@ -50,11 +116,56 @@ lazy_static::lazy_static! {
 pub use config::*;
 mod config {
     use super::*;
+    /// Version of the config format written by this build. Bump it and add a step to `migrate`
+    /// when the format changes in a way that `#[serde(default)]` can't handle.
+    pub const CONFIG_VERSION: u32 = 2;
+
     #[derive(Clone, Debug, Serialize, Deserialize, Default)]
     pub struct Config {
+        /// Configs from before versioning have no version, and count as version 0
+        #[serde(default)]
+        pub version: u32,
         pub repos: IndexMap<Uuid, RepoConfig>,
         pub selected_repo: Option<Opt<RepoOption>>,
         pub passphrase_hash: Option<String>,
+        /// Hide repos without targets from the repo pick list
+        #[serde(default)]
+        pub hide_empty_repos: bool,
+        /// Whether the passphrase is remembered in the system keyring (see `keychain`)
+        #[serde(default)]
+        pub remember_passphrase: bool,
+        /// Backups beyond this many wait in a queue. 1 runs them one after another. Scheduled
+        /// duplications take slots too, and wait for one when due.
+        #[serde(default = "default_max_concurrent_backups")]
+        pub max_concurrent_backups: usize,
+        /// Run backups at reduced CPU and IO priority (see `backup::Priority`)
+        #[serde(default = "default_background_priority")]
+        pub background_priority: bool,
+        /// Back up all targets of the selected repo once it is unlocked at startup
+        #[serde(default)]
+        pub backup_on_startup: bool,
+        #[serde(default)]
+        pub log_level: log::LogLevel,
+        /// Include the source location of each log message, e.g. for bug reports
+        #[serde(default)]
+        pub log_file_location: bool,
+        /// How often opening the selected repo at startup is retried while its home is missing,
+        /// e.g. until a network share is mounted
+        #[serde(default = "default_open_retries")]
+        pub open_retries: u32,
+        /// Excludes that new targets start out with. They are ordinary excludes of the target
+        /// from then on; existing targets are unaffected.
+        #[serde(default)]
+        pub default_excludes: Vec<String>,
+    }
+    pub fn default_open_retries() -> u32 {
+        5
+    }
+    pub fn default_max_concurrent_backups() -> usize {
+        1
+    }
+    pub fn default_background_priority() -> bool {
+        true
     }
     impl Config {
         pub fn selected_repo_mut(&mut self) -> Option<&mut RepoConfig> {
@@ -84,40 +195,638 @@ mod config {
         /// be changed.
         pub id: Uuid,
         pub name: String,
+        /// Free text notes, e.g. why the repo exists
+        #[serde(default)]
+        pub description: String,
         pub home: PathBuf,
         pub targets: Vec<Target>,
+        /// Seconds that unreferenced chunks are kept before `gc` may delete them
+        #[serde(default = "default_gc_grace_secs")]
+        pub gc_grace_secs: u64,
+        /// Warn when the disk of `home` has less free space than this. 0 never warns.
+        #[serde(default = "default_low_space_bytes")]
+        pub low_space_bytes: u64,
+        /// Refuse backups when the disk of `home` has less free space than this, rather than
+        /// filling it up with a partial snapshot. 0 never refuses.
+        #[serde(default = "default_critical_space_bytes")]
+        pub critical_space_bytes: u64,
+        /// Demo repo living in the temp dir. Deleted on exit unless the user chooses to keep it.
+        #[serde(default)]
+        pub ephemeral: bool,
+        /// Never write to the repo, e.g. a copy mounted read-only: no backups (also not from
+        /// system timers), duplications, deletions or compactions. Restoring still works.
+        #[serde(default)]
+        pub read_only: bool,
+        /// Store a SHA-256 of each new snapshot in `snapshot_checksums`
+        #[serde(default)]
+        pub checksums: bool,
+        /// SHA-256 (hex) of snapshots by name, written when `checksums` is on
+        #[serde(default)]
+        pub snapshot_checksums: BTreeMap<String, String>,
+        /// Read each new snapshot back right after the backup, like a test restore
+        #[serde(default)]
+        pub verify_after_backup: bool,
+        /// Snapshots that could not be read back after their backup, with the error, by name.
+        /// Cleared by a successful VERIFY.
+        #[serde(default)]
+        pub failed_verifications: BTreeMap<String, String>,
+        /// Free-form tags of snapshots by name. Kept here because snapshot names can't change
+        /// without rewriting the snapshot.
+        #[serde(default)]
+        pub snapshot_tags: BTreeMap<String, Vec<String>>,
+        /// Exclude patterns applied to every target of the repo, in addition to their own
+        #[serde(default)]
+        pub repo_excludes: Vec<String>,
+        /// Names of snapshots that bup never deletes, whatever their age, e.g. when pruning
+        #[serde(default)]
+        pub pinned_snapshots: BTreeSet<String>,
+        /// Where to report failed backups and duplications, e.g. for servers nobody looks at
+        #[serde(default)]
+        pub notifiers: Vec<notify::Notifier>,
+        /// Also report backups and duplications that succeeded
+        #[serde(default)]
+        pub notify_on_success: bool,
+        /// Groups of targets collapsed in the Overview (see `Target::group`). `""` is the targets
+        /// without a group.
+        #[serde(default)]
+        pub collapsed_groups: BTreeSet<String>,
+        /// Restores and verifications reading from the repo at the same time; more wait. Few
+        /// keep a slow disk from thrashing.
+        #[serde(default = "default_read_threads")]
+        pub read_threads: usize,
         // pub settings: RepoSettings,
     }
+    /// State of a repo at a glance, for the repo pick list
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub enum RepoStatus {
+        Ok,
+        /// A target wasn't backed up within the shortest interval of its duplications
+        Overdue,
+        /// The last backup of a target failed
+        Failed,
+    }
+    impl RepoConfig {
+        /// `read_threads`, clamped to what makes sense
+        pub fn read_limit(&self) -> backup::ReadLimit {
+            backup::ReadLimit {
+                repo: self.id,
+                max: self.read_threads.clamp(1, MAX_READ_THREADS),
+            }
+        }
+        pub fn status(&self, now: DateTime<Utc>) -> RepoStatus {
+            if self.ephemeral {
+                return RepoStatus::Ok;
+            }
+            let mut status = RepoStatus::Ok;
+            for target in &self.targets {
+                let last_success = match target.last_result {
+                    Some(LastResult {
+                        status: BackupStatus::Failed,
+                        ..
+                    }) => return RepoStatus::Failed,
+                    Some(ref last_result) => Some(last_result.time),
+                    None => None,
+                };
+                let interval = target
+                    .duplication
+                    .iter()
+                    .filter_map(|duplication| chrono::Duration::from_std(duplication.interval).ok())
+                    .min();
+                if let Some(interval) = interval {
+                    if last_success.map_or(true, |time| now - time > interval) {
+                        status = RepoStatus::Overdue;
+                    }
+                }
+            }
+            status
+        }
+        /// Duplications that should be run on schedule. Ephemeral repos are never duplicated.
+        pub fn scheduled_duplications(&self) -> impl Iterator<Item = (&Target, &Duplication)> {
+            let targets = if self.ephemeral {
+                &[][..]
+            } else {
+                &self.targets[..]
+            };
+            targets.iter().flat_map(|target| {
+                target
+                    .duplication
+                    .iter()
+                    .map(move |duplication| (target, duplication))
+            })
+        }
+        /// Names of the groups of the targets, sorted (see `Target::group`)
+        pub fn groups(&self) -> Vec<String> {
+            let groups: BTreeSet<&String> = self
+                .targets
+                .iter()
+                .filter_map(|target| target.group.as_ref())
+                .collect();
+            groups.into_iter().cloned().collect()
+        }
+    }
+    fn default_gc_grace_secs() -> u64 {
+        DEFAULT_GC_GRACE_SECS
+    }
+    fn default_read_threads() -> usize {
+        DEFAULT_READ_THREADS
+    }
+    fn default_low_space_bytes() -> u64 {
+        DEFAULT_LOW_SPACE_BYTES
+    }
+    fn default_critical_space_bytes() -> u64 {
+        DEFAULT_CRITICAL_SPACE_BYTES
+    }
 
     #[derive(Clone, Debug, Serialize, Deserialize, Default)]
     pub struct Target {
+        /// Identifies the target within the app, e.g. across reorderings of the list. Configs
+        /// from before it existed get a new one.
+        #[serde(default = "Uuid::new_v4")]
+        pub id: Uuid,
         pub repo: Uuid,
         pub name: String,
+        /// Free text notes, e.g. what is backed up and the retention it needs
+        #[serde(default)]
+        pub description: String,
         /// Paths to include in the backup
-        pub sources: Vec<Option<PathBuf>>,
+        pub sources: Vec<Source>,
         /// Exclude pattern sent to `tar` via `--exclude`
         pub excludes: Vec<String>,
+        /// Exclude hidden files and directories within the sources
+        #[serde(default)]
+        pub exclude_hidden: bool,
+        /// Exclude presets, expanded into patterns at backup time
+        #[serde(default)]
+        pub exclude_presets: Vec<ExcludePreset>,
+        /// Compress the archive before it reaches rdedup
+        #[serde(default)]
+        pub pre_compress: Option<Compression>,
+        /// Archive format written by `tar`
+        #[serde(default)]
+        pub tar_format: TarFormat,
+        /// Targets with the same group are listed together in the Overview
+        #[serde(default)]
+        pub group: Option<String>,
+        /// Store the holes of sparse files (VM images, databases) as holes rather than zeros.
+        /// Restoring them as sparse files needs a tar that supports it too.
+        #[serde(default)]
+        pub handle_sparse: bool,
+        /// Shell command run before the backup. The backup is aborted if it fails.
+        #[serde(default)]
+        pub pre_hook: Option<String>,
+        /// Shell command run after the backup (also when it failed). Failure is only logged.
+        #[serde(default)]
+        pub post_hook: Option<String>,
+        /// A backup archiving more files than this asks for confirmation first
+        #[serde(default)]
+        pub max_files: Option<u64>,
+        /// A backup archiving more bytes than this asks for confirmation first
+        #[serde(default)]
+        pub max_total_bytes: Option<u64>,
+        /// Show what each backup would archive and ask for confirmation first, like for the
+        /// first backup of the target
+        #[serde(default)]
+        pub preview_backups: bool,
+        /// Outcome of the most recent backup attempt
+        #[serde(default)]
+        pub last_result: Option<LastResult>,
+        /// Snapshot written by the backup in flight. Still set at startup means that the backup
+        /// was interrupted (e.g. the app crashed); what it left behind is then cleaned up (see
+        /// `backup::discard_interrupted`).
+        #[serde(default)]
+        pub interrupted_backup: Option<String>,
+        /// Sizes of the most recent successful backups, oldest first, at most
+        /// `SIZE_HISTORY_LEN`
+        #[serde(default)]
+        pub size_history: Vec<PreviousSnapshot>,
         pub duplication: Vec<Duplication>,
     }
+    impl Target {
+        /// Whether a backup of the target has ever succeeded (as far as the config tells)
+        pub fn backed_up(&self) -> bool {
+            !self.size_history.is_empty()
+                || self
+                    .last_result
+                    .as_ref()
+                    .map_or(false, |result| result.status != BackupStatus::Failed)
+        }
+        /// Appends `snapshot` to `size_history`, dropping the oldest entries beyond the cap
+        pub fn record_size(&mut self, snapshot: &PreviousSnapshot) {
+            if snapshot.bytes.is_none() {
+                return;
+            }
+            self.size_history.push(snapshot.clone());
+            let excess = self.size_history.len().saturating_sub(SIZE_HISTORY_LEN);
+            self.size_history.drain(..excess);
+        }
+    }
+
+    /// Compression applied by `tar` itself. Restores recognize it by its magic bytes, so changing
+    /// it doesn't affect existing snapshots.
+    #[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+    pub enum Compression {
+        Gzip,
+        Zstd,
+    }
+    impl Compression {
+        pub const ALL: [Compression; 2] = [Compression::Gzip, Compression::Zstd];
+        pub fn tar_option(&self) -> &'static str {
+            match self {
+                Compression::Gzip => "--gzip",
+                Compression::Zstd => "--zstd",
+            }
+        }
+        /// Recognize a compressed archive by its first bytes
+        pub fn from_magic(head: &[u8]) -> Option<Compression> {
+            if head.starts_with(&[0x1f, 0x8b]) {
+                Some(Compression::Gzip)
+            } else if head.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+                Some(Compression::Zstd)
+            } else {
+                None
+            }
+        }
+    }
+    impl std::fmt::Display for Compression {
+        fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            match self {
+                Compression::Gzip => write!(f, "gzip"),
+                Compression::Zstd => write!(f, "zstd"),
+            }
+        }
+    }
+
+    /// Archive format of the snapshots (tar `--format`).
+    ///
+    /// Restores done by bup detect the format, so changing it doesn't affect existing snapshots.
+    /// It matters when a snapshot is restored with another tar, e.g. after `rdedup load` on a
+    /// machine without bup:
+    /// - `Posix` (pax) stores long paths, high-resolution timestamps and extended attributes in a
+    ///   standard way. Any modern tar reads it; very old ones extract the extra headers as files.
+    /// - `Gnu` supports long paths with GNU extensions, which GNU tar and bsdtar read, but e.g.
+    ///   busybox tar only partially.
+    /// - `Ustar` is read by every tar, but fails on paths longer than 255 bytes, names longer than
+    ///   100 bytes and files larger than 8 GiB, and drops extended attributes.
+    #[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+    pub enum TarFormat {
+        Gnu,
+        Posix,
+        Ustar,
+    }
+    impl Default for TarFormat {
+        fn default() -> Self {
+            TarFormat::Posix
+        }
+    }
+    impl TarFormat {
+        pub const ALL: [TarFormat; 3] = [TarFormat::Posix, TarFormat::Gnu, TarFormat::Ustar];
+        /// Value of `--format` for `tar`, or `None` if it can't write this format
+        pub fn tar_name(&self, tar: backup::TarImpl) -> Option<&'static str> {
+            use backup::TarImpl;
+            match (tar, self) {
+                (TarImpl::Gnu, TarFormat::Gnu) => Some("gnu"),
+                (TarImpl::Gnu, TarFormat::Posix) => Some("posix"),
+                (TarImpl::Gnu, TarFormat::Ustar) => Some("ustar"),
+                (TarImpl::Bsd, TarFormat::Gnu) => Some("gnutar"),
+                (TarImpl::Bsd, TarFormat::Posix) => Some("pax"),
+                (TarImpl::Bsd, TarFormat::Ustar) => Some("ustar"),
+                (TarImpl::Unknown, _) => None,
+            }
+        }
+    }
+    impl std::fmt::Display for TarFormat {
+        fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            match self {
+                TarFormat::Gnu => write!(f, "GNU"),
+                TarFormat::Posix => write!(f, "POSIX (pax)"),
+                TarFormat::Ustar => write!(f, "ustar"),
+            }
+        }
+    }
+
+    #[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+    pub enum BackupStatus {
+        Ok,
+        /// Snapshot written, but e.g. some files changed while being archived
+        Warnings,
+        Failed,
+    }
+    impl BackupStatus {
+        pub fn icon(&self) -> Icon {
+            match self {
+                BackupStatus::Ok => Icon::Ok,
+                BackupStatus::Warnings => Icon::Warning,
+                BackupStatus::Failed => Icon::Failed,
+            }
+        }
+        pub fn color(&self) -> Color {
+            match self {
+                BackupStatus::Ok => Color::from_rgb(0.2, 0.7, 0.2),
+                BackupStatus::Warnings => Color::from_rgb(0.9, 0.6, 0.2),
+                BackupStatus::Failed => Color::from_rgb(0.8, 0.2, 0.2),
+            }
+        }
+    }
+
+    #[derive(Clone, Debug, Serialize, Deserialize)]
+    pub struct LastResult {
+        pub status: BackupStatus,
+        pub time: DateTime<Utc>,
+        /// Snapshot name, warnings or error
+        pub message: String,
+    }
+    impl LastResult {
+        pub fn new(result: &Result<backup::BackupDone, BupError>) -> Self {
+            let (status, message) = match result {
+                Ok(backup::BackupDone {
+                    snapshot,
+                    verified: Some(Err(e)),
+                    ..
+                }) => (
+                    BackupStatus::Failed,
+                    format!(
+                        "{} was written, but reading it back failed: {}",
+                        snapshot.name, e
+                    ),
+                ),
+                Ok(done) if done.warnings.is_empty() => {
+                    (BackupStatus::Ok, done.snapshot.name.clone())
+                }
+                Ok(done) => (
+                    BackupStatus::Warnings,
+                    format!("{}\n{}", done.snapshot.name, done.warnings.join("\n")),
+                ),
+                Err(e) => (BackupStatus::Failed, e.to_string()),
+            };
+            Self {
+                status,
+                time: Utc::now(),
+                message,
+            }
+        }
+    }
+
+    /// Before config version 2, a source was just an `Option<PathBuf>`, which became `path`.
+    #[derive(Clone, Debug, Serialize, Deserialize, Default)]
+    pub struct Source {
+        pub path: Option<PathBuf>,
+        /// Don't cross into other file systems (tar `--one-file-system`)
+        #[serde(default)]
+        pub one_file_system: bool,
+    }
 
     #[derive(Clone, Debug, Serialize, Deserialize)]
     pub struct Duplication {
-        interval: Duration,
-        kind: DuplicationKind,
+        pub interval: Duration,
+        pub kind: DuplicationKind,
+        /// When the duplication last finished successfully
+        #[serde(default)]
+        pub last_run: Option<DateTime<Utc>>,
+        /// When the duplication was last started or resumed, successful or not
+        #[serde(default)]
+        pub last_attempt: Option<DateTime<Utc>>,
+        /// Set when the last attempt failed
+        #[serde(default)]
+        pub last_error: Option<String>,
+        /// Attempts per run, when failing with transient errors
+        #[serde(default = "default_max_attempts")]
+        pub max_attempts: u32,
+        /// Start of the run in flight. Still set at startup means that the run was interrupted
+        /// (e.g. the app was closed); it is then resumed right away, and keeps its start time.
+        #[serde(default)]
+        pub in_progress: Option<DateTime<Utc>>,
+        /// Runs only start within this daily window (local time)
+        #[serde(default)]
+        pub window: Option<TimeWindow>,
+    }
+    impl Duplication {
+        pub fn new(interval: Duration, kind: DuplicationKind) -> Self {
+            Duplication {
+                interval,
+                kind,
+                last_run: None,
+                last_attempt: None,
+                last_error: None,
+                max_attempts: default_max_attempts(),
+                in_progress: None,
+                window: None,
+            }
+        }
+        /// `None` if it has never run or was interrupted, meaning that it is due right away.
+        /// A failed run is not repeated before the next interval (it was already retried).
+        /// Runs missed while the machine was off or asleep are not caught up one by one: a single
+        /// run is due, and the next interval counts from its start.
+        pub fn next_run(&self) -> Option<DateTime<Utc>> {
+            if self.in_progress.is_some() {
+                return None;
+            }
+            let interval = chrono::Duration::from_std(self.interval).ok()?;
+            self.last_attempt
+                .or(self.last_run)
+                .map(|last_run| last_run + interval)
+        }
+        /// When the next run may start: `next_run`, deferred to the next opening of `window`.
+        /// At least `now`.
+        pub fn next_start(&self, now: DateTime<Utc>) -> DateTime<Utc> {
+            let due = self.next_run().map_or(now, |next| next.max(now));
+            match self.window {
+                Some(window) => window
+                    .next_opening(due.with_timezone(&Local))
+                    .with_timezone(&Utc),
+                None => due,
+            }
+        }
+        /// Pull times later than `now` back to `now`. They are left behind by the clock being
+        /// set back, and would otherwise hold off the next run for as long as the clock jumped.
+        /// Returns whether any time changed.
+        pub fn clamp_times(&mut self, now: DateTime<Utc>) -> bool {
+            let mut changed = false;
+            for time in [
+                &mut self.last_run,
+                &mut self.last_attempt,
+                &mut self.in_progress,
+            ] {
+                if let Some(ref mut time) = time {
+                    if *time > now {
+                        *time = now;
+                        changed = true;
+                    }
+                }
+            }
+            changed
+        }
+    }
+
+    /// Daily time window in local time. `end` before `start` spans midnight; equal means all day.
+    #[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+    pub struct TimeWindow {
+        pub start: NaiveTime,
+        pub end: NaiveTime,
+    }
+    impl Default for TimeWindow {
+        /// Overnight
+        fn default() -> Self {
+            TimeWindow {
+                start: NaiveTime::from_hms(1, 0, 0),
+                end: NaiveTime::from_hms(6, 0, 0),
+            }
+        }
+    }
+    impl TimeWindow {
+        pub fn contains(&self, time: NaiveTime) -> bool {
+            if self.start <= self.end {
+                self.start == self.end || (self.start <= time && time < self.end)
+            } else {
+                time >= self.start || time < self.end
+            }
+        }
+        /// `time` if it is within the window, else the next start of the window
+        pub fn next_opening(&self, time: DateTime<Local>) -> DateTime<Local> {
+            if self.contains(time.time()) {
+                return time;
+            }
+            let mut date = time.naive_local().date();
+            if time.time() >= self.start {
+                date = date.succ();
+            }
+            // `None` if the start falls in a DST gap; then start right away
+            Local
+                .from_local_datetime(&date.and_time(self.start))
+                .earliest()
+                .unwrap_or(time)
+        }
+    }
+    impl std::fmt::Display for TimeWindow {
+        fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            write!(
+                f,
+                "{}-{}",
+                self.start.format("%H:%M"),
+                self.end.format("%H:%M")
+            )
+        }
+    }
+    fn default_max_attempts() -> u32 {
+        3
     }
     #[derive(Clone, Debug, Serialize, Deserialize)]
     pub enum DuplicationKind {
-        Disk { path: PathBuf },
+        Disk {
+            path: PathBuf,
+        },
+        /// A set of removable disks that are swapped, e.g. one kept offsite. Each run goes to
+        /// one of the disks that is mounted.
+        DiskRotation {
+            paths: Vec<PathBuf>,
+            /// Disk of the last successful run. Another available disk is preferred next time.
+            #[serde(default)]
+            last_used: Option<PathBuf>,
+        },
+        /// An external program that copies the repo wherever it likes (rclone, a borg mirror, an
+        /// uploader). It gets `BUP_REPO_HOME`, `BUP_TARGET` and `BUP_SNAPSHOT` (the latest
+        /// snapshot of the target) in its environment, and `$NAME` or `${NAME}` of these in `args`
+        /// is replaced by the value; failure is retried like a transient error.
+        Command {
+            program: String,
+            args: Vec<String>,
+        },
+        /// A folder on a host reachable over SSH, copied to with `rsync`. Logs in with the keys
+        /// of the user (`~/.ssh`, or the SSH agent); passwords are never asked for or stored.
+        Sftp {
+            host: String,
+            /// Empty for the user of the SSH config, or else the local user
+            user: String,
+            /// Folder on the host that the repo is copied into, like the folder of a disk
+            path: String,
+            /// `None` for the port of the SSH config, or else 22
+            #[serde(default)]
+            port: Option<u16>,
+        },
         // TODO S3
         // TODO Syncthing?
     }
+    impl DuplicationKind {
+        /// Destination folders. An empty path is not set yet.
+        pub fn paths(&self) -> Vec<&PathBuf> {
+            match self {
+                DuplicationKind::Disk { path } => vec![path],
+                DuplicationKind::DiskRotation { paths, .. } => paths.iter().collect(),
+                DuplicationKind::Command { .. } | DuplicationKind::Sftp { .. } => Vec::new(),
+            }
+        }
+        /// All paths, including `last_used`
+        pub fn paths_mut(&mut self) -> Vec<&mut PathBuf> {
+            match self {
+                DuplicationKind::Disk { path } => vec![path],
+                DuplicationKind::DiskRotation {
+                    paths, last_used, ..
+                } => paths.iter_mut().chain(last_used).collect(),
+                DuplicationKind::Command { .. } | DuplicationKind::Sftp { .. } => Vec::new(),
+            }
+        }
+        /// Add a disk, turning a single disk into a rotation
+        pub fn push_path(&mut self, new_path: PathBuf) {
+            match self {
+                DuplicationKind::Disk { path } => {
+                    *self = DuplicationKind::DiskRotation {
+                        paths: vec![std::mem::take(path), new_path],
+                        last_used: None,
+                    }
+                }
+                DuplicationKind::DiskRotation { paths, .. } => paths.push(new_path),
+                // Have no disks
+                DuplicationKind::Command { .. } | DuplicationKind::Sftp { .. } => (),
+            }
+        }
+        /// Remove a disk of a rotation, turning a rotation of one disk into a single disk
+        pub fn remove_path(&mut self, i: usize) {
+            if let DuplicationKind::DiskRotation { paths, .. } = self {
+                if paths.len() > 1 {
+                    paths.remove(i);
+                }
+                if paths.len() == 1 {
+                    *self = DuplicationKind::Disk {
+                        path: paths.remove(0),
+                    };
+                }
+            }
+        }
+    }
+    impl std::fmt::Display for DuplicationKind {
+        fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            match self {
+                DuplicationKind::Disk { path } => write!(f, "Disk {}", path.display()),
+                DuplicationKind::DiskRotation { paths, .. } => write!(
+                    f,
+                    "Disks {}",
+                    paths
+                        .iter()
+                        .map(|path| path.display().to_string())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+                DuplicationKind::Command { program, args } => {
+                    write!(f, "Command {}", program)?;
+                    for arg in args {
+                        write!(f, " {}", arg)?;
+                    }
+                    Ok(())
+                }
+                DuplicationKind::Sftp { .. } => write!(f, "SSH {}", duplicate::remote_dest(self)),
+            }
+        }
+    }
 }
 
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct PreviousSnapshot {
     /// Superfluous in some cases
     pub name: String,
     pub timestamp: DateTime<Utc>,
-    pub bytes: usize,
+    /// Set when the snapshot has been renamed
+    pub label: Option<String>,
+    /// `None` until computed
+    pub bytes: Option<usize>,
 }
 
 #[derive(Clone, Debug, Eq, Serialize, Deserialize)]
@@ -139,35 +848,149 @@ impl<T> std::fmt::Display for Opt<T> {
 #[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
 pub enum RepoOption {
     New,
+    Import,
     Select(Uuid),
 }
 impl RepoOption {
     fn id(&self) -> Option<Uuid> {
         match self {
-            RepoOption::New => None,
+            RepoOption::New | RepoOption::Import => None,
             RepoOption::Select(id) => Some(*id),
         }
     }
 }
 
+/// Panel of the Overview when there is nothing to list yet: what is missing, how to go on, and
+/// buttons for it (primary first)
+fn view_empty_state<'a>(
+    title: &str,
+    guidance: &str,
+    primary: (&'a mut button::State, &str, Message),
+    secondary: (&'a mut button::State, &str, Message),
+) -> Element<'a, Message> {
+    let (s_primary, primary_label, primary_message) = primary;
+    let (s_secondary, secondary_label, secondary_message) = secondary;
+    Container::new(
+        Column::new()
+            .spacing(20)
+            .push(h3(title))
+            .push(Text::new(guidance).size(TEXT_SIZE - 4))
+            .push(
+                Row::new()
+                    .spacing(10)
+                    .push(
+                        Button::new(s_primary, Text::new(primary_label).size(TEXT_SIZE))
+                            .padding(12)
+                            .style(style::Button::Primary)
+                            .on_press(primary_message),
+                    )
+                    .push(
+                        Button::new(s_secondary, Text::new(secondary_label).size(TEXT_SIZE - 4))
+                            .padding(12)
+                            .style(style::Button::Text)
+                            .on_press(secondary_message),
+                    ),
+            ),
+    )
+    .style(style::ListItemExpanded)
+    .width(Length::Fill)
+    .padding(20)
+    .into()
+}
+
+/// Remember in `repo` whether the snapshot of `done` could be read back after the backup
+pub fn record_verification(repo: &mut RepoConfig, done: &backup::BackupDone) {
+    match done.verified {
+        Some(Err(ref e)) => {
+            repo.failed_verifications
+                .insert(done.snapshot.name.clone(), e.clone());
+        }
+        Some(Ok(_)) => {
+            repo.failed_verifications.remove(&done.snapshot.name);
+        }
+        None => (),
+    }
+}
+
+/// Options of the repo pick list. The icon of a repo shows its `RepoStatus`.
 fn repo_options<'a, I: Iterator<Item = &'a RepoConfig>>(repos: I) -> Vec<Opt<RepoOption>> {
+    let now = Utc::now();
     std::iter::once(Opt {
         name: "New repo...".to_string(),
         value: RepoOption::New,
     })
-    .chain(repos.map(|repo| Opt {
-        name: format!("{} {}", Icon::Repo, repo.name),
-        value: RepoOption::Select(repo.id),
+    .chain(std::iter::once(Opt {
+        name: "Import existing repo...".to_string(),
+        value: RepoOption::Import,
+    }))
+    .chain(repos.map(|repo| {
+        let icon = match repo.status(now) {
+            RepoStatus::Ok => Icon::Repo,
+            RepoStatus::Overdue => Icon::Warning,
+            RepoStatus::Failed => Icon::Failed,
+        };
+        Opt {
+            name: if repo.ephemeral {
+                format!("{} {} (demo)", icon, repo.name)
+            } else {
+                format!("{} {}", icon, repo.name)
+            },
+            value: RepoOption::Select(repo.id),
+        }
     }))
     .collect()
 }
 
 pub fn main() -> iced::Result {
+    // `--data-dir <path>` is a shorthand for `BUP_DATA_DIR`; set before any other thread exists
+    let mut args = std::env::args().skip(1);
+    let mut backup_target = None;
+    while let Some(arg) = args.next() {
+        let value = match arg.as_str() {
+            "--data-dir" | "--backup" => match args.next() {
+                Some(value) => value,
+                None => {
+                    eprintln!("{} needs a value", arg);
+                    std::process::exit(2);
+                }
+            },
+            _ => continue,
+        };
+        if arg == "--data-dir" {
+            std::env::set_var(DATA_DIR_VAR, value);
+        } else {
+            match value.parse::<Uuid>() {
+                Ok(id) => backup_target = Some(id),
+                Err(_) => {
+                    eprintln!("--backup needs the id of a target");
+                    std::process::exit(2);
+                }
+            }
+        }
+    }
+    if let Some(dir) = std::env::var_os(DATA_DIR_VAR).filter(|dir| !dir.is_empty()) {
+        if let Err(e) = std::fs::create_dir_all(&dir) {
+            eprintln!("Cannot create {}: {}", Path::new(&dir).display(), e);
+            std::process::exit(1);
+        }
+    }
+    // Run by a system timer (see `timer`)
+    if let Some(id) = backup_target {
+        if let Err(e) = headless::backup(id) {
+            eprintln!("Backup failed: {:#}", e);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
     ctrlc::set_handler(move || {
         SHOULD_EXIT.store(true, std::sync::atomic::Ordering::Relaxed);
     })
     .expect("Error setting Ctrl-C handler");
-    Ui::run(Settings::default())
+    Ui::run(Settings {
+        // Closing is handled in `Message::CloseRequested`, to not lose unsaved edits
+        exit_on_close_request: false,
+        ..Settings::default()
+    })
 }
 
 /// Application state for different scenes
@@ -178,13 +1001,52 @@ pub enum Scene {
         error: Option<String>,
         s_pass1: text_input::State,
         s_pass2: text_input::State,
+        /// Remember the passphrase in the system keyring once accepted
+        remember: bool,
+        /// Show the new passphrase as typed, to check it
+        reveal: bool,
         s_confirm: button::State,
+        s_demo: button::State,
+        s_reveal: button::State,
+        s_trim: button::State,
+    },
+    /// Passphrase accepted, the selected repo is being opened in the background
+    Unlocking {
+        /// Number of the current or last attempt to open the repo, from 1
+        attempt: u32,
+        /// When to try again after a transient failure, and the failure
+        retry: Option<(Instant, String)>,
+        s_retry: button::State,
+        s_give_up: button::State,
+    },
+    /// The app can't start, e.g. because the config file is broken. The config is not saved in
+    /// this scene, so that the broken file is kept until the user resets it.
+    Fatal {
+        message: String,
+        s_open_folder: button::State,
+        s_reset: button::State,
     },
     Overview {
         list: Vec<ListItemState>,
+        /// One per group header, in the order shown
+        s_groups: Vec<button::State>,
         new_button: button::State,
-        selected_target: Option<usize>,
+        s_split: button::State,
+        /// Snapshots of `Ui::snapshots_target`
+        snapshots: SnapshotList,
+        error: Option<String>,
+        /// Asks for the passphrase of the selected repo, when the app passphrase doesn't unlock it
+        passphrase_prompt: Option<PassphrasePrompt>,
+        /// Show the signals behind the health badge
+        show_health: bool,
+        s_health: button::State,
         s_open_settings: button::State,
+        s_edit_repo: button::State,
+        s_keep_demo: button::State,
+        s_undo_delete: button::State,
+        /// Calls to action of the panel shown without targets or without a repo
+        s_empty_primary: button::State,
+        s_empty_secondary: button::State,
         // The `None` means "New"
         s_repo_pick_list: pick_list::State<Opt<RepoOption>>,
     },
@@ -192,14 +1054,10 @@ pub enum Scene {
         editor: TargetEditor,
     },
     CreateRepo {
-        name: String,
-        home: Option<PathBuf>,
-
-        error: Option<String>,
-        s_cancel_button: button::State,
-        s_save_button: button::State,
-        s_name: text_input::State,
-        s_home: FilePicker,
+        editor: RepoEditor,
+    },
+    EditRepo {
+        editor: RepoEditor,
     },
     EditTarget {
         editor: TargetEditor,
@@ -207,9 +1065,72 @@ pub enum Scene {
     },
     Settings {
         s_back_button: button::State,
+        s_export: button::State,
+        s_inventory: button::State,
+        s_bulk_edit: button::State,
+        s_reconcile: button::State,
+        s_compact: button::State,
+        s_fewer_backups: button::State,
+        s_more_backups: button::State,
+        s_fewer_reads: button::State,
+        s_more_reads: button::State,
+        s_forget_passphrase: button::State,
+        s_log_level: pick_list::State<log::LogLevel>,
+        s_inspector: button::State,
+        s_fewer_retries: button::State,
+        s_more_retries: button::State,
+        s_new_default_exclude: button::State,
+        /// Input and delete button per default exclude
+        s_default_excludes: Vec<(text_input::State, button::State)>,
+    },
+    Export {
+        wizard: ExportWizard,
+    },
+    /// Copy settings of the selected repo to the other repos
+    BulkEdit {
+        bulk: BulkEdit,
+    },
+    /// Export the snapshot list of the selected repo to a CSV file
+    Inventory {
+        inventory: Inventory,
+    },
+    /// Compare the targets of the selected repo with its snapshots
+    Reconcile {
+        reconcile: Reconcile,
+    },
+    Restore {
+        restore: Restore,
+    },
+    /// Export a snapshot to a tar file
+    TarExport {
+        export: TarExport,
+    },
+    /// Create targets from the subdirectories of a folder
+    Split {
+        split: Split,
     },
 }
+/// Repos that a target of the repo `repo_id` can be moved to
+fn move_destinations(config: &Config, repo_id: Uuid) -> Vec<Opt<Uuid>> {
+    config
+        .repos
+        .values()
+        .filter(|other| other.id != repo_id && !other.ephemeral)
+        .map(|other| Opt {
+            name: other.name.clone(),
+            value: other.id,
+        })
+        .collect()
+}
+
 impl Scene {
+    pub fn fatal(message: String) -> Scene {
+        Scene::Fatal {
+            message,
+            s_open_folder: Default::default(),
+            s_reset: Default::default(),
+        }
+    }
     pub fn init() -> Scene {
         Scene::Initial {
             passphrase1: String::new(),
@@ -217,7 +1138,12 @@ impl Scene {
             error: None,
             s_pass1: Default::default(),
             s_pass2: Default::default(),
+            remember: false,
+            reveal: false,
             s_confirm: Default::default(),
+            s_demo: Default::default(),
+            s_reveal: Default::default(),
+            s_trim: Default::default(),
         }
     }
     pub fn overview(config: &Config) -> Scene {
@@ -225,39 +1151,114 @@ impl Scene {
         let n_targets = repo.map(|repo| repo.targets.len()).unwrap_or(0);
         Scene::Overview {
             list: Vec::new(),
+            s_groups: Vec::new(),
             new_button: Default::default(),
-            selected_target: None,
+            s_split: Default::default(),
+            snapshots: Default::default(),
+            error: None,
+            passphrase_prompt: None,
+            show_health: false,
+            s_health: Default::default(),
             s_open_settings: Default::default(),
+            s_edit_repo: Default::default(),
+            s_keep_demo: Default::default(),
+            s_undo_delete: Default::default(),
+            s_empty_primary: Default::default(),
+            s_empty_secondary: Default::default(),
             s_repo_pick_list: Default::default(),
         }
     }
-    pub fn create_target(repo_id: Uuid) -> Scene {
-        Scene::CreateTarget {
-            editor: TargetEditor::new_target(repo_id),
-        }
+    pub fn create_target(config: &Config, repo: &RepoConfig) -> Scene {
+        let excludes = config
+            .default_excludes
+            .iter()
+            .filter(|exclude| !exclude.trim().is_empty())
+            .cloned()
+            .collect();
+        let mut editor = TargetEditor::new_target(repo.id, excludes);
+        editor.repo_excludes = repo.repo_excludes.clone();
+        editor.repo_compresses = rdedup::compresses(&repo.home);
+        editor.groups = repo.groups();
+        Scene::CreateTarget { editor }
     }
-    pub fn create_repo() -> Scene {
+    pub fn create_repo(mode: RepoEditorMode) -> Scene {
         Scene::CreateRepo {
-            name: String::new(),
-            home: None,
-            error: None,
-
-            s_cancel_button: Default::default(),
-            s_save_button: Default::default(),
-            s_name: Default::default(),
-            s_home: Default::default(),
+            editor: RepoEditor::new_repo(mode),
         }
     }
-    pub fn edit(target_index: usize, config: &Config) -> Scene {
-        let target = config.selected_repo().unwrap().targets[target_index].clone();
-        Scene::EditTarget {
-            editor: TargetEditor::with_target(target),
+    pub fn edit_repo(repo: &RepoConfig) -> Scene {
+        Scene::EditRepo {
+            editor: RepoEditor::with_repo(repo.clone()),
+        }
+    }
+    /// Overview showing an error banner
+    pub fn overview_error(config: &Config, error: String) -> Scene {
+        let mut scene = Scene::overview(config);
+        if let Scene::Overview {
+            error: ref mut e, ..
+        } = scene
+        {
+            *e = Some(error);
+        }
+        scene
+    }
+    /// Fails if the selected repo or the target no longer exists
+    pub fn edit(target_index: usize, config: &Config) -> Result<Scene, String> {
+        let repo = config.selected_repo().ok_or("No repo selected")?;
+        let target = repo
+            .targets
+            .get(target_index)
+            .ok_or("Target does not exist anymore")?
+            .clone();
+        let mut editor = TargetEditor::with_target(target);
+        editor.repo_excludes = repo.repo_excludes.clone();
+        editor.repo_compresses = rdedup::compresses(&repo.home);
+        editor.groups = repo.groups();
+        editor.destinations = move_destinations(config, repo.id);
+        Ok(Scene::EditTarget {
+            editor,
             target_index,
+        })
+    }
+    /// Restore `snapshot` of the selected repo, or of one of its copies
+    pub fn restore(config: &Config, snapshot: String) -> Scene {
+        let (homes, reads) = match config.selected_repo() {
+            Some(repo) => (
+                std::iter::once(repo.home.clone())
+                    .chain(duplicate::copies(repo))
+                    .collect(),
+                repo.read_limit(),
+            ),
+            None => Default::default(),
+        };
+        Scene::Restore {
+            restore: Restore::new(snapshot, homes, reads),
+        }
+    }
+    pub fn tar_export(snapshot: String) -> Scene {
+        Scene::TarExport {
+            export: TarExport::new(snapshot),
         }
     }
     pub fn settings() -> Scene {
         Scene::Settings {
             s_back_button: Default::default(),
+            s_export: Default::default(),
+            s_inventory: Default::default(),
+            s_bulk_edit: Default::default(),
+            s_reconcile: Default::default(),
+            s_compact: Default::default(),
+            s_fewer_backups: Default::default(),
+            s_more_backups: Default::default(),
+            s_fewer_reads: Default::default(),
+            s_more_reads: Default::default(),
+            s_forget_passphrase: Default::default(),
+            s_log_level: Default::default(),
+            s_inspector: Default::default(),
+            s_fewer_retries: Default::default(),
+            s_more_retries: Default::default(),
+            s_new_default_exclude: Default::default(),
+            s_default_excludes: Vec::new(),
         }
     }
 }
@@ -272,6 +1273,79 @@ pub struct Ui {
     /// Current opened repo.
     /// Optional: Error might occur when opening, and it won't be opened until inside Overview
     repo: Option<Repo>,
+    /// Passphrases of repos that are not unlocked by `passphrase`, entered during this session
+    repo_passphrases: HashMap<Uuid, String>,
+    /// Cached, since computing it lists all snapshots
+    health: HashMap<Uuid, health::RepoHealth>,
+    /// Set when the config could not be written. Shown as a banner in every scene until a save
+    /// succeeds.
+    save_error: Option<String>,
+    s_retry_save: button::State,
+    /// The config as last written, to tell whether an autosave is needed
+    saved_config: Option<String>,
+    last_autosave: Instant,
+    /// Monotonic and wall clock time of the last tick, to detect clock jumps
+    clock: Option<(Instant, DateTime<Utc>)>,
+    /// Current width of the window, to switch to narrow layouts
+    window_width: u32,
+    /// Backups waiting for a free slot (see `Config::max_concurrent_backups`), oldest first
+    backup_queue: std::collections::VecDeque<QueuedBackup>,
+    /// Backups in flight. A repo is not compacted, moved or switched away from while it has any.
+    running_backups: Vec<RunningBackup>,
+    /// Held back until the running backups it would leave behind are done (see `guard_backups`),
+    /// shown as a banner offering to cancel them
+    guarded: Option<Guarded>,
+    s_cancel_backups: button::State,
+    s_dismiss_guard: button::State,
+    /// Snapshot names per repo, with the generation they were listed at. Valid while that is
+    /// still the generation in `generations`. Only changes made by bup bump the generation;
+    /// changes made by other tools need a manual refresh.
+    snapshot_names: HashMap<Uuid, (u64, Vec<String>)>,
+    /// Incremented whenever bup writes or removes a snapshot of the repo
+    generations: HashMap<Uuid, u64>,
+    /// Duplications in flight, by repo, target index and duplication index
+    running_duplications: Vec<(Uuid, usize, usize)>,
+    /// Repo currently being compacted. Backups to it are refused meanwhile.
+    compacting: Option<Uuid>,
+    /// Repo being copied to a new home (see `duplicate::relocate`). Its backups stay queued
+    /// meanwhile, so that they are not written to the old home.
+    relocating: Option<Uuid>,
+    /// Written by the compacting thread
+    gc_progress: Arc<Mutex<rdedup::GcProgress>>,
+    /// Copy of `gc_progress`, refreshed on `Message::GcProgress`
+    gc_shown: rdedup::GcProgress,
+    /// Outcome of the last gc, with the repo it was of
+    compact_result: Option<(Uuid, Result<rdedup::Compacted, String>)>,
+    /// Shown in the Settings when open. `Some(None)` while inspecting.
+    inspection: Option<Option<Result<rdedup::Inspection, String>>>,
+    /// Usage per repo, with the generation it was computed at. `None` while computing.
+    usage: HashMap<Uuid, (u64, Option<Result<rdedup::Usage, String>>)>,
+    /// Space on the disk of each repo, with when it was last asked for. `None` until known; a
+    /// failed check is logged and leaves the previous value.
+    free_space: HashMap<Uuid, (Instant, Option<rdedup::Space>)>,
+    /// Space on the disks of duplications by path, `None` inside while checking, `None` in the
+    /// result if the disk isn't there (see `duplicate::disk_space`)
+    disk_space: HashMap<PathBuf, Option<Result<Option<rdedup::Space>, String>>>,
+    /// Whether the backups of `Config::backup_on_startup` were queued already
+    startup_backups_queued: bool,
+    /// Recently deleted targets with their index in their repo, most recent last. Not saved.
+    deleted_targets: Vec<(usize, Target)>,
+    /// Targets expanded in the Overview. Kept here, since the scene is rebuilt often.
+    expanded: HashSet<Uuid>,
+    /// Target of the Overview whose snapshots are listed: the one expanded last
+    snapshots_target: Option<Uuid>,
+    /// Command palette, shown above the scene while open
+    palette: Option<Palette>,
+    /// Changes since the last backup by repo and target name, with the time of that backup and
+    /// when they were estimated. `None` while estimating.
+    changes: HashMap<
+        (Uuid, String),
+        (
+            DateTime<Utc>,
+            Instant,
+            Option<Result<backup::Changes, String>>,
+        ),
+    >,
 
     argon2: Argon2<'static>,
 }
@@ -284,21 +1358,121 @@ pub enum Message {
     NewTarget,
     EditTarget(usize),
     ListItem(usize, ListItemMessage),
+    SnapshotList(SnapshotListMessage),
+    Restore(RestoreMessage),
+    TarExport(TarExportMessage),
     TargetEditor(TargetEditorMessage),
     OpenSettings,
+    OpenExport,
+    Export(ExportMessage),
+    OpenInventory,
+    OpenBulkEdit,
+    BulkEdit(BulkEditMessage),
+    Inventory(InventoryMessage),
+    OpenReconcile,
+    Reconcile(ReconcileMessage),
+    OpenSplit,
+    Split(SplitMessage),
     PickRepo(Opt<RepoOption>),
+    HealthChecked(Uuid, health::RepoHealth),
+    ToggleHealth,
+    SetRepoPassphrase(String),
+    ConfirmRepoPassphrase,
+    EditRepo,
+    RetrySave,
+    /// Cancel the backups that `Ui::guarded` waits for
+    CancelGuardedBackups,
+    /// Don't go ahead with `Ui::guarded` after all
+    DismissGuard,
+    SetHideEmptyRepos(bool),
+    SetBackgroundPriority(bool),
+    SetBackupOnStartup(bool),
+    SetLogLevel(log::LogLevel),
+    SetLogFileLocation(bool),
+    SetMaxConcurrentBackups(usize),
+    /// Set `RepoConfig::read_threads` of the selected repo
+    SetReadThreads(usize),
+    SetOpenRetries(u32),
+    NewDefaultExclude,
+    SetDefaultExclude(usize, String),
+    DelDefaultExclude(usize),
+    ForgetPassphrase,
+    WindowResized(u32),
+    FileDropped(PathBuf),
+    /// The window is asked to close
+    CloseRequested,
+    /// Open or close the command palette
+    TogglePalette,
+    Palette(PaletteMessage),
+
+    // Scene::Fatal
+    OpenConfigFolder,
+    /// Move the broken config aside and start over with defaults
+    ResetConfig,
 
     // Scene::Initial
     SetPassphrase1(String),
     SetPassphrase2(String),
+    /// Show or hide the new passphrase as typed
+    ToggleRevealPassphrase,
+    /// Remove leading and trailing whitespace from the new passphrase
+    TrimPassphrase,
+    SetRemember(bool),
     InitialConfirm,
+    /// Confirm the passphrase, then create and select a demo repo
+    TryDemo,
+    /// Stop treating the selected (demo) repo as ephemeral
+    KeepDemo,
+    /// Restore the last deleted target
+    UndoDelete,
 
-    // Repo editor (maybe make a new component)
-    SetRepoName(String),
-    SetRepoHome(PathBuf),
-    SaveRepo,
-    RepoHome(path::Message),
-    RepoSaveResult(Result<Redacted<Repo>, String>),
+    RepoEditor(RepoEditorMessage),
+    RepoSaveResult(Result<Redacted<Repo>, BupError>),
+    /// The repo with this id was copied from the old home `.1` to the new home `.2`
+    Relocated(Uuid, PathBuf, PathBuf, Result<Redacted<Repo>, String>),
+    OldHomeDeleted(Result<(), String>),
+    /// The selected repo was opened after unlocking. Also tells whether the passphrase unlocks it.
+    Unlocked(Result<(Redacted<Repo>, Result<(), String>), rdedup::OpenError>),
+    /// The repo picked in the Overview was opened. Also tells whether the passphrase unlocks it.
+    RepoPicked(
+        Opt<RepoOption>,
+        Result<(Redacted<Repo>, Result<(), String>), rdedup::OpenError>,
+    ),
+    /// Whether the passphrase entered for the repo with this id unlocks it
+    PassphraseChecked(Uuid, String, Result<(), String>),
+    /// Try opening the selected repo again now, instead of waiting for the next retry
+    RetryOpen,
+    /// Stop waiting for the selected repo to appear
+    GiveUpOpen,
+    BackupResult(Uuid, usize, Result<backup::BackupDone, BupError>),
+    /// With the disk that was written to
+    DuplicationResult(Uuid, usize, usize, Result<PathBuf, BupError>),
+    /// Snapshot names of a repo were listed at the given generation
+    NamesListed(Uuid, u64, Result<Vec<String>, String>),
+    /// Open or close the inspector of the selected repo in the Settings
+    ToggleInspector,
+    Inspected(Result<rdedup::Inspection, String>),
+    /// Notifications about a backup were sent (see `RepoConfig::notifiers`)
+    Notified(Result<(), String>),
+    /// Cleaned up after the interrupted backup of a target (repo id, target id, snapshot name)
+    InterruptedBackupDiscarded(Uuid, Uuid, String, Result<backup::Interrupted, String>),
+    /// Collapse or expand the group of targets with this name (`""` for no group)
+    ToggleGroup(String),
+    /// GC the selected repo now
+    Compact,
+    /// Refresh the shown progress of a running gc
+    GcProgress,
+    Compacted(Uuid, Result<rdedup::Compacted, String>),
+    /// Dry run before backing up target `.1` of repo `.0`
+    LimitsChecked(Uuid, usize, Result<backup::DryRun, String>),
+    /// Usage of a repo, computed at the given generation
+    UsageComputed(Uuid, u64, Result<rdedup::Usage, String>),
+    /// Space on the disk of a repo
+    FreeSpaceChecked(Uuid, Result<rdedup::Space, String>),
+    /// Space on the disk of a duplication
+    DiskSpaceChecked(PathBuf, Result<Option<rdedup::Space>, String>),
+    /// Changes under target `.1` of repo `.0` since its backup at `.2`
+    ChangesEstimated(Uuid, String, DateTime<Utc>, Result<backup::Changes, String>),
 }
 
 pub fn init_repo(path: &Path, passphrase: String, log: Logger) -> anyhow::Result<Repo> {
@@ -327,30 +1501,119 @@ impl Application for Ui {
     type Message = Message;
     type Flags = ();
     fn new(_flags: ()) -> (Self, Command<Message>) {
-        let config = Config::load()
-            .context("Could not deserialize config file")
-            .unwrap();
-
         let log = log::logger();
-        (
-            Ui {
-                scene: Scene::init(),
-                config,
-                s_scrollable: Default::default(),
-                log,
-                repo: None,
-                passphrase: None,
-                argon2: Argon2::default(),
-            },
-            Command::none(),
-        )
+        let (config, scene) = match Config::load().context("Could not load the config file") {
+            Ok(config) => (config, Scene::init()),
+            Err(e) => {
+                error!(log, "{:#}", e);
+                (Config::default(), Scene::fatal(format!("{:#}", e)))
+            }
+        };
+        log::configure(config.log_level, config.log_file_location);
+        let save_error = Config::probe_writable().err().map(|e| format!("{:#}", e));
+        if let Some(ref e) = save_error {
+            error!(log, "Config is not writable: {}", e);
+        }
+        let saved_config = config.to_json().ok();
+        let mut ui = Ui {
+            scene,
+            config,
+            s_scrollable: Default::default(),
+            log,
+            repo: None,
+            repo_passphrases: HashMap::new(),
+            health: HashMap::new(),
+            passphrase: None,
+            save_error,
+            s_retry_save: Default::default(),
+            saved_config,
+            last_autosave: Instant::now(),
+            clock: None,
+            window_width: iced::window::Settings::default().size.0,
+            backup_queue: Default::default(),
+            running_backups: Vec::new(),
+            guarded: None,
+            s_cancel_backups: Default::default(),
+            s_dismiss_guard: Default::default(),
+            running_duplications: Vec::new(),
+            snapshot_names: HashMap::new(),
+            generations: HashMap::new(),
+            compacting: None,
+            relocating: None,
+            gc_progress: Default::default(),
+            gc_shown: Default::default(),
+            compact_result: None,
+            inspection: None,
+            usage: HashMap::new(),
+            free_space: HashMap::new(),
+            disk_space: HashMap::new(),
+            changes: HashMap::new(),
+            startup_backups_queued: false,
+            deleted_targets: Vec::new(),
+            expanded: HashSet::new(),
+            snapshots_target: None,
+            palette: None,
+            argon2: Argon2::default(),
+        };
+        let command = match ui.scene {
+            Scene::Fatal { .. } => Command::none(),
+            _ => ui.unlock_from_keychain(),
+        };
+        (ui, command)
     }
 
     fn should_exit(&self) -> bool {
         SHOULD_EXIT.load(std::sync::atomic::Ordering::Relaxed)
     }
     fn subscription(&self) -> Subscription<Message> {
-        iced::time::every(Duration::from_secs(1)).map(Message::Tick)
+        let tick = iced::time::every(Duration::from_secs(1)).map(Message::Tick);
+        let window = iced_native::subscription::events_with(|event, _status| match event {
+            iced_native::Event::Window(iced_native::window::Event::Resized { width, .. }) => {
+                Some(Message::WindowResized(width))
+            }
+            iced_native::Event::Window(iced_native::window::Event::FileDropped(path)) => {
+                Some(Message::FileDropped(path))
+            }
+            iced_native::Event::Window(iced_native::window::Event::CloseRequested) => {
+                Some(Message::CloseRequested)
+            }
+            iced_native::Event::Keyboard(iced_native::keyboard::Event::KeyPressed {
+                key_code,
+                modifiers,
+            }) => {
+                use iced_native::keyboard::KeyCode;
+                match key_code {
+                    KeyCode::P if modifiers.control() => Some(Message::TogglePalette),
+                    // Ignored unless the palette is open
+                    KeyCode::Escape => Some(Message::Palette(PaletteMessage::Close)),
+                    KeyCode::Up => Some(Message::Palette(PaletteMessage::Up)),
+                    KeyCode::Down => Some(Message::Palette(PaletteMessage::Down)),
+                    _ => None,
+                }
+            }
+            _ => None,
+        });
+        let mut subscriptions = vec![tick, window];
+        if self.compacting.is_some() {
+            subscriptions
+                .push(iced::time::every(Duration::from_millis(500)).map(|_| Message::GcProgress));
+        }
+        match self.scene {
+            Scene::Restore { ref restore } if restore.is_running() => subscriptions.push(
+                iced::time::every(Duration::from_millis(200))
+                    .map(|_| Message::Restore(RestoreMessage::Progress)),
+            ),
+            Scene::TarExport { ref export } if export.is_running() => subscriptions.push(
+                iced::time::every(Duration::from_millis(200))
+                    .map(|_| Message::TarExport(TarExportMessage::Progress)),
+            ),
+            Scene::Inventory { ref inventory } if inventory.is_running() => subscriptions.push(
+                iced::time::every(Duration::from_millis(200))
+                    .map(|_| Message::Inventory(InventoryMessage::Progress)),
+            ),
+            _ => (),
+        }
+        Subscription::batch(subscriptions)
     }
 
     fn title(&self) -> String {
@@ -359,121 +1622,1310 @@ impl Application for Ui {
 
     fn update(&mut self, message: Message) -> Command<Message> {
         match message {
-            Message::Tick(_) => Command::none(),
-            Message::ToOverview => {
-                self.scene = Scene::overview(&self.config);
-                Command::none()
-            }
-            Message::NewTarget => {
-                if let Some(Opt {
-                    value: RepoOption::Select(repo_id),
-                    ..
-                }) = self.config.selected_repo
-                {
-                    self.scene = Scene::create_target(repo_id);
+            Message::Tick(now) => {
+                if now.duration_since(self.last_autosave) >= AUTOSAVE_INTERVAL {
+                    self.last_autosave = now;
+                    self.autosave();
                 }
-                Command::none()
-            }
-            Message::EditTarget(index) => {
-                self.scene = Scene::edit(index, &self.config);
-                Command::none()
+                let retry_open = match self.scene {
+                    Scene::Unlocking {
+                        retry: Some((at, _)),
+                        ..
+                    } if now >= at => self.open_selected_repo(),
+                    _ => Command::none(),
+                };
+                self.check_clock(now);
+                Command::batch(vec![
+                    self.start_due_duplications(),
+                    self.check_free_space(),
+                    retry_open,
+                ])
             }
-            Message::ListItem(i, msg) => match msg {
-                ListItemMessage::Edit => {
-                    self.scene = Scene::edit(i, &self.config);
-                    Command::none()
+            Message::NamesListed(repo_id, generation, result) => match result {
+                Ok(names) => {
+                    self.snapshot_names.insert(repo_id, (generation, names));
+                    Command::batch(vec![self.load_snapshots(), self.estimate_changes()])
                 }
-                ListItemMessage::Expand => {
-                    match self.scene {
-                        Scene::Overview {
-                            ref mut selected_target,
-                            ..
-                        } => {
-                            if selected_target.is_some() {
-                                *selected_target = None
-                            } else {
-                                *selected_target = Some(i)
-                            }
-                        }
-                        // Scene::Overview {selected_target: None} =>
-                        _ => unreachable!(),
+                Err(e) => {
+                    if let Scene::Overview {
+                        ref mut snapshots, ..
+                    } = self.scene
+                    {
+                        *snapshots =
+                            SnapshotList::loaded(Err(e), Default::default(), Default::default());
                     }
-                    // TODO: expand
                     Command::none()
                 }
             },
-            Message::TargetEditor(msg) => {
-                match msg {
-                    TargetEditorMessage::Save => {
-                        // Easier to do the pattern matching on scene first, due to the need of
-                        // capturing `target_index` optionally. (ran into borrowing issues)
-                        let (editor, target_index) = match &mut self.scene {
-                            Scene::CreateTarget { ref mut editor } => (Some(editor), None),
-                            Scene::EditTarget {
-                                ref mut editor,
-                                target_index,
-                            } => (Some(editor), Some(target_index)),
-                            _ => panic!(),
-                        };
-                        if let Some(editor) = editor {
-                            match verify_target(&editor.target) {
-                                Ok(()) => {
-                                    let repo = self.config.selected_repo_mut().unwrap();
-                                    if let Some(target_index) = target_index {
-                                        repo.targets[*target_index] = editor.target.clone();
-                                    } else {
-                                        repo.targets.push(editor.target.clone());
-                                    }
-                                    self.scene = Scene::overview(&self.config);
-                                }
-                                Err(e) => editor.error = Some(e),
+            Message::DuplicationResult(repo_id, i, j, result) => {
+                self.running_duplications.retain(|r| *r != (repo_id, i, j));
+                let mut notify = Command::none();
+                let repo = self.config.repos.get_mut(&repo_id);
+                let notifiers = repo
+                    .as_ref()
+                    .map(|repo| (repo.notifiers.clone(), repo.notify_on_success));
+                let target = repo.and_then(|repo| repo.targets.get_mut(i));
+                let target_name = target.as_ref().map(|target| target.name.clone());
+                let duplication = target.and_then(|target| target.duplication.get_mut(j));
+                if let (Some(duplication), Some(target_name), Some((notifiers, on_success))) =
+                    (duplication, target_name, notifiers)
+                {
+                    let mut entry = history::Entry::new(
+                        history::Action::Duplication,
+                        repo_id,
+                        target_name,
+                        result.as_ref().map(|_| ()).map_err(|e| e.to_string()),
+                    );
+                    entry.duration_secs = duplication
+                        .in_progress
+                        .and_then(|started| (Utc::now() - started).to_std().ok())
+                        .map(|duration| duration.as_secs_f64());
+                    history::append(&entry, &self.log);
+                    if !notifiers.is_empty() && notify::wanted(&entry, on_success) {
+                        notify = Command::perform(
+                            notify::notify_async(notifiers, entry),
+                            Message::Notified,
+                        );
+                    }
+                    duplication.in_progress = None;
+                    match result {
+                        Ok(disk) => {
+                            duplication.last_run = Some(Utc::now());
+                            duplication.last_error = None;
+                            if let DuplicationKind::DiskRotation {
+                                ref mut last_used, ..
+                            } = duplication.kind
+                            {
+                                *last_used = Some(disk);
                             }
                         }
+                        Err(e) => duplication.last_error = Some(e.to_string()),
                     }
-                    TargetEditorMessage::Cancel => {
-                        self.scene = Scene::overview(&self.config);
-                    }
-                    _ => (),
+                    self.save_config();
                 }
-                match &mut self.scene {
-                    Scene::CreateTarget { editor, .. } | Scene::EditTarget { editor, .. } => {
-                        editor.update(msg).map(Message::TargetEditor)
+                // Its slot may be waited for
+                Command::batch(vec![notify, self.start_queued_backups()])
+            }
+            Message::WindowResized(width) => {
+                self.window_width = width;
+                Command::none()
+            }
+            Message::OpenConfigFolder => {
+                if let Some(folder) = config_path().parent() {
+                    if let Err(e) = open_in_file_manager(folder) {
+                        error!(self.log, "Opening {}: {}", folder.display(), e);
                     }
-                    // Possible because scene might change above
-                    _ => Command::none(),
                 }
-            }
-            Message::OpenSettings => {
-                self.scene = Scene::settings();
                 Command::none()
             }
-            Message::PickRepo(repo) => {
+            Message::ResetConfig => {
+                let result: anyhow::Result<Config> = try {
+                    let path = config_path();
+                    let backup = path.with_file_name(format!(
+                        "config.json.broken-{}",
+                        Utc::now().format(rdedup::SNAPSHOT_TIME_FORMAT)
+                    ));
+                    std::fs::rename(&path, &backup)
+                        .with_context(|| format!("Moving the config to {}", backup.display()))?;
+                    info!(self.log, "Moved the broken config to {:?}", backup);
+                    Config::load()?
+                };
+                match result {
+                    Ok(config) => {
+                        self.config = config;
+                        self.scene = Scene::init();
+                    }
+                    Err(e) => self.scene = Scene::fatal(format!("{:#}", e)),
+                }
+                Command::none()
+            }
+            Message::CloseRequested => {
+                match self.scene {
+                    Scene::CreateTarget { ref mut editor }
+                    | Scene::EditTarget { ref mut editor, .. }
+                        if editor.dirty =>
+                    {
+                        editor.leaving = Some(target_editor::Leave::Quit)
+                    }
+                    _ => {
+                        if self.guard_backups(Guarded::Quit) {
+                            SHOULD_EXIT.store(true, std::sync::atomic::Ordering::Relaxed)
+                        }
+                    }
+                }
+                Command::none()
+            }
+            Message::FileDropped(path) => match self.scene {
+                // Only the target editor has a use for dropped folders
+                Scene::CreateTarget { ref mut editor }
+                | Scene::EditTarget { ref mut editor, .. } => editor
+                    .update(TargetEditorMessage::Dropped(path))
+                    .map(Message::TargetEditor),
+                _ => Command::none(),
+            },
+            Message::ToOverview => {
+                self.scene = Scene::overview(&self.config);
+                Command::none()
+            }
+            Message::TogglePalette => {
+                // Most actions leave the scene. Not offered where that would drop unsaved edits
+                // of a target, or detach a running restore or export from its progress.
+                let available = match self.scene {
+                    Scene::Initial { .. } | Scene::Unlocking { .. } | Scene::Fatal { .. } => false,
+                    Scene::CreateTarget { ref editor } | Scene::EditTarget { ref editor, .. } => {
+                        !editor.dirty
+                    }
+                    Scene::Restore { ref restore } => !restore.is_running(),
+                    Scene::TarExport { ref export } => !export.is_running(),
+                    Scene::Inventory { ref inventory } => !inventory.is_running(),
+                    _ => true,
+                };
+                self.palette = match self.palette {
+                    None if available => Some(Palette::new(self.palette_actions())),
+                    _ => None,
+                };
+                Command::none()
+            }
+            Message::Palette(msg) => {
+                let palette = match self.palette {
+                    Some(ref mut palette) => palette,
+                    None => return Command::none(),
+                };
+                let action = match msg {
+                    PaletteMessage::Submit => palette.selected(),
+                    PaletteMessage::Run(i) => palette.actions.get(i).map(|a| a.message.clone()),
+                    PaletteMessage::Close => None,
+                    msg => {
+                        palette.update(msg);
+                        return Command::none();
+                    }
+                };
+                self.palette = None;
+                match action {
+                    Some(action) => {
+                        // Actions on targets are handled by the Overview
+                        if matches!(action, Message::ListItem(..))
+                            && !matches!(self.scene, Scene::Overview { .. })
+                        {
+                            self.scene = Scene::overview(&self.config);
+                        }
+                        self.update(action)
+                    }
+                    None => Command::none(),
+                }
+            }
+            Message::NewTarget => {
+                self.scene = match self.config.selected_repo() {
+                    Some(repo) => Scene::create_target(&self.config, repo),
+                    None => Scene::overview_error(&self.config, "No repo selected".to_string()),
+                };
+                Command::none()
+            }
+            Message::EditTarget(index) => {
+                self.edit_target(index);
+                Command::none()
+            }
+            Message::ListItem(i, msg) => match msg {
+                ListItemMessage::ToggleLastResult => {
+                    if let Scene::Overview { ref mut list, .. } = self.scene {
+                        if let Some(item) = list.get_mut(i) {
+                            item.show_last_result = !item.show_last_result;
+                        }
+                    }
+                    Command::none()
+                }
+                ListItemMessage::Edit => {
+                    self.edit_target(i);
+                    Command::none()
+                }
+                ListItemMessage::Delete => {
+                    if let Err(e) = self.delete_target(i) {
+                        self.overview_error(e);
+                    }
+                    Command::none()
+                }
+                ListItemMessage::Backup => {
+                    let target = self.config.selected_repo().and_then(|repo| {
+                        let target = repo.targets.get(i)?;
+                        Some((
+                            repo.id,
+                            backup::with_repo_excludes(target, &repo.repo_excludes),
+                        ))
+                    });
+                    match target {
+                        // The first backup is where a wrong source costs most, so it is always
+                        // previewed
+                        Some((repo_id, target))
+                            if target.preview_backups
+                                || !target.backed_up()
+                                || target.max_files.is_some()
+                                || target.max_total_bytes.is_some() =>
+                        {
+                            let preview = target.preview_backups || !target.backed_up();
+                            Command::perform(
+                                backup::check_limits_async(target, preview),
+                                move |result| Message::LimitsChecked(repo_id, i, result),
+                            )
+                        }
+                        _ => self.queue_backup(i),
+                    }
+                }
+                ListItemMessage::BackupAnyway => {
+                    self.set_over_limit(i, None);
+                    self.queue_backup(i)
+                }
+                ListItemMessage::DismissOverLimit => {
+                    self.set_over_limit(i, None);
+                    Command::none()
+                }
+                ListItemMessage::CancelQueued => {
+                    let repo_id = self.config.selected_repo().map(|repo| repo.id);
+                    self.backup_queue.retain(|queued| {
+                        Some(queued.repo_id) != repo_id || queued.target_index != i
+                    });
+                    Command::none()
+                }
+                ListItemMessage::Expand => {
+                    let id = match self
+                        .config
+                        .selected_repo()
+                        .and_then(|repo| repo.targets.get(i))
+                    {
+                        Some(target) => target.id,
+                        None => return Command::none(),
+                    };
+                    if self.expanded.remove(&id) {
+                        if self.snapshots_target == Some(id) {
+                            self.snapshots_target = None;
+                        }
+                    } else {
+                        self.expanded.insert(id);
+                        self.snapshots_target = Some(id);
+                    }
+                    Command::batch(vec![
+                        self.load_snapshots(),
+                        self.estimate_changes(),
+                        self.check_disk_space(i),
+                    ])
+                }
+                ListItemMessage::CheckDiskSpace => self.check_disk_space(i),
+            },
+            Message::SnapshotList(msg) => {
+                match msg {
+                    SnapshotListMessage::Restore(ref snapshot) => {
+                        self.scene = Scene::restore(&self.config, snapshot.clone());
+                        if let Scene::Restore { ref restore } = self.scene {
+                            return restore
+                                .check_sources(self.log.clone())
+                                .map(Message::Restore);
+                        }
+                    }
+                    SnapshotListMessage::ExportTar(ref snapshot) => {
+                        self.scene = Scene::tar_export(snapshot.clone());
+                    }
+                    SnapshotListMessage::Rename if self.selected_read_only() => {
+                        let error = Err("The repo is read-only".to_string());
+                        return self
+                            .update(Message::SnapshotList(SnapshotListMessage::Renamed(error)));
+                    }
+                    SnapshotListMessage::Rename => {
+                        let passphrase = self
+                            .config
+                            .selected_repo()
+                            .map(|repo| self.repo_passphrase(repo.id));
+                        if let (
+                            Scene::Overview {
+                                snapshots:
+                                    SnapshotList {
+                                        rename: Some(rename),
+                                        ..
+                                    },
+                                ..
+                            },
+                            Some(repo),
+                            Some(passphrase),
+                        ) = (&self.scene, self.repo.clone(), passphrase)
+                        {
+                            return Command::perform(
+                                backup::rename_snapshot_async(
+                                    repo,
+                                    rename.snapshot.clone(),
+                                    rename.label.clone(),
+                                    passphrase,
+                                    self.log.clone(),
+                                ),
+                                |result| {
+                                    Message::SnapshotList(SnapshotListMessage::Renamed(result))
+                                },
+                            );
+                        }
+                    }
+                    SnapshotListMessage::Verify(ref name) => {
+                        let passphrase = self
+                            .config
+                            .selected_repo()
+                            .map(|repo| (self.repo_passphrase(repo.id), repo.read_limit()));
+                        let expected = self
+                            .config
+                            .selected_repo()
+                            .and_then(|repo| repo.snapshot_checksums.get(name).cloned());
+                        let name = name.clone();
+                        let command = match (self.repo.clone(), passphrase) {
+                            (Some(repo), Some((passphrase, reads))) => Command::perform(
+                                backup::verify_snapshot_async(
+                                    repo,
+                                    name.clone(),
+                                    expected,
+                                    passphrase,
+                                    reads,
+                                ),
+                                move |result| {
+                                    Message::SnapshotList(SnapshotListMessage::Verified(
+                                        name.clone(),
+                                        result,
+                                    ))
+                                },
+                            ),
+                            _ => Command::none(),
+                        };
+                        if let Scene::Overview {
+                            ref mut snapshots, ..
+                        } = self.scene
+                        {
+                            let _ = snapshots.update(msg);
+                        }
+                        return command;
+                    }
+                    SnapshotListMessage::Verified(ref name, Err(ref e)) => {
+                        error!(self.log, "Verifying snapshot {} failed: {}", name, e)
+                    }
+                    SnapshotListMessage::Verified(ref name, Ok(())) => {
+                        let cleared = self.config.selected_repo_mut().map_or(false, |repo| {
+                            repo.failed_verifications.remove(name).is_some()
+                        });
+                        if cleared {
+                            if let Scene::Overview {
+                                ref mut snapshots, ..
+                            } = self.scene
+                            {
+                                snapshots.failed_verifications.remove(name);
+                            }
+                            self.save_config();
+                        }
+                    }
+                    SnapshotListMessage::Renamed(Ok(ref new_name)) => {
+                        let old_name = match self.scene {
+                            Scene::Overview {
+                                snapshots:
+                                    SnapshotList {
+                                        rename: Some(ref rename),
+                                        ..
+                                    },
+                                ..
+                            } => Some(rename.snapshot.clone()),
+                            _ => None,
+                        };
+                        // The checksum, tags and failed verification follow the snapshot to its
+                        // new name
+                        let sum = old_name.as_ref().and_then(|old_name| {
+                            self.config
+                                .selected_repo_mut()?
+                                .snapshot_checksums
+                                .remove(old_name)
+                        });
+                        if let (Some(sum), Some(repo)) = (sum, self.config.selected_repo_mut()) {
+                            repo.snapshot_checksums.insert(new_name.clone(), sum);
+                        }
+                        let tags = old_name.as_ref().and_then(|old_name| {
+                            self.config
+                                .selected_repo_mut()?
+                                .snapshot_tags
+                                .remove(old_name)
+                        });
+                        if let (Some(tags), Some(repo)) = (tags, self.config.selected_repo_mut()) {
+                            repo.snapshot_tags.insert(new_name.clone(), tags);
+                        }
+                        if let (Some(old_name), Some(repo)) =
+                            (old_name.as_ref(), self.config.selected_repo_mut())
+                        {
+                            if repo.pinned_snapshots.remove(old_name) {
+                                repo.pinned_snapshots.insert(new_name.clone());
+                            }
+                            if let Some(error) = repo.failed_verifications.remove(old_name) {
+                                repo.failed_verifications.insert(new_name.clone(), error);
+                            }
+                        }
+                        self.save_config();
+                        if let Some(repo_id) = self.config.selected_repo().map(|repo| repo.id) {
+                            self.snapshots_changed(repo_id);
+                        }
+                        return self.load_snapshots();
+                    }
+                    SnapshotListMessage::SaveTags => {
+                        if let Scene::Overview {
+                            snapshots:
+                                SnapshotList {
+                                    tagging: Some(ref tagging),
+                                    ref mut tags,
+                                    ..
+                                },
+                            ..
+                        } = self.scene
+                        {
+                            let snapshot_tags = tags;
+                            let (name, tags) =
+                                (tagging.snapshot.clone(), parse_tags(&tagging.text));
+                            if tags.is_empty() {
+                                snapshot_tags.remove(&name);
+                            } else {
+                                snapshot_tags.insert(name.clone(), tags.clone());
+                            }
+                            if let Some(repo) = self.config.selected_repo_mut() {
+                                if tags.is_empty() {
+                                    repo.snapshot_tags.remove(&name);
+                                } else {
+                                    repo.snapshot_tags.insert(name, tags);
+                                }
+                            }
+                            self.save_config();
+                        }
+                    }
+                    SnapshotListMessage::TogglePin(ref name) => {
+                        if let Some(repo) = self.config.selected_repo_mut() {
+                            if !repo.pinned_snapshots.remove(name) {
+                                repo.pinned_snapshots.insert(name.clone());
+                            }
+                            let pinned = repo.pinned_snapshots.clone();
+                            if let Scene::Overview {
+                                ref mut snapshots, ..
+                            } = self.scene
+                            {
+                                snapshots.pinned = pinned;
+                            }
+                            self.save_config();
+                        }
+                    }
+                    SnapshotListMessage::Refresh => {
+                        if let Some(repo_id) = self.config.selected_repo().map(|repo| repo.id) {
+                            self.snapshots_changed(repo_id);
+                        }
+                        return self.load_snapshots();
+                    }
+                    _ => (),
+                }
+                match self.scene {
+                    Scene::Overview {
+                        ref mut snapshots, ..
+                    } => snapshots.update(msg).map(Message::SnapshotList),
+                    _ => Command::none(),
+                }
+            }
+            Message::Restore(msg) => {
+                let passphrase = self
+                    .config
+                    .selected_repo()
+                    .map(|repo| self.repo_passphrase(repo.id));
+                match msg {
+                    RestoreMessage::Back => {
+                        self.scene = Scene::overview(&self.config);
+                        return self.load_snapshots();
+                    }
+                    RestoreMessage::Start | RestoreMessage::StartWith(_) => {
+                        let collision = match msg {
+                            RestoreMessage::StartWith(collision) => Some(collision),
+                            _ => None,
+                        };
+                        if let (Scene::Restore { restore }, Some(passphrase)) =
+                            (&mut self.scene, passphrase)
+                        {
+                            return restore
+                                .start(passphrase, collision, self.log.clone())
+                                .map(Message::Restore);
+                        }
+                    }
+                    RestoreMessage::ListMembers => {
+                        if let (Scene::Restore { restore }, Some(repo), Some(passphrase)) =
+                            (&mut self.scene, self.repo.clone(), passphrase)
+                        {
+                            return restore.list_members(repo, passphrase).map(Message::Restore);
+                        }
+                    }
+                    RestoreMessage::Test => {
+                        if let (Scene::Restore { restore }, Some(repo), Some(passphrase)) =
+                            (&mut self.scene, self.repo.clone(), passphrase)
+                        {
+                            return restore
+                                .test(repo, passphrase, self.log.clone())
+                                .map(Message::Restore);
+                        }
+                    }
+                    _ => (),
+                }
+                let finished = matches!(msg, RestoreMessage::Finished(_));
+                match self.scene {
+                    Scene::Restore { ref mut restore } => {
+                        let command = restore.update(msg).map(Message::Restore);
+                        let repo_id = self.config.selected_repo().map(|repo| repo.id);
+                        if let (true, Some(repo_id)) = (finished, repo_id) {
+                            if let Some(entry) = restore.history_entry(repo_id) {
+                                history::append(&entry, &self.log);
+                            }
+                        }
+                        command
+                    }
+                    _ => Command::none(),
+                }
+            }
+            Message::TarExport(msg) => {
+                match msg {
+                    TarExportMessage::Back => {
+                        self.scene = Scene::overview(&self.config);
+                        return self.load_snapshots();
+                    }
+                    TarExportMessage::Start => {
+                        let passphrase = self
+                            .config
+                            .selected_repo()
+                            .map(|repo| self.repo_passphrase(repo.id));
+                        if let (Scene::TarExport { export }, Some(repo), Some(passphrase)) =
+                            (&mut self.scene, self.repo.clone(), passphrase)
+                        {
+                            return export
+                                .start(repo, passphrase, self.log.clone())
+                                .map(Message::TarExport);
+                        }
+                    }
+                    _ => (),
+                }
+                match self.scene {
+                    Scene::TarExport { ref mut export } => {
+                        export.update(msg).map(Message::TarExport)
+                    }
+                    _ => Command::none(),
+                }
+            }
+            Message::TargetEditor(msg) => {
+                match msg {
+                    TargetEditorMessage::Save | TargetEditorMessage::Apply => {
+                        let close = matches!(msg, TargetEditorMessage::Save);
+                        // Easier to do the pattern matching on scene first, due to the need of
+                        // capturing `target_index` optionally. (ran into borrowing issues)
+                        let (editor, target_index) = match &mut self.scene {
+                            Scene::CreateTarget { ref mut editor } => (Some(editor), None),
+                            Scene::EditTarget {
+                                ref mut editor,
+                                target_index,
+                            } => (Some(editor), Some(target_index)),
+                            _ => panic!(),
+                        };
+                        // Index of a new target once added, to go on editing it
+                        let mut created = None;
+                        if let Some(editor) = editor {
+                            match verify_target(&editor.target) {
+                                Ok(()) => {
+                                    let repo = self.config.repos.get_mut(&editor.target.repo);
+                                    let result = match (repo, target_index) {
+                                        (Some(repo), Some(target_index)) => {
+                                            match repo.targets.get_mut(*target_index) {
+                                                Some(target) => {
+                                                    *target = editor.target.clone();
+                                                    Ok(())
+                                                }
+                                                None => Err("Target does not exist anymore"),
+                                            }
+                                        }
+                                        (Some(repo), None) => {
+                                            repo.targets.push(editor.target.clone());
+                                            created = Some(repo.targets.len() - 1);
+                                            Ok(())
+                                        }
+                                        (None, _) => Err("Repo does not exist anymore"),
+                                    };
+                                    match result {
+                                        Ok(()) => {
+                                            editor.dirty = false;
+                                            editor.error = None;
+                                            if close {
+                                                self.scene = Scene::overview(&self.config);
+                                            }
+                                            self.save_config();
+                                        }
+                                        Err(e) => editor.error = Some(e.to_string()),
+                                    }
+                                }
+                                Err(e) => editor.error = Some(e.to_string()),
+                            }
+                        }
+                        // Further applies update the target instead of adding it again
+                        if let (false, Some(target_index)) = (close, created) {
+                            let scene = std::mem::replace(&mut self.scene, Scene::init());
+                            self.scene = match scene {
+                                Scene::CreateTarget { mut editor } => {
+                                    editor.existing = true;
+                                    editor.destinations =
+                                        move_destinations(&self.config, editor.target.repo);
+                                    Scene::EditTarget {
+                                        editor,
+                                        target_index,
+                                    }
+                                }
+                                scene => scene,
+                            };
+                        }
+                    }
+                    TargetEditorMessage::InstallTimer => {
+                        // The saved target, since the timer backs up what is in the config
+                        if let Scene::EditTarget {
+                            ref mut editor,
+                            target_index,
+                        } = self.scene
+                        {
+                            let target = self
+                                .config
+                                .selected_repo()
+                                .and_then(|repo| repo.targets.get(target_index).cloned());
+                            if let Some(target) = target {
+                                let interval = editor.timer_interval;
+                                info!(
+                                    self.log,
+                                    "Installing {} timer for {}", interval, target.name
+                                );
+                                let _ = editor.update(msg);
+                                return Command::perform(
+                                    timer::install_async(target, interval),
+                                    |result| {
+                                        Message::TargetEditor(TargetEditorMessage::TimerInstalled(
+                                            result,
+                                        ))
+                                    },
+                                );
+                            }
+                        }
+                    }
+                    TargetEditorMessage::MoveToRepo => {
+                        if let Scene::EditTarget {
+                            editor:
+                                TargetEditor {
+                                    ref target,
+                                    move_to: Some(ref move_to),
+                                    ..
+                                },
+                            target_index,
+                        } = self.scene
+                        {
+                            let (target, to) = (target.clone(), move_to.value);
+                            match self.move_target(target_index, target, to) {
+                                Ok(()) => self.scene = Scene::overview(&self.config),
+                                Err(e) => {
+                                    if let Scene::EditTarget { ref mut editor, .. } = self.scene {
+                                        editor.error = Some(e);
+                                    }
+                                }
+                            }
+                        }
+                    }
+                    TargetEditorMessage::Cancel => match self.scene {
+                        Scene::CreateTarget { ref editor }
+                        | Scene::EditTarget { ref editor, .. }
+                            if editor.dirty => {}
+                        _ => self.scene = Scene::overview(&self.config),
+                    },
+                    TargetEditorMessage::Discard => {
+                        let quit = match self.scene {
+                            Scene::CreateTarget { ref editor }
+                            | Scene::EditTarget { ref editor, .. } => {
+                                editor.leaving == Some(target_editor::Leave::Quit)
+                            }
+                            _ => false,
+                        };
+                        if quit && self.guard_backups(Guarded::Quit) {
+                            SHOULD_EXIT.store(true, std::sync::atomic::Ordering::Relaxed);
+                        }
+                        self.scene = Scene::overview(&self.config);
+                    }
+                    _ => (),
+                }
+                match &mut self.scene {
+                    Scene::CreateTarget { editor, .. } | Scene::EditTarget { editor, .. } => {
+                        editor.update(msg).map(Message::TargetEditor)
+                    }
+                    // Possible because scene might change above
+                    _ => Command::none(),
+                }
+            }
+            Message::OpenSettings => {
+                self.scene = Scene::settings();
+                self.inspection = None;
+                self.compute_usage()
+            }
+            Message::ToggleInspector => {
+                if self.inspection.take().is_some() {
+                    return Command::none();
+                }
+                match self.config.selected_repo() {
+                    Some(repo) => {
+                        self.inspection = Some(None);
+                        Command::perform(
+                            rdedup::inspect_async(repo.home.clone(), self.log.clone()),
+                            Message::Inspected,
+                        )
+                    }
+                    None => {
+                        self.inspection = Some(Some(Err("No repo selected".to_string())));
+                        Command::none()
+                    }
+                }
+            }
+            Message::Inspected(result) => {
+                // Otherwise closed meanwhile
+                if let Some(ref mut inspection) = self.inspection {
+                    *inspection = Some(result);
+                }
+                Command::none()
+            }
+            Message::ToggleGroup(group) => {
+                if let Some(repo) = self.config.selected_repo_mut() {
+                    if !repo.collapsed_groups.remove(&group) {
+                        repo.collapsed_groups.insert(group);
+                    }
+                }
+                self.save_config();
+                Command::none()
+            }
+            Message::Notified(result) => {
+                if let Err(e) = result {
+                    error!(self.log, "Sending notifications failed: {}", e);
+                }
+                Command::none()
+            }
+            Message::InterruptedBackupDiscarded(repo_id, target_id, name, result) => {
+                let outcome = match result {
+                    Ok(outcome) => outcome,
+                    Err(e) => {
+                        // Left set, to try again on the next launch
+                        error!(self.log, "Cleaning up interrupted backup {:?}: {}", name, e);
+                        return Command::none();
+                    }
+                };
+                info!(self.log, "Interrupted backup {:?}: {:?}", name, outcome);
+                let target = self
+                    .config
+                    .repos
+                    .get_mut(&repo_id)
+                    .and_then(|repo| repo.targets.iter_mut().find(|t| t.id == target_id));
+                if let Some(target) = target {
+                    // Unless a new backup has started since
+                    if target.interrupted_backup.as_ref() == Some(&name) {
+                        target.interrupted_backup = None;
+                    }
+                    let (status, message) = match outcome {
+                        backup::Interrupted::NotWritten => (
+                            BackupStatus::Failed,
+                            "Interrupted before the snapshot was written".to_string(),
+                        ),
+                        backup::Interrupted::Removed => (
+                            BackupStatus::Failed,
+                            format!("Interrupted; removed the incomplete snapshot {}", name),
+                        ),
+                        backup::Interrupted::Complete => (BackupStatus::Ok, name),
+                    };
+                    target.last_result = Some(LastResult {
+                        status,
+                        time: Utc::now(),
+                        message,
+                    });
+                }
+                self.save_config();
+                if outcome == backup::Interrupted::Removed {
+                    self.snapshots_changed(repo_id);
+                    return self.load_snapshots();
+                }
+                Command::none()
+            }
+            Message::UsageComputed(repo_id, generation, result) => {
+                if let Err(ref e) = result {
+                    error!(self.log, "Measuring repo {} failed: {}", repo_id, e);
+                }
+                match self.usage.get_mut(&repo_id) {
+                    Some(entry) if entry.0 == generation => entry.1 = Some(result),
+                    _ => (),
+                }
+                Command::none()
+            }
+            Message::FreeSpaceChecked(repo_id, result) => {
+                match result {
+                    Ok(space) => {
+                        if let Some(entry) = self.free_space.get_mut(&repo_id) {
+                            entry.1 = Some(space);
+                        }
+                    }
+                    Err(e) => error!(self.log, "Checking free space of repo failed: {}", e),
+                }
+                Command::none()
+            }
+            Message::DiskSpaceChecked(disk, result) => {
+                self.disk_space.insert(disk, Some(result));
+                Command::none()
+            }
+            Message::ChangesEstimated(repo_id, target_name, since, result) => {
+                if let Err(ref e) = result {
+                    error!(
+                        self.log,
+                        "Estimating changes of {} failed: {}", target_name, e
+                    );
+                }
+                match self.changes.get_mut(&(repo_id, target_name)) {
+                    // Otherwise a newer backup was made meanwhile
+                    Some(entry) if entry.0 == since => entry.2 = Some(result),
+                    _ => (),
+                }
+                Command::none()
+            }
+            Message::OpenExport => {
+                self.scene = Scene::Export {
+                    wizard: Default::default(),
+                };
+                Command::none()
+            }
+            Message::OpenSplit => {
+                if self.config.selected_repo().is_some() {
+                    self.scene = Scene::Split {
+                        split: Default::default(),
+                    };
+                }
+                Command::none()
+            }
+            Message::Split(msg) => {
+                match msg {
+                    SplitMessage::Back => {
+                        self.scene = Scene::overview(&self.config);
+                        return Command::none();
+                    }
+                    SplitMessage::Confirm => {
+                        let targets = match (&self.scene, self.config.selected_repo()) {
+                            (Scene::Split { split }, Some(repo)) => {
+                                split.targets(repo.id, &repo.targets)
+                            }
+                            _ => return Command::none(),
+                        };
+                        // Appended, so that the indices of running backups stay valid
+                        if let Some(repo) = self.config.selected_repo_mut() {
+                            info!(self.log, "Adding {} targets from a split", targets.len());
+                            repo.targets.extend(targets);
+                        }
+                        self.save_config();
+                        self.scene = Scene::overview(&self.config);
+                        return Command::none();
+                    }
+                    _ => (),
+                }
+                match self.scene {
+                    Scene::Split { ref mut split } => split.update(msg).map(Message::Split),
+                    _ => Command::none(),
+                }
+            }
+            Message::OpenBulkEdit => {
+                self.scene = Scene::BulkEdit {
+                    bulk: Default::default(),
+                };
+                Command::none()
+            }
+            Message::BulkEdit(msg) => {
+                if let Scene::BulkEdit { ref mut bulk } = self.scene {
+                    match msg {
+                        BulkEditMessage::Back => self.scene = Scene::settings(),
+                        BulkEditMessage::Apply => {
+                            let n = bulk.apply(&mut self.config);
+                            info!(self.log, "Copied repo settings to {} repos", n);
+                            self.save_config();
+                        }
+                        msg => bulk.update(msg),
+                    }
+                }
+                Command::none()
+            }
+            Message::OpenInventory => {
+                self.scene = Scene::Inventory {
+                    inventory: Default::default(),
+                };
+                Command::none()
+            }
+            Message::Inventory(msg) => {
+                match msg {
+                    InventoryMessage::Back => {
+                        self.scene = Scene::settings();
+                        return Command::none();
+                    }
+                    InventoryMessage::Start => {
+                        let selected = self
+                            .config
+                            .selected_repo()
+                            .map(|repo| (repo.clone(), self.repo_passphrase(repo.id)));
+                        if let (
+                            Scene::Inventory { inventory },
+                            Some(repo),
+                            Some((config, passphrase)),
+                        ) = (&mut self.scene, self.repo.clone(), selected)
+                        {
+                            return inventory
+                                .start(repo, config, passphrase)
+                                .map(Message::Inventory);
+                        }
+                    }
+                    _ => (),
+                }
+                match self.scene {
+                    Scene::Inventory { ref mut inventory } => {
+                        inventory.update(msg).map(Message::Inventory)
+                    }
+                    _ => Command::none(),
+                }
+            }
+            Message::OpenReconcile => {
+                self.scene = Scene::Reconcile {
+                    reconcile: Default::default(),
+                };
+                self.list_for_reconcile()
+            }
+            Message::Reconcile(msg) => {
+                let mut command = Command::none();
+                match msg {
+                    ReconcileMessage::Back => self.scene = Scene::settings(),
+                    ReconcileMessage::Recreate(ref name) => {
+                        if let Some(repo) = self.config.selected_repo_mut() {
+                            info!(self.log, "Recreating target {:?}", name);
+                            let repo_id = repo.id;
+                            repo.targets.push(Target {
+                                id: Uuid::new_v4(),
+                                repo: repo_id,
+                                name: name.clone(),
+                                ..Default::default()
+                            });
+                            self.save_config();
+                        }
+                    }
+                    ReconcileMessage::Delete(_) if self.selected_read_only() => {
+                        let error = Err("The repo is read-only".to_string());
+                        return self.update(Message::Reconcile(ReconcileMessage::Deleted(error)));
+                    }
+                    ReconcileMessage::Delete(ref names) => {
+                        match (&self.repo, self.config.selected_repo()) {
+                            (Some(repo), Some(repo_config)) => {
+                                let (pinned, names): (Vec<String>, Vec<String>) = names
+                                    .iter()
+                                    .cloned()
+                                    .partition(|name| repo_config.pinned_snapshots.contains(name));
+                                if !pinned.is_empty() {
+                                    info!(self.log, "Keeping pinned snapshots {:?}", pinned);
+                                }
+                                info!(self.log, "Deleting {} orphan snapshots", names.len());
+                                let repo_id = repo_config.id;
+                                command = Command::perform(
+                                    rdedup::remove_async(repo.clone(), names.clone()),
+                                    move |result| {
+                                        Message::Reconcile(ReconcileMessage::Deleted(
+                                            result.map(|()| (repo_id, names)),
+                                        ))
+                                    },
+                                );
+                                self.snapshots_changed(repo_id);
+                            }
+                            _ => error!(self.log, "[User error] No repo opened"),
+                        }
+                    }
+                    ReconcileMessage::Deleted(Ok((repo_id, ref names))) => {
+                        // Only now, since they are kept if deleting fails
+                        if let Some(repo_config) = self.config.repos.get_mut(&repo_id) {
+                            for name in names {
+                                repo_config.snapshot_checksums.remove(name);
+                                repo_config.snapshot_tags.remove(name);
+                                repo_config.failed_verifications.remove(name);
+                            }
+                            self.save_config();
+                        }
+                        // Once for all the deleted snapshots, to free their data
+                        command = Command::batch(vec![self.list_for_reconcile(), self.compact()])
+                    }
+                    _ => (),
+                }
+                let repo = self.config.selected_repo();
+                if let Scene::Reconcile { ref mut reconcile } = self.scene {
+                    reconcile.update(msg, repo);
+                }
+                command
+            }
+            Message::Export(msg) => {
+                if let ExportMessage::Back = msg {
+                    self.scene = Scene::settings();
+                }
+                match self.scene {
+                    Scene::Export { ref mut wizard } => {
+                        wizard.update(msg, &self.config).map(Message::Export)
+                    }
+                    _ => Command::none(),
+                }
+            }
+            Message::TryDemo => {
+                let command = self.update(Message::InitialConfirm);
+                if self.passphrase.is_some() {
+                    match demo::create_demo(self.passphrase.clone().unwrap(), self.log.clone()) {
+                        Ok((repo_config, repo)) => {
+                            info!(self.log, "Created demo repo at {:?}", repo_config.home);
+                            self.repo = Some(repo);
+                            self.config.selected_repo = Some(Opt {
+                                name: repo_config.name.clone(),
+                                value: RepoOption::Select(repo_config.id),
+                            });
+                            self.config.repos.insert(repo_config.id, repo_config);
+                            self.scene = Scene::overview(&self.config);
+                        }
+                        Err(e) => error!(self.log, "Could not create demo repo: {:#}", e),
+                    }
+                }
+                command
+            }
+            Message::UndoDelete => {
+                if let Err(e) = self.undo_delete() {
+                    self.overview_error(e);
+                }
+                Command::none()
+            }
+            Message::KeepDemo => {
+                if let Some(repo) = self.config.selected_repo_mut() {
+                    repo.ephemeral = false;
+                }
+                self.save_config();
+                Command::none()
+            }
+            Message::SetHideEmptyRepos(hide) => {
+                self.config.hide_empty_repos = hide;
+                Command::none()
+            }
+            Message::SetBackgroundPriority(background) => {
+                self.config.background_priority = background;
+                Command::none()
+            }
+            Message::SetBackupOnStartup(backup_on_startup) => {
+                self.config.backup_on_startup = backup_on_startup;
+                Command::none()
+            }
+            Message::SetLogLevel(level) => {
+                self.config.log_level = level;
+                log::configure(level, self.config.log_file_location);
+                Command::none()
+            }
+            Message::SetLogFileLocation(file_location) => {
+                self.config.log_file_location = file_location;
+                log::configure(self.config.log_level, file_location);
+                Command::none()
+            }
+            Message::ForgetPassphrase => {
+                if let Err(e) = keychain::forget() {
+                    error!(self.log, "{:#}", e);
+                }
+                self.config.remember_passphrase = false;
+                self.save_config();
+                Command::none()
+            }
+            Message::NewDefaultExclude => {
+                self.config.default_excludes.push(String::new());
+                Command::none()
+            }
+            Message::SetDefaultExclude(i, exclude) => {
+                self.config.default_excludes[i] = exclude;
+                Command::none()
+            }
+            Message::DelDefaultExclude(i) => {
+                self.config.default_excludes.remove(i);
+                Command::none()
+            }
+            Message::SetOpenRetries(retries) => {
+                self.config.open_retries = retries;
+                self.save_config();
+                Command::none()
+            }
+            Message::SetMaxConcurrentBackups(max) => {
+                self.config.max_concurrent_backups = max.max(1);
+                self.start_queued_backups()
+            }
+            Message::SetReadThreads(max) => {
+                // Reads already waiting keep the limit they started with
+                if let Some(repo) = self.config.selected_repo_mut() {
+                    repo.read_threads = max.clamp(1, MAX_READ_THREADS);
+                }
+                Command::none()
+            }
+            Message::RetrySave => {
+                self.save_config();
+                Command::none()
+            }
+            Message::CancelGuardedBackups => {
+                if let Some(ref guarded) = self.guarded {
+                    let selected = self.config.selected_repo().map(|repo| repo.id);
+                    for running in self.guarded_backups(guarded) {
+                        info!(
+                            self.log,
+                            "Cancelling backup of target {} of repo {}",
+                            running.target_index,
+                            running.repo_id
+                        );
+                        running
+                            .cancel
+                            .store(true, std::sync::atomic::Ordering::Relaxed);
+                    }
+                    // They would take the freed slots
+                    match guarded {
+                        Guarded::PickRepo(_) => self
+                            .backup_queue
+                            .retain(|queued| Some(queued.repo_id) != selected),
+                        Guarded::Quit => self.backup_queue.clear(),
+                    }
+                }
+                Command::none()
+            }
+            Message::DismissGuard => {
+                self.guarded = None;
+                Command::none()
+            }
+            Message::EditRepo => {
+                if let Some(repo) = self.config.selected_repo() {
+                    self.scene = Scene::edit_repo(repo);
+                }
+                Command::none()
+            }
+            Message::PickRepo(repo) => {
+                let switching = match repo.value {
+                    RepoOption::Select(id) => self.config.selected_repo().map(|r| r.id) != Some(id),
+                    RepoOption::New | RepoOption::Import => false,
+                };
+                if switching && !self.guard_backups(Guarded::PickRepo(repo.clone())) {
+                    return Command::none();
+                }
                 match repo.value {
-                    RepoOption::New => self.scene = Scene::create_repo(),
+                    RepoOption::New => self.scene = Scene::create_repo(RepoEditorMode::Create),
+                    RepoOption::Import => self.scene = Scene::create_repo(RepoEditorMode::Import),
                     RepoOption::Select(id) => {
-                        // Find repo in config
-
-                        let result: anyhow::Result<()> = try {
-                            let repo_config =
-                                self.config.find_repo(id).context("Cannot find repo")?;
-
-                            let url = &Url::from_directory_path(&repo_config.home)
-                                .map_err(|()| anyhow::Error::msg("Url->Path"))?;
-                            info!(self.log, "Opening repo at {}", url);
-
-                            let repo = Repo::open(url, self.log.clone())?;
-                            self.repo = Some(repo);
-                        };
-
-                        match result {
-                            Ok(()) => self.config.selected_repo = Some(repo),
-                            Err(e) => error!(self.log, "[User error] {:#?}", e),
+                        if let Some(repo_config) = self.config.find_repo(id) {
+                            info!(self.log, "Opening repo at {}", repo_config.home.display());
+                            // The repo exists, but its passphrase might have been changed
+                            // outside of bup
+                            return Command::perform(
+                                rdedup::open_async(
+                                    repo_config.home.clone(),
+                                    self.repo_passphrase(id),
+                                    self.log.clone(),
+                                ),
+                                move |result| {
+                                    Message::RepoPicked(
+                                        repo.clone(),
+                                        result.map(|(repo, unlocked)| (Redacted(repo), unlocked)),
+                                    )
+                                },
+                            );
+                        }
+                        error!(self.log, "[User error] Cannot find repo");
+                    }
+                }
+                Command::none()
+            }
+            Message::RepoPicked(repo, result) => {
+                let id = match repo.value {
+                    RepoOption::Select(id) => id,
+                    RepoOption::New | RepoOption::Import => return Command::none(),
+                };
+                match result {
+                    Ok((Redacted(opened), unlocked)) => {
+                        self.repo = Some(opened);
+                        self.config.selected_repo = Some(repo);
+                        if let Scene::Overview {
+                            ref mut passphrase_prompt,
+                            ..
+                        } = self.scene
+                        {
+                            *passphrase_prompt = match unlocked {
+                                Ok(()) => None,
+                                Err(e) => {
+                                    info!(self.log, "Passphrase needed: {}", e);
+                                    Some(PassphrasePrompt::new(id))
+                                }
+                            };
                         }
+                        self.check_health(false)
+                    }
+                    Err(e) => {
+                        error!(self.log, "[User error] {}", e.message);
+                        Command::none()
                     }
                 }
+            }
+            Message::HealthChecked(id, health) => {
+                self.health.insert(id, health);
+                Command::none()
+            }
+            Message::ToggleHealth => {
+                if let Scene::Overview {
+                    ref mut show_health,
+                    ..
+                } = self.scene
+                {
+                    *show_health = !*show_health;
+                }
                 Command::none()
             }
 
+            Message::SetRepoPassphrase(pass) => {
+                if let Scene::Overview {
+                    passphrase_prompt: Some(ref mut prompt),
+                    ..
+                } = self.scene
+                {
+                    prompt.passphrase = pass;
+                }
+                Command::none()
+            }
+            Message::ConfirmRepoPassphrase => {
+                if let Scene::Overview {
+                    passphrase_prompt: Some(ref mut prompt),
+                    ..
+                } = self.scene
+                {
+                    if let (false, Some(repo)) = (prompt.checking, self.repo.clone()) {
+                        prompt.checking = true;
+                        prompt.error = None;
+                        let (id, passphrase) = (prompt.repo_id, prompt.passphrase.clone());
+                        return Command::perform(
+                            rdedup::check_passphrase_async(repo, passphrase.clone()),
+                            move |result| Message::PassphraseChecked(id, passphrase, result),
+                        );
+                    }
+                }
+                Command::none()
+            }
+            Message::PassphraseChecked(id, passphrase, result) => {
+                if let Scene::Overview {
+                    ref mut passphrase_prompt,
+                    ..
+                } = self.scene
+                {
+                    match (passphrase_prompt.as_mut(), result) {
+                        (Some(prompt), _) if prompt.repo_id != id => (),
+                        (Some(_), Ok(())) => {
+                            self.repo_passphrases.insert(id, passphrase);
+                            *passphrase_prompt = None;
+                            return Command::batch(vec![
+                                self.discard_interrupted_backups(),
+                                self.queue_startup_backups(),
+                            ]);
+                        }
+                        (Some(prompt), Err(_)) => {
+                            prompt.checking = false;
+                            prompt.error = Some("Wrong passphrase".to_string());
+                        }
+                        (None, _) => (),
+                    }
+                }
+                Command::none()
+            }
             Message::SetPassphrase1(pass) => match &mut self.scene {
                 Scene::Initial {
                     ref mut passphrase1,
@@ -484,6 +2936,34 @@ impl Application for Ui {
                 }
                 _ => Command::none(),
             },
+            Message::SetRemember(remember) => {
+                if let Scene::Initial {
+                    remember: ref mut r,
+                    ..
+                } = self.scene
+                {
+                    *r = remember;
+                }
+                Command::none()
+            }
+            Message::ToggleRevealPassphrase => {
+                if let Scene::Initial { ref mut reveal, .. } = self.scene {
+                    *reveal = !*reveal;
+                }
+                Command::none()
+            }
+            Message::TrimPassphrase => {
+                if let Scene::Initial {
+                    ref mut passphrase1,
+                    ref mut passphrase2,
+                    ..
+                } = self.scene
+                {
+                    *passphrase1 = passphrase1.trim().to_string();
+                    *passphrase2 = passphrase2.trim().to_string();
+                }
+                Command::none()
+            }
             Message::SetPassphrase2(pass) => match &mut self.scene {
                 Scene::Initial {
                     ref mut passphrase2,
@@ -499,8 +2979,10 @@ impl Application for Ui {
                     ref passphrase1,
                     ref passphrase2,
                     ref mut error,
+                    remember,
                     ..
                 } => {
+                    let remember = *remember;
                     if let Some(ref passphrase_hash) = self.config.passphrase_hash {
                         let hash = PasswordHash::new(&passphrase_hash).unwrap();
                         if self
@@ -508,8 +2990,8 @@ impl Application for Ui {
                             .verify_password(&passphrase1.as_bytes(), &hash)
                             .is_ok()
                         {
-                            self.passphrase = Some(passphrase1.clone());
-                            self.scene = Scene::overview(&self.config);
+                            let passphrase = passphrase1.clone();
+                            return self.accept_passphrase(passphrase, remember);
                         } else {
                             *error = Some("Wrong passphrase".to_string());
                         }
@@ -517,8 +2999,9 @@ impl Application for Ui {
                         if passphrase1 == passphrase2 {
                             self.config.passphrase_hash =
                                 Some(hash_passphrase(&self.argon2, &passphrase1));
-                            self.passphrase = Some(passphrase1.clone());
-                            self.scene = Scene::overview(&self.config);
+                            let passphrase = passphrase1.clone();
+                            self.save_config();
+                            return self.accept_passphrase(passphrase, remember);
                         } else {
                             *error = Some("Passphrases don't match".to_string());
                         }
@@ -527,87 +3010,341 @@ impl Application for Ui {
                 }
                 _ => Command::none(),
             },
-            Message::SetRepoName(new_name) => match self.scene {
-                Scene::CreateRepo { ref mut name, .. } => {
-                    *name = new_name;
-                    Command::none()
-                }
-                _ => Command::none(),
-            },
-            Message::SetRepoHome(new_home) => match self.scene {
-                Scene::CreateRepo { ref mut home, .. } => {
-                    *home = Some(new_home);
-                    Command::none()
-                }
-                _ => Command::none(),
-            },
-            Message::SaveRepo => match &mut self.scene {
-                Scene::CreateRepo {
-                    name,
-                    home,
-                    ref mut error,
-                    ..
-                } => {
-                    if !name.is_empty() {
-                        if let Some(home) = home {
-                            match init_repo(
-                                home,
-                                self.passphrase.clone().unwrap(),
-                                self.log.clone(),
-                            ) {
-                                Ok(repo) => {
-                                    self.repo = Some(repo);
-                                    let id = Uuid::new_v4();
-                                    self.config.repos.insert(
-                                        id,
-                                        RepoConfig {
-                                            id,
-                                            name: name.clone(),
-                                            home: home.clone(),
-                                            targets: Default::default(),
-                                        },
-                                    );
-                                    self.config.selected_repo = Some(Opt {
-                                        name: name.clone(),
-                                        value: RepoOption::Select(id),
-                                    });
-                                    self.scene = Scene::overview(&self.config);
-                                    Command::none()
+            Message::RepoEditor(msg) => {
+                match msg {
+                    RepoEditorMessage::Save => {
+                        let editor = match &mut self.scene {
+                            Scene::CreateRepo { ref mut editor }
+                            | Scene::EditRepo { ref mut editor } => editor,
+                            _ => panic!(),
+                        };
+                        let result: Result<(), BupError> = try {
+                            let mut repo = editor.finish()?;
+                            if editor.mode != RepoEditorMode::Edit {
+                                verify_new_home(&repo.home, &self.config)?;
+                            }
+                            match editor.mode {
+                                RepoEditorMode::Create => {
+                                    if is_rdedup_repo(&repo.home) {
+                                        editor.offer_import = true;
+                                        Err(BupError::InvalidRepo(format!(
+                                            "{} already holds an rdedup repo",
+                                            repo.home.display()
+                                        )))?;
+                                    }
+                                    let opened = init_repo(
+                                        &repo.home,
+                                        self.passphrase.clone().unwrap(),
+                                        self.log.clone(),
+                                    )
+                                    .map_err(BupError::repo_open)?;
+                                    self.repo = Some(opened);
                                 }
-                                Err(e) => {
-                                    *error = Some(e.to_string());
-                                    Command::none()
+                                RepoEditorMode::Import => {
+                                    let opened =
+                                        rdedup::open_existing(&repo.home, self.log.clone())
+                                            .map_err(BupError::repo_open)?;
+                                    repo.targets = rdedup::seed_targets(&opened, repo.id)
+                                        .map_err(BupError::repo_open)?;
+                                    self.repo = Some(opened);
                                 }
+                                RepoEditorMode::Edit => (),
                             }
-                        } else {
-                            *error = Some("Home path must be set".to_string());
-                            Command::none()
+                            self.config.selected_repo = Some(Opt {
+                                name: repo.name.clone(),
+                                value: RepoOption::Select(repo.id),
+                            });
+                            self.config.repos.insert(repo.id, repo);
+                        };
+                        match result {
+                            Ok(()) => {
+                                self.scene = Scene::overview(&self.config);
+                                self.save_config();
+                            }
+                            Err(e) => editor.error = Some(e.to_string()),
                         }
-                    } else {
-                        *error = Some("Name must be non-empty".to_string());
+                    }
+                    RepoEditorMessage::Cancel => {
+                        self.scene = Scene::overview(&self.config);
+                    }
+                    RepoEditorMessage::Move => {
+                        let id = match self.scene {
+                            Scene::EditRepo { ref editor } => editor.repo.id,
+                            _ => panic!(),
+                        };
+                        let busy = self.running_backups.iter().any(|r| r.repo_id == id)
+                            || self.running_duplications.iter().any(|r| r.0 == id)
+                            || self.compacting == Some(id);
+                        let from = self.config.repos.get(&id).map(|repo| repo.home.clone());
+                        let passphrase = self.repo_passphrase(id);
+                        let editor = match self.scene {
+                            Scene::EditRepo { ref mut editor } => editor,
+                            _ => panic!(),
+                        };
+                        match (from, editor.move_to.clone()) {
+                            _ if self.relocating.is_some() => (),
+                            _ if busy => {
+                                editor.relocation = Some(Some(Err(
+                                    "Wait for the running backups, duplications and compaction \
+                                     of the repo to finish"
+                                        .to_string(),
+                                )))
+                            }
+                            (Some(from), Some(to)) => {
+                                self.relocating = Some(id);
+                                editor.relocation = Some(None);
+                                editor.old_deleted = None;
+                                return Command::perform(
+                                    duplicate::relocate_async(
+                                        from.clone(),
+                                        to.clone(),
+                                        passphrase,
+                                        self.log.clone(),
+                                    ),
+                                    move |result| {
+                                        Message::Relocated(id, from.clone(), to.clone(), result)
+                                    },
+                                );
+                            }
+                            _ => (),
+                        }
+                    }
+                    RepoEditorMessage::DeleteOldHome => {
+                        if let Scene::EditRepo { ref editor } = self.scene {
+                            if let Some(Some(Ok(ref old_home))) = editor.relocation {
+                                return Command::perform(
+                                    duplicate::remove_old_home_async(old_home.clone()),
+                                    Message::OldHomeDeleted,
+                                );
+                            }
+                        }
+                    }
+                    _ => (),
+                }
+                match &mut self.scene {
+                    Scene::CreateRepo { editor } | Scene::EditRepo { editor } => {
+                        editor.update(msg).map(Message::RepoEditor)
+                    }
+                    // Possible because scene might change above
+                    _ => Command::none(),
+                }
+            }
+            Message::Relocated(repo_id, old_home, new_home, result) => {
+                self.relocating = None;
+                let result = match result {
+                    Ok(Redacted(repo)) => {
+                        info!(self.log, "Relocated repo to {}", new_home.display());
+                        if let Some(repo_config) = self.config.repos.get_mut(&repo_id) {
+                            repo_config.home = new_home.clone();
+                        }
+                        // From now on, only the new home is written to
+                        for queued in self.backup_queue.iter_mut() {
+                            if queued.repo_id == repo_id {
+                                queued.repo = repo.clone();
+                            }
+                        }
+                        if self.config.selected_repo().map(|repo| repo.id) == Some(repo_id) {
+                            self.repo = Some(repo);
+                        }
+                        self.save_config();
+                        Ok(old_home)
+                    }
+                    Err(e) => {
+                        error!(self.log, "Relocating repo failed: {}", e);
+                        Err(e)
+                    }
+                };
+                if let Scene::EditRepo { ref mut editor } = self.scene {
+                    if editor.repo.id == repo_id {
+                        if result.is_ok() {
+                            editor.repo.home = new_home;
+                            editor.move_to = None;
+                        }
+                        editor.relocation = Some(Some(result));
+                    }
+                }
+                self.start_queued_backups()
+            }
+            Message::OldHomeDeleted(result) => {
+                if let Err(ref e) = result {
+                    error!(self.log, "{}", e);
+                }
+                if let Scene::EditRepo { ref mut editor } = self.scene {
+                    editor.old_deleted = Some(result);
+                }
+                Command::none()
+            }
+            Message::BackupResult(repo_id, i, result) => {
+                self.snapshots_changed(repo_id);
+                let started = self
+                    .running_backups
+                    .iter()
+                    .position(|r| r.repo_id == repo_id && r.target_index == i)
+                    .map(|pos| self.running_backups.remove(pos).started);
+                match result {
+                    Ok(ref done) => info!(
+                        self.log,
+                        "Backup of target {} done: {} {:?}", i, done.snapshot.name, done.warnings
+                    ),
+                    Err(ref e) => error!(self.log, "Backup of target {} failed: {}", i, e),
+                }
+                let mut notify = Command::none();
+                if let Some(repo) = self.config.repos.get_mut(&repo_id) {
+                    if let Some(target) = repo.targets.get_mut(i) {
+                        target.last_result = Some(LastResult::new(&result));
+                        target.interrupted_backup = None;
+                        if let Ok(ref done) = result {
+                            target.record_size(&done.snapshot);
+                        }
+                        let entry =
+                            history::Entry::backup(repo_id, target.name.clone(), &result, started);
+                        history::append(&entry, &self.log);
+                        if !repo.notifiers.is_empty()
+                            && notify::wanted(&entry, repo.notify_on_success)
+                        {
+                            notify = Command::perform(
+                                notify::notify_async(repo.notifiers.clone(), entry),
+                                Message::Notified,
+                            );
+                        }
+                    }
+                }
+                if let (
+                    Ok(backup::BackupDone {
+                        snapshot,
+                        sha256: Some(sha256),
+                        ..
+                    }),
+                    Some(repo),
+                ) = (&result, self.config.repos.get_mut(&repo_id))
+                {
+                    repo.snapshot_checksums
+                        .insert(snapshot.name.clone(), sha256.clone());
+                }
+                if let (Ok(done), Some(repo)) = (&result, self.config.repos.get_mut(&repo_id)) {
+                    record_verification(repo, done);
+                }
+                self.save_config();
+                Command::batch(vec![
+                    notify,
+                    self.load_snapshots(),
+                    self.check_health(true),
+                    self.start_queued_backups(),
+                    self.resume_guarded(),
+                ])
+            }
+            Message::Compact => self.compact(),
+            Message::GcProgress => {
+                self.gc_shown = *self.gc_progress.lock().unwrap();
+                Command::none()
+            }
+            Message::LimitsChecked(repo_id, i, result) => {
+                // The list shows another repo now
+                if self.config.selected_repo().map(|repo| repo.id) != Some(repo_id) {
+                    return Command::none();
+                }
+                if let (Scene::Overview { ref mut list, .. }, Ok(dry_run)) =
+                    (&mut self.scene, &result)
+                {
+                    if let Some(item) = list.get_mut(i) {
+                        item.sparse_files = dry_run.sparse_files;
+                    }
+                }
+                match result {
+                    Ok(backup::DryRun {
+                        over_limit: None, ..
+                    }) => self.queue_backup(i),
+                    Ok(backup::DryRun {
+                        over_limit: Some(over_limit),
+                        ..
+                    }) => {
+                        self.set_over_limit(i, Some(over_limit));
                         Command::none()
                     }
+                    Err(e) => {
+                        // The guard is a convenience; don't let it block the backup
+                        error!(self.log, "Checking backup limits: {}", e);
+                        self.queue_backup(i)
+                    }
                 }
-                _ => Command::none(),
-            },
-            Message::RepoHome(msg) => match &mut self.scene {
-                Scene::CreateRepo {
-                    ref mut home,
-                    ref mut s_home,
-                    ..
-                } => {
-                    if let path::Message::Path(ref path) = msg {
-                        *home = Some(path.clone());
+            }
+            Message::Compacted(id, result) => {
+                self.compacting = None;
+                self.usage.remove(&id);
+                if let Err(ref e) = result {
+                    error!(self.log, "Compacting repo {} failed: {}", id, e);
+                }
+                self.gc_shown = *self.gc_progress.lock().unwrap();
+                self.compact_result = Some((id, result));
+                // Backups of the repo may have been waiting for it
+                self.start_queued_backups()
+            }
+            Message::Unlocked(result) => {
+                // The demo may have been created in the meantime
+                let attempt = match self.scene {
+                    Scene::Unlocking { attempt, .. } => attempt,
+                    _ => return Command::none(),
+                };
+                if let Err(ref e) = result {
+                    if e.transient && attempt <= self.config.open_retries {
+                        info!(self.log, "Repo not available yet: {}", e.message);
+                        if let Scene::Unlocking { ref mut retry, .. } = self.scene {
+                            // Backing off, up to a minute
+                            let delay = Duration::from_secs((2 * attempt as u64).min(60));
+                            *retry = Some((Instant::now() + delay, e.message.clone()));
+                        }
+                        return Command::none();
                     }
-                    s_home.update(msg).map(Message::RepoHome)
                 }
+                self.scene = Scene::overview(&self.config);
+                match result {
+                    Ok((Redacted(repo), unlocked)) => {
+                        self.repo = Some(repo);
+                        if let (
+                            Scene::Overview {
+                                passphrase_prompt, ..
+                            },
+                            Err(e),
+                            Some(repo),
+                        ) = (&mut self.scene, unlocked, self.config.selected_repo())
+                        {
+                            info!(self.log, "Passphrase needed: {}", e);
+                            *passphrase_prompt = Some(PassphrasePrompt::new(repo.id));
+                            // Backed up on startup once the passphrase is entered
+                            return self.check_health(false);
+                        }
+                        Command::batch(vec![
+                            self.check_health(false),
+                            self.discard_interrupted_backups(),
+                            self.queue_startup_backups(),
+                        ])
+                    }
+                    Err(e) => {
+                        self.scene = Scene::overview_error(&self.config, e.message);
+                        Command::none()
+                    }
+                }
+            }
+            Message::RetryOpen => match self.scene {
+                Scene::Unlocking { retry: Some(_), .. } => self.open_selected_repo(),
                 _ => Command::none(),
             },
+            Message::GiveUpOpen => {
+                if let Scene::Unlocking {
+                    retry: Some((_, ref error)),
+                    ..
+                } = self.scene
+                {
+                    let error = error.clone();
+                    self.scene = Scene::overview_error(&self.config, error);
+                }
+                Command::none()
+            }
             Message::RepoSaveResult(result) => match &mut self.scene {
-                Scene::CreateRepo { ref mut error, .. } => {
+                Scene::CreateRepo { ref mut editor } => {
                     match result {
                         Ok(repo) => (), // TODO??
-                        Err(e) => *error = Some(e),
+                        Err(e) => editor.error = Some(e.to_string()),
                     }
                     Command::none()
                 }
@@ -617,53 +3354,270 @@ impl Application for Ui {
     }
 
     fn view(&mut self) -> Element<Message> {
+        // Cheap; the usage itself is computed in the background
+        let usage_summary = self.usage_summary();
+        let guard_warning = self.guarded.as_ref().map(|guarded| {
+            let n = self.guarded_backups(guarded).len();
+            let backups = if n == 1 {
+                "A backup is".to_string()
+            } else {
+                format!("{} backups are", n)
+            };
+            match guarded {
+                Guarded::PickRepo(_) => format!(
+                    "{} running in this repo. Switching repos waits until they are done.",
+                    backups
+                ),
+                Guarded::Quit => format!(
+                    "{} running. Quitting waits until they are done; an interrupted backup \
+                     is discarded.",
+                    backups
+                ),
+            }
+        });
         let config = &self.config;
-        let w: Container<Message> = match &mut self.scene {
+        let read_only = config.selected_repo().map_or(false, |repo| repo.read_only);
+        let scene: Container<Message> = match &mut self.scene {
             Scene::Initial {
                 passphrase1,
                 passphrase2,
                 s_pass1,
                 s_pass2,
+                remember,
+                reveal,
                 s_confirm,
+                s_demo,
+                s_reveal,
+                s_trim,
                 error,
             } => Container::new({
-                let mut column = Column::new().padding(20).spacing(20).push(
+                // Setting a new passphrase, rather than unlocking
+                let new = self.config.passphrase_hash.is_none();
+                let hide = !(new && *reveal);
+                let mut pass1 =
                     TextInput::new(s_pass1, "Passphrase", passphrase1, Message::SetPassphrase1)
-                        .password()
                         .style(style::TextInput)
-                        .size(H3_SIZE),
+                        .size(H3_SIZE);
+                if hide {
+                    pass1 = pass1.password();
+                }
+                let mut column = Column::new().padding(20).spacing(20).push(
+                    Row::new()
+                        .spacing(10)
+                        .push(Icon::Lock.h3())
+                        .push(h3("Locked")),
                 );
-                if self.config.passphrase_hash.is_none() {
-                    column = column.push(
-                        TextInput::new(
-                            s_pass2,
-                            "Confirm passphrase",
-                            passphrase2,
-                            Message::SetPassphrase2,
+                if new {
+                    let mut pass2 = TextInput::new(
+                        s_pass2,
+                        "Confirm passphrase",
+                        passphrase2,
+                        Message::SetPassphrase2,
+                    )
+                    .style(style::TextInput)
+                    .size(H3_SIZE);
+                    if hide {
+                        pass2 = pass2.password();
+                    }
+                    column = column
+                        .push(
+                            Row::new().spacing(10).push(pass1).push(
+                                Button::new(
+                                    s_reveal,
+                                    if *reveal { Icon::Conceal } else { Icon::Reveal }.text(),
+                                )
+                                .padding(4)
+                                .style(style::Button::Icon {
+                                    hover_color: Color::WHITE,
+                                })
+                                .on_press(Message::ToggleRevealPassphrase),
+                            ),
                         )
-                        .password()
-                        .style(style::TextInput)
-                        .size(H3_SIZE),
-                    );
+                        .push(pass2);
+                    for warning in passphrase_warnings(passphrase1) {
+                        column = column.push(
+                            Text::new(warning)
+                                .size(TEXT_SIZE - 4)
+                                .color(Color::from_rgb(0.9, 0.6, 0.2)),
+                        );
+                    }
+                    if passphrase1.trim() != passphrase1 {
+                        column = column.push(
+                            Button::new(s_trim, Text::new("REMOVE THE SPACES").size(TEXT_SIZE - 4))
+                                .padding(8)
+                                .style(style::Button::Text)
+                                .on_press(Message::TrimPassphrase),
+                        );
+                    }
+                } else {
+                    column = column.push(pass1);
+                }
+                let mut button = Button::new(s_confirm, Text::new("CONFIRM").size(TEXT_SIZE));
+                if !passphrase1.is_empty() {
+                    button = button.on_press(Message::InitialConfirm);
                 }
-                let button = Button::new(s_confirm, Text::new("CONFIRM").size(TEXT_SIZE))
-                    .on_press(Message::InitialConfirm);
+                let demo_button = Button::new(s_demo, Text::new("TRY DEMO").size(TEXT_SIZE))
+                    .style(style::Button::Text)
+                    .on_press(Message::TryDemo);
 
-                column = column.push(button);
+                column = column
+                    .push(
+                        Checkbox::new(
+                            *remember,
+                            "Remember passphrase in system keyring",
+                            Message::SetRemember,
+                        )
+                        .size(TEXT_SIZE)
+                        .text_size(TEXT_SIZE),
+                    )
+                    .push(Row::new().spacing(20).push(button).push(demo_button));
                 if let Some(error) = error {
                     column = column
                         .push(Text::new(error.as_str()).color(Color::from_rgb(0.5, 0.0, 0.0)));
                 }
                 column
             }),
+            Scene::Fatal {
+                message,
+                s_open_folder,
+                s_reset,
+            } => Container::new(
+                Column::new()
+                    .padding(20)
+                    .spacing(20)
+                    .max_width(800)
+                    .push(h3("bup could not start"))
+                    .push(Text::new(message.as_str()).color(Color::from_rgb(0.8, 0.2, 0.2)))
+                    .push(Text::new(format!(
+                        "Config file: {}",
+                        config_path().display()
+                    )))
+                    .push(
+                        Row::new()
+                            .spacing(10)
+                            .push(
+                                Button::new(
+                                    s_open_folder,
+                                    Text::new("OPEN CONFIG FOLDER").size(TEXT_SIZE - 4),
+                                )
+                                .padding(8)
+                                .style(style::Button::Text)
+                                .on_press(Message::OpenConfigFolder),
+                            )
+                            .push(
+                                Button::new(
+                                    s_reset,
+                                    Text::new("RESET TO DEFAULTS").size(TEXT_SIZE - 4),
+                                )
+                                .padding(8)
+                                .style(style::Button::Primary)
+                                .on_press(Message::ResetConfig),
+                            ),
+                    )
+                    .push(
+                        Text::new(
+                            "Resetting keeps a copy of the current config file next to it. Your \
+                             repos are not touched.",
+                        )
+                        .size(TEXT_SIZE - 4),
+                    ),
+            )
+            .width(Length::Fill)
+            .align_x(Horizontal::Center),
+            Scene::Unlocking {
+                attempt,
+                retry,
+                s_retry,
+                s_give_up,
+            } => {
+                let mut column = Column::new().padding(20).spacing(10);
+                match retry {
+                    None => {
+                        column = column.push(
+                            Row::new()
+                                .spacing(10)
+                                .push(Icon::Unlock.h3())
+                                .push(h3("Unlocking...")),
+                        )
+                    }
+                    Some((_, error)) => {
+                        let home = self
+                            .config
+                            .selected_repo()
+                            .map(|repo| repo.home.display().to_string())
+                            .unwrap_or_default();
+                        column = column
+                            .push(h3(format!("Waiting for repo at {}...", home)))
+                            .push(
+                                Text::new(format!(
+                                    "Attempt {} of {}: {}",
+                                    attempt,
+                                    self.config.open_retries + 1,
+                                    error
+                                ))
+                                .size(TEXT_SIZE - 4),
+                            )
+                            .push(
+                                Row::new()
+                                    .spacing(10)
+                                    .push(
+                                        Button::new(
+                                            s_give_up,
+                                            Text::new("GIVE UP").size(TEXT_SIZE - 4),
+                                        )
+                                        .padding(8)
+                                        .style(style::Button::Text)
+                                        .on_press(Message::GiveUpOpen),
+                                    )
+                                    .push(
+                                        Button::new(
+                                            s_retry,
+                                            Text::new("RETRY NOW").size(TEXT_SIZE - 4),
+                                        )
+                                        .padding(8)
+                                        .style(style::Button::Primary)
+                                        .on_press(Message::RetryOpen),
+                                    ),
+                            );
+                    }
+                }
+                Container::new(column)
+            }
             Scene::Overview {
                 list,
+                s_groups,
                 new_button,
-                selected_target,
+                s_split,
+                snapshots,
+                error,
+                passphrase_prompt,
+                show_health,
+                s_health,
                 s_open_settings,
+                s_edit_repo,
+                s_keep_demo,
+                s_undo_delete,
+                s_empty_primary,
+                s_empty_secondary,
                 s_repo_pick_list,
             } => {
-                let repo_options = repo_options(self.config.repos.values());
+                let selected_id = self
+                    .config
+                    .selected_repo
+                    .as_ref()
+                    .and_then(|selected| selected.value.id());
+                let hide_empty = self.config.hide_empty_repos;
+                let repo_options = repo_options(self.config.repos.values().filter(|repo| {
+                    !hide_empty || !repo.targets.is_empty() || Some(repo.id) == selected_id
+                }));
+                // The stored selection has the name from when it was picked; show the current one
+                let selected_option = self.config.selected_repo.as_ref().map(|selected| {
+                    repo_options
+                        .iter()
+                        .find(|option| *option == selected)
+                        .unwrap_or(selected)
+                        .clone()
+                });
 
                 let mut button = Button::new(new_button, Text::new("NEW BUP").size(TEXT_SIZE - 4))
                     .style(style::Button::Primary);
@@ -677,7 +3631,7 @@ impl Application for Ui {
                         PickList::new(
                             s_repo_pick_list,
                             repo_options,
-                            self.config.selected_repo.clone(),
+                            selected_option,
                             Message::PickRepo,
                         )
                         .font(ICONS)
@@ -689,11 +3643,52 @@ impl Application for Ui {
                     //
                     let repo = selected_repo.value.id().and_then(|id| config.find_repo(id));
                     if let Some(repo) = repo {
-                        header = header.push(Text::new(repo.home.display().to_string()))
+                        header = header
+                            .push(Text::new(repo.home.display().to_string()))
+                            .push(
+                                Button::new(s_edit_repo, Icon::Edit.text())
+                                    .padding(4)
+                                    .style(style::Button::Icon {
+                                        hover_color: Color::WHITE,
+                                    })
+                                    .on_press(Message::EditRepo),
+                            );
+                        if let Some(health) = self.health.get(&repo.id) {
+                            let level = health.level();
+                            header = header.push(
+                                Button::new(
+                                    s_health,
+                                    Text::new(level.to_string())
+                                        .size(TEXT_SIZE - 4)
+                                        .color(level.color()),
+                                )
+                                .padding(4)
+                                .style(style::Button::Text)
+                                .on_press(Message::ToggleHealth),
+                            );
+                        }
+                        if repo.ephemeral {
+                            header = header
+                                .push(
+                                    Text::new("DEMO - deleted on exit")
+                                        .color(Color::from_rgb(0.9, 0.6, 0.2)),
+                                )
+                                .push(
+                                    Button::new(s_keep_demo, Text::new("KEEP").size(TEXT_SIZE - 4))
+                                        .style(style::Button::Text)
+                                        .on_press(Message::KeepDemo),
+                                );
+                        }
                     }
                 }
 
-                header = header.push(button);
+                let mut split_button =
+                    Button::new(s_split, Text::new("SPLIT FOLDER").size(TEXT_SIZE - 4))
+                        .style(style::Button::Text);
+                if self.config.selected_repo.is_some() {
+                    split_button = split_button.on_press(Message::OpenSplit);
+                }
+                header = header.push(button).push(split_button);
 
                 header = header.push(
                     Container::new(
@@ -711,108 +3706,658 @@ impl Application for Ui {
                 );
 
                 let mut overview: Column<Message> = Column::new().spacing(20);
+                let mut snapshots = Some(snapshots);
                 if let Some(repo) = self.config.selected_repo() {
-                    for (i, (target, state)) in zip_list(&repo.targets, list).enumerate() {
-                        let is_selected = selected_target.map(|s| s == i).unwrap_or(false);
+                    if !repo.description.is_empty() {
                         overview = overview.push(
-                            state
-                                .view(&target, is_selected)
-                                .map(move |msg| Message::ListItem(i, msg)),
+                            Text::new(&repo.description)
+                                .color(Color::from_rgb(0.6, 0.6, 0.6))
+                                .size(TEXT_SIZE - 4),
+                        );
+                    }
+                    if self.compacting == Some(repo.id) {
+                        overview = overview
+                            .push(Text::new(gc_progress_text(&self.gc_shown)).size(TEXT_SIZE - 4));
+                    } else if let Some((id, Ok(compacted))) = self.compact_result {
+                        if id == repo.id {
+                            overview = overview.push(
+                                Text::new(format!(
+                                    "Compacted the repo, reclaiming {}",
+                                    format_bytes(compacted.before.saturating_sub(compacted.after))
+                                ))
+                                .size(TEXT_SIZE - 4),
+                            );
+                        }
+                    }
+                    // Without any groups, the targets are listed as they are, without headers.
+                    // Otherwise the targets without a group come first, then the groups by name.
+                    let grouped = repo.targets.iter().any(|target| target.group.is_some());
+                    let mut sections: BTreeMap<Option<&str>, Vec<_>> = BTreeMap::new();
+                    for (i, (target, state)) in zip_list(&repo.targets, list).enumerate() {
+                        sections
+                            .entry(target.group.as_deref())
+                            .or_default()
+                            .push((i, target, state));
+                    }
+                    s_groups.resize(sections.len(), Default::default());
+                    for ((group, items), s_group) in sections.into_iter().zip(s_groups) {
+                        let key = group.unwrap_or("");
+                        let collapsed = repo.collapsed_groups.contains(key);
+                        if grouped {
+                            overview = overview.push(
+                                Button::new(
+                                    s_group,
+                                    Row::new()
+                                        .spacing(8)
+                                        .push(if collapsed {
+                                            Icon::Expand.text()
+                                        } else {
+                                            Icon::Collapse.text()
+                                        })
+                                        .push(Text::new(format!(
+                                            "{} ({})",
+                                            group.unwrap_or("Ungrouped"),
+                                            items.len()
+                                        ))),
+                                )
+                                .padding(4)
+                                .style(style::Button::Text)
+                                .on_press(Message::ToggleGroup(key.to_string())),
+                            );
+                            if collapsed {
+                                continue;
+                            }
+                        }
+                        for (i, target, state) in items {
+                            let is_selected = self.expanded.contains(&target.id);
+                            let backup_state =
+                                backup_state(&self.running_backups, &self.backup_queue, repo.id, i);
+                            let changes = self
+                                .changes
+                                .get(&(repo.id, target.name.clone()))
+                                .map(|(_, _, changes)| changes);
+                            overview = overview.push(
+                                state
+                                    .view(
+                                        target,
+                                        is_selected,
+                                        !repo.ephemeral && !repo.read_only,
+                                        repo.read_only,
+                                        backup_state,
+                                        changes,
+                                        &self.disk_space,
+                                    )
+                                    .map(move |msg| Message::ListItem(i, msg)),
+                            );
+                            if is_selected && self.snapshots_target == Some(target.id) {
+                                if let Some(snapshots) = snapshots.take() {
+                                    overview =
+                                        overview.push(snapshots.view().map(Message::SnapshotList));
+                                }
+                            }
+                        }
+                    }
+                    if repo.targets.is_empty() {
+                        overview = overview.push(view_empty_state(
+                            "No backup targets yet",
+                            "A target is a set of folders that are backed up together. Click NEW \
+                             BUP to add one, or SPLIT FOLDER to make a target of each subfolder \
+                             of a folder.",
+                            (s_empty_primary, "NEW BUP", Message::NewTarget),
+                            (s_empty_secondary, "SPLIT FOLDER", Message::OpenSplit),
+                        ));
+                    }
+                } else {
+                    overview = overview.push(view_empty_state(
+                        "No repo selected",
+                        "Backups are stored in a repo: an encrypted, deduplicated folder, e.g. \
+                         on an external disk. Create a new one, or import one made before.",
+                        (
+                            s_empty_primary,
+                            "NEW REPO",
+                            Message::PickRepo(Opt {
+                                name: "New repo...".to_string(),
+                                value: RepoOption::New,
+                            }),
+                        ),
+                        (
+                            s_empty_secondary,
+                            "IMPORT REPO",
+                            Message::PickRepo(Opt {
+                                name: "Import existing repo...".to_string(),
+                                value: RepoOption::Import,
+                            }),
+                        ),
+                    ));
+                }
+
+                let mut column = Column::new().push(header);
+                let health = self
+                    .config
+                    .selected_repo()
+                    .and_then(|repo| self.health.get(&repo.id));
+                if let (true, Some(health)) = (*show_health, health) {
+                    column = column.push(
+                        Container::new(
+                            Column::new()
+                                .spacing(4)
+                                .push(Text::new(format!(
+                                    "Checked {}",
+                                    health.computed.format("%Y-%m-%d %H:%M")
+                                )))
+                                .push_iter(health.signals.iter().map(|signal| {
+                                    Text::new(&signal.description).color(signal.level.color())
+                                })),
+                        )
+                        .style(style::ListItemExpanded)
+                        .padding(10),
+                    );
+                }
+                if let Some(prompt) = passphrase_prompt {
+                    column = column.push(prompt.view());
+                }
+                let space = self.config.selected_repo().and_then(|repo| {
+                    let space = self.free_space.get(&repo.id)?.1?;
+                    Some((repo, space))
+                });
+                if let Some((repo, space)) = space {
+                    let free = format!(
+                        "Only {} of {} free on the disk of this repo",
+                        format_bytes(space.available),
+                        format_bytes(space.total)
+                    );
+                    if space.available < repo.critical_space_bytes {
+                        column = column.push(
+                            Text::new(format!(
+                                "{}. Backups are refused until there is more.",
+                                free
+                            ))
+                            .color(Color::from_rgb(0.8, 0.2, 0.2)),
                         );
+                    } else if space.available < repo.low_space_bytes {
+                        column = column.push(Text::new(free).color(Color::from_rgb(0.9, 0.6, 0.2)));
                     }
                 }
-
-                Container::new(
-                    Column::new()
-                        .push(header)
-                        .push(Scrollable::new(&mut self.s_scrollable).push(overview)),
-                )
+                let deleted = self.deleted_targets.last().filter(|(_, target)| {
+                    Some(target.repo) == self.config.selected_repo().map(|repo| repo.id)
+                });
+                if let Some((_, target)) = deleted {
+                    column = column.push(
+                        Row::new()
+                            .spacing(10)
+                            .push(Text::new(format!("Deleted {}", target.name)))
+                            .push(
+                                Button::new(s_undo_delete, Text::new("UNDO").size(TEXT_SIZE - 4))
+                                    .padding(4)
+                                    .style(style::Button::Text)
+                                    .on_press(Message::UndoDelete),
+                            ),
+                    );
+                }
+                if let Some(error) = error {
+                    column = column.push(
+                        Text::new(format!("Error: {}", error))
+                            .color(Color::from_rgb(0.8, 0.2, 0.2)),
+                    );
+                }
+                Container::new(column.push(Scrollable::new(&mut self.s_scrollable).push(overview)))
             }
             Scene::CreateTarget { editor } | Scene::EditTarget { editor, .. } => {
+                let narrow = self.window_width < target_editor::NARROW_WIDTH;
                 // Center the editor
-                Container::new(editor.view().map(Message::TargetEditor))
+                Container::new(editor.view(narrow).map(Message::TargetEditor))
+                    .padding(if narrow { 10 } else { 50 })
+                    .align_x(Horizontal::Center)
+                    .width(Length::Fill)
+                    .height(Length::Fill)
+            }
+            Scene::CreateRepo { editor } | Scene::EditRepo { editor } => {
+                Container::new(editor.view().map(Message::RepoEditor))
                     .padding(50)
                     .align_x(Horizontal::Center)
                     .width(Length::Fill)
                     .height(Length::Fill)
             }
-            Scene::CreateRepo {
-                name,
-                home,
-                error,
-                ref mut s_cancel_button,
-                ref mut s_save_button,
-                ref mut s_name,
-                ref mut s_home,
+            Scene::Restore { restore } => Container::new(restore.view().map(Message::Restore))
+                .padding(50)
+                .align_x(Horizontal::Center)
+                .width(Length::Fill)
+                .height(Length::Fill),
+            Scene::TarExport { export } => Container::new(export.view().map(Message::TarExport))
+                .padding(50)
+                .align_x(Horizontal::Center)
+                .width(Length::Fill)
+                .height(Length::Fill),
+            Scene::BulkEdit { bulk } => Container::new(bulk.view(config).map(Message::BulkEdit))
+                .padding(50)
+                .align_x(Horizontal::Center)
+                .width(Length::Fill)
+                .height(Length::Fill),
+            Scene::Inventory { inventory } => {
+                let repo_name = config
+                    .selected_repo()
+                    .map_or(String::new(), |repo| repo.name.clone());
+                Container::new(inventory.view(&repo_name).map(Message::Inventory))
+                    .padding(50)
+                    .align_x(Horizontal::Center)
+                    .width(Length::Fill)
+                    .height(Length::Fill)
+            }
+            Scene::Split { split } => Container::new(split.view().map(Message::Split))
+                .padding(50)
+                .align_x(Horizontal::Center)
+                .width(Length::Fill)
+                .height(Length::Fill),
+            Scene::Reconcile { reconcile } => {
+                Container::new(reconcile.view(read_only).map(Message::Reconcile))
+                    .padding(50)
+                    .align_x(Horizontal::Center)
+                    .width(Length::Fill)
+                    .height(Length::Fill)
+            }
+            Scene::Export { wizard } => Container::new(wizard.view(config).map(Message::Export))
+                .padding(50)
+                .align_x(Horizontal::Center)
+                .width(Length::Fill)
+                .height(Length::Fill),
+            Scene::Settings {
+                s_back_button,
+                s_export,
+                s_inventory,
+                s_bulk_edit,
+                s_reconcile,
+                s_compact,
+                s_fewer_backups,
+                s_more_backups,
+                s_fewer_reads,
+                s_more_reads,
+                s_forget_passphrase,
+                s_log_level,
+                s_inspector,
+                s_fewer_retries,
+                s_more_retries,
+                s_new_default_exclude,
+                s_default_excludes,
             } => Container::new(
+                Column::new()
+                    .spacing(20)
+                    .push(
+                        Button::new(s_back_button, Text::new("BACK").size(TEXT_SIZE - 4))
+                            .style(style::Button::Text)
+                            .on_press(Message::ToOverview),
+                    )
+                    .push(Text::new(usage_summary))
+                    .push(
+                        Button::new(
+                            s_export,
+                            Text::new("EXPORT FOR ANOTHER MACHINE").size(TEXT_SIZE - 4),
+                        )
+                        .padding(8)
+                        .style(style::Button::Text)
+                        .on_press(Message::OpenExport),
+                    )
+                    .push(
+                        Button::new(
+                            s_inventory,
+                            Text::new("EXPORT SNAPSHOT LIST (CSV)").size(TEXT_SIZE - 4),
+                        )
+                        .padding(8)
+                        .style(style::Button::Text)
+                        .on_press(Message::OpenInventory),
+                    )
+                    .push({
+                        let mut column = Column::new();
+                        if config.repos.len() > 1 {
+                            column = column.push(
+                                Button::new(
+                                    s_bulk_edit,
+                                    Text::new("COPY SETTINGS TO OTHER REPOS").size(TEXT_SIZE - 4),
+                                )
+                                .padding(8)
+                                .style(style::Button::Text)
+                                .on_press(Message::OpenBulkEdit),
+                            );
+                        }
+                        column
+                    })
+                    .push(
+                        Button::new(
+                            s_reconcile,
+                            Text::new("COMPARE TARGETS WITH SNAPSHOTS").size(TEXT_SIZE - 4),
+                        )
+                        .padding(8)
+                        .style(style::Button::Text)
+                        .on_press(Message::OpenReconcile),
+                    )
+                    .push({
+                        let mut column = Column::new().spacing(8);
+                        let mut compact = Button::new(
+                            s_compact,
+                            Text::new("COMPACT / GC REPO NOW").size(TEXT_SIZE - 4),
+                        )
+                        .padding(8)
+                        .style(style::Button::Text);
+                        if read_only {
+                            column = column.push(with_tooltip(compact, "The repo is read-only"));
+                        } else {
+                            if self.compacting.is_none() {
+                                compact = compact.on_press(Message::Compact);
+                            }
+                            column = column.push(compact);
+                        }
+                        if self.compacting.is_some() {
+                            column = column.push(
+                                Text::new(gc_progress_text(&self.gc_shown)).size(TEXT_SIZE - 4),
+                            );
+                        }
+                        match self
+                            .compact_result
+                            .as_ref()
+                            .map(|(_, result)| result.clone())
+                        {
+                            Some(Ok(rdedup::Compacted { before, after })) => {
+                                column = column.push(
+                                    Text::new(format!(
+                                        "Reclaimed {} ({} -> {})",
+                                        format_bytes(before.saturating_sub(after)),
+                                        format_bytes(before),
+                                        format_bytes(after)
+                                    ))
+                                    .size(TEXT_SIZE - 4),
+                                )
+                            }
+                            Some(Err(ref error)) => {
+                                column = column.push(
+                                    Text::new(format!("Error: {}", error))
+                                        .color(Color::from_rgb(0.5, 0.0, 0.0)),
+                                )
+                            }
+                            None => (),
+                        }
+                        column
+                    })
+                    .push(
+                        Checkbox::new(
+                            self.config.hide_empty_repos,
+                            "Hide repos without targets",
+                            Message::SetHideEmptyRepos,
+                        )
+                        .size(TEXT_SIZE)
+                        .text_size(TEXT_SIZE),
+                    )
+                    .push(
+                        Checkbox::new(
+                            self.config.background_priority,
+                            "Run backups in background priority",
+                            Message::SetBackgroundPriority,
+                        )
+                        .size(TEXT_SIZE)
+                        .text_size(TEXT_SIZE),
+                    )
+                    .push(
+                        Checkbox::new(
+                            self.config.backup_on_startup,
+                            "Back up all targets of the selected repo when bup starts",
+                            Message::SetBackupOnStartup,
+                        )
+                        .size(TEXT_SIZE)
+                        .text_size(TEXT_SIZE),
+                    )
+                    .push({
+                        let mut forget = Button::new(
+                            s_forget_passphrase,
+                            Text::new("FORGET REMEMBERED PASSPHRASE").size(TEXT_SIZE - 4),
+                        )
+                        .padding(8)
+                        .style(style::Button::Text);
+                        if self.config.remember_passphrase {
+                            forget = forget.on_press(Message::ForgetPassphrase);
+                        }
+                        forget
+                    })
+                    .push({
+                        let max = self.config.max_concurrent_backups.max(1);
+                        let mut fewer = Button::new(s_fewer_backups, Text::new("-"))
+                            .padding(4)
+                            .style(style::Button::Text);
+                        if max > 1 {
+                            fewer = fewer.on_press(Message::SetMaxConcurrentBackups(max - 1));
+                        }
+                        Row::new()
+                            .spacing(10)
+                            .push(Text::new(
+                                "Backups and duplications running at the same time:",
+                            ))
+                            .push(fewer)
+                            .push(Text::new(max.to_string()))
+                            .push(
+                                Button::new(s_more_backups, Text::new("+"))
+                                    .padding(4)
+                                    .style(style::Button::Text)
+                                    .on_press(Message::SetMaxConcurrentBackups(max + 1)),
+                            )
+                    })
+                    .push({
+                        let mut row = Row::new().spacing(10);
+                        if let Some(repo) = self.config.selected_repo() {
+                            let max = repo.read_limit().max;
+                            let mut fewer = Button::new(s_fewer_reads, Text::new("-"))
+                                .padding(4)
+                                .style(style::Button::Text);
+                            if max > 1 {
+                                fewer = fewer.on_press(Message::SetReadThreads(max - 1));
+                            }
+                            let mut more = Button::new(s_more_reads, Text::new("+"))
+                                .padding(4)
+                                .style(style::Button::Text);
+                            if max < MAX_READ_THREADS {
+                                more = more.on_press(Message::SetReadThreads(max + 1));
+                            }
+                            row = row
+                                .push(Text::new("Reads of this repo at the same time:"))
+                                .push(fewer)
+                                .push(Text::new(max.to_string()))
+                                .push(more)
+                                .push(help(
+                                    "Restores, test restores and verifications of the repo \
+                                     beyond this many wait for the others to finish. Lower it \
+                                     for a repo on a slow disk.",
+                                ));
+                        }
+                        row
+                    })
+                    .push({
+                        let retries = self.config.open_retries;
+                        let mut fewer = Button::new(s_fewer_retries, Text::new("-"))
+                            .padding(4)
+                            .style(style::Button::Text);
+                        if retries > 0 {
+                            fewer = fewer.on_press(Message::SetOpenRetries(retries - 1));
+                        }
+                        Row::new()
+                            .spacing(10)
+                            .push(Text::new("Retries when the repo is missing at startup:"))
+                            .push(fewer)
+                            .push(Text::new(retries.to_string()))
+                            .push(
+                                Button::new(s_more_retries, Text::new("+"))
+                                    .padding(4)
+                                    .style(style::Button::Text)
+                                    .on_press(Message::SetOpenRetries(retries + 1)),
+                            )
+                            .push(help(
+                                "For a repo on a network share or removable disk that is mounted \
+                                 a little after login. Other errors are not retried.",
+                            ))
+                    })
+                    .push(
+                        Row::new()
+                            .spacing(10)
+                            .push(Text::new("Excludes of new targets:"))
+                            .push(
+                                Button::new(s_new_default_exclude, Icon::New.text())
+                                    .style(style::Button::Icon {
+                                        hover_color: Color::WHITE,
+                                    })
+                                    .padding(BUTTON_PAD)
+                                    .on_press(Message::NewDefaultExclude),
+                            )
+                            .push(help(
+                                "Filled into every new target, where they can be edited or \
+                                 removed like any other exclude. Existing targets keep theirs.",
+                            )),
+                    )
+                    .push(
+                        zip_list(&self.config.default_excludes, s_default_excludes)
+                            .enumerate()
+                            .fold(
+                                Column::new().spacing(4),
+                                |column, (i, (exclude, (s_exclude, s_delete)))| {
+                                    column.push(
+                                        Row::new()
+                                            .push(
+                                                TextInput::new(
+                                                    s_exclude,
+                                                    "Exclude string",
+                                                    exclude,
+                                                    move |s| Message::SetDefaultExclude(i, s),
+                                                )
+                                                .style(style::TextInput)
+                                                .size(TEXT_SIZE),
+                                            )
+                                            .push(
+                                                Button::new(s_delete, Icon::Delete.text())
+                                                    .on_press(Message::DelDefaultExclude(i))
+                                                    .padding(0)
+                                                    .style(style::Button::Icon {
+                                                        hover_color: Color::from_rgb(0.7, 0.2, 0.2),
+                                                    }),
+                                            ),
+                                    )
+                                },
+                            ),
+                    )
+                    .push(
+                        Row::new()
+                            .spacing(10)
+                            .push(Text::new("Log:"))
+                            .push(
+                                PickList::new(
+                                    s_log_level,
+                                    &log::LogLevel::ALL[..],
+                                    Some(self.config.log_level),
+                                    Message::SetLogLevel,
+                                )
+                                .style(style::Dropdown),
+                            )
+                            .push(help(
+                                "How much bup writes to the terminal it was started from. Debug \
+                                 is noisy, but helps when reporting a bug.",
+                            )),
+                    )
+                    .push(
+                        Checkbox::new(
+                            self.config.log_file_location,
+                            "Show source locations in the log",
+                            Message::SetLogFileLocation,
+                        )
+                        .size(TEXT_SIZE)
+                        .text_size(TEXT_SIZE),
+                    )
+                    .push(
+                        Row::new()
+                            .spacing(10)
+                            .push(Text::new(format!(
+                                "History: {}",
+                                history::history_path().display()
+                            )))
+                            .push(help(
+                                "One JSON object per line for every finished backup, duplication \
+                                 and restore, for scripts and monitoring. Only ever appended to.",
+                            )),
+                    )
+                    .push(
+                        Button::new(
+                            s_inspector,
+                            Text::new(if self.inspection.is_some() {
+                                "HIDE ADVANCED / INSPECTOR"
+                            } else {
+                                "ADVANCED / INSPECTOR"
+                            })
+                            .size(TEXT_SIZE - 4),
+                        )
+                        .padding(8)
+                        .style(style::Button::Text)
+                        .on_press(Message::ToggleInspector),
+                    )
+                    .push(view_inspection(self.inspection.as_ref())),
+            ),
+        };
+        let mut w = Column::new();
+        if let Some(ref save_error) = self.save_error {
+            w = w.push(
                 Container::new(
-                    Column::new()
-                        .padding(20)
+                    Row::new()
                         .spacing(20)
                         .push(
-                            Row::new().spacing(8).push(Icon::Repo.h3()).push(
-                                TextInput::new(s_name, "Repo name", &name, Message::SetRepoName)
-                                    .style(style::TextInput)
-                                    .size(H3_SIZE),
-                            ),
+                            Text::new(format!("Changes are not saved: {}", save_error))
+                                .color(Color::from_rgb(0.8, 0.2, 0.2))
+                                .width(Length::Fill),
                         )
                         .push(
-                            Row::new().spacing(8).push(Text::new("RDEDUP_HOME:")).push(
-                                s_home
-                                    .view(home.as_ref().map(|x| x.as_path()), TEXT_SIZE)
-                                    .map(Message::RepoHome),
-                            ),
+                            Button::new(
+                                &mut self.s_retry_save,
+                                Text::new("RETRY SAVE").size(TEXT_SIZE - 4),
+                            )
+                            .padding(8)
+                            .style(style::Button::Text)
+                            .on_press(Message::RetrySave),
+                        ),
+                )
+                .padding(10),
+            );
+        }
+        if let Some(warning) = guard_warning {
+            w = w.push(
+                Container::new(
+                    Row::new()
+                        .spacing(20)
+                        .push(
+                            Text::new(warning)
+                                .color(Color::from_rgb(0.9, 0.6, 0.2))
+                                .width(Length::Fill),
                         )
                         .push(
-                            Container::new({
-                                let mut row = Row::new()
-                                    .spacing(10)
-                                    .push(
-                                        Button::new(
-                                            s_cancel_button,
-                                            Text::new("CANCEL").size(TEXT_SIZE - 4),
-                                        )
-                                        .padding(8)
-                                        .style(style::Button::Text)
-                                        .on_press(Message::ToOverview),
-                                    )
-                                    .push(
-                                        Button::new(
-                                            s_save_button,
-                                            Text::new("SAVE").size(TEXT_SIZE - 4),
-                                        )
-                                        .padding(8)
-                                        .style(style::Button::Primary)
-                                        .on_press(Message::SaveRepo),
-                                    );
-                                if let Some(error) = error {
-                                    row = row.push(
-                                        Text::new(format!("Error: {}", error.as_str()))
-                                            .color(Color::from_rgb(0.5, 0.0, 0.0)),
-                                    );
-                                }
-                                row
-                            })
-                            .width(Length::Fill), // .align_x(Horizontal::End),
+                            Button::new(
+                                &mut self.s_dismiss_guard,
+                                Text::new("NEVER MIND").size(TEXT_SIZE - 4),
+                            )
+                            .padding(8)
+                            .style(style::Button::Text)
+                            .on_press(Message::DismissGuard),
+                        )
+                        .push(
+                            Button::new(
+                                &mut self.s_cancel_backups,
+                                Text::new("CANCEL BACKUPS").size(TEXT_SIZE - 4),
+                            )
+                            .padding(8)
+                            .style(style::Button::Text)
+                            .on_press(Message::CancelGuardedBackups),
                         ),
                 )
-                .style(style::DialogContainer)
-                .width(Length::Fill)
-                .max_width(1000)
-                .height(Length::Shrink),
-            )
-            .padding(50)
-            .align_x(Horizontal::Center)
-            .width(Length::Fill)
-            .height(Length::Fill),
-            Scene::Settings { s_back_button } => Container::new(
-                Column::new().push(
-                    Button::new(s_back_button, Text::new("BACK").size(TEXT_SIZE - 4))
-                        .style(style::Button::Text)
-                        .on_press(Message::ToOverview),
-                ),
-            ),
-        };
+                .padding(10),
+            );
+        }
+        if let Some(ref mut palette) = self.palette {
+            w = w.push(
+                Container::new(palette.view().map(Message::Palette))
+                    .width(Length::Fill)
+                    .center_x()
+                    .padding(10),
+            );
+        }
+        let w = w.push(scene);
         // To apply a global style
         Container::new(w)
             .style(style::MenuContainer)
@@ -823,13 +4368,191 @@ impl Application for Ui {
     }
 }
 
+/// Pitfalls of a new passphrase that would make it hard to type again. The state of Caps Lock
+/// can't be queried through iced, so it is guessed from the letters typed.
+fn passphrase_warnings(passphrase: &str) -> Vec<&'static str> {
+    let mut warnings = Vec::new();
+    if passphrase.trim() != passphrase {
+        warnings.push("The passphrase starts or ends with a space, which is easy to miss.");
+    }
+    let letters = passphrase.chars().filter(|c| c.is_alphabetic());
+    let (mut upper, mut lower) = (0, 0);
+    for letter in letters {
+        if letter.is_uppercase() {
+            upper += 1;
+        } else if letter.is_lowercase() {
+            lower += 1;
+        }
+    }
+    if upper >= 3 && lower == 0 {
+        warnings.push("The passphrase is all capitals. Is Caps Lock on?");
+    }
+    warnings
+}
+
+/// What a running gc has done so far
+fn gc_progress_text(progress: &rdedup::GcProgress) -> String {
+    if progress.reclaimed == 0 {
+        "Compacting...".to_string()
+    } else {
+        format!(
+            "Compacting... reclaimed {} of {} so far",
+            format_bytes(progress.reclaimed),
+            format_bytes(progress.before)
+        )
+    }
+}
+
+/// The `Ui::inspection` of the selected repo, if open
+fn view_inspection<'a>(
+    inspection: Option<&Option<Result<rdedup::Inspection, String>>>,
+) -> Element<'a, Message> {
+    let inspection = match inspection {
+        None => return Column::new().into(),
+        Some(None) => return Text::new("Inspecting...").size(TEXT_SIZE - 4).into(),
+        Some(Some(Err(error))) => {
+            return Text::new(format!("Error: {}", error))
+                .color(Color::from_rgb(0.5, 0.0, 0.0))
+                .into()
+        }
+        Some(Some(Ok(inspection))) => inspection,
+    };
+    let stats_row = |name: &str, stats: &rdedup::FileStats| {
+        Row::new()
+            .spacing(20)
+            .push(
+                Text::new(name)
+                    .size(TEXT_SIZE - 4)
+                    .width(Length::Units(250)),
+            )
+            .push(
+                Text::new(format!("{} files", stats.files))
+                    .size(TEXT_SIZE - 4)
+                    .width(Length::Units(150)),
+            )
+            .push(Text::new(format_bytes(stats.bytes)).size(TEXT_SIZE - 4))
+    };
+    let mut column = Column::new()
+        .spacing(4)
+        .push(Text::new(format!("{} snapshot names", inspection.n_names)).size(TEXT_SIZE - 4))
+        .push(Text::new("By kind of data").size(TEXT_SIZE - 2));
+    for (kind, stats) in &inspection.kinds {
+        column = column.push(stats_row(kind, stats));
+    }
+    column = column.push(Text::new("By top-level entry").size(TEXT_SIZE - 2));
+    for (name, stats) in &inspection.top_level {
+        column = column.push(stats_row(name, stats));
+    }
+    column
+        .push(Text::new("Settings on disk (config.yml)").size(TEXT_SIZE - 2))
+        .push(
+            Text::new(
+                inspection
+                    .settings
+                    .clone()
+                    .unwrap_or_else(|| "None found".to_string()),
+            )
+            .size(TEXT_SIZE - 6),
+        )
+        .into()
+}
+
+/// Inline dialog asking for the passphrase of one repo
+pub struct PassphrasePrompt {
+    pub repo_id: Uuid,
+    pub passphrase: String,
+    pub error: Option<String>,
+    /// The passphrase is being checked against the repo
+    pub checking: bool,
+    s_input: text_input::State,
+    s_confirm: button::State,
+}
+impl PassphrasePrompt {
+    pub fn new(repo_id: Uuid) -> Self {
+        Self {
+            repo_id,
+            passphrase: String::new(),
+            error: None,
+            checking: false,
+            s_input: Default::default(),
+            s_confirm: Default::default(),
+        }
+    }
+    pub fn view(&mut self) -> Element<Message> {
+        let mut row = Row::new()
+            .spacing(20)
+            .push(Text::new(
+                "The passphrase does not unlock this repo. Repo passphrase:",
+            ))
+            .push(
+                TextInput::new(
+                    &mut self.s_input,
+                    "Passphrase",
+                    &self.passphrase,
+                    Message::SetRepoPassphrase,
+                )
+                .password()
+                .on_submit(Message::ConfirmRepoPassphrase)
+                .style(style::TextInput)
+                .size(TEXT_SIZE)
+                .width(Length::Units(250)),
+            )
+            .push({
+                let confirm =
+                    Button::new(&mut self.s_confirm, Text::new("UNLOCK").size(TEXT_SIZE - 4))
+                        .padding(8)
+                        .style(style::Button::Primary);
+                if self.checking {
+                    confirm
+                } else {
+                    confirm.on_press(Message::ConfirmRepoPassphrase)
+                }
+            });
+        if self.checking {
+            row = row.push(Text::new("Checking...").size(TEXT_SIZE - 4));
+        }
+        if let Some(ref error) = self.error {
+            row = row.push(Text::new(error).color(Color::from_rgb(0.8, 0.2, 0.2)));
+        }
+        Container::new(row)
+            .style(style::DialogContainer)
+            .padding(10)
+            .into()
+    }
+}
+
 #[derive(Default, Debug, Clone)]
 pub struct ListItemState {
     s_button: button::State,
     s_button2: button::State,
+    s_delete: button::State,
+    s_backup: button::State,
+    s_last_result: button::State,
+    /// Whether the message of the last backup result is shown
+    show_last_result: bool,
+    /// Set when a backup was refused for exceeding the limits of the target
+    pub over_limit: Option<backup::OverLimit>,
+    /// `DryRun::sparse_files` of the last dry run
+    pub sparse_files: u64,
+    s_backup_anyway: button::State,
+    s_dismiss_over_limit: button::State,
+    s_check_disk_space: button::State,
 }
 impl ListItemState {
-    pub fn view(&mut self, target: &Target, selected: bool) -> Element<ListItemMessage> {
+    /// `scheduled`: whether duplications of this target are run on schedule at all.
+    /// `read_only`: `RepoConfig::read_only` of the repo; disables backing up
+    /// `changes`: estimate of the changes since the last backup, `None` inside while estimating.
+    /// `disk_space`: see `Ui::disk_space`
+    pub fn view(
+        &mut self,
+        target: &Target,
+        selected: bool,
+        scheduled: bool,
+        read_only: bool,
+        backup_state: Option<BackupState>,
+        changes: Option<&Option<Result<backup::Changes, String>>>,
+        disk_space: &HashMap<PathBuf, Option<Result<Option<rdedup::Space>, String>>>,
+    ) -> Element<ListItemMessage> {
         let header = Row::new()
             .height(Length::Units(36))
             .width(Length::Fill)
@@ -839,108 +4562,1529 @@ impl ListItemState {
                     .align_x(Horizontal::Left)
                     .width(Length::Fill)
                     .height(Length::Fill),
-            )
-            .push(
-                Container::new(
-                    Button::new(&mut self.s_button2, Icon::Edit.text())
-                        .padding(6)
-                        .style(style::Button::Icon {
-                            hover_color: Color::WHITE,
-                        })
-                        .on_press(ListItemMessage::Edit),
-                )
-                .align_x(Horizontal::Right)
-                .width(Length::Fill),
             );
+        let header = match backup_state {
+            Some(BackupState::Queued) => header.push(Text::new("QUEUED").size(TEXT_SIZE - 6)),
+            Some(BackupState::Running) => header.push(Text::new("RUNNING").size(TEXT_SIZE - 6)),
+            None => header,
+        };
+        let header = match target.last_result {
+            Some(ref last_result) => header.push(
+                Button::new(
+                    &mut self.s_last_result,
+                    last_result
+                        .status
+                        .icon()
+                        .text()
+                        .color(last_result.status.color()),
+                )
+                .padding(6)
+                .style(style::Button::Icon {
+                    hover_color: Color::WHITE,
+                })
+                .on_press(ListItemMessage::ToggleLastResult),
+            ),
+            None => header,
+        };
+        let header = header.push(
+            Container::new(
+                Row::new()
+                    .push(
+                        Button::new(&mut self.s_button2, Icon::Edit.text())
+                            .padding(6)
+                            .style(style::Button::Icon {
+                                hover_color: Color::WHITE,
+                            })
+                            .on_press(ListItemMessage::Edit),
+                    )
+                    .push(
+                        Button::new(&mut self.s_delete, Icon::Delete.text())
+                            .padding(6)
+                            .style(style::Button::Icon {
+                                hover_color: Color::from_rgb(0.7, 0.2, 0.2),
+                            })
+                            .on_press(ListItemMessage::Delete),
+                    ),
+            )
+            .align_x(Horizontal::Right)
+            .width(Length::Fill),
+        );
         let mut column = Column::new();
         column = column.push(
             Button::new(&mut self.s_button, header)
                 .on_press(ListItemMessage::Expand)
                 .style(style::ListItemHeader { selected }),
         );
+        if let (true, Some(last_result)) = (self.show_last_result, &target.last_result) {
+            column = column.push(
+                Container::new(
+                    Text::new(format!(
+                        "{}: {}",
+                        last_result.time.format("%Y-%m-%d %H:%M:%S"),
+                        last_result.message
+                    ))
+                    .size(TEXT_SIZE - 4)
+                    .color(last_result.status.color()),
+                )
+                .padding(10),
+            );
+        }
         if selected {
+            let now = Utc::now();
+            let mut details = Column::new().spacing(4);
+            if !target.description.is_empty() {
+                details = details.push(Text::new(&target.description));
+            }
+            details = details.push(size_chart::view(&target.size_history));
+            if target.duplication.is_empty() {
+                details = details.push(Text::new("No duplications"));
+            }
+            for duplication in &target.duplication {
+                let next = if !scheduled {
+                    "disabled".to_string()
+                } else {
+                    match duplication.next_start(now) {
+                        next if next > now => format!("in {}", format_duration(next - now)),
+                        _ => "now".to_string(),
+                    }
+                };
+                let window = match duplication.window {
+                    Some(window) => format!(" (runs {})", window),
+                    None => String::new(),
+                };
+                details = details.push(Text::new(format!(
+                    "{} - next: {}{}",
+                    duplication.kind, next, window
+                )));
+                if let Some(started) = duplication.in_progress {
+                    let text = if duplication.last_attempt > Some(started) {
+                        format!(
+                            "Resuming previous duplication (started {} ago)",
+                            format_duration(now - started)
+                        )
+                    } else {
+                        "Duplicating...".to_string()
+                    };
+                    details = details.push(Text::new(text).size(TEXT_SIZE - 4));
+                }
+                if let Some(ref error) = duplication.last_error {
+                    details = details.push(
+                        Text::new(format!("Last attempt failed: {}", error))
+                            .size(TEXT_SIZE - 4)
+                            .color(Color::from_rgb(0.8, 0.2, 0.2)),
+                    );
+                }
+                for disk in duplication.kind.paths() {
+                    let (text, color) = match disk_space.get(disk) {
+                        Some(Some(Ok(Some(space)))) => (
+                            format!(
+                                "{} free of {}",
+                                format_bytes(space.available),
+                                format_bytes(space.total)
+                            ),
+                            Color::from_rgb(0.0, 0.5, 0.0),
+                        ),
+                        Some(Some(Ok(None))) => {
+                            ("unavailable".to_string(), Color::from_rgb(0.9, 0.6, 0.2))
+                        }
+                        Some(Some(Err(error))) => (error.clone(), Color::from_rgb(0.8, 0.2, 0.2)),
+                        Some(None) => ("checking...".to_string(), Color::from_rgb(0.6, 0.6, 0.6)),
+                        None => continue,
+                    };
+                    details = details.push(
+                        Row::new()
+                            .spacing(8)
+                            .push(Text::new(disk.display().to_string()).size(TEXT_SIZE - 4))
+                            .push(Text::new(text).size(TEXT_SIZE - 4).color(color)),
+                    );
+                }
+            }
+            if target
+                .duplication
+                .iter()
+                .any(|duplication| !duplication.kind.paths().is_empty())
+            {
+                details = details.push(
+                    Button::new(
+                        &mut self.s_check_disk_space,
+                        Text::new("REFRESH FREE SPACE").size(TEXT_SIZE - 4),
+                    )
+                    .padding(4)
+                    .style(style::Button::Text)
+                    .on_press(ListItemMessage::CheckDiskSpace),
+                );
+            }
+            match changes {
+                Some(None) => {
+                    details = details.push(
+                        Text::new("Estimating changes since the last backup...")
+                            .size(TEXT_SIZE - 4),
+                    )
+                }
+                Some(Some(Ok(changes))) if changes.files == 0 => {
+                    details = details
+                        .push(Text::new("No changes since the last backup").size(TEXT_SIZE - 4))
+                }
+                Some(Some(Ok(changes))) => {
+                    details = details.push(
+                        Text::new(format!(
+                            "~{} changed in {} files since the last backup",
+                            format_bytes(changes.bytes),
+                            changes.files
+                        ))
+                        .size(TEXT_SIZE - 4),
+                    )
+                }
+                Some(Some(Err(error))) => {
+                    details = details.push(
+                        Text::new(format!("Could not estimate changes: {}", error))
+                            .size(TEXT_SIZE - 4)
+                            .color(Color::from_rgb(0.8, 0.2, 0.2)),
+                    )
+                }
+                None => (),
+            }
+            if let Some(over_limit) = self.over_limit {
+                let exceeds = over_limit.exceeds(target);
+                let limits = [
+                    target.max_files.map(|max| format!("{} files", max)),
+                    target.max_total_bytes.map(format_bytes),
+                ];
+                let message = if exceeds {
+                    format!(
+                        "This backup would include at least {} files ({}), more than the limit \
+                         of {}. Is a source wrong?",
+                        over_limit.files,
+                        format_bytes(over_limit.bytes),
+                        limits
+                            .iter()
+                            .flatten()
+                            .cloned()
+                            .collect::<Vec<_>>()
+                            .join(" / ")
+                    )
+                } else {
+                    format!(
+                        "This backup would include {} files ({}). Are the sources and excludes \
+                         right?",
+                        over_limit.files,
+                        format_bytes(over_limit.bytes)
+                    )
+                };
+                details = details
+                    .push(Text::new(message).color(Color::from_rgb(0.9, 0.6, 0.2)))
+                    .push(
+                        Row::new()
+                            .spacing(10)
+                            .push(
+                                Button::new(
+                                    &mut self.s_dismiss_over_limit,
+                                    Text::new("DON'T BACK UP").size(TEXT_SIZE - 4),
+                                )
+                                .padding(8)
+                                .style(style::Button::Text)
+                                .on_press(ListItemMessage::DismissOverLimit),
+                            )
+                            .push(
+                                Button::new(
+                                    &mut self.s_backup_anyway,
+                                    Text::new(if exceeds { "BACK UP ANYWAY" } else { "BACK UP" })
+                                        .size(TEXT_SIZE - 4),
+                                )
+                                .padding(8)
+                                .style(style::Button::Primary)
+                                .on_press(ListItemMessage::BackupAnyway),
+                            ),
+                    );
+            }
+            // Also when the backup went ahead, since it may be much larger than needed
+            if self.sparse_files > 0 && !target.handle_sparse {
+                details = details.push(
+                    Text::new(format!(
+                        "{} large sparse files found, like VM images or databases. \"Handle \
+                         sparse files\" in the target stores their holes as holes, which may \
+                         shrink the backup a lot.",
+                        self.sparse_files
+                    ))
+                    .size(TEXT_SIZE - 4)
+                    .color(Color::from_rgb(0.9, 0.6, 0.2)),
+                );
+            }
+            let backup = match backup_state {
+                None if read_only => Button::new(
+                    &mut self.s_backup,
+                    Text::new("BACKUP NOW").size(TEXT_SIZE - 4),
+                )
+                .style(style::Button::Primary),
+                None => Button::new(
+                    &mut self.s_backup,
+                    Text::new("BACKUP NOW").size(TEXT_SIZE - 4),
+                )
+                .style(style::Button::Primary)
+                .on_press(ListItemMessage::Backup),
+                Some(BackupState::Queued) => Button::new(
+                    &mut self.s_backup,
+                    Text::new("CANCEL QUEUED BACKUP").size(TEXT_SIZE - 4),
+                )
+                .style(style::Button::Text)
+                .on_press(ListItemMessage::CancelQueued),
+                // Running backups can't be cancelled
+                Some(BackupState::Running) => Button::new(
+                    &mut self.s_backup,
+                    Text::new("BACKING UP...").size(TEXT_SIZE - 4),
+                )
+                .style(style::Button::Primary),
+            };
             column = column.push(
-                Container::new(Text::new("Details goes here"))
-                    .style(style::ListItemExpanded)
-                    .width(Length::Fill)
-                    .padding(10),
+                Container::new(details.push(if read_only {
+                    with_tooltip(backup.padding(8), "The repo is read-only")
+                } else {
+                    backup.padding(8).into()
+                }))
+                .style(style::ListItemExpanded)
+                .width(Length::Fill)
+                .padding(10),
             );
         }
 
-        column.into()
-    }
+        column.into()
+    }
+}
+#[derive(Clone, Debug)]
+pub enum ListItemMessage {
+    Expand,
+    Edit,
+    /// Delete the target. Can be undone with `Message::UndoDelete`.
+    Delete,
+    Backup,
+    /// Back up although the target exceeds its limits
+    BackupAnyway,
+    DismissOverLimit,
+    /// Remove the backup of this target from the queue
+    CancelQueued,
+    ToggleLastResult,
+    /// Refresh the space on the disks of the duplications
+    CheckDiskSpace,
+}
+
+/// A backup in `Ui::running_backups`
+pub struct RunningBackup {
+    repo_id: Uuid,
+    target_index: usize,
+    /// For `history`
+    started: Instant,
+    /// See `backup::BackupOptions::cancel`
+    cancel: std::sync::Arc<AtomicBool>,
+}
+
+/// Something that would leave running backups behind, held back in `Ui::guarded` until they are
+/// done or cancelled
+#[derive(Debug, Clone)]
+pub enum Guarded {
+    /// Switch to another repo
+    PickRepo(Opt<RepoOption>),
+    Quit,
+}
+
+/// A backup waiting in `Ui::backup_queue`
+pub struct QueuedBackup {
+    repo_id: Uuid,
+    target_index: usize,
+    /// Opened when queued, since the selected repo may change meanwhile
+    repo: Repo,
 }
-#[derive(Clone, Debug)]
-pub enum ListItemMessage {
-    Expand,
-    Edit,
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BackupState {
+    Queued,
+    Running,
+}
+
+fn backup_state(
+    running: &[RunningBackup],
+    queue: &std::collections::VecDeque<QueuedBackup>,
+    repo_id: Uuid,
+    target_index: usize,
+) -> Option<BackupState> {
+    if running
+        .iter()
+        .any(|running| running.repo_id == repo_id && running.target_index == target_index)
+    {
+        Some(BackupState::Running)
+    } else if queue
+        .iter()
+        .any(|queued| queued.repo_id == repo_id && queued.target_index == target_index)
+    {
+        Some(BackupState::Queued)
+    } else {
+        None
+    }
 }
 
-fn verify_target(target: &Target) -> Result<(), String> {
+fn verify_target(target: &Target) -> Result<(), BupError> {
+    let invalid = |message: &str| Err(BupError::InvalidTarget(message.to_string()));
     if target.name.is_empty() {
-        return Err("Name should not be empty".to_string());
+        return invalid("Name should not be empty");
     }
     if target.sources.is_empty() {
-        return Err("Should have at least one source".to_string());
+        return invalid("Should have at least one source");
     }
     for source in &target.sources {
-        if source.is_none() {
-            return Err("All sources should have a path".to_string());
+        if source.path.is_none() {
+            return invalid("All sources should have a path");
         }
     }
     for exclude in &target.excludes {
-        if exclude.is_empty() {
-            return Err("No exclude should be empty".to_string());
+        exclude::validate(exclude).map_err(BupError::InvalidTarget)?;
+    }
+    for duplication in &target.duplication {
+        if duplication
+            .kind
+            .paths()
+            .iter()
+            .any(|path| path.as_os_str().is_empty())
+        {
+            return invalid("All duplication disks should have a path");
+        }
+        if let DuplicationKind::Command { ref program, .. } = duplication.kind {
+            if program.trim().is_empty() {
+                return invalid("All duplication commands should have a program");
+            }
+        }
+        if let DuplicationKind::Sftp {
+            ref host, ref path, ..
+        } = duplication.kind
+        {
+            if host.trim().is_empty() || path.trim().is_empty() {
+                return invalid("All SSH duplications should have a host and a folder");
+            }
+        }
+    }
+    if target.tar_format.tar_name(*backup::TAR).is_none() {
+        return Err(BupError::InvalidTarget(format!(
+            "The installed tar can't write the {} format. Install GNU tar or bsdtar.",
+            target.tar_format
+        )));
+    }
+    if target.handle_sparse && *backup::TAR == backup::TarImpl::Unknown {
+        return invalid("The installed tar can't handle sparse files. Install GNU tar or bsdtar.");
+    }
+    for preset in &target.exclude_presets {
+        if !preset.supported(*backup::TAR) {
+            return Err(BupError::InvalidTarget(format!(
+                "The exclude preset {} needs GNU tar",
+                preset
+            )));
         }
     }
     Ok(())
 }
 
+fn verify_repo(repo: &RepoConfig) -> Result<(), BupError> {
+    if repo.name.is_empty() {
+        return Err(BupError::InvalidRepo("Name must be non-empty".to_string()));
+    }
+    if repo.home.as_os_str().is_empty() {
+        return Err(BupError::InvalidRepo("Home path must be set".to_string()));
+    }
+    for exclude in &repo.repo_excludes {
+        exclude::validate(exclude).map_err(BupError::InvalidRepo)?;
+    }
+    for notifier in &repo.notifiers {
+        notifier.validate().map_err(BupError::InvalidRepo)?;
+    }
+    Ok(())
+}
+
+/// Whether `dir` holds the metadata of an rdedup repo
+fn is_rdedup_repo(dir: &Path) -> bool {
+    dir.join("config.yml").is_file()
+}
+
+/// Refuses `home` for a new or imported repo if it is the home of a registered repo, lies inside
+/// one (or another rdedup repo) or contains one, or lies in the data directory of bup
+fn verify_new_home(home: &Path, config: &Config) -> Result<(), BupError> {
+    // Compare resolved paths where possible, so that symlinks and `..` don't hide nesting
+    let resolve = |path: &Path| path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    let home = resolve(home);
+    for repo in config.repos.values() {
+        let other = resolve(&repo.home);
+        let problem = if home == other {
+            "is already registered as"
+        } else if home.starts_with(&other) {
+            "is inside"
+        } else if other.starts_with(&home) {
+            "contains"
+        } else {
+            continue;
+        };
+        return Err(BupError::InvalidRepo(format!(
+            "{} {} the repo {} ({})",
+            home.display(),
+            problem,
+            repo.name,
+            other.display()
+        )));
+    }
+    if let Some(outer) = home.ancestors().skip(1).find(|dir| is_rdedup_repo(dir)) {
+        return Err(BupError::InvalidRepo(format!(
+            "{} is inside the rdedup repo {}",
+            home.display(),
+            outer.display()
+        )));
+    }
+    let data_dir = resolve(&data_dir());
+    if home.starts_with(&data_dir) {
+        return Err(BupError::InvalidRepo(format!(
+            "{} is inside the data directory of bup ({})",
+            home.display(),
+            data_dir.display()
+        )));
+    }
+    Ok(())
+}
+
 // Persistent state
 
-fn config_path() -> std::path::PathBuf {
-    let mut path = if let Some(project_dirs) = directories_next::ProjectDirs::from("", "", "Bup") {
+/// Overrides the directory of the config file, e.g. to run isolated profiles
+pub const DATA_DIR_VAR: &str = "BUP_DATA_DIR";
+
+fn data_dir() -> std::path::PathBuf {
+    if let Some(dir) = std::env::var_os(DATA_DIR_VAR).filter(|dir| !dir.is_empty()) {
+        dir.into()
+    } else if let Some(project_dirs) = directories_next::ProjectDirs::from("", "", "Bup") {
         project_dirs.data_dir().into()
     } else {
         std::env::current_dir().unwrap_or_else(|_| std::path::PathBuf::new())
-    };
-
-    path.push("config.json");
+    }
+}
 
-    path
+fn config_path() -> std::path::PathBuf {
+    data_dir().join("config.json")
 }
 
 impl Config {
     /// bool: true if config was newly created
     pub fn load() -> anyhow::Result<Self> {
         match std::fs::read_to_string(config_path()) {
-            Ok(contents) => Ok(serde_json::from_str(&contents)?),
-            Err(_) => Ok(Config::default()),
+            Ok(contents) => {
+                let json: serde_json::Value = serde_json::from_str(&contents)?;
+                let version = match json.get("version") {
+                    Some(version) => version.as_u64().context("Invalid config version")? as u32,
+                    None => 0,
+                };
+                if version > CONFIG_VERSION {
+                    anyhow::bail!(
+                        "Config has version {}, but this version of bup only understands up to {}",
+                        version,
+                        CONFIG_VERSION
+                    );
+                }
+                let mut config: Config = serde_json::from_value(migrate(json, version)?)?;
+                config.version = CONFIG_VERSION;
+                Ok(config)
+            }
+            Err(_) => Ok(Config {
+                version: CONFIG_VERSION,
+                max_concurrent_backups: default_max_concurrent_backups(),
+                background_priority: default_background_priority(),
+                open_retries: default_open_retries(),
+                ..Default::default()
+            }),
         }
     }
 
+    pub fn to_json(&self) -> anyhow::Result<String> {
+        Ok(serde_json::to_string_pretty(&self)?)
+    }
+
     pub fn save(&self) -> anyhow::Result<()> {
-        use std::io::Write;
-        let json = serde_json::to_string_pretty(&self)?;
+        Self::write(&self.to_json()?)
+    }
 
+    /// Write `json` as the config file. It is written next to it first and then renamed over it,
+    /// so that the old config stays intact if writing is interrupted.
+    pub fn write(json: &str) -> anyhow::Result<()> {
+        use std::io::Write;
         let path = config_path();
-        println!("Saving to path: {}", path.display());
+        let result: anyhow::Result<()> = try {
+            if let Some(dir) = path.parent() {
+                std::fs::create_dir_all(dir)?;
+            }
+            let tmp = path.with_extension("json.tmp");
+            let mut file = std::fs::File::create(&tmp)?;
+            file.write_all(json.as_bytes())?;
+            file.sync_all()?;
+            std::fs::rename(&tmp, &path)?;
+        };
+        result.with_context(|| format!("Cannot write {}", path.display()))
+    }
 
-        if let Some(dir) = path.parent() {
-            std::fs::create_dir_all(dir)?;
+    /// How backups of the targets of repo `repo_id` run
+    pub fn backup_options(&self, repo_id: Uuid) -> backup::BackupOptions {
+        let repo = self.repos.get(&repo_id);
+        backup::BackupOptions {
+            priority: if self.background_priority {
+                backup::Priority::Background
+            } else {
+                backup::Priority::Normal
+            },
+            checksum: repo.map_or(false, |repo| repo.checksums),
+            min_free_space: repo
+                .filter(|repo| repo.critical_space_bytes > 0)
+                .map(|repo| (repo.home.clone(), repo.critical_space_bytes)),
+            repo_excludes: repo
+                .map(|repo| repo.repo_excludes.clone())
+                .unwrap_or_default(),
+            cancel: Default::default(),
+            snapshot_name: None,
+            read_only: repo.map_or(false, |repo| repo.read_only),
+            verify: repo.map_or(false, |repo| repo.verify_after_backup),
         }
+    }
 
+    /// Check that the config directory can be written to, without touching the config file itself
+    pub fn probe_writable() -> anyhow::Result<()> {
+        let path = config_path();
+        let dir = path.parent().unwrap_or_else(|| Path::new("."));
+        let probe = dir.join(".write-probe");
+        let result: anyhow::Result<()> = try {
+            std::fs::create_dir_all(dir)?;
+            std::fs::write(&probe, b"")?;
+            std::fs::remove_file(&probe)?;
+        };
+        result.with_context(|| format!("Cannot write to {}", dir.display()))
+    }
+}
+impl Ui {
+    /// Compute the health of the selected repo in the background, unless it is cached
+    fn check_health(&mut self, force: bool) -> Command<Message> {
+        match (self.config.selected_repo(), self.repo.clone()) {
+            (Some(config), Some(repo)) if force || !self.health.contains_key(&config.id) => {
+                let id = config.id;
+                Command::perform(health::check_async(repo, config.clone()), move |health| {
+                    Message::HealthChecked(id, health)
+                })
+            }
+            _ => Command::none(),
+        }
+    }
+    /// List the snapshots of the opened repo for `Scene::Reconcile`
+    fn list_for_reconcile(&self) -> Command<Message> {
+        match self.repo {
+            Some(ref repo) => Command::perform(rdedup::list_names_async(repo.clone()), |result| {
+                Message::Reconcile(ReconcileMessage::Listed(result))
+            }),
+            None => Command::perform(async { Err("No repo opened".to_string()) }, |result| {
+                Message::Reconcile(ReconcileMessage::Listed(result))
+            }),
+        }
+    }
+    /// Queue backups of all targets of the selected repo, once per run and if
+    /// `Config::backup_on_startup` is set. Never for a demo repo.
+    fn queue_startup_backups(&mut self) -> Command<Message> {
+        if !self.config.backup_on_startup || self.startup_backups_queued {
+            return Command::none();
+        }
+        let n_targets = match self.config.selected_repo() {
+            Some(repo) if !repo.ephemeral && !repo.read_only => repo.targets.len(),
+            _ => return Command::none(),
+        };
+        self.startup_backups_queued = true;
+        info!(self.log, "Backing up {} targets on startup", n_targets);
+        // Like pressing BACKUP NOW on each, so that limits are checked
+        let commands = (0..n_targets)
+            .map(|i| self.update(Message::ListItem(i, ListItemMessage::Backup)))
+            .collect();
+        Command::batch(commands)
+    }
+    /// GC the selected repo in the background, unless a backup to it is running
+    fn compact(&mut self) -> Command<Message> {
+        let repo_config = self.config.selected_repo().cloned();
+        let id = repo_config.as_ref().map(|repo| repo.id).unwrap_or_default();
+        match (self.repo.clone(), repo_config) {
+            _ if self.compacting.is_some() => (),
+            (_, Some(ref repo_config)) if repo_config.read_only => {
+                self.compact_result = Some((id, Err("The repo is read-only".to_string())))
+            }
+            (Some(_), Some(_))
+                if self
+                    .running_backups
+                    .iter()
+                    .any(|running| running.repo_id == id) =>
+            {
+                self.compact_result =
+                    Some((id, Err("Wait for the running backup to finish".to_string())))
+            }
+            (Some(repo), Some(repo_config)) => {
+                self.compacting = Some(id);
+                self.compact_result = None;
+                self.gc_progress = Default::default();
+                self.gc_shown = Default::default();
+                return Command::perform(
+                    rdedup::compact_async(
+                        repo,
+                        repo_config,
+                        self.gc_progress.clone(),
+                        self.log.clone(),
+                    ),
+                    move |result| Message::Compacted(id, result),
+                );
+            }
+            _ => self.compact_result = Some((id, Err("No repo opened".to_string()))),
+        }
+        Command::none()
+    }
+    /// Clean up after the backups of the selected repo that were interrupted, once the repo is
+    /// unlocked
+    fn discard_interrupted_backups(&mut self) -> Command<Message> {
+        let (repo, repo_config) = match (self.repo.clone(), self.config.selected_repo()) {
+            (Some(repo), Some(repo_config)) if !repo_config.read_only => (repo, repo_config),
+            _ => return Command::none(),
+        };
+        let repo_id = repo_config.id;
+        let passphrase = self.repo_passphrase(repo_id);
+        let commands = repo_config
+            .targets
+            .iter()
+            .filter_map(|target| Some((target.id, target.interrupted_backup.clone()?)))
+            // Not interrupted, but still running
+            .filter(|(target_id, _)| {
+                !self.running_backups.iter().any(|running| {
+                    running.repo_id == repo_id
+                        && repo_config.targets.get(running.target_index).map(|t| t.id)
+                            == Some(*target_id)
+                })
+            })
+            .map(|(target_id, name)| {
+                Command::perform(
+                    backup::discard_interrupted_async(
+                        repo.clone(),
+                        name.clone(),
+                        passphrase.clone(),
+                        self.log.clone(),
+                    ),
+                    move |result| {
+                        Message::InterruptedBackupDiscarded(repo_id, target_id, name, result)
+                    },
+                )
+            })
+            .collect();
+        Command::batch(commands)
+    }
+    /// Queue a backup of target `i` of the selected repo, unless it is queued or running already
+    fn queue_backup(&mut self, i: usize) -> Command<Message> {
+        let repo_id = self.config.selected_repo().map(|repo| repo.id);
+        match (self.repo.clone(), repo_id) {
+            (Some(_), Some(_)) if self.selected_read_only() => {
+                error!(self.log, "[User error] The repo is read-only");
+                Command::none()
+            }
+            (Some(repo), Some(repo_id)) => {
+                let state = backup_state(&self.running_backups, &self.backup_queue, repo_id, i);
+                if state.is_none() {
+                    self.backup_queue.push_back(QueuedBackup {
+                        repo_id,
+                        target_index: i,
+                        repo,
+                    });
+                }
+                self.start_queued_backups()
+            }
+            _ => {
+                error!(self.log, "[User error] No repo opened");
+                Command::none()
+            }
+        }
+    }
+    fn set_over_limit(&mut self, i: usize, over_limit: Option<backup::OverLimit>) {
+        if let Scene::Overview { ref mut list, .. } = self.scene {
+            if let Some(item) = list.get_mut(i) {
+                item.over_limit = over_limit;
+            }
+        }
+    }
+    /// Time of the newest snapshot of target `i` of the selected repo, from the cached snapshot
+    /// names or else from its last backup
+    fn last_backup_time(&self, i: usize) -> Option<DateTime<Utc>> {
+        let repo = self.config.selected_repo()?;
+        let target = repo.targets.get(i)?;
+        let cached = self.snapshot_names.get(&repo.id).and_then(|(_, names)| {
+            rdedup::snapshots_of(names, &target.name)
+                .first()
+                .map(|snapshot| snapshot.timestamp)
+        });
+        cached.or_else(|| {
+            let last_result = target.last_result.as_ref()?;
+            if let BackupStatus::Failed = last_result.status {
+                return None;
+            }
+            let name = last_result.message.lines().next()?;
+            rdedup::parse_snapshot_name(name).map(|(_, time)| time)
+        })
+    }
+    /// Index of `snapshots_target` in the selected repo, if it is still expanded
+    fn snapshots_target_index(&self) -> Option<usize> {
+        let id = self
+            .snapshots_target
+            .filter(|id| self.expanded.contains(id))?;
+        self.config
+            .selected_repo()?
+            .targets
+            .iter()
+            .position(|target| target.id == id)
+    }
+    /// Estimate the changes since the last backup of the expanded targets of the Overview, unless
+    /// a recent estimate exists
+    fn estimate_changes(&mut self) -> Command<Message> {
+        if !matches!(self.scene, Scene::Overview { .. }) {
+            return Command::none();
+        }
+        let (repo_id, targets) = match self.config.selected_repo() {
+            Some(repo) => (
+                repo.id,
+                repo.targets
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, target)| self.expanded.contains(&target.id))
+                    .map(|(i, target)| (i, target.clone()))
+                    .collect::<Vec<_>>(),
+            ),
+            None => return Command::none(),
+        };
+        let mut commands = Vec::new();
+        for (i, target) in targets {
+            let since = match self.last_backup_time(i) {
+                Some(since) => since,
+                None => continue,
+            };
+            let key = (repo_id, target.name.clone());
+            match self.changes.get(&key) {
+                Some((s, estimated, _)) if *s == since && estimated.elapsed() < CHANGES_MAX_AGE => {
+                    continue
+                }
+                _ => (),
+            }
+            self.changes.insert(key, (since, Instant::now(), None));
+            let target_name = target.name.clone();
+            commands.push(Command::perform(
+                backup::changed_since_async(target, since),
+                move |result| {
+                    Message::ChangesEstimated(repo_id, target_name.clone(), since, result)
+                },
+            ));
+        }
+        Command::batch(commands)
+    }
+    /// Check the free space on the disk of the selected repo, if it wasn't checked recently
+    fn check_free_space(&mut self) -> Command<Message> {
+        let (repo_id, home) = match self.config.selected_repo() {
+            Some(repo) if repo.low_space_bytes > 0 || repo.critical_space_bytes > 0 => {
+                (repo.id, repo.home.clone())
+            }
+            _ => return Command::none(),
+        };
+        let now = Instant::now();
+        match self.free_space.get_mut(&repo_id) {
+            Some((checked, _)) if now.duration_since(*checked) < FREE_SPACE_INTERVAL => {
+                return Command::none()
+            }
+            Some((checked, _)) => *checked = now,
+            None => {
+                self.free_space.insert(repo_id, (now, None));
+            }
+        }
+        Command::perform(rdedup::space_async(home), move |result| {
+            Message::FreeSpaceChecked(repo_id, result)
+        })
+    }
+    /// Check the space on the disks of the duplications of target `i` of the selected repo
+    fn check_disk_space(&mut self, i: usize) -> Command<Message> {
+        let disks: Vec<PathBuf> = match self
+            .config
+            .selected_repo()
+            .and_then(|repo| repo.targets.get(i))
         {
-            let mut file = std::fs::File::create(path)?;
-
-            file.write_all(json.as_bytes())?;
+            Some(target) => target
+                .duplication
+                .iter()
+                .flat_map(|duplication| duplication.kind.paths())
+                .cloned()
+                .collect(),
+            None => return Command::none(),
+        };
+        self.check_disks(disks)
+    }
+    /// Check the space on `disks`, except those being checked already
+    fn check_disks(&mut self, disks: Vec<PathBuf>) -> Command<Message> {
+        let mut commands = Vec::new();
+        for disk in disks {
+            if disk.as_os_str().is_empty() || matches!(self.disk_space.get(&disk), Some(None)) {
+                continue;
+            }
+            self.disk_space.insert(disk.clone(), None);
+            commands.push(Command::perform(
+                duplicate::disk_space_async(disk.clone()),
+                move |result| Message::DiskSpaceChecked(disk.clone(), result),
+            ));
         }
-
+        Command::batch(commands)
+    }
+    /// Compute the usage of the repos that have none cached for their current generation
+    fn compute_usage(&mut self) -> Command<Message> {
+        let mut commands = Vec::new();
+        for repo in self.config.repos.values().filter(|repo| !repo.ephemeral) {
+            let generation = self.generations.get(&repo.id).copied().unwrap_or(0);
+            if matches!(self.usage.get(&repo.id), Some((g, _)) if *g == generation) {
+                continue;
+            }
+            self.usage.insert(repo.id, (generation, None));
+            let repo_id = repo.id;
+            commands.push(Command::perform(
+                rdedup::usage_async(
+                    repo.home.clone(),
+                    self.repo_passphrase(repo_id),
+                    self.log.clone(),
+                ),
+                move |result| Message::UsageComputed(repo_id, generation, result),
+            ));
+        }
+        Command::batch(commands)
+    }
+    /// Like "Protecting 1.4 TiB across 3 repos (312 GiB stored)"
+    fn usage_summary(&self) -> String {
+        let (mut logical, mut stored, mut repos, mut unknown, mut computing) = (0, 0, 0, 0, 0);
+        for repo in self.config.repos.values().filter(|repo| !repo.ephemeral) {
+            repos += 1;
+            match self.usage.get(&repo.id) {
+                Some((_, Some(Ok(usage)))) => {
+                    logical += usage.logical;
+                    stored += usage.stored;
+                }
+                Some((_, Some(Err(_)))) => unknown += 1,
+                _ => computing += 1,
+            }
+        }
+        let mut summary = format!(
+            "Protecting {} across {} repo{} ({} stored)",
+            format_bytes(logical),
+            repos,
+            if repos == 1 { "" } else { "s" },
+            format_bytes(stored)
+        );
+        if unknown > 0 {
+            summary += &format!(", {} unknown", unknown);
+        }
+        if computing > 0 {
+            summary += &format!(", measuring {}...", computing);
+        }
+        summary
+    }
+    /// Passphrase of the repo with the given id. Unless another one has been entered for it, this
+    /// is the app passphrase.
+    /// `RepoConfig::read_only` of the selected repo
+    fn selected_read_only(&self) -> bool {
+        self.config
+            .selected_repo()
+            .map_or(false, |repo| repo.read_only)
+    }
+    fn repo_passphrase(&self, id: Uuid) -> String {
+        self.repo_passphrases
+            .get(&id)
+            .or(self.passphrase.as_ref())
+            .cloned()
+            .unwrap_or_default()
+    }
+    /// What the command palette offers: actions on the targets of the selected repo, switching
+    /// repos and opening the other scenes
+    fn palette_actions(&self) -> Vec<Action> {
+        let action = |label: String, message| Action { label, message };
+        let mut actions = Vec::new();
+        if let Some(repo) = self.config.selected_repo() {
+            for (i, target) in repo.targets.iter().enumerate() {
+                actions.push(action(
+                    format!("Back up {}", target.name),
+                    Message::ListItem(i, ListItemMessage::Backup),
+                ));
+                // Restoring starts from the snapshot list, shown when the target is expanded
+                if !self.expanded.contains(&target.id) {
+                    actions.push(action(
+                        format!("Restore {}", target.name),
+                        Message::ListItem(i, ListItemMessage::Expand),
+                    ));
+                }
+                actions.push(action(
+                    format!("Edit {}", target.name),
+                    Message::EditTarget(i),
+                ));
+            }
+        }
+        actions.push(action("Create target".to_string(), Message::NewTarget));
+        actions.push(action(
+            "Split folder into targets".to_string(),
+            Message::OpenSplit,
+        ));
+        actions.push(action("Open overview".to_string(), Message::ToOverview));
+        actions.push(action("Open settings".to_string(), Message::OpenSettings));
+        actions.push(action("Edit repo".to_string(), Message::EditRepo));
+        actions.push(action("Compact repo".to_string(), Message::Compact));
+        actions.push(action("Export config".to_string(), Message::OpenExport));
+        actions.push(action(
+            "Reconcile snapshots".to_string(),
+            Message::OpenReconcile,
+        ));
+        let selected = self.config.selected_repo().map(|repo| repo.id);
+        for repo in self.config.repos.values() {
+            if Some(repo.id) != selected {
+                actions.push(action(
+                    format!("Switch to repo {}", repo.name),
+                    Message::PickRepo(Opt {
+                        name: repo.name.clone(),
+                        value: RepoOption::Select(repo.id),
+                    }),
+                ));
+            }
+        }
+        actions
+    }
+    /// The running backups that `guarded` would leave behind: those of the selected repo when
+    /// switching repos, all of them when quitting
+    fn guarded_backups(&self, guarded: &Guarded) -> Vec<&RunningBackup> {
+        let selected = self.config.selected_repo().map(|repo| repo.id);
+        self.running_backups
+            .iter()
+            .filter(|running| match guarded {
+                Guarded::PickRepo(_) => Some(running.repo_id) == selected,
+                Guarded::Quit => true,
+            })
+            .collect()
+    }
+    /// Whether `guarded` may go ahead. If it would leave running backups behind, it is held back
+    /// in `guarded` instead, to go ahead once they are done.
+    fn guard_backups(&mut self, guarded: Guarded) -> bool {
+        if self.guarded_backups(&guarded).is_empty() {
+            self.guarded = None;
+            true
+        } else {
+            self.guarded = Some(guarded);
+            false
+        }
+    }
+    /// Go ahead with `guarded` if the backups it waits for are done
+    fn resume_guarded(&mut self) -> Command<Message> {
+        match self.guarded {
+            Some(ref guarded) if self.guarded_backups(guarded).is_empty() => (),
+            _ => return Command::none(),
+        }
+        match self.guarded.take() {
+            Some(Guarded::PickRepo(repo)) => self.update(Message::PickRepo(repo)),
+            Some(Guarded::Quit) => {
+                SHOULD_EXIT.store(true, std::sync::atomic::Ordering::Relaxed);
+                Command::none()
+            }
+            None => Command::none(),
+        }
+    }
+    /// Fails while backups or duplications of the repo are queued or running. They refer to
+    /// targets by index, so the targets of the repo must not be removed or reordered meanwhile.
+    fn check_targets_idle(&self, repo_id: Uuid) -> Result<(), String> {
+        if self
+            .running_backups
+            .iter()
+            .any(|running| running.repo_id == repo_id)
+            || self
+                .backup_queue
+                .iter()
+                .any(|queued| queued.repo_id == repo_id)
+            || self
+                .running_duplications
+                .iter()
+                .any(|(id, _, _)| *id == repo_id)
+        {
+            return Err(
+                "Wait until the backups and duplications of this repo are done".to_string(),
+            );
+        }
+        Ok(())
+    }
+    /// Remove target `i` of the selected repo, keeping it for `UndoDelete`
+    fn delete_target(&mut self, i: usize) -> Result<(), String> {
+        let repo_id = self.config.selected_repo().ok_or("No repo selected")?.id;
+        self.check_targets_idle(repo_id)?;
+        let repo = self
+            .config
+            .repos
+            .get_mut(&repo_id)
+            .ok_or("Repo does not exist anymore")?;
+        if i >= repo.targets.len() {
+            return Err("Target does not exist anymore".to_string());
+        }
+        let target = repo.targets.remove(i);
+        info!(self.log, "Deleting target {:?}", target.name);
+        if let Scene::Overview { ref mut list, .. } = self.scene {
+            if i < list.len() {
+                list.remove(i);
+            }
+        }
+        self.deleted_targets.push((i, target));
+        if self.deleted_targets.len() > UNDO_DEPTH {
+            self.deleted_targets.remove(0);
+        }
+        self.save_config();
+        Ok(())
+    }
+    /// Show `error` in the banner of the Overview
+    fn overview_error(&mut self, error: String) {
+        if let Scene::Overview {
+            error: ref mut e, ..
+        } = self.scene
+        {
+            *e = Some(error);
+        }
+    }
+    /// Put the last deleted target back where it was
+    fn undo_delete(&mut self) -> Result<(), String> {
+        let (i, target) = self.deleted_targets.pop().ok_or("Nothing to undo")?;
+        let repo_id = target.repo;
+        if let Err(e) = self.check_targets_idle(repo_id) {
+            self.deleted_targets.push((i, target));
+            return Err(e);
+        }
+        let repo = self
+            .config
+            .repos
+            .get_mut(&repo_id)
+            .ok_or("The repo of the target does not exist anymore")?;
+        let i = i.min(repo.targets.len());
+        info!(self.log, "Restoring deleted target {:?}", target.name);
+        repo.targets.insert(i, target);
+        if let Scene::Overview { ref mut list, .. } = self.scene {
+            if i <= list.len() {
+                list.insert(i, Default::default());
+            }
+        }
+        self.save_config();
+        Ok(())
+    }
+    /// Move target `index` of its repo to the repo `to`, as `target` (i.e. with the edits made to
+    /// it). Its snapshots stay in the old repo.
+    fn move_target(&mut self, index: usize, mut target: Target, to: Uuid) -> Result<(), String> {
+        verify_target(&target).map_err(|e| e.to_string())?;
+        let from = target.repo;
+        if to == from {
+            return Err("The target is in that repo already".to_string());
+        }
+        self.check_targets_idle(from)?;
+        let destination = self
+            .config
+            .repos
+            .get(&to)
+            .ok_or("Repo does not exist anymore")?;
+        if destination
+            .targets
+            .iter()
+            .any(|other| other.name == target.name)
+        {
+            // Their snapshots would mix
+            return Err(format!(
+                "{} already has a target named {}",
+                destination.name, target.name
+            ));
+        }
+        let destination_name = destination.name.clone();
+        let source = self
+            .config
+            .repos
+            .get_mut(&from)
+            .ok_or("Repo does not exist anymore")?;
+        if index >= source.targets.len() {
+            return Err("Target does not exist anymore".to_string());
+        }
+        source.targets.remove(index);
+        info!(
+            self.log,
+            "Moving target {:?} to repo {}", target.name, destination_name
+        );
+        target.repo = to;
+        if let Some(destination) = self.config.repos.get_mut(&to) {
+            destination.targets.push(target);
+        }
+        self.save_config();
         Ok(())
     }
+    /// Go to the editor of the target, or back to the Overview if it cannot be edited
+    fn edit_target(&mut self, index: usize) {
+        self.scene = match Scene::edit(index, &self.config) {
+            Ok(scene) => scene,
+            Err(e) => Scene::overview_error(&self.config, e),
+        };
+    }
+    /// Compare how far the wall clock moved since the last tick with the monotonic clock, and
+    /// log a jump: a clock change, an NTP correction, or a suspend (the monotonic clock stands
+    /// still while suspended on some platforms). Duplication times left in the future, by a jump
+    /// back now or while the app was closed, are pulled back to now.
+    fn check_clock(&mut self, now: Instant) {
+        let wall = Utc::now();
+        if let Some((last, last_wall)) = self.clock.replace((now, wall)) {
+            let elapsed = chrono::Duration::from_std(now.duration_since(last))
+                .unwrap_or_else(|_| chrono::Duration::zero());
+            let jump = (wall - last_wall) - elapsed;
+            let threshold = chrono::Duration::from_std(CLOCK_JUMP).unwrap();
+            if jump >= threshold {
+                info!(
+                    self.log,
+                    "Clock jumped forward by {} (suspend or clock change); missed duplications \
+                     run once",
+                    format_duration(jump)
+                );
+            } else if -jump >= threshold {
+                error!(
+                    self.log,
+                    "Clock jumped back by {}; rescheduling duplications from now",
+                    format_duration(-jump)
+                );
+            }
+        }
+        let mut changed = false;
+        for repo in self.config.repos.values_mut() {
+            for target in &mut repo.targets {
+                for duplication in &mut target.duplication {
+                    changed |= duplication.clamp_times(wall);
+                }
+            }
+        }
+        if changed {
+            info!(
+                self.log,
+                "Pulled duplication times in the future back to now"
+            );
+            self.save_config();
+        }
+    }
+    /// Start the scheduled duplications that are due and not running yet. Nothing runs while
+    /// locked.
+    fn start_due_duplications(&mut self) -> Command<Message> {
+        if self.passphrase.is_none() {
+            return Command::none();
+        }
+        let now = Utc::now();
+        let max = self.config.max_concurrent_backups.max(1);
+        let mut commands = Vec::new();
+        let mut disks = Vec::new();
+        for repo in self.config.repos.values_mut() {
+            if repo.ephemeral || repo.read_only {
+                continue;
+            }
+            for (i, target) in repo.targets.iter_mut().enumerate() {
+                for (j, duplication) in target.duplication.iter_mut().enumerate() {
+                    let key = (repo.id, i, j);
+                    let due = duplication.next_start(now) <= now;
+                    if !due || self.running_duplications.contains(&key) {
+                        continue;
+                    }
+                    // Stays due, and starts on a later tick once a slot is free
+                    if self.running_backups.len() + self.running_duplications.len() >= max {
+                        continue;
+                    }
+                    duplication.last_attempt = Some(now);
+                    duplication.in_progress.get_or_insert(now);
+                    self.running_duplications.push(key);
+                    disks.extend(duplication.kind.paths().into_iter().cloned());
+                    let (repo_id, home) = (repo.id, repo.home.clone());
+                    commands.push(Command::perform(
+                        duplicate::duplicate_async(
+                            home,
+                            duplication.clone(),
+                            target.name.clone(),
+                            self.log.clone(),
+                        ),
+                        move |result| Message::DuplicationResult(repo_id, i, j, result),
+                    ));
+                }
+            }
+        }
+        if !commands.is_empty() {
+            // Persist `in_progress`, to resume if the app doesn't exit cleanly
+            self.save_config();
+        }
+        // Refresh what the Overview shows of the disks about to be written to
+        commands.push(self.check_disks(disks));
+        Command::batch(commands)
+    }
+    /// Start queued backups while there are free slots, shared with the scheduled duplications.
+    /// Backups of a repo that is being compacted or relocated stay queued.
+    fn start_queued_backups(&mut self) -> Command<Message> {
+        let mut commands = Vec::new();
+        let mut i = 0;
+        while i < self.backup_queue.len()
+            && self.running_backups.len() + self.running_duplications.len()
+                < self.config.max_concurrent_backups.max(1)
+        {
+            let queued_repo = Some(self.backup_queue[i].repo_id);
+            if self.compacting == queued_repo || self.relocating == queued_repo {
+                i += 1;
+                continue;
+            }
+            let QueuedBackup {
+                repo_id,
+                target_index,
+                repo,
+            } = self.backup_queue.remove(i).unwrap();
+            let target = self
+                .config
+                .repos
+                .get(&repo_id)
+                .and_then(|repo| repo.targets.get(target_index).cloned());
+            let target = match target {
+                Some(target) => target,
+                // Removed while queued
+                None => continue,
+            };
+            let passphrase = self.repo_passphrase(repo_id);
+            let mut options = self.config.backup_options(repo_id);
+            let name = rdedup::snapshot_name(&target.name, Utc::now());
+            options.snapshot_name = Some(name.clone());
+            if let Some(target) = self
+                .config
+                .repos
+                .get_mut(&repo_id)
+                .and_then(|repo| repo.targets.get_mut(target_index))
+            {
+                target.interrupted_backup = Some(name);
+            }
+            self.running_backups.push(RunningBackup {
+                repo_id,
+                target_index,
+                started: Instant::now(),
+                cancel: options.cancel.clone(),
+            });
+            commands.push(Command::perform(
+                backup::run_backup_async(repo, target, passphrase, options, self.log.clone()),
+                move |result| Message::BackupResult(repo_id, target_index, result),
+            ));
+        }
+        if !commands.is_empty() {
+            // Persist `interrupted_backup`, to clean up if the app doesn't exit cleanly
+            self.save_config();
+        }
+        Command::batch(commands)
+    }
+    /// Unlock with the passphrase remembered in the keyring, if any and still correct
+    fn unlock_from_keychain(&mut self) -> Command<Message> {
+        if !self.config.remember_passphrase {
+            return Command::none();
+        }
+        let passphrase = match (keychain::load(&self.log), &self.config.passphrase_hash) {
+            (Some(passphrase), Some(hash)) => {
+                let hash = PasswordHash::new(hash).unwrap();
+                if self
+                    .argon2
+                    .verify_password(passphrase.as_bytes(), &hash)
+                    .is_err()
+                {
+                    info!(self.log, "Remembered passphrase is outdated");
+                    return Command::none();
+                }
+                passphrase
+            }
+            _ => return Command::none(),
+        };
+        self.passphrase = Some(passphrase);
+        self.unlock()
+    }
+    /// Use the accepted `passphrase`, remembering it in the keyring if asked to, and unlock
+    fn accept_passphrase(&mut self, passphrase: String, remember: bool) -> Command<Message> {
+        if remember {
+            match keychain::store(&passphrase) {
+                Ok(()) => {
+                    self.config.remember_passphrase = true;
+                    self.save_config();
+                }
+                // Not fatal; the passphrase just has to be entered next time
+                Err(e) => error!(self.log, "{:#}", e),
+            }
+        }
+        self.passphrase = Some(passphrase);
+        self.unlock()
+    }
+    /// After the passphrase is accepted: open the selected repo in the background, showing
+    /// `Scene::Unlocking` meanwhile
+    fn unlock(&mut self) -> Command<Message> {
+        let repo = match self.config.selected_repo() {
+            Some(repo) => repo,
+            None => {
+                self.scene = Scene::overview(&self.config);
+                return Command::none();
+            }
+        };
+        self.scene = Scene::Unlocking {
+            attempt: 0,
+            retry: None,
+            s_retry: Default::default(),
+            s_give_up: Default::default(),
+        };
+        self.open_selected_repo()
+    }
+    /// Start the next attempt of `Scene::Unlocking` to open the selected repo
+    fn open_selected_repo(&mut self) -> Command<Message> {
+        let repo = match self.config.selected_repo() {
+            Some(repo) => repo,
+            None => return Command::none(),
+        };
+        if let Scene::Unlocking {
+            ref mut attempt,
+            ref mut retry,
+            ..
+        } = self.scene
+        {
+            *attempt += 1;
+            *retry = None;
+        }
+        Command::perform(
+            rdedup::open_async(
+                repo.home.clone(),
+                self.repo_passphrase(repo.id),
+                self.log.clone(),
+            ),
+            |result| Message::Unlocked(result.map(|(repo, unlocked)| (Redacted(repo), unlocked))),
+        )
+    }
+    /// Mark the cached snapshot names of the repo as outdated
+    fn snapshots_changed(&mut self, repo_id: Uuid) {
+        *self.generations.entry(repo_id).or_default() += 1;
+    }
+    /// (Re)load the snapshot list of `snapshots_target` in the Overview, from the cache if it is
+    /// up to date
+    fn load_snapshots(&mut self) -> Command<Message> {
+        let index = self.snapshots_target_index();
+        match (&mut self.scene, index) {
+            (Scene::Overview { snapshots, .. }, Some(i)) => {
+                let repo_config = self.config.selected_repo();
+                let target = repo_config.and_then(|repo| repo.targets.get(i));
+                let (repo_id, target) = match (repo_config, target) {
+                    (Some(repo_config), Some(target)) => (repo_config.id, target),
+                    _ => return Command::none(),
+                };
+                let generation = self.generations.get(&repo_id).copied().unwrap_or(0);
+                match (self.snapshot_names.get(&repo_id), self.repo.clone()) {
+                    (Some((cached, names)), _) if *cached == generation => {
+                        // Keep the filter while the list updates after a backup
+                        let filter = std::mem::take(&mut snapshots.filter);
+                        *snapshots = SnapshotList::loaded(
+                            Ok(rdedup::snapshots_of(names, &target.name)),
+                            repo_config
+                                .map(|repo| repo.snapshot_tags.clone())
+                                .unwrap_or_default(),
+                            repo_config
+                                .map(|repo| repo.pinned_snapshots.clone())
+                                .unwrap_or_default(),
+                        );
+                        snapshots.filter = filter;
+                        snapshots.failed_verifications = repo_config
+                            .map(|repo| repo.failed_verifications.clone())
+                            .unwrap_or_default();
+                        Command::none()
+                    }
+                    (_, Some(repo)) => {
+                        *snapshots = SnapshotList::default();
+                        Command::perform(rdedup::list_names_async(repo), move |result| {
+                            Message::NamesListed(repo_id, generation, result)
+                        })
+                    }
+                    _ => Command::none(),
+                }
+            }
+            _ => Command::none(),
+        }
+    }
+    /// Save the config, keeping track of any error so that it can be shown to the user
+    fn save_config(&mut self) {
+        if let Scene::Fatal { .. } = self.scene {
+            return;
+        }
+        debug!(self.log, "Saving config to {}", config_path().display());
+        let result = self.config.to_json().and_then(|json| {
+            Config::write(&json)?;
+            Ok(json)
+        });
+        match result {
+            Ok(json) => {
+                self.saved_config = Some(json);
+                self.save_error = None;
+            }
+            Err(e) => {
+                error!(self.log, "Error saving config: {:#}", e);
+                self.save_error = Some(format!("{:#}", e));
+            }
+        }
+    }
+    /// Save the config if it changed since it was last written
+    fn autosave(&mut self) {
+        if let Scene::Fatal { .. } = self.scene {
+            return;
+        }
+        match self.config.to_json() {
+            Ok(json) if self.saved_config.as_ref() != Some(&json) => self.save_config(),
+            Ok(_) => (),
+            Err(e) => error!(self.log, "Error serializing config: {:#}", e),
+        }
+    }
+}
+/// Upgrade a config of version `from` to the shape of `CONFIG_VERSION`, one version at a time.
+fn migrate(mut json: serde_json::Value, from: u32) -> anyhow::Result<serde_json::Value> {
+    for version in from..CONFIG_VERSION {
+        json = match version {
+            // 0 -> 1: Only introduced `version`
+            0 => json,
+            // 1 -> 2: Sources went from `Option<PathBuf>` to `Source { path, .. }`
+            1 => {
+                if let Some(repos) = json.get_mut("repos").and_then(|r| r.as_object_mut()) {
+                    for repo in repos.values_mut() {
+                        let targets = repo.get_mut("targets").and_then(|t| t.as_array_mut());
+                        for target in targets.into_iter().flatten() {
+                            let sources = target.get_mut("sources").and_then(|s| s.as_array_mut());
+                            for source in sources.into_iter().flatten() {
+                                *source = serde_json::json!({ "path": source.take() });
+                            }
+                        }
+                    }
+                }
+                json
+            }
+            _ => unreachable!(),
+        };
+    }
+    Ok(json)
 }
+
 impl Drop for Ui {
     fn drop(&mut self) {
+        // Keep the broken config for the user to inspect or fix
+        if let Scene::Fatal { .. } = self.scene {
+            return;
+        }
+        let ephemeral: Vec<Uuid> = self
+            .config
+            .repos
+            .values()
+            .filter(|repo| repo.ephemeral)
+            .map(|repo| repo.id)
+            .collect();
+        for id in ephemeral {
+            let repo = self.config.repos.shift_remove(&id).unwrap();
+            if let Err(e) = demo::remove_demo(&repo) {
+                eprintln!("Error removing demo repo: {:#}", e);
+            }
+            if self
+                .config
+                .selected_repo
+                .as_ref()
+                .and_then(|s| s.value.id())
+                == Some(id)
+            {
+                self.config.selected_repo = None;
+            }
+        }
         let result = self.config.save();
         if let Err(e) = result {
             eprintln!("Error saving state: {}", e);
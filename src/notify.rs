@@ -0,0 +1,217 @@
+//! Notifying about finished backups and scheduled duplications (see `RepoConfig::notifiers`),
+//! for unattended use. Sent with `curl`, which speaks both HTTP and SMTP.
+use crate::*;
+use std::io::Write;
+use std::process::{Command as Process, Stdio};
+
+/// Where to send a notification
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Notifier {
+    /// POST the `history::Entry` of the backup as JSON to `url`
+    Webhook { url: String },
+    /// Mail via SMTP. The password is read from the environment variable `password_var` when
+    /// sending, so that it is not stored in the config.
+    Email {
+        /// E.g. `smtps://smtp.example.com:465`
+        smtp_url: String,
+        from: String,
+        to: String,
+        /// Empty to send without logging in
+        user: String,
+        password_var: String,
+    },
+}
+impl Notifier {
+    pub fn email() -> Self {
+        Notifier::Email {
+            smtp_url: String::new(),
+            from: String::new(),
+            to: String::new(),
+            user: String::new(),
+            password_var: "BUP_SMTP_PASSWORD".to_string(),
+        }
+    }
+    /// Set the field named by `field`. Fields of other kinds of notifiers are ignored.
+    pub fn set(&mut self, field: Field, value: String) {
+        match (self, field) {
+            (Notifier::Webhook { url }, Field::Url) => *url = value,
+            (Notifier::Email { smtp_url, .. }, Field::Url) => *smtp_url = value,
+            (Notifier::Email { from, .. }, Field::From) => *from = value,
+            (Notifier::Email { to, .. }, Field::To) => *to = value,
+            (Notifier::Email { user, .. }, Field::User) => *user = value,
+            (Notifier::Email { password_var, .. }, Field::PasswordVar) => *password_var = value,
+            _ => (),
+        }
+    }
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            Notifier::Webhook { url } => match Url::parse(url) {
+                Ok(url) if url.scheme() == "http" || url.scheme() == "https" => Ok(()),
+                _ => Err(format!("Invalid webhook URL {:?}", url)),
+            },
+            Notifier::Email {
+                smtp_url, from, to, ..
+            } => {
+                match Url::parse(smtp_url) {
+                    Ok(url) if url.scheme() == "smtp" || url.scheme() == "smtps" => (),
+                    _ => return Err(format!("Invalid SMTP URL {:?}", smtp_url)),
+                }
+                if !from.contains('@') || !to.contains('@') {
+                    return Err("Email notifications need a from and a to address".to_string());
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// A field of a `Notifier`, for editing
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Field {
+    /// The webhook URL, or the SMTP URL
+    Url,
+    From,
+    To,
+    User,
+    PasswordVar,
+}
+
+/// Whether to notify about `entry` at all
+pub fn wanted(entry: &history::Entry, on_success: bool) -> bool {
+    on_success || entry.result == "failed"
+}
+
+/// Write a new file that only this user can read, since the temp dir is shared
+fn write_private(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(path)?.write_all(contents)
+}
+
+fn send(notifier: &Notifier, entry: &history::Entry) -> anyhow::Result<()> {
+    let json = serde_json::to_string(entry)?;
+    let mut curl = Process::new("curl");
+    curl.args(&["--silent", "--show-error", "--fail", "--max-time", "30"]);
+    // Credentials are passed as a curl config on stdin, to keep them off the command line
+    let mut config = String::new();
+    let mut body_file = None;
+    match notifier {
+        Notifier::Webhook { url } => {
+            curl.args(&[
+                "--header",
+                "Content-Type: application/json",
+                "--data-binary",
+            ])
+            .arg(&json)
+            .arg(url);
+        }
+        Notifier::Email {
+            smtp_url,
+            from,
+            to,
+            user,
+            password_var,
+        } => {
+            if !user.is_empty() {
+                // Not over plain text
+                curl.arg("--ssl-reqd");
+                let password = std::env::var(password_var)
+                    .with_context(|| format!("The SMTP password is not in ${}", password_var))?;
+                let escape = |s: &str| s.replace('\\', "\\\\").replace('"', "\\\"");
+                config = format!("user = \"{}:{}\"\n", escape(user), escape(&password));
+            }
+            let action = match entry.action {
+                history::Action::Backup => "backup",
+                history::Action::Duplication => "duplication",
+                history::Action::Restore => "restore",
+            };
+            let subject = format!(
+                "bup: {} of {} {}",
+                action,
+                entry.target,
+                if entry.result == "failed" {
+                    "failed"
+                } else {
+                    "succeeded"
+                }
+            );
+            let mut body = format!(
+                "From: {}\r\nTo: {}\r\nSubject: {}\r\nDate: {}\r\n\r\nTime: {}\r\nResult: {}\r\n",
+                from,
+                to,
+                subject,
+                Utc::now().to_rfc2822(),
+                entry.time.with_timezone(&Local),
+                entry.result
+            );
+            if let Some(ref error) = entry.error {
+                body.push_str(&format!("Error: {}\r\n", error));
+            }
+            for warning in &entry.warnings {
+                body.push_str(&format!("Warning: {}\r\n", warning));
+            }
+            body.push_str(&format!("\r\n{}\r\n", json));
+            let path = std::env::temp_dir().join(format!("bup-mail-{}.txt", Uuid::new_v4()));
+            write_private(&path, body.as_bytes())
+                .with_context(|| format!("Writing {}", path.display()))?;
+            curl.args(&["--mail-from", from, "--mail-rcpt", to, "--upload-file"])
+                .arg(&path)
+                .arg("--url")
+                .arg(smtp_url);
+            body_file = Some(path);
+        }
+    }
+    let result: anyhow::Result<()> = try {
+        let mut child = curl
+            .args(&["--config", "-"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+            .context("Spawning curl")?;
+        child
+            .stdin
+            .take()
+            .context("curl stdin")?
+            .write_all(config.as_bytes())?;
+        let output = child.wait_with_output().context("Waiting for curl")?;
+        if !output.status.success() {
+            Err(anyhow::anyhow!(
+                "curl failed ({}): {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ))?;
+        }
+    };
+    if let Some(path) = body_file {
+        let _ = std::fs::remove_file(path);
+    }
+    result
+}
+
+/// Send `entry` to all `notifiers`. Failures don't stop the other notifiers, and are returned
+/// together.
+pub fn notify(notifiers: &[Notifier], entry: &history::Entry) -> Result<(), String> {
+    let errors: Vec<String> = notifiers
+        .iter()
+        .filter_map(|notifier| send(notifier, entry).err())
+        .map(|e| format!("{:#}", e))
+        .collect();
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors.join("; "))
+    }
+}
+
+/// `notify` on a blocking thread
+pub async fn notify_async(notifiers: Vec<Notifier>, entry: history::Entry) -> Result<(), String> {
+    tokio::task::spawn_blocking(move || notify(&notifiers, &entry))
+        .await
+        .map_err(|e| e.to_string())?
+}
@@ -0,0 +1,127 @@
+//! Command palette: run actions by typing part of their name, opened with Ctrl+P
+use super::*;
+
+/// Number of matching actions shown
+const SHOWN_ACTIONS: usize = 10;
+
+#[derive(Debug, Clone)]
+pub enum PaletteMessage {
+    SetQuery(String),
+    /// Move the selection up
+    Up,
+    /// Move the selection down
+    Down,
+
+    // Meant for outside
+    /// Run the selected action
+    Submit,
+    /// Run the action with this index in `Palette::actions`
+    Run(usize),
+    Close,
+}
+
+/// Something the palette can do, by sending `message` as if a button was pressed
+#[derive(Debug, Clone)]
+pub struct Action {
+    pub label: String,
+    pub message: Message,
+}
+
+pub struct Palette {
+    pub actions: Vec<Action>,
+    query: String,
+    /// Indices into `actions` that match `query`, best first
+    matches: Vec<usize>,
+    /// Index into `matches`
+    selected: usize,
+
+    s_query: text_input::State,
+    s_actions: Vec<button::State>,
+}
+impl Palette {
+    pub fn new(actions: Vec<Action>) -> Self {
+        let mut palette = Self {
+            actions,
+            query: String::new(),
+            matches: Vec::new(),
+            selected: 0,
+            s_query: text_input::State::focused(),
+            s_actions: Vec::new(),
+        };
+        palette.filter();
+        palette
+    }
+    fn filter(&mut self) {
+        let query = &self.query;
+        let mut matches: Vec<(i64, usize)> = self
+            .actions
+            .iter()
+            .enumerate()
+            .filter_map(|(i, action)| Some((util::fuzzy_score(query, &action.label)?, i)))
+            .collect();
+        // Stable, so that equally good matches keep their order
+        matches.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+        self.matches = matches
+            .into_iter()
+            .map(|(_, i)| i)
+            .take(SHOWN_ACTIONS)
+            .collect();
+        self.selected = 0;
+    }
+    /// The message of the selected action
+    pub fn selected(&self) -> Option<Message> {
+        let i = *self.matches.get(self.selected)?;
+        Some(self.actions[i].message.clone())
+    }
+    pub fn view(&mut self) -> Element<'_, PaletteMessage> {
+        let mut column = Column::new().spacing(4).push(
+            TextInput::new(
+                &mut self.s_query,
+                "Type a command",
+                &self.query,
+                PaletteMessage::SetQuery,
+            )
+            .on_submit(PaletteMessage::Submit)
+            .style(style::TextInput)
+            .padding(8)
+            .size(TEXT_SIZE),
+        );
+        if self.matches.is_empty() {
+            column = column.push(Text::new("No matching command").size(TEXT_SIZE - 2));
+        }
+        let actions = &self.actions;
+        let selected = self.selected;
+        for (n, (&i, state)) in zip_list(&self.matches, &mut self.s_actions).enumerate() {
+            column = column.push(
+                Button::new(state, Text::new(&actions[i].label).size(TEXT_SIZE - 2))
+                    .width(Length::Fill)
+                    .padding(4)
+                    .style(if n == selected {
+                        style::Button::Primary
+                    } else {
+                        style::Button::Text
+                    })
+                    .on_press(PaletteMessage::Run(i)),
+            );
+        }
+        Container::new(column)
+            .style(style::DialogContainer)
+            .width(Length::Fill)
+            .max_width(600)
+            .padding(10)
+            .into()
+    }
+    pub fn update(&mut self, message: PaletteMessage) {
+        match message {
+            PaletteMessage::SetQuery(query) => {
+                self.query = query;
+                self.filter();
+            }
+            PaletteMessage::Up => self.selected = self.selected.saturating_sub(1),
+            PaletteMessage::Down => {
+                self.selected = (self.selected + 1).min(self.matches.len().saturating_sub(1))
+            }
+            PaletteMessage::Submit | PaletteMessage::Run(_) | PaletteMessage::Close => (),
+        }
+    }
+}
@@ -1,6 +1,6 @@
 use crate::style;
-use iced::button;
-use iced::{Button, Command, Element, Length, Row, Text};
+use iced::{button, text_input};
+use iced::{Button, Color, Column, Command, Element, Length, Row, Text, TextInput};
 use nfd::Response;
 use serde::{Deserialize, Serialize};
 use std::io;
@@ -52,15 +52,27 @@ pub async fn open() -> anyhow::Result<PathBuf> {
 
 #[derive(Default, Clone, Serialize, Deserialize, Debug)]
 pub struct FilePicker {
+    /// Typed or pasted path, not yet submitted
+    #[serde(skip)]
+    text: String,
+    #[serde(skip)]
+    error: Option<String>,
     #[serde(skip)]
     s_button: button::State,
+    #[serde(skip)]
+    s_text: text_input::State,
 }
 
 #[derive(Debug, Clone)]
 pub enum Message {
     Error(String),
+    /// The dialog was closed without picking a folder
+    Cancelled,
     Path(PathBuf),
     SelectPath,
+    SetText(String),
+    /// Use the typed path, if it is an existing directory
+    SubmitText,
 }
 impl FilePicker {
     pub fn new() -> Self {
@@ -70,10 +82,35 @@ impl FilePicker {
         match msg {
             Message::SelectPath => Command::perform(open(), |result| match result {
                 Ok(path) => Message::Path(path),
-                Err(e) => Message::Error(e.to_string()),
+                Err(e) => match e.downcast_ref::<io::Error>() {
+                    Some(e) if e.kind() == io::ErrorKind::Interrupted => Message::Cancelled,
+                    _ => Message::Error(e.to_string()),
+                },
             }),
-            Message::Path(_) => Command::none(),
-            _ => Command::none(),
+            Message::Path(_) => {
+                self.error = None;
+                Command::none()
+            }
+            Message::Error(error) => {
+                self.error = Some(error);
+                Command::none()
+            }
+            Message::Cancelled => Command::none(),
+            Message::SetText(text) => {
+                self.text = text;
+                Command::none()
+            }
+            Message::SubmitText => {
+                let path = PathBuf::from(self.text.trim());
+                if path.is_dir() {
+                    self.text.clear();
+                    // Let the parent pick up the path like one from the dialog
+                    Command::perform(async move { path }, Message::Path)
+                } else {
+                    self.error = Some(format!("{} is not an existing folder", path.display()));
+                    Command::none()
+                }
+            }
         }
     }
     pub fn view(&mut self, path: Option<&Path>, text_size: u16) -> Element<Message> {
@@ -81,14 +118,32 @@ impl FilePicker {
             Some(path) => path.display().to_string(),
             None => "Select folder".to_string(),
         };
-        Row::new()
+        let row = Row::new()
             .width(Length::Fill)
+            .spacing(10)
             .push(
                 Button::new(&mut self.s_button, Text::new(text).size(text_size))
                     .padding(0)
                     .style(style::Button::Path)
                     .on_press(Message::SelectPath),
             )
-            .into()
+            .push(
+                TextInput::new(&mut self.s_text, "Paste path", &self.text, Message::SetText)
+                    .on_submit(Message::SubmitText)
+                    .style(style::TextInput)
+                    .size(text_size - 4)
+                    .width(Length::Units(200)),
+            );
+        match self.error {
+            Some(ref error) => Column::new()
+                .push(row)
+                .push(
+                    Text::new(error)
+                        .size(text_size - 4)
+                        .color(Color::from_rgb(0.5, 0.0, 0.0)),
+                )
+                .into(),
+            None => row.into(),
+        }
     }
 }
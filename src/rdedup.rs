@@ -1,8 +1,61 @@
+use crate::{PreviousSnapshot, Target};
 use anyhow::Context;
+use chrono::{DateTime, NaiveDateTime, Utc};
 use rdedup_lib::{settings::Repo as RepoSettings, Repo};
 use slog::Logger;
 use std::path::Path;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, Mutex,
+};
 use url::Url;
+use uuid::Uuid;
+
+/// Snapshots are named `<target name>@<timestamp>`, with the timestamp in this format.
+/// A renamed snapshot has a label appended: `<target name>@<timestamp>+<label>`.
+pub const SNAPSHOT_TIME_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+
+pub fn snapshot_name(target_name: &str, time: DateTime<Utc>) -> String {
+    format!("{}@{}", target_name, time.format(SNAPSHOT_TIME_FORMAT))
+}
+
+/// Inverse of `snapshot_name`, ignoring any label. `None` if the snapshot was not named by bup.
+pub fn parse_snapshot_name(name: &str) -> Option<(&str, DateTime<Utc>)> {
+    let (target_name, rest) = name.rsplit_once('@')?;
+    let time = rest.split('+').next()?;
+    let time = NaiveDateTime::parse_from_str(time, SNAPSHOT_TIME_FORMAT).ok()?;
+    Some((target_name, DateTime::from_utc(time, Utc)))
+}
+
+/// Label of a renamed snapshot
+pub fn snapshot_label(name: &str) -> Option<&str> {
+    let (_, rest) = name.rsplit_once('@')?;
+    rest.split_once('+').map(|(_, label)| label)
+}
+
+/// Name of snapshot `name` with its label replaced by `label`
+pub fn with_label(name: &str, label: &str) -> Option<String> {
+    let (target_name, time) = parse_snapshot_name(name)?;
+    Some(format!("{}+{}", snapshot_name(target_name, time), label))
+}
+
+/// Labels may only contain ASCII letters, digits, `-`, `_` and `.`, so that they can't be confused
+/// with the rest of the name, and are valid file names.
+pub fn verify_label(label: &str) -> Result<(), String> {
+    if label.is_empty() {
+        return Err("Label should not be empty".to_string());
+    }
+    if label.starts_with('.') {
+        return Err("Label should not start with '.'".to_string());
+    }
+    if !label
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+    {
+        return Err("Label may only contain letters, digits, '-', '_' and '.'".to_string());
+    }
+    Ok(())
+}
 
 pub fn init(
     path: &Path,
@@ -16,3 +69,466 @@ pub fn init(
     Repo::init(&url, &move || Ok(passphrase.clone()), settings, log)
         .context("Initialing Rdedup Repo")
 }
+
+/// Open a repo that must already exist, and check that it is readable.
+pub fn open_existing(path: &Path, log: Logger) -> anyhow::Result<Repo> {
+    let url = Url::from_directory_path(path)
+        .ok()
+        .context("RDEDUP_DIR url from path")?;
+    let repo = Repo::open(&url, log)
+        .with_context(|| format!("{} is not an rdedup repo", path.display()))?;
+    repo.list_names().context("Listing snapshots")?;
+    Ok(repo)
+}
+
+/// Why a repo could not be opened
+#[derive(Debug, Clone)]
+pub struct OpenError {
+    pub message: String,
+    /// The home is missing or empty, like a network share or removable disk that is not mounted
+    /// yet. Opening again later may succeed.
+    pub transient: bool,
+}
+
+/// Open the repo at `path` on a blocking thread, and check whether `passphrase` unlocks it
+pub async fn open_async(
+    path: std::path::PathBuf,
+    passphrase: String,
+    log: Logger,
+) -> Result<(Repo, Result<(), String>), OpenError> {
+    tokio::task::spawn_blocking(move || {
+        let repo = open_existing(&path, log).map_err(|e| OpenError {
+            message: format!("{:#}", e),
+            transient: std::fs::read_dir(&path)
+                .map_or(true, |mut entries| entries.next().is_none()),
+        })?;
+        let unlocked = check_passphrase(&repo, passphrase).map_err(|e| format!("{:#}", e));
+        Ok((repo, unlocked))
+    })
+    .await
+    .map_err(|e| OpenError {
+        message: e.to_string(),
+        transient: false,
+    })?
+}
+
+/// One (empty) target per distinct target name found among the snapshots of `repo`.
+/// Snapshots not named by bup are ignored.
+pub fn seed_targets(repo: &Repo, repo_id: Uuid) -> anyhow::Result<Vec<Target>> {
+    let names = repo.list_names().context("Listing snapshots")?;
+    let mut targets: Vec<Target> = Vec::new();
+    for name in &names {
+        if let Some((target_name, _)) = parse_snapshot_name(name) {
+            if !targets.iter().any(|target| target.name == target_name) {
+                targets.push(Target {
+                    id: Uuid::new_v4(),
+                    repo: repo_id,
+                    name: target_name.to_string(),
+                    ..Default::default()
+                });
+            }
+        }
+    }
+    Ok(targets)
+}
+
+/// Check that `passphrase` can decrypt the keys of `repo`
+pub fn check_passphrase(repo: &Repo, passphrase: String) -> anyhow::Result<()> {
+    repo.unlock_decrypt(&move || Ok(passphrase.clone()))
+        .context("Unlocking repo")?;
+    Ok(())
+}
+
+/// `check_passphrase` on a blocking thread, since deriving the key takes a while
+pub async fn check_passphrase_async(repo: Repo, passphrase: String) -> Result<(), String> {
+    tokio::task::spawn_blocking(move || check_passphrase(&repo, passphrase))
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| format!("{:#}", e))
+}
+
+/// Snapshots of the target named `target_name` among `names`, newest first
+pub fn snapshots_of(names: &[String], target_name: &str) -> Vec<PreviousSnapshot> {
+    let mut snapshots: Vec<PreviousSnapshot> = names
+        .iter()
+        .cloned()
+        .filter_map(|name| {
+            let (target, timestamp) = parse_snapshot_name(&name)?;
+            if target != target_name {
+                return None;
+            }
+            Some(PreviousSnapshot {
+                timestamp,
+                label: snapshot_label(&name).map(|label| label.to_string()),
+                name,
+                bytes: None,
+            })
+        })
+        .collect();
+    snapshots.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
+    snapshots
+}
+
+/// Names of all snapshots of `repo`, on a blocking thread
+pub async fn list_names_async(repo: Repo) -> Result<Vec<String>, String> {
+    tokio::task::spawn_blocking(move || repo.list_names().context("Listing snapshots"))
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| format!("{:#}", e))
+}
+
+/// Garbage collect `repo`, honouring the grace period configured for it.
+/// All calls to `Repo::gc` should go through here.
+pub fn gc(repo: &Repo, config: &crate::RepoConfig, log: &Logger) -> anyhow::Result<()> {
+    slog::info!(
+        log,
+        "GC repo {:?} with grace period {}s",
+        config.name,
+        config.gc_grace_secs
+    );
+    repo.gc(config.gc_grace_secs)
+        .context("Garbage collecting Rdedup Repo")
+}
+
+/// Size of the repo before and after a `gc`
+#[derive(Debug, Clone, Copy)]
+pub struct Compacted {
+    pub before: u64,
+    pub after: u64,
+}
+
+/// Progress of a running `compact_async`, shared between its thread and the UI
+#[derive(Debug, Clone, Copy, Default)]
+pub struct GcProgress {
+    /// Size of the repo when the gc started
+    pub before: u64,
+    /// Shrinking of the repo so far, measured every `GC_SAMPLE_INTERVAL`
+    pub reclaimed: u64,
+}
+
+/// How often the size of the repo is measured during a gc. Measuring walks the whole repo, so
+/// not too often.
+const GC_SAMPLE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// `gc` on a blocking thread, measuring the size of the repo dir around it.
+/// rdedup reports no progress during gc, so the size is also measured meanwhile, into `progress`.
+pub async fn compact_async(
+    repo: Repo,
+    config: crate::RepoConfig,
+    progress: Arc<Mutex<GcProgress>>,
+    log: Logger,
+) -> Result<Compacted, String> {
+    tokio::task::spawn_blocking(move || -> anyhow::Result<Compacted> {
+        let before = crate::dir_size(&config.home).context("Measuring repo")?;
+        *progress.lock().unwrap() = GcProgress {
+            before,
+            reclaimed: 0,
+        };
+        let done = Arc::new(AtomicBool::new(false));
+        let sampler = {
+            let (done, progress, home) = (done.clone(), progress.clone(), config.home.clone());
+            std::thread::spawn(move || {
+                while !done.load(Ordering::Relaxed) {
+                    std::thread::park_timeout(GC_SAMPLE_INTERVAL);
+                    // Files vanish while walking; skip such a sample
+                    if let Ok(size) = crate::dir_size(&home) {
+                        progress.lock().unwrap().reclaimed = before.saturating_sub(size);
+                    }
+                }
+            })
+        };
+        let result = gc(&repo, &config, &log);
+        done.store(true, Ordering::Relaxed);
+        sampler.thread().unpark();
+        let _ = sampler.join();
+        result?;
+        let after = crate::dir_size(&config.home).context("Measuring repo")?;
+        progress.lock().unwrap().reclaimed = before.saturating_sub(after);
+        Ok(Compacted { before, after })
+    })
+    .await
+    .map_err(|e| e.to_string())?
+    .map_err(|e| format!("{:#}", e))
+}
+
+/// Data protected by a repo: the size of the newest snapshot of each target, and the size of the
+/// repo on disk
+#[derive(Debug, Clone, Copy)]
+pub struct Usage {
+    pub logical: u64,
+    pub stored: u64,
+}
+
+/// Compute the `Usage` of the repo at `home`. Snapshots not named by bup are not counted.
+pub fn usage(home: &Path, passphrase: String, log: Logger) -> anyhow::Result<Usage> {
+    let repo = open_existing(home, log)?;
+    let decrypt = repo
+        .unlock_decrypt(&move || Ok(passphrase.clone()))
+        .context("Unlocking repo")?;
+    let mut newest: Vec<(&str, DateTime<Utc>, &String)> = Vec::new();
+    let names = repo.list_names().context("Listing snapshots")?;
+    for name in &names {
+        if let Some((target, time)) = parse_snapshot_name(name) {
+            match newest.iter_mut().find(|(t, _, _)| *t == target) {
+                Some(entry) if entry.1 < time => *entry = (target, time, name),
+                Some(_) => (),
+                None => newest.push((target, time, name)),
+            }
+        }
+    }
+    let mut logical = 0;
+    for (_, _, name) in newest {
+        logical += repo
+            .du(name, &decrypt)
+            .with_context(|| format!("Measuring {}", name))?
+            .bytes;
+    }
+    let stored = crate::dir_size(home).context("Measuring repo")?;
+    Ok(Usage { logical, stored })
+}
+
+/// `usage` on a blocking thread. Reads the index of every measured snapshot, so this is slow.
+pub async fn usage_async(
+    home: std::path::PathBuf,
+    passphrase: String,
+    log: Logger,
+) -> Result<Usage, String> {
+    tokio::task::spawn_blocking(move || usage(&home, passphrase, log))
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| format!("{:#}", e))
+}
+
+/// Space on the disk holding a repo
+#[derive(Debug, Clone, Copy)]
+pub struct Space {
+    /// Free space usable by this user
+    pub available: u64,
+    pub total: u64,
+}
+
+/// The `Space` of the disk holding the repo at `home`
+pub fn space(home: &Path) -> anyhow::Result<Space> {
+    Ok(Space {
+        available: fs2::available_space(home)
+            .with_context(|| format!("Measuring free space of {}", home.display()))?,
+        total: fs2::total_space(home)
+            .with_context(|| format!("Measuring size of {}", home.display()))?,
+    })
+}
+
+/// `space` on a blocking thread, since it may hang on network disks
+pub async fn space_async(home: std::path::PathBuf) -> Result<Space, String> {
+    tokio::task::spawn_blocking(move || space(&home))
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| format!("{:#}", e))
+}
+
+/// Whether the repo at `home` compresses its chunks, from its settings file (`config.yml`).
+/// `None` if that can't be told, e.g. while the repo isn't reachable.
+pub fn compresses(home: &Path) -> Option<bool> {
+    let settings = std::fs::read_to_string(home.join("config.yml")).ok()?;
+    compresses_in(&settings)
+}
+
+/// The `compression` setting of a `config.yml` says `none`, as a value or a tag, when the repo
+/// doesn't compress, and names the algorithm otherwise. It may continue on indented lines.
+fn compresses_in(settings: &str) -> Option<bool> {
+    let mut lines = settings
+        .lines()
+        .skip_while(|line| !line.starts_with("compression:"));
+    let first = lines.next()?.trim_start_matches("compression:");
+    let value = std::iter::once(first)
+        .chain(lines.take_while(|line| line.starts_with(' ')))
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase();
+    Some(
+        !value
+            .split(|c: char| !c.is_alphanumeric())
+            .any(|word| word == "none"),
+    )
+}
+
+/// Files and bytes under one part of a repo
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FileStats {
+    pub files: u64,
+    pub bytes: u64,
+}
+
+/// What is on disk in a repo, read without changing it
+#[derive(Debug, Clone)]
+pub struct Inspection {
+    /// Contents of the settings file of the repo (`config.yml`), if there is one
+    pub settings: Option<String>,
+    pub n_names: usize,
+    /// By top-level entry of the repo dir, e.g. generations
+    pub top_level: Vec<(String, FileStats)>,
+    /// By the kind of data: the nearest enclosing `chunk`, `index` or `name` dir
+    pub kinds: Vec<(String, FileStats)>,
+}
+
+/// Walk the repo at `home` for an `Inspection`. Only reads.
+pub fn inspect(home: &Path, log: Logger) -> anyhow::Result<Inspection> {
+    const KINDS: [&str; 3] = ["chunk", "index", "name"];
+    let repo = open_existing(home, log)?;
+    let n_names = repo.list_names().context("Listing snapshots")?.len();
+    let settings = match std::fs::read_to_string(home.join("config.yml")) {
+        Ok(settings) => Some(settings),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(e) => return Err(e).context("Reading config.yml"),
+    };
+    let mut top_level: std::collections::BTreeMap<String, FileStats> = Default::default();
+    let mut kinds: std::collections::BTreeMap<String, FileStats> = Default::default();
+    let mut stack = vec![home.to_path_buf()];
+    while let Some(dir) = stack.pop() {
+        let entries =
+            std::fs::read_dir(&dir).with_context(|| format!("Listing {}", dir.display()))?;
+        for entry in entries {
+            let entry = entry?;
+            let metadata = entry.metadata()?;
+            if metadata.is_dir() {
+                stack.push(entry.path());
+                continue;
+            }
+            let path = entry.path();
+            let relative = path.strip_prefix(home).unwrap_or(&path);
+            let components: Vec<String> = relative
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy().into_owned())
+                .collect();
+            let top = components.first().cloned().unwrap_or_default();
+            // The file itself is not a kind of dir
+            let kind = components[..components.len().saturating_sub(1)]
+                .iter()
+                .rev()
+                .find(|c| KINDS.contains(&c.as_str()))
+                .cloned()
+                .unwrap_or_else(|| "other".to_string());
+            for stats in [
+                top_level.entry(top).or_default(),
+                kinds.entry(kind).or_default(),
+            ] {
+                stats.files += 1;
+                stats.bytes += metadata.len();
+            }
+        }
+    }
+    Ok(Inspection {
+        settings,
+        n_names,
+        top_level: top_level.into_iter().collect(),
+        kinds: kinds.into_iter().collect(),
+    })
+}
+
+pub async fn inspect_async(home: std::path::PathBuf, log: Logger) -> Result<Inspection, String> {
+    tokio::task::spawn_blocking(move || inspect(&home, log))
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| format!("{:#}", e))
+}
+
+/// Remove the snapshots `names` from `repo`, on a blocking thread. Their data is freed by the next
+/// `gc`, so remove many snapshots in one call and gc once afterwards.
+pub async fn remove_async(repo: Repo, names: Vec<String>) -> Result<(), String> {
+    tokio::task::spawn_blocking(move || -> anyhow::Result<()> {
+        for name in &names {
+            repo.rm(name)
+                .with_context(|| format!("Removing {}", name))?;
+        }
+        Ok(())
+    })
+    .await
+    .map_err(|e| e.to_string())?
+    .map_err(|e| format!("{:#}", e))
+}
+
+/// Quote `field` for CSV if needed
+fn csv_field(field: &str) -> String {
+    if field.contains(|c| matches!(c, ',' | '"' | '\n' | '\r')) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Write a list of the snapshots of `repo` (of `config`) as a CSV file into the folder `dest`,
+/// with the size of each, for record keeping. Measuring reads the index of every snapshot, so
+/// this is slow; `progress` counts the measured snapshots. Returns the file written.
+pub fn export_inventory(
+    repo: &Repo,
+    config: &crate::RepoConfig,
+    dest: &Path,
+    passphrase: String,
+    progress: Arc<Mutex<crate::backup::RestoreProgress>>,
+    cancel: Arc<AtomicBool>,
+) -> anyhow::Result<std::path::PathBuf> {
+    let decrypt = repo
+        .unlock_decrypt(&move || Ok(passphrase.clone()))
+        .context("Unlocking repo")?;
+    let mut names = repo.list_names().context("Listing snapshots")?;
+    names.sort();
+    progress.lock().unwrap().total_files = Some(names.len());
+    let mut csv = String::from("name,target,group,time,label,bytes,pinned\n");
+    for name in &names {
+        if cancel.load(Ordering::Relaxed) {
+            anyhow::bail!("Cancelled");
+        }
+        progress.lock().unwrap().current_file = name.clone();
+        let bytes = repo
+            .du(name, &decrypt)
+            .with_context(|| format!("Measuring {}", name))?
+            .bytes;
+        // Snapshots not named by bup are listed with their name only
+        let parsed = parse_snapshot_name(name);
+        let target = parsed.map_or("", |(target, _)| target);
+        let group = config
+            .targets
+            .iter()
+            .find(|t| t.name == target)
+            .and_then(|t| t.group.as_deref())
+            .unwrap_or("");
+        let fields = [
+            name.clone(),
+            target.to_string(),
+            group.to_string(),
+            parsed.map_or(String::new(), |(_, time)| time.to_rfc3339()),
+            snapshot_label(name).unwrap_or("").to_string(),
+            bytes.to_string(),
+            config.pinned_snapshots.contains(name).to_string(),
+        ];
+        let fields: Vec<String> = fields.iter().map(|field| csv_field(field)).collect();
+        csv.push_str(&fields.join(","));
+        csv.push('\n');
+        let mut progress = progress.lock().unwrap();
+        progress.files += 1;
+        progress.bytes += bytes;
+    }
+    let file = dest.join(format!(
+        "{}-snapshots-{}.csv",
+        config.name,
+        Utc::now().format("%Y%m%d")
+    ));
+    std::fs::write(&file, csv).with_context(|| format!("Writing {}", file.display()))?;
+    Ok(file)
+}
+
+/// `export_inventory` on a blocking thread
+pub async fn export_inventory_async(
+    repo: Repo,
+    config: crate::RepoConfig,
+    dest: std::path::PathBuf,
+    passphrase: String,
+    progress: Arc<Mutex<crate::backup::RestoreProgress>>,
+    cancel: Arc<AtomicBool>,
+) -> Result<std::path::PathBuf, String> {
+    tokio::task::spawn_blocking(move || {
+        export_inventory(&repo, &config, &dest, passphrase, progress, cancel)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+    .map_err(|e| format!("{:#}", e))
+}
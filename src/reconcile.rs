@@ -0,0 +1,284 @@
+//! Compare the targets of the selected repo with the snapshots in it. Snapshots are grouped by the
+//! target name encoded in their names (see `rdedup::parse_snapshot_name`).
+use super::*;
+
+#[derive(Debug, Clone)]
+pub enum ReconcileMessage {
+    Listed(Result<Vec<String>, String>),
+    /// Ask to confirm deleting the snapshots of the orphan with this index
+    AskDelete(usize),
+    CancelDelete,
+
+    // Meant for outside
+    /// Add a target with this name to the config
+    Recreate(String),
+    /// Delete these snapshots (confirmed)
+    Delete(Vec<String>),
+    /// The snapshots deleted from the repo with this id
+    Deleted(Result<(Uuid, Vec<String>), String>),
+    Back,
+}
+
+/// Snapshots of a target name that has no target in the config
+pub struct Orphan {
+    pub target_name: String,
+    pub snapshots: Vec<String>,
+    pub newest: DateTime<Utc>,
+
+    s_recreate: button::State,
+    s_delete: button::State,
+}
+
+#[derive(Default)]
+pub struct Reconcile {
+    /// All snapshot names of the repo, `None` while listing
+    names: Option<Result<Vec<String>, String>>,
+    orphans: Vec<Orphan>,
+    /// Targets in the config without any snapshot
+    unused: Vec<String>,
+    /// Number of snapshots not named by bup
+    foreign: usize,
+    /// Index of the orphan whose snapshots are about to be deleted
+    confirm_delete: Option<usize>,
+    deleting: bool,
+    error: Option<String>,
+
+    s_confirm: button::State,
+    s_cancel: button::State,
+    s_back: button::State,
+    s_scrollable: scrollable::State,
+}
+
+impl Reconcile {
+    /// Regroup the listed snapshots, after they or the targets of `repo` changed
+    pub fn rebuild(&mut self, repo: &RepoConfig) {
+        self.orphans.clear();
+        self.unused.clear();
+        self.foreign = 0;
+        self.confirm_delete = None;
+        let names = match self.names {
+            Some(Ok(ref names)) => names,
+            _ => return,
+        };
+        for name in names {
+            let (target_name, time) = match rdedup::parse_snapshot_name(name) {
+                Some(parsed) => parsed,
+                None => {
+                    self.foreign += 1;
+                    continue;
+                }
+            };
+            if repo.targets.iter().any(|target| target.name == target_name) {
+                continue;
+            }
+            match self
+                .orphans
+                .iter_mut()
+                .find(|orphan| orphan.target_name == target_name)
+            {
+                Some(orphan) => {
+                    orphan.snapshots.push(name.clone());
+                    orphan.newest = orphan.newest.max(time);
+                }
+                None => self.orphans.push(Orphan {
+                    target_name: target_name.to_string(),
+                    snapshots: vec![name.clone()],
+                    newest: time,
+                    s_recreate: Default::default(),
+                    s_delete: Default::default(),
+                }),
+            }
+        }
+        self.orphans
+            .sort_by(|a, b| a.target_name.cmp(&b.target_name));
+        self.unused = repo
+            .targets
+            .iter()
+            .filter(|target| {
+                !names.iter().any(|name| {
+                    rdedup::parse_snapshot_name(name).map(|(target_name, _)| target_name)
+                        == Some(target.name.as_str())
+                })
+            })
+            .map(|target| target.name.clone())
+            .collect();
+    }
+    pub fn update(&mut self, msg: ReconcileMessage, repo: Option<&RepoConfig>) {
+        match msg {
+            ReconcileMessage::Listed(result) => {
+                self.deleting = false;
+                if let Err(ref e) = result {
+                    self.error = Some(e.clone());
+                }
+                self.names = Some(result);
+                if let Some(repo) = repo {
+                    self.rebuild(repo);
+                }
+            }
+            ReconcileMessage::AskDelete(i) => self.confirm_delete = Some(i),
+            ReconcileMessage::CancelDelete => self.confirm_delete = None,
+            ReconcileMessage::Recreate(_) => {
+                if let Some(repo) = repo {
+                    self.rebuild(repo);
+                }
+            }
+            ReconcileMessage::Delete(_) => {
+                self.confirm_delete = None;
+                self.deleting = true;
+                self.error = None;
+            }
+            ReconcileMessage::Deleted(result) => {
+                if let Err(e) = result {
+                    self.deleting = false;
+                    self.error = Some(e);
+                }
+                // Otherwise the snapshots are listed again
+            }
+            ReconcileMessage::Back => (),
+        }
+    }
+    /// `read_only`: `RepoConfig::read_only` of the repo; disables deleting
+    pub fn view(&mut self, read_only: bool) -> Element<'_, ReconcileMessage> {
+        let mut column = Column::new()
+            .padding(20)
+            .spacing(20)
+            .push(h3("Targets and snapshots"))
+            .push(
+                Text::new(
+                    "Snapshots are named after their target. Renaming a target or editing the \
+                     config by hand leaves snapshots without a target.",
+                )
+                .size(TEXT_SIZE - 4),
+            );
+        match self.names {
+            None => column = column.push(Text::new("Listing snapshots...")),
+            Some(Err(_)) => (),
+            Some(Ok(_)) => {
+                let mut orphans = Column::new()
+                    .spacing(8)
+                    .push(h3("Snapshots without target"));
+                if self.orphans.is_empty() {
+                    orphans = orphans.push(Text::new("None").size(TEXT_SIZE - 4));
+                }
+                let confirm_delete = self.confirm_delete;
+                let deleting = self.deleting;
+                // Only one orphan asks for confirmation at a time
+                let mut s_confirm = Some((&mut self.s_cancel, &mut self.s_confirm));
+                for (i, orphan) in self.orphans.iter_mut().enumerate() {
+                    let mut row = Row::new()
+                        .spacing(10)
+                        .push(
+                            Text::new(format!(
+                                "{} - {} snapshots, newest {}",
+                                orphan.target_name,
+                                orphan.snapshots.len(),
+                                orphan.newest.format("%Y-%m-%d %H:%M")
+                            ))
+                            .width(Length::Fill),
+                        )
+                        .push(
+                            Button::new(
+                                &mut orphan.s_recreate,
+                                Text::new("RECREATE TARGET").size(TEXT_SIZE - 4),
+                            )
+                            .padding(4)
+                            .style(style::Button::Text)
+                            .on_press(ReconcileMessage::Recreate(orphan.target_name.clone())),
+                        );
+                    if confirm_delete != Some(i) {
+                        let mut delete = Button::new(
+                            &mut orphan.s_delete,
+                            Text::new("DELETE SNAPSHOTS").size(TEXT_SIZE - 4),
+                        )
+                        .padding(4)
+                        .style(style::Button::Text);
+                        if read_only {
+                            row = row.push(with_tooltip(delete, "The repo is read-only"));
+                        } else {
+                            if !deleting {
+                                delete = delete.on_press(ReconcileMessage::AskDelete(i));
+                            }
+                            row = row.push(delete);
+                        }
+                    }
+                    orphans = orphans.push(row);
+                    let confirm = if confirm_delete == Some(i) {
+                        s_confirm.take()
+                    } else {
+                        None
+                    };
+                    if let Some((s_cancel, s_confirm)) = confirm {
+                        orphans = orphans.push(
+                            Row::new()
+                                .spacing(10)
+                                .push(
+                                    Text::new(format!(
+                                        "Delete all {} snapshots of {}? This can't be undone.",
+                                        orphan.snapshots.len(),
+                                        orphan.target_name
+                                    ))
+                                    .color(Color::from_rgb(0.9, 0.6, 0.2))
+                                    .width(Length::Fill),
+                                )
+                                .push(
+                                    Button::new(s_cancel, Text::new("CANCEL").size(TEXT_SIZE - 4))
+                                        .padding(4)
+                                        .style(style::Button::Text)
+                                        .on_press(ReconcileMessage::CancelDelete),
+                                )
+                                .push(
+                                    Button::new(s_confirm, Text::new("DELETE").size(TEXT_SIZE - 4))
+                                        .padding(4)
+                                        .style(style::Button::Primary)
+                                        .on_press(ReconcileMessage::Delete(
+                                            orphan.snapshots.clone(),
+                                        )),
+                                ),
+                        );
+                    }
+                }
+                if self.deleting {
+                    orphans = orphans.push(Text::new("Deleting...").size(TEXT_SIZE - 4));
+                }
+                column = column.push(orphans);
+
+                let mut unused = Column::new()
+                    .spacing(4)
+                    .push(h3("Targets without snapshots"));
+                if self.unused.is_empty() {
+                    unused = unused.push(Text::new("None").size(TEXT_SIZE - 4));
+                }
+                for name in &self.unused {
+                    unused = unused.push(Text::new(name.as_str()).size(TEXT_SIZE - 4));
+                }
+                column = column.push(unused);
+                if self.foreign > 0 {
+                    column = column.push(
+                        Text::new(format!(
+                            "{} snapshots were not made by bup and are left alone.",
+                            self.foreign
+                        ))
+                        .size(TEXT_SIZE - 4),
+                    );
+                }
+            }
+        }
+        if let Some(ref error) = self.error {
+            column = column.push(Text::new(error.as_str()).color(Color::from_rgb(0.5, 0.0, 0.0)));
+        }
+        column = column.push(
+            Button::new(&mut self.s_back, Text::new("BACK").size(TEXT_SIZE - 4))
+                .padding(8)
+                .style(style::Button::Text)
+                .on_press(ReconcileMessage::Back),
+        );
+        Scrollable::new(&mut self.s_scrollable)
+            .push(
+                Container::new(column)
+                    .style(style::DialogContainer)
+                    .width(Length::Fill)
+                    .max_width(1000),
+            )
+            .into()
+    }
+}
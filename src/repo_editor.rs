@@ -0,0 +1,626 @@
+use super::*;
+
+#[derive(Debug, Clone)]
+pub enum RepoEditorMessage {
+    SetName(String),
+    SetDescription(String),
+    Home(path::Message),
+    SetGcGrace(String),
+    SetChecksums(bool),
+    SetReadOnly(bool),
+    SetVerifyAfterBackup(bool),
+    SetLowSpace(String),
+    SetCriticalSpace(String),
+    NewExclude,
+    SetExclude(usize, String),
+    DelExclude(usize),
+    NewWebhook,
+    NewEmail,
+    SetNotifier(usize, notify::Field, String),
+    DelNotifier(usize),
+    SetNotifyOnSuccess(bool),
+    MoveTo(path::Message),
+    /// Switch to `RepoEditorMode::Import`, when the chosen home already holds a repo
+    ImportInstead,
+
+    // Meant for outside
+    /// Save button pressed
+    Save,
+    /// Cancel button pressed
+    Cancel,
+    /// Copy the repo to `RepoEditor::move_to` and use it from there
+    Move,
+    /// Delete the old home after a move
+    DeleteOldHome,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RepoEditorMode {
+    /// Initialize a new rdedup repo
+    Create,
+    /// Register an rdedup repo that already exists on disk (e.g. created by the `rdedup` CLI)
+    Import,
+    /// Edit a repo that is already in the config
+    Edit,
+}
+impl Default for RepoEditorMode {
+    fn default() -> Self {
+        RepoEditorMode::Create
+    }
+}
+
+#[derive(Default)]
+pub struct RepoEditor {
+    pub repo: RepoConfig,
+    pub mode: RepoEditorMode,
+    /// Text of the grace period input. Parsed into `repo.gc_grace_secs` by `finish`.
+    pub gc_grace: String,
+    /// Texts of the free space thresholds in GiB, parsed like `gc_grace`
+    pub low_space: String,
+    pub critical_space: String,
+    pub error: Option<String>,
+    /// Set when the home of a new repo turns out to hold an rdedup repo already
+    pub offer_import: bool,
+    /// New home to move the repo to, in `Edit` mode (see `duplicate::relocate`)
+    pub move_to: Option<PathBuf>,
+    /// `Some(None)` while moving. After a move, holds the old home, which is left as it was.
+    pub relocation: Option<Option<Result<PathBuf, String>>>,
+    pub old_deleted: Option<Result<(), String>>,
+
+    s_name: text_input::State,
+    s_description: text_input::State,
+    s_home: FilePicker,
+    s_move_to: FilePicker,
+    s_move: button::State,
+    s_delete_old: button::State,
+    s_gc_grace: text_input::State,
+    s_low_space: text_input::State,
+    s_critical_space: text_input::State,
+    s_new_exclude: button::State,
+    /// One per exclude in `repo.repo_excludes`
+    s_excludes: Vec<(text_input::State, button::State)>,
+    s_new_webhook: button::State,
+    s_new_email: button::State,
+    /// One per notifier in `repo.notifiers`: the inputs of its fields, and its delete button
+    s_notifiers: Vec<([text_input::State; 5], button::State)>,
+    s_save_button: button::State,
+    s_cancel_button: button::State,
+    s_import_instead: button::State,
+}
+impl RepoEditor {
+    /// `mode` should be `Create` or `Import`
+    pub fn new_repo(mode: RepoEditorMode) -> Self {
+        Self::new(
+            RepoConfig {
+                id: Uuid::new_v4(),
+                gc_grace_secs: DEFAULT_GC_GRACE_SECS,
+                low_space_bytes: DEFAULT_LOW_SPACE_BYTES,
+                critical_space_bytes: DEFAULT_CRITICAL_SPACE_BYTES,
+                read_threads: DEFAULT_READ_THREADS,
+                ..Default::default()
+            },
+            mode,
+        )
+    }
+    pub fn with_repo(repo: RepoConfig) -> Self {
+        Self::new(repo, RepoEditorMode::Edit)
+    }
+    fn new(repo: RepoConfig, mode: RepoEditorMode) -> Self {
+        Self {
+            gc_grace: repo.gc_grace_secs.to_string(),
+            low_space: format_gib(repo.low_space_bytes),
+            critical_space: format_gib(repo.critical_space_bytes),
+            repo,
+            mode,
+            ..Default::default()
+        }
+    }
+    /// Apply the text inputs to `repo` and verify the result
+    pub fn finish(&mut self) -> Result<RepoConfig, BupError> {
+        self.repo.gc_grace_secs = parse_gc_grace(&self.gc_grace).map_err(BupError::InvalidRepo)?;
+        self.repo.low_space_bytes =
+            parse_gib(&self.low_space, "Low space warning").map_err(BupError::InvalidRepo)?;
+        self.repo.critical_space_bytes =
+            parse_gib(&self.critical_space, "Refusing backups").map_err(BupError::InvalidRepo)?;
+        verify_repo(&self.repo)?;
+        Ok(self.repo.clone())
+    }
+    pub fn view(&mut self) -> Element<'_, RepoEditorMessage> {
+        let home = if self.repo.home.as_os_str().is_empty() {
+            None
+        } else {
+            Some(self.repo.home.as_path())
+        };
+        let home: Element<_> = if self.mode != RepoEditorMode::Edit {
+            self.s_home
+                .view(home, TEXT_SIZE)
+                .map(RepoEditorMessage::Home)
+        } else {
+            // The home of an existing repo cannot be changed
+            Text::new(self.repo.home.display().to_string())
+                .size(TEXT_SIZE)
+                .into()
+        };
+        let mut buttons = Row::new()
+            .spacing(10)
+            .push(
+                Button::new(
+                    &mut self.s_cancel_button,
+                    Text::new("CANCEL").size(TEXT_SIZE - 4),
+                )
+                .padding(8)
+                .style(style::Button::Text)
+                .on_press(RepoEditorMessage::Cancel),
+            )
+            .push(
+                Button::new(
+                    &mut self.s_save_button,
+                    Text::new("SAVE").size(TEXT_SIZE - 4),
+                )
+                .padding(8)
+                .style(style::Button::Primary)
+                .on_press(RepoEditorMessage::Save),
+            );
+        if let Some(ref error) = self.error {
+            buttons = buttons
+                .push(Text::new(format!("Error: {}", error)).color(Color::from_rgb(0.5, 0.0, 0.0)));
+        }
+        if self.offer_import {
+            buttons = buttons.push(
+                Button::new(
+                    &mut self.s_import_instead,
+                    Text::new("IMPORT INSTEAD").size(TEXT_SIZE - 4),
+                )
+                .padding(8)
+                .style(style::Button::Primary)
+                .on_press(RepoEditorMessage::ImportInstead),
+            );
+        }
+        let relocation = if self.mode == RepoEditorMode::Edit {
+            Some(view_relocation(
+                self.move_to.as_deref(),
+                &self.relocation,
+                &self.old_deleted,
+                &mut self.s_move_to,
+                &mut self.s_move,
+                &mut self.s_delete_old,
+            ))
+        } else {
+            None
+        };
+        let x = Column::new()
+            .padding(20)
+            .spacing(20)
+            .push(h3(match self.mode {
+                RepoEditorMode::Create => "New repo",
+                RepoEditorMode::Import => "Import existing repo",
+                RepoEditorMode::Edit => "Edit repo",
+            }))
+            .push(
+                Row::new().spacing(8).push(Icon::Repo.h3()).push(
+                    TextInput::new(
+                        &mut self.s_name,
+                        "Repo name",
+                        &self.repo.name,
+                        RepoEditorMessage::SetName,
+                    )
+                    .style(style::TextInput)
+                    .size(H3_SIZE),
+                ),
+            )
+            .push(
+                TextInput::new(
+                    &mut self.s_description,
+                    "Description, e.g. what the repo is for",
+                    &self.repo.description,
+                    RepoEditorMessage::SetDescription,
+                )
+                .style(style::TextInput)
+                .size(TEXT_SIZE),
+            )
+            .push(
+                Row::new()
+                    .spacing(8)
+                    .push(Text::new("RDEDUP_HOME:"))
+                    .push(home),
+            )
+            .push(match relocation {
+                Some(relocation) => relocation,
+                None => Column::new().into(),
+            })
+            .push(
+                Row::new()
+                    .spacing(8)
+                    .push(Text::new("Excluded in all targets:"))
+                    .push(
+                        Button::new(&mut self.s_new_exclude, Icon::New.text())
+                            .style(style::Button::Icon {
+                                hover_color: Color::WHITE,
+                            })
+                            .padding(BUTTON_PAD)
+                            .on_press(RepoEditorMessage::NewExclude),
+                    )
+                    .push(help(
+                        "Patterns like the excludes of a target, applied to every target of this \
+                         repo, e.g. lost+found or .Trash.",
+                    )),
+            )
+            .push(zip_list(&self.repo.repo_excludes, &mut self.s_excludes).enumerate().fold(
+                Column::new().spacing(4),
+                |column, (i, (exclude, (s_exclude, s_delete)))| {
+                    column.push(
+                        Row::new()
+                            .push(
+                                TextInput::new(s_exclude, "Exclude string", exclude, move |s| {
+                                    RepoEditorMessage::SetExclude(i, s)
+                                })
+                                .style(style::TextInput)
+                                .size(TEXT_SIZE),
+                            )
+                            .push(
+                                Button::new(s_delete, Icon::Delete.text())
+                                    .on_press(RepoEditorMessage::DelExclude(i))
+                                    .padding(0)
+                                    .style(style::Button::Icon {
+                                        hover_color: Color::from_rgb(0.7, 0.2, 0.2),
+                                    }),
+                            ),
+                    )
+                },
+            ))
+            .push(
+                Row::new()
+                    .spacing(8)
+                    .push(Text::new("Notify:"))
+                    .push(
+                        Button::new(&mut self.s_new_webhook, Text::new("+ WEBHOOK").size(TEXT_SIZE - 4))
+                            .padding(4)
+                            .style(style::Button::Text)
+                            .on_press(RepoEditorMessage::NewWebhook),
+                    )
+                    .push(
+                        Button::new(&mut self.s_new_email, Text::new("+ EMAIL").size(TEXT_SIZE - 4))
+                            .padding(4)
+                            .style(style::Button::Text)
+                            .on_press(RepoEditorMessage::NewEmail),
+                    )
+                    .push(help(
+                        "Report failed backups, also those of system timers. A webhook gets a JSON \
+                         POST like a line of history.jsonl. Emails are sent via SMTP with the \
+                         password taken from an environment variable, so that it is not stored. \
+                         Both need curl.",
+                    )),
+            )
+            .push(zip_list(&self.repo.notifiers, &mut self.s_notifiers).enumerate().fold(
+                Column::new().spacing(4),
+                |column, (i, (notifier, (s_fields, s_delete)))| {
+                    column.push(view_notifier(i, notifier, s_fields, s_delete))
+                },
+            ))
+            .push(
+                Checkbox::new(
+                    self.repo.notify_on_success,
+                    "Also notify about backups and duplications that succeeded",
+                    RepoEditorMessage::SetNotifyOnSuccess,
+                )
+                .size(TEXT_SIZE)
+                .text_size(TEXT_SIZE),
+            )
+            // Advanced settings
+            .push(h3("Advanced"))
+            .push(
+                Row::new()
+                    .spacing(8)
+                    .push(Text::new("GC grace period (seconds):"))
+                    .push(
+                        TextInput::new(
+                            &mut self.s_gc_grace,
+                            "Seconds",
+                            &self.gc_grace,
+                            RepoEditorMessage::SetGcGrace,
+                        )
+                        .style(style::TextInput)
+                        .size(TEXT_SIZE),
+                    )
+                    .push(help(
+                        "Chunks no longer used by any snapshot are only deleted by a compaction \
+                         once they have been unused this long. Protects backups that are \
+                         running while compacting.",
+                    )),
+            )
+            .push(
+                Row::new()
+                    .spacing(8)
+                    .push(
+                        Checkbox::new(
+                            self.repo.checksums,
+                            "Store checksums of new snapshots, to verify them later (slower backups)",
+                            RepoEditorMessage::SetChecksums,
+                        )
+                        .size(TEXT_SIZE)
+                        .text_size(TEXT_SIZE),
+                    )
+                    .push(help(
+                        "Each backup also computes a SHA-256 of the archive. VERIFY in the \
+                         snapshot list always checks the chunks of the snapshot, and with a \
+                         checksum also reads the snapshot back and compares it.",
+                    )),
+            )
+            .push(
+                Row::new()
+                    .spacing(8)
+                    .push(
+                        Checkbox::new(
+                            self.repo.verify_after_backup,
+                            "Read each new snapshot back after the backup (slower backups)",
+                            RepoEditorMessage::SetVerifyAfterBackup,
+                        )
+                        .size(TEXT_SIZE)
+                        .text_size(TEXT_SIZE),
+                    )
+                    .push(help(
+                        "Right after writing a snapshot, decrypts all its chunks and reads the \
+                         archive through tar, like a test restore. A snapshot that fails is \
+                         kept, marked in the snapshot list, and the backup counts as failed; \
+                         the earlier snapshots are untouched.",
+                    )),
+            )
+            .push(
+                Row::new()
+                    .spacing(8)
+                    .push(
+                        Checkbox::new(
+                            self.repo.read_only,
+                            "Read-only",
+                            RepoEditorMessage::SetReadOnly,
+                        )
+                        .size(TEXT_SIZE)
+                        .text_size(TEXT_SIZE),
+                    )
+                    .push(help(
+                        "Never write to this repo, e.g. for an offsite copy mounted read-only. \
+                         Snapshots can still be browsed, restored and verified, but backups, \
+                         duplications, deleting snapshots and compacting are turned off, also \
+                         for system timers.",
+                    )),
+            )
+            .push(
+                Row::new()
+                    .spacing(8)
+                    .push(Text::new("Warn below free space (GiB):"))
+                    .push(
+                        TextInput::new(
+                            &mut self.s_low_space,
+                            "GiB",
+                            &self.low_space,
+                            RepoEditorMessage::SetLowSpace,
+                        )
+                        .style(style::TextInput)
+                        .size(TEXT_SIZE),
+                    )
+                    .push(Text::new("Refuse backups below (GiB):"))
+                    .push(
+                        TextInput::new(
+                            &mut self.s_critical_space,
+                            "GiB",
+                            &self.critical_space,
+                            RepoEditorMessage::SetCriticalSpace,
+                        )
+                        .style(style::TextInput)
+                        .size(TEXT_SIZE),
+                    )
+                    .push(help(
+                        "Free space on the disk of RDEDUP_HOME. The Overview warns below the first, \
+                         and backups fail right away below the second instead of filling the disk \
+                         with a partial snapshot. 0 turns either off.",
+                    )),
+            )
+            .push(Container::new(buttons).width(Length::Fill));
+        Container::new(x)
+            .style(style::DialogContainer)
+            .width(Length::Fill)
+            .max_width(1000)
+            .height(Length::Shrink)
+            .into()
+    }
+    pub fn update(&mut self, message: RepoEditorMessage) -> Command<RepoEditorMessage> {
+        match message {
+            RepoEditorMessage::SetName(name) => self.repo.name = name,
+            RepoEditorMessage::SetDescription(description) => self.repo.description = description,
+            RepoEditorMessage::Home(msg) => {
+                if let path::Message::Path(ref path) = msg {
+                    self.repo.home = path.clone();
+                    self.offer_import = false;
+                }
+                return self.s_home.update(msg).map(RepoEditorMessage::Home);
+            }
+            RepoEditorMessage::SetGcGrace(grace) => self.gc_grace = grace,
+            RepoEditorMessage::SetChecksums(checksums) => self.repo.checksums = checksums,
+            RepoEditorMessage::SetReadOnly(read_only) => self.repo.read_only = read_only,
+            RepoEditorMessage::SetVerifyAfterBackup(verify) => {
+                self.repo.verify_after_backup = verify
+            }
+            RepoEditorMessage::SetLowSpace(text) => self.low_space = text,
+            RepoEditorMessage::SetCriticalSpace(text) => self.critical_space = text,
+            RepoEditorMessage::NewExclude => self.repo.repo_excludes.push(String::new()),
+            RepoEditorMessage::SetExclude(i, exclude) => self.repo.repo_excludes[i] = exclude,
+            RepoEditorMessage::DelExclude(i) => {
+                self.repo.repo_excludes.remove(i);
+                self.s_excludes.remove(i);
+            }
+            RepoEditorMessage::NewWebhook => self
+                .repo
+                .notifiers
+                .push(notify::Notifier::Webhook { url: String::new() }),
+            RepoEditorMessage::NewEmail => self.repo.notifiers.push(notify::Notifier::email()),
+            RepoEditorMessage::SetNotifier(i, field, value) => {
+                self.repo.notifiers[i].set(field, value)
+            }
+            RepoEditorMessage::DelNotifier(i) => {
+                self.repo.notifiers.remove(i);
+                self.s_notifiers.remove(i);
+            }
+            RepoEditorMessage::SetNotifyOnSuccess(on_success) => {
+                self.repo.notify_on_success = on_success
+            }
+            RepoEditorMessage::MoveTo(msg) => {
+                if let path::Message::Path(ref path) = msg {
+                    self.move_to = Some(path.clone());
+                }
+                return self.s_move_to.update(msg).map(RepoEditorMessage::MoveTo);
+            }
+            RepoEditorMessage::ImportInstead => {
+                self.mode = RepoEditorMode::Import;
+                self.offer_import = false;
+                self.error = None;
+            }
+            RepoEditorMessage::Move => (),
+            RepoEditorMessage::DeleteOldHome => (),
+            RepoEditorMessage::Save => (),
+            RepoEditorMessage::Cancel => (),
+        }
+        Command::none()
+    }
+}
+
+/// Moving the repo to a new home, with the outcome of the last move
+fn view_relocation<'a>(
+    move_to: Option<&Path>,
+    relocation: &Option<Option<Result<PathBuf, String>>>,
+    old_deleted: &Option<Result<(), String>>,
+    s_move_to: &'a mut FilePicker,
+    s_move: &'a mut button::State,
+    s_delete_old: &'a mut button::State,
+) -> Element<'a, RepoEditorMessage> {
+    let mut move_button = Button::new(s_move, Text::new("MOVE").size(TEXT_SIZE - 4))
+        .padding(4)
+        .style(style::Button::Text);
+    if move_to.is_some() && !matches!(relocation, Some(None)) {
+        move_button = move_button.on_press(RepoEditorMessage::Move);
+    }
+    let mut column = Column::new().spacing(8).push(
+        Row::new()
+            .spacing(8)
+            .push(Text::new("Move to:"))
+            .push(
+                s_move_to
+                    .view(move_to, TEXT_SIZE)
+                    .map(RepoEditorMessage::MoveTo),
+            )
+            .push(move_button)
+            .push(help(
+                "Copies the repo to a new, empty folder, e.g. on a bigger disk, checks the \
+                 copy, and uses it from then on. The old folder is kept until you delete it. \
+                 Unlike a duplication, this happens once.",
+            )),
+    );
+    match relocation {
+        Some(None) => column = column.push(Text::new("Moving...").size(TEXT_SIZE - 4)),
+        Some(Some(Ok(old_home))) => {
+            column = column.push(
+                Text::new(format!(
+                    "Moved. The old copy is still at {}.",
+                    old_home.display()
+                ))
+                .size(TEXT_SIZE - 4)
+                .color(Color::from_rgb(0.0, 0.5, 0.0)),
+            );
+            column = match old_deleted {
+                Some(Ok(())) => column.push(Text::new("Deleted the old copy").size(TEXT_SIZE - 4)),
+                Some(Err(e)) => column.push(
+                    Text::new(e.as_str())
+                        .size(TEXT_SIZE - 4)
+                        .color(Color::from_rgb(0.8, 0.0, 0.0)),
+                ),
+                None => column.push(
+                    Button::new(
+                        s_delete_old,
+                        Text::new("DELETE OLD COPY").size(TEXT_SIZE - 4),
+                    )
+                    .padding(4)
+                    .style(style::Button::Text)
+                    .on_press(RepoEditorMessage::DeleteOldHome),
+                ),
+            };
+        }
+        Some(Some(Err(e))) => {
+            column = column.push(
+                Text::new(format!("Moving failed: {}", e))
+                    .size(TEXT_SIZE - 4)
+                    .color(Color::from_rgb(0.8, 0.0, 0.0)),
+            )
+        }
+        None => (),
+    }
+    column.into()
+}
+
+/// The inputs of one notifier, in a row
+fn view_notifier<'a>(
+    i: usize,
+    notifier: &notify::Notifier,
+    s_fields: &'a mut [text_input::State; 5],
+    s_delete: &'a mut button::State,
+) -> Element<'a, RepoEditorMessage> {
+    use notify::{Field, Notifier};
+    let fields: Vec<(Field, &str, &str)> = match notifier {
+        Notifier::Webhook { url } => vec![(Field::Url, "Webhook URL (https://...)", url)],
+        Notifier::Email {
+            smtp_url,
+            from,
+            to,
+            user,
+            password_var,
+        } => vec![
+            (Field::Url, "SMTP URL (smtps://host:465)", smtp_url),
+            (Field::From, "From", from),
+            (Field::To, "To", to),
+            (Field::User, "SMTP user", user),
+            (Field::PasswordVar, "Password env var", password_var),
+        ],
+    };
+    let row = fields.into_iter().zip(s_fields.iter_mut()).fold(
+        Row::new().spacing(4),
+        |row, ((field, placeholder, value), state)| {
+            row.push(
+                TextInput::new(state, placeholder, value, move |value| {
+                    RepoEditorMessage::SetNotifier(i, field, value)
+                })
+                .style(style::TextInput)
+                .size(TEXT_SIZE - 2),
+            )
+        },
+    );
+    row.push(
+        Button::new(s_delete, Icon::Delete.text())
+            .on_press(RepoEditorMessage::DelNotifier(i))
+            .padding(0)
+            .style(style::Button::Icon {
+                hover_color: Color::from_rgb(0.7, 0.2, 0.2),
+            }),
+    )
+    .into()
+}
+
+fn parse_gc_grace(grace: &str) -> Result<u64, String> {
+    let grace: i64 = grace
+        .trim()
+        .parse()
+        .map_err(|_| "GC grace period should be a whole number of seconds".to_string())?;
+    if grace < 0 {
+        return Err("GC grace period should not be negative".to_string());
+    }
+    Ok(grace as u64)
+}
+
+const GIB: f64 = (1u64 << 30) as f64;
+fn format_gib(bytes: u64) -> String {
+    (bytes as f64 / GIB).to_string()
+}
+/// Parse the GiB of the input named `what` into bytes
+fn parse_gib(text: &str, what: &str) -> Result<u64, String> {
+    match text.trim().parse::<f64>() {
+        Ok(gib) if gib >= 0.0 => Ok((gib * GIB) as u64),
+        _ => Err(format!("{} should be a number of GiB", what)),
+    }
+}
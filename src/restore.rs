@@ -0,0 +1,776 @@
+use super::*;
+use backup::{Collision, RestoreProgress, RestoreSelection};
+use std::collections::BTreeSet;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, Mutex,
+};
+
+/// Entries shown of a folder while choosing files
+const MAX_ENTRIES: usize = 500;
+
+#[derive(Debug, Clone)]
+pub enum RestoreMessage {
+    Dest(path::Message),
+    /// Refresh the shown progress of a running restore
+    Progress,
+    Finished(Result<PathBuf, String>),
+    /// A test restore finished, with the number of files read
+    TestFinished(Result<usize, String>),
+    /// Whether each of `Restore::sources` has the snapshot
+    SourcesChecked(Vec<Result<(), String>>),
+    /// Read from this one of `Restore::sources` first
+    SetSource(Opt<usize>),
+    /// Cancel the running restore
+    Cancel,
+    Listed(Result<Vec<String>, String>),
+    /// Show the folder with this path in the snapshot ("" is the root)
+    Browse(String),
+    /// Choose or unchoose the file or folder with this path
+    Select(String, bool),
+    ClearSelection,
+    /// Open the restored folder, or the closest existing folder around it, in the file manager
+    OpenDest,
+    /// Don't start after all, when asked what to do with existing files
+    CancelCollision,
+
+    // Meant for outside
+    /// Start button pressed
+    Start,
+    /// Start after deciding what to do with existing files
+    StartWith(Collision),
+    /// List the files of the snapshot, to choose from
+    ListMembers,
+    /// Read the whole snapshot without writing any files
+    Test,
+    /// Back button pressed
+    Back,
+}
+
+/// A file or folder in the browsed folder of the snapshot
+struct Entry {
+    name: String,
+    /// Member path in the snapshot, without trailing `/`
+    path: String,
+    is_dir: bool,
+}
+
+/// The files and folders directly in folder `dir` of the listed `members`
+fn entries(members: &[String], dir: &str) -> Vec<Entry> {
+    let prefix = if dir.is_empty() {
+        String::new()
+    } else {
+        format!("{}/", dir)
+    };
+    let mut entries: BTreeMap<&str, bool> = BTreeMap::new();
+    for member in members {
+        let rest = match member.strip_prefix(&prefix) {
+            Some(rest) if !rest.is_empty() => rest,
+            _ => continue,
+        };
+        let (name, is_dir) = match rest.split_once('/') {
+            Some((name, _)) => (name, true),
+            None => (rest, false),
+        };
+        *entries.entry(name).or_default() |= is_dir;
+    }
+    entries
+        .into_iter()
+        .map(|(name, is_dir)| Entry {
+            name: name.to_string(),
+            path: format!("{}{}", prefix, name),
+            is_dir,
+        })
+        .collect()
+}
+
+/// Whether `path` is `selected` or inside a selected folder
+fn is_selected(selected: &BTreeSet<String>, path: &str) -> bool {
+    selected.iter().any(|s| {
+        path == s
+            || path
+                .strip_prefix(s.as_str())
+                .map_or(false, |rest| rest.starts_with('/'))
+    })
+}
+
+/// Unselect `path`. Inside a selected folder, the folder is replaced by the rest of what is in
+/// it, level by level down to `path`.
+fn unselect(selected: &mut BTreeSet<String>, members: &[String], path: &str) {
+    if selected.remove(path) {
+        return;
+    }
+    let inside = |folder: &str| {
+        path.strip_prefix(folder)
+            .map_or(false, |rest| rest.starts_with('/'))
+    };
+    let mut dir = match selected.iter().find(|folder| inside(folder)).cloned() {
+        Some(folder) => folder,
+        None => return,
+    };
+    selected.remove(&dir);
+    loop {
+        let mut next = None;
+        for entry in entries(members, &dir) {
+            if inside(&entry.path) {
+                next = Some(entry.path);
+            } else if entry.path != path {
+                selected.insert(entry.path);
+            }
+        }
+        match next {
+            Some(folder) => dir = folder,
+            None => break,
+        }
+    }
+}
+
+/// Choosing files and folders of a snapshot to restore
+#[derive(Default)]
+struct Chooser {
+    /// Members of the snapshot. `None` inside while listing.
+    members: Option<Option<Result<Vec<String>, String>>>,
+    /// Folder being browsed ("" is the root)
+    browsing: String,
+    entries: Vec<Entry>,
+    /// Chosen files and folders; a folder includes everything in it. Empty restores everything.
+    selected: BTreeSet<String>,
+
+    s_choose: button::State,
+    s_up: button::State,
+    s_clear: button::State,
+    s_entries: Vec<button::State>,
+}
+
+impl Chooser {
+    fn view(&mut self) -> Element<'_, RestoreMessage> {
+        let mut column = Column::new().spacing(4);
+        match self.members {
+            None => {
+                return Button::new(
+                    &mut self.s_choose,
+                    Text::new("CHOOSE FILES...").size(TEXT_SIZE - 4),
+                )
+                .padding(8)
+                .style(style::Button::Text)
+                .on_press(RestoreMessage::ListMembers)
+                .into()
+            }
+            Some(None) => return Text::new("Listing files...").into(),
+            Some(Some(Err(ref error))) => {
+                return Text::new(format!("Error listing files: {}", error))
+                    .color(Color::from_rgb(0.5, 0.0, 0.0))
+                    .into()
+            }
+            Some(Some(Ok(_))) => (),
+        }
+        let mut header = Row::new()
+            .spacing(10)
+            .push(Text::new(format!("/{}", self.browsing)));
+        if !self.browsing.is_empty() {
+            let parent = match self.browsing.rsplit_once('/') {
+                Some((parent, _)) => parent.to_string(),
+                None => String::new(),
+            };
+            header = header.push(
+                Button::new(&mut self.s_up, Text::new("UP").size(TEXT_SIZE - 6))
+                    .padding(2)
+                    .style(style::Button::Text)
+                    .on_press(RestoreMessage::Browse(parent)),
+            );
+        }
+        column = column.push(header);
+        let selected = &self.selected;
+        for (entry, state) in zip_list(self.entries.iter().take(MAX_ENTRIES), &mut self.s_entries) {
+            let path = entry.path.clone();
+            let mut row = Row::new().spacing(8).push(
+                Checkbox::new(
+                    is_selected(selected, &entry.path),
+                    if entry.is_dir {
+                        format!("{}/", entry.name)
+                    } else {
+                        entry.name.clone()
+                    },
+                    move |b| RestoreMessage::Select(path.clone(), b),
+                )
+                .size(TEXT_SIZE - 4)
+                .text_size(TEXT_SIZE - 4),
+            );
+            if entry.is_dir {
+                row = row.push(
+                    Button::new(state, Text::new("OPEN").size(TEXT_SIZE - 6))
+                        .padding(2)
+                        .style(style::Button::Text)
+                        .on_press(RestoreMessage::Browse(entry.path.clone())),
+                );
+            }
+            column = column.push(row);
+        }
+        if self.entries.len() > MAX_ENTRIES {
+            column = column.push(
+                Text::new(format!(
+                    "and {} more, not shown",
+                    self.entries.len() - MAX_ENTRIES
+                ))
+                .size(TEXT_SIZE - 4),
+            );
+        }
+        let summary = if self.selected.is_empty() {
+            "Nothing chosen: the whole snapshot is restored".to_string()
+        } else {
+            format!(
+                "{} chosen, restored into the destination itself",
+                self.selected.len()
+            )
+        };
+        let mut footer = Row::new()
+            .spacing(10)
+            .push(Text::new(summary).size(TEXT_SIZE - 4));
+        if !self.selected.is_empty() {
+            footer = footer.push(
+                Button::new(&mut self.s_clear, Text::new("CLEAR").size(TEXT_SIZE - 6))
+                    .padding(2)
+                    .style(style::Button::Text)
+                    .on_press(RestoreMessage::ClearSelection),
+            );
+        }
+        column.push(footer).into()
+    }
+}
+
+/// Restoring one snapshot, or chosen files of it, to a directory of choice
+#[derive(Default)]
+pub struct Restore {
+    pub snapshot: String,
+    pub dest: Option<PathBuf>,
+    /// Homes of the repo and of its copies (see `duplicate::copies`), with whether each has the
+    /// snapshot (`None` while checking)
+    sources: Vec<(PathBuf, Option<Result<(), String>>)>,
+    /// Index in `sources` to read from first; the others are fallbacks
+    source: usize,
+    /// Of the repo, shared with its other restores and verifications
+    reads: backup::ReadLimit,
+    /// Written by the restoring thread
+    progress: Arc<Mutex<RestoreProgress>>,
+    /// Copy of `progress`, refreshed on `RestoreMessage::Progress`
+    shown: RestoreProgress,
+    cancel: Arc<AtomicBool>,
+    running: bool,
+    started: Option<Instant>,
+    result: Option<Result<PathBuf, String>>,
+    test_result: Option<Result<usize, String>>,
+    /// Set when opening the restored folder failed, e.g. without a file manager
+    open_error: Option<String>,
+
+    chooser: Chooser,
+    /// Set when chosen files exist in the destination, until the user decides what to do
+    ask_collision: bool,
+
+    s_dest: FilePicker,
+    s_source: pick_list::State<Opt<usize>>,
+    s_start: button::State,
+    s_test: button::State,
+    s_cancel: button::State,
+    s_back: button::State,
+    s_overwrite: button::State,
+    s_skip: button::State,
+    s_new_folder: button::State,
+    s_cancel_collision: button::State,
+    s_open_dest: button::State,
+}
+impl Restore {
+    /// Restore `snapshot` from the first of `homes` (the repo, then its copies) that has it
+    pub fn new(snapshot: String, homes: Vec<PathBuf>, reads: backup::ReadLimit) -> Self {
+        Self {
+            snapshot,
+            sources: homes.into_iter().map(|home| (home, None)).collect(),
+            reads,
+            ..Default::default()
+        }
+    }
+    /// Check in the background which of the sources have the snapshot, if there is a choice
+    pub fn check_sources(&self, log: Logger) -> Command<RestoreMessage> {
+        if self.sources.len() < 2 {
+            return Command::none();
+        }
+        Command::perform(
+            backup::check_copies_async(
+                self.sources.iter().map(|(home, _)| home.clone()).collect(),
+                self.snapshot.clone(),
+                log,
+            ),
+            RestoreMessage::SourcesChecked,
+        )
+    }
+    /// The sources in the order to try them: the chosen one first, leaving out those known not to
+    /// have the snapshot
+    fn homes(&self) -> Vec<PathBuf> {
+        let chosen = self.sources.get(self.source).into_iter();
+        let others = self
+            .sources
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| *i != self.source)
+            .map(|(_, source)| source);
+        chosen
+            .chain(others)
+            .filter(|(_, checked)| !matches!(checked, Some(Err(_))))
+            .map(|(home, _)| home.clone())
+            .collect()
+    }
+    fn source_option(&self, i: usize) -> Opt<usize> {
+        Opt {
+            name: if i == 0 {
+                "The repo".to_string()
+            } else {
+                format!("Copy at {}", self.sources[i].0.display())
+            },
+            value: i,
+        }
+    }
+    pub fn is_running(&self) -> bool {
+        self.running
+    }
+    /// What to record in `history` about the finished restore
+    pub fn history_entry(&self, repo: Uuid) -> Option<history::Entry> {
+        let result = self.result.as_ref()?;
+        let target = rdedup::parse_snapshot_name(&self.snapshot)
+            .map_or(self.snapshot.as_str(), |(target, _)| target);
+        let mut entry = history::Entry::new(
+            history::Action::Restore,
+            repo,
+            target.to_string(),
+            result.as_ref().map(|_| ()).map_err(|e| e.clone()),
+        );
+        entry.bytes = Some(self.shown.bytes);
+        entry.duration_secs = self.started.map(|started| started.elapsed().as_secs_f64());
+        Some(entry)
+    }
+    /// List the members of the snapshot in the background
+    pub fn list_members(&mut self, repo: Repo, passphrase: String) -> Command<RestoreMessage> {
+        self.chooser.members = Some(None);
+        Command::perform(
+            backup::list_members_async(repo, self.snapshot.clone(), passphrase),
+            RestoreMessage::Listed,
+        )
+    }
+    /// Start restoring in the background. If chosen files exist in the destination, asks what to
+    /// do with them first, unless `collision` says so.
+    pub fn start(
+        &mut self,
+        passphrase: String,
+        collision: Option<Collision>,
+        log: Logger,
+    ) -> Command<RestoreMessage> {
+        let dest = match self.dest {
+            Some(ref dest) => dest.clone(),
+            None => {
+                self.result = Some(Err("Choose where to restore to".to_string()));
+                return Command::none();
+            }
+        };
+        let selected = &self.chooser.selected;
+        let selection = if selected.is_empty() {
+            RestoreSelection::All
+        } else {
+            let collision = match collision {
+                Some(collision) => collision,
+                None if selected.iter().any(|path| dest.join(path).exists()) => {
+                    self.ask_collision = true;
+                    return Command::none();
+                }
+                None => Collision::Overwrite,
+            };
+            RestoreSelection::Members {
+                members: selected.iter().cloned().collect(),
+                collision,
+            }
+        };
+        self.ask_collision = false;
+        self.progress = Default::default();
+        if let Some(Some(Ok(ref members))) = self.chooser.members {
+            self.progress.lock().unwrap().total_files = Some(
+                members
+                    .iter()
+                    .filter(|member| is_selected(selected, member.trim_end_matches('/')))
+                    .count(),
+            );
+        }
+        self.shown = Default::default();
+        self.cancel = Default::default();
+        self.running = true;
+        self.started = Some(Instant::now());
+        self.result = None;
+        self.open_error = None;
+        self.test_result = None;
+        Command::perform(
+            backup::restore_async(
+                self.homes(),
+                self.snapshot.clone(),
+                dest,
+                selection,
+                passphrase,
+                self.progress.clone(),
+                self.cancel.clone(),
+                self.reads,
+                log,
+            ),
+            RestoreMessage::Finished,
+        )
+    }
+    /// Start a test restore in the background (see `backup::test_restore`)
+    pub fn test(&mut self, repo: Repo, passphrase: String, log: Logger) -> Command<RestoreMessage> {
+        self.progress = Default::default();
+        self.shown = Default::default();
+        self.cancel = Default::default();
+        self.running = true;
+        self.result = None;
+        self.open_error = None;
+        self.test_result = None;
+        Command::perform(
+            backup::test_restore_async(
+                repo,
+                self.snapshot.clone(),
+                passphrase,
+                self.progress.clone(),
+                self.cancel.clone(),
+                self.reads,
+                log,
+            ),
+            RestoreMessage::TestFinished,
+        )
+    }
+    pub fn view(&mut self) -> Element<'_, RestoreMessage> {
+        let running = self.running;
+        let mut column = Column::new()
+            .padding(20)
+            .spacing(20)
+            .push(h3(format!("Restore {}", self.snapshot)))
+            .push(
+                Row::new().spacing(8).push(Text::new("Restore to:")).push(
+                    self.s_dest
+                        .view(self.dest.as_deref(), TEXT_SIZE)
+                        .map(RestoreMessage::Dest),
+                ),
+            );
+        if self.sources.len() > 1 && !running {
+            let options: Vec<_> = (0..self.sources.len())
+                .filter(|i| matches!(self.sources[*i].1, Some(Ok(()))))
+                .map(|i| self.source_option(i))
+                .collect();
+            let checking = self.sources.iter().any(|(_, checked)| checked.is_none());
+            let mut row = Row::new().spacing(8).push(Text::new("Read from:"));
+            if checking {
+                row = row.push(Text::new("Checking the copies...").size(TEXT_SIZE - 4));
+            } else if options.is_empty() {
+                row = row.push(
+                    Text::new("No copy of the repo has the snapshot")
+                        .size(TEXT_SIZE - 4)
+                        .color(Color::from_rgb(0.5, 0.0, 0.0)),
+                );
+            } else {
+                let selected = Some(self.source_option(self.source));
+                row = row
+                    .push(
+                        PickList::new(
+                            &mut self.s_source,
+                            options,
+                            selected,
+                            RestoreMessage::SetSource,
+                        )
+                        .style(style::Dropdown),
+                    )
+                    .push(help(
+                        "The snapshot is also in copies made by duplications. Should reading it \
+                         fail midway, the restore starts over from another copy that has it.",
+                    ));
+            }
+            column = column.push(row);
+        }
+        if !running {
+            column = column.push(self.chooser.view());
+        }
+
+        if running {
+            let shown = &self.shown;
+            column = match shown.total_files {
+                Some(total) => column
+                    .push(ProgressBar::new(
+                        0.0..=total.max(1) as f32,
+                        shown.files as f32,
+                    ))
+                    .push(Text::new(format!("{} / {} files", shown.files, total))),
+                None => column.push(Text::new(format!("{} files", shown.files))),
+            };
+            column = column
+                .push(Text::new(format!("{} bytes", shown.bytes)).size(TEXT_SIZE - 4))
+                .push(Text::new(&shown.current_file).size(TEXT_SIZE - 4))
+                .push(
+                    Button::new(&mut self.s_cancel, Text::new("CANCEL").size(TEXT_SIZE - 4))
+                        .padding(8)
+                        .style(style::Button::Text)
+                        .on_press(RestoreMessage::Cancel),
+                );
+        } else if self.ask_collision {
+            column = column
+                .push(
+                    Text::new("Some of the chosen files exist in the destination.")
+                        .color(Color::from_rgb(0.9, 0.6, 0.2)),
+                )
+                .push(
+                    Row::new()
+                        .spacing(10)
+                        .push(
+                            Button::new(
+                                &mut self.s_cancel_collision,
+                                Text::new("CANCEL").size(TEXT_SIZE - 4),
+                            )
+                            .padding(8)
+                            .style(style::Button::Text)
+                            .on_press(RestoreMessage::CancelCollision),
+                        )
+                        .push(
+                            Button::new(
+                                &mut self.s_skip,
+                                Text::new("KEEP EXISTING").size(TEXT_SIZE - 4),
+                            )
+                            .padding(8)
+                            .style(style::Button::Text)
+                            .on_press(RestoreMessage::StartWith(Collision::Skip)),
+                        )
+                        .push(
+                            Button::new(
+                                &mut self.s_new_folder,
+                                Text::new("RESTORE TO NEW FOLDER").size(TEXT_SIZE - 4),
+                            )
+                            .padding(8)
+                            .style(style::Button::Text)
+                            .on_press(RestoreMessage::StartWith(Collision::NewFolder)),
+                        )
+                        .push(
+                            Button::new(
+                                &mut self.s_overwrite,
+                                Text::new("OVERWRITE").size(TEXT_SIZE - 4),
+                            )
+                            .padding(8)
+                            .style(style::Button::Primary)
+                            .on_press(RestoreMessage::StartWith(Collision::Overwrite)),
+                        ),
+                );
+        } else {
+            column = column.push(
+                Row::new()
+                    .spacing(10)
+                    .push(
+                        Button::new(&mut self.s_back, Text::new("BACK").size(TEXT_SIZE - 4))
+                            .padding(8)
+                            .style(style::Button::Text)
+                            .on_press(RestoreMessage::Back),
+                    )
+                    .push(
+                        Button::new(
+                            &mut self.s_test,
+                            Text::new("TEST RESTORE").size(TEXT_SIZE - 4),
+                        )
+                        .padding(8)
+                        .style(style::Button::Text)
+                        .on_press(RestoreMessage::Test),
+                    )
+                    .push(help(
+                        "Reads the whole snapshot without writing any files, to check that it can \
+                         be restored. Faster than a restore, and catches corrupted chunks.",
+                    ))
+                    .push(
+                        Button::new(&mut self.s_start, Text::new("RESTORE").size(TEXT_SIZE - 4))
+                            .padding(8)
+                            .style(style::Button::Primary)
+                            .on_press(RestoreMessage::Start),
+                    ),
+            );
+        }
+        match self.result {
+            Some(Ok(ref dir)) => {
+                // The restored folder may have been (re)moved since
+                let label = if dir.is_dir() {
+                    "OPEN FOLDER"
+                } else {
+                    "OPEN ENCLOSING FOLDER"
+                };
+                column = column.push(
+                    Row::new()
+                        .spacing(10)
+                        .push(Text::new(format!("Restored to {}", dir.display())))
+                        .push(
+                            Button::new(
+                                &mut self.s_open_dest,
+                                Text::new(label).size(TEXT_SIZE - 4),
+                            )
+                            .padding(8)
+                            .style(style::Button::Text)
+                            .on_press(RestoreMessage::OpenDest),
+                        ),
+                );
+                if let Some(ref error) = self.open_error {
+                    column = column.push(
+                        Text::new(format!("Could not open the folder: {}", error))
+                            .size(TEXT_SIZE - 4)
+                            .color(Color::from_rgb(0.5, 0.0, 0.0)),
+                    );
+                }
+            }
+            Some(Err(ref error)) => {
+                column = column.push(
+                    Text::new(format!("Error: {}", error)).color(Color::from_rgb(0.5, 0.0, 0.0)),
+                )
+            }
+            None => (),
+        }
+        match self.test_result {
+            Some(Ok(files)) => {
+                column = column.push(
+                    Text::new(format!("Test restore OK: all {} files are readable", files))
+                        .color(Color::from_rgb(0.0, 0.5, 0.0)),
+                )
+            }
+            Some(Err(ref error)) => {
+                column = column.push(
+                    Text::new(format!("Test restore failed: {}", error))
+                        .color(Color::from_rgb(0.5, 0.0, 0.0)),
+                )
+            }
+            None => (),
+        }
+        Container::new(column)
+            .style(style::DialogContainer)
+            .width(Length::Fill)
+            .max_width(1000)
+            .height(Length::Shrink)
+            .into()
+    }
+    pub fn update(&mut self, message: RestoreMessage) -> Command<RestoreMessage> {
+        match message {
+            RestoreMessage::Dest(msg) => {
+                if let path::Message::Path(ref path) = msg {
+                    self.dest = Some(path.clone());
+                    self.ask_collision = false;
+                }
+                return self.s_dest.update(msg).map(RestoreMessage::Dest);
+            }
+            RestoreMessage::Progress => self.shown = self.progress.lock().unwrap().clone(),
+            RestoreMessage::Finished(result) => {
+                self.shown = self.progress.lock().unwrap().clone();
+                self.running = false;
+                self.result = Some(result);
+            }
+            RestoreMessage::TestFinished(result) => {
+                self.shown = self.progress.lock().unwrap().clone();
+                self.running = false;
+                self.test_result = Some(result);
+            }
+            RestoreMessage::SourcesChecked(results) => {
+                for ((_, checked), result) in self.sources.iter_mut().zip(results) {
+                    *checked = Some(result);
+                }
+                // Prefer the repo itself, then the first copy that has the snapshot
+                if let Some(i) = self
+                    .sources
+                    .iter()
+                    .position(|(_, checked)| matches!(checked, Some(Ok(()))))
+                {
+                    self.source = i;
+                }
+            }
+            RestoreMessage::SetSource(option) => self.source = option.value,
+            RestoreMessage::Cancel => self.cancel.store(true, Ordering::Relaxed),
+            RestoreMessage::Listed(result) => {
+                let chooser = &mut self.chooser;
+                chooser.browsing = String::new();
+                if let Ok(ref members) = result {
+                    chooser.entries = entries(members, "");
+                }
+                chooser.members = Some(Some(result));
+            }
+            RestoreMessage::Browse(dir) => {
+                let chooser = &mut self.chooser;
+                if let Some(Some(Ok(ref members))) = chooser.members {
+                    chooser.entries = entries(members, &dir);
+                }
+                chooser.browsing = dir;
+            }
+            RestoreMessage::Select(path, true) => {
+                // A folder includes everything in it
+                let prefix = format!("{}/", path);
+                self.chooser.selected.retain(|s| !s.starts_with(&prefix));
+                self.chooser.selected.insert(path);
+                self.ask_collision = false;
+            }
+            RestoreMessage::Select(path, false) => {
+                let chooser = &mut self.chooser;
+                match chooser.members {
+                    Some(Some(Ok(ref members))) => unselect(&mut chooser.selected, members, &path),
+                    _ => {
+                        chooser.selected.remove(&path);
+                    }
+                }
+                self.ask_collision = false;
+            }
+            RestoreMessage::ClearSelection => {
+                self.chooser.selected.clear();
+                self.ask_collision = false;
+            }
+            RestoreMessage::OpenDest => {
+                if let Some(Ok(ref dir)) = self.result {
+                    let folder = dir.ancestors().find(|folder| folder.is_dir());
+                    self.open_error = match folder {
+                        Some(folder) => open_in_file_manager(folder).err().map(|e| {
+                            if e.kind() == std::io::ErrorKind::NotFound {
+                                "no file manager found".to_string()
+                            } else {
+                                e.to_string()
+                            }
+                        }),
+                        None => Some(format!("{} no longer exists", dir.display())),
+                    };
+                }
+            }
+            RestoreMessage::CancelCollision => self.ask_collision = false,
+            RestoreMessage::Start => (),
+            RestoreMessage::StartWith(_) => (),
+            RestoreMessage::ListMembers => (),
+            RestoreMessage::Test => (),
+            RestoreMessage::Back => (),
+        }
+        Command::none()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn members() -> Vec<String> {
+        ["a/", "a/b/", "a/b/c.txt", "a/b/d.txt", "a/e.txt", "f.txt"]
+            .iter()
+            .map(|member| member.to_string())
+            .collect()
+    }
+
+    fn set(paths: &[&str]) -> BTreeSet<String> {
+        paths.iter().map(|path| path.to_string()).collect()
+    }
+
+    #[test]
+    fn unselect_selected() {
+        let mut selected = set(&["a", "f.txt"]);
+        unselect(&mut selected, &members(), "a");
+        assert_eq!(selected, set(&["f.txt"]));
+    }
+
+    #[test]
+    fn unselect_inside_selected_folder() {
+        let mut selected = set(&["a"]);
+        unselect(&mut selected, &members(), "a/b/c.txt");
+        assert_eq!(selected, set(&["a/b/d.txt", "a/e.txt"]));
+        assert!(!is_selected(&selected, "a/b/c.txt"));
+    }
+}
@@ -0,0 +1,83 @@
+//! Sparkline of the sizes of the recent snapshots of a target (see `Target::size_history`), to
+//! spot a sudden jump, like an accidentally included folder, or steady growth
+use crate::*;
+use iced::canvas::{self, Canvas, Cursor, Frame, Geometry, Path, Stroke};
+use iced::{Point, Rectangle};
+
+const WIDTH: u16 = 240;
+const HEIGHT: u16 = 40;
+
+/// Sizes in bytes, oldest first
+struct SizeChart {
+    sizes: Vec<f32>,
+}
+
+impl<Message> canvas::Program<Message> for SizeChart {
+    fn draw(&self, bounds: Rectangle, _cursor: Cursor) -> Vec<Geometry> {
+        let mut frame = Frame::new(bounds.size());
+        let min = self.sizes.iter().copied().fold(f32::INFINITY, f32::min);
+        let max = self.sizes.iter().copied().fold(0.0, f32::max);
+        // Flat when all sizes are equal
+        let range = (max - min).max(1.0);
+        // Inset by half the line width, so that the extremes aren't clipped
+        let (width, height) = (frame.width() - 2.0, frame.height() - 2.0);
+        let step = width / (self.sizes.len() - 1) as f32;
+        let line = Path::new(|path| {
+            for (i, size) in self.sizes.iter().enumerate() {
+                let point = Point::new(
+                    1.0 + i as f32 * step,
+                    1.0 + height * (1.0 - (size - min) / range),
+                );
+                if i == 0 {
+                    path.move_to(point);
+                } else {
+                    path.line_to(point);
+                }
+            }
+        });
+        frame.stroke(
+            &line,
+            Stroke::default()
+                .with_color(Color::from_rgb(0.3, 0.6, 0.9))
+                .with_width(2.0),
+        );
+        vec![frame.into_geometry()]
+    }
+}
+
+/// The sizes in `history` as a sparkline, with the range and the latest size
+pub fn view<'a, Message: 'a>(history: &[PreviousSnapshot]) -> Element<'a, Message> {
+    let sizes: Vec<usize> = history
+        .iter()
+        .filter_map(|snapshot| snapshot.bytes)
+        .collect();
+    let (min, max, latest) = match (sizes.iter().min(), sizes.iter().max(), sizes.last()) {
+        (Some(min), Some(max), Some(latest)) if sizes.len() >= 2 => (*min, *max, *latest),
+        _ => {
+            return Text::new("Snapshot sizes: insufficient data")
+                .size(TEXT_SIZE - 4)
+                .into()
+        }
+    };
+    Row::new()
+        .spacing(10)
+        .push(Text::new("Snapshot sizes:").size(TEXT_SIZE - 4))
+        .push(
+            Canvas::new(SizeChart {
+                sizes: sizes.iter().map(|size| *size as f32).collect(),
+            })
+            .width(Length::Units(WIDTH))
+            .height(Length::Units(HEIGHT)),
+        )
+        .push(
+            Text::new(format!(
+                "latest {}, {} to {} over the last {}",
+                format_bytes(latest as u64),
+                format_bytes(min as u64),
+                format_bytes(max as u64),
+                sizes.len()
+            ))
+            .size(TEXT_SIZE - 4),
+        )
+        .into()
+}
@@ -0,0 +1,476 @@
+use super::*;
+
+/// Number of snapshots shown per page
+pub const SNAPSHOTS_PER_PAGE: usize = 50;
+
+#[derive(Debug, Clone)]
+pub enum SnapshotListMessage {
+    Loaded(Result<Vec<PreviousSnapshot>, String>),
+    NextPage,
+    PrevPage,
+    /// Start editing the label of the snapshot with this name
+    StartRename(String),
+    SetLabel(String),
+    CancelRename,
+    Renamed(Result<String, String>),
+    /// Result of verifying the snapshot with this name
+    Verified(String, Result<(), String>),
+    /// Start editing the tags of the snapshot with this name
+    StartTagging(String),
+    SetTags(String),
+    CancelTagging,
+    /// Only show snapshots with a tag containing this text
+    SetFilter(String),
+    // Meant for outside
+    /// Restore the snapshot with this name
+    Restore(String),
+    /// Export the snapshot with this name to a tar file
+    ExportTar(String),
+    /// List the snapshots again, for changes made outside of bup
+    Refresh,
+    /// Confirm the label being edited
+    Rename,
+    /// Verify the chunks, and the stored checksum if any, of the snapshot with this name
+    Verify(String),
+    /// Confirm the tags being edited (see `SnapshotList::tagging`)
+    SaveTags,
+    /// Pin or unpin the snapshot with this name (see `RepoConfig::pinned_snapshots`)
+    TogglePin(String),
+}
+
+/// Label being edited
+#[derive(Default)]
+pub struct Rename {
+    pub snapshot: String,
+    pub label: String,
+    s_label: text_input::State,
+    s_confirm: button::State,
+    s_cancel: button::State,
+}
+
+/// Tags being edited, as comma separated text
+#[derive(Default)]
+pub struct Tagging {
+    pub snapshot: String,
+    pub text: String,
+    s_text: text_input::State,
+    s_confirm: button::State,
+    s_cancel: button::State,
+}
+
+/// Buttons of a snapshot row
+#[derive(Default, Clone)]
+struct RowState {
+    s_restore: button::State,
+    s_export: button::State,
+    s_rename: button::State,
+    s_verify: button::State,
+    s_tags: button::State,
+    s_pin: button::State,
+}
+
+/// Paged list of the snapshots of one target, newest first
+#[derive(Default)]
+pub struct SnapshotList {
+    /// `None` while loading
+    snapshots: Option<Vec<PreviousSnapshot>>,
+    page: usize,
+    error: Option<String>,
+    pub rename: Option<Rename>,
+    rename_error: Option<String>,
+    /// Checksum verifications by snapshot name, `None` while verifying
+    verify: HashMap<String, Option<Result<(), String>>>,
+    /// Tags by snapshot name (see `RepoConfig::snapshot_tags`)
+    pub tags: BTreeMap<String, Vec<String>>,
+    pub tagging: Option<Tagging>,
+    pub filter: String,
+    /// See `RepoConfig::pinned_snapshots`
+    pub pinned: BTreeSet<String>,
+    /// See `RepoConfig::failed_verifications`
+    pub failed_verifications: BTreeMap<String, String>,
+
+    s_prev: button::State,
+    s_next: button::State,
+    s_refresh: button::State,
+    s_filter: text_input::State,
+    /// One per snapshot on the current page
+    s_rows: Vec<RowState>,
+}
+/// The list is cached, so snapshots written or removed by other tools only show up after this
+fn refresh_button(state: &mut button::State) -> Button<'_, SnapshotListMessage> {
+    Button::new(state, Text::new("REFRESH").size(TEXT_SIZE - 4))
+        .padding(4)
+        .style(style::Button::Text)
+        .on_press(SnapshotListMessage::Refresh)
+}
+
+/// Tags typed as comma separated text, trimmed and without duplicates
+pub fn parse_tags(text: &str) -> Vec<String> {
+    let mut tags: Vec<String> = Vec::new();
+    for tag in text.split(',').map(str::trim).filter(|tag| !tag.is_empty()) {
+        if !tags.iter().any(|t| t == tag) {
+            tags.push(tag.to_string());
+        }
+    }
+    tags
+}
+
+/// The snapshots that pass `filter`: those with a tag containing it, ignoring case
+fn filtered<'a>(
+    snapshots: &'a [PreviousSnapshot],
+    tags: &BTreeMap<String, Vec<String>>,
+    filter: &str,
+) -> Vec<&'a PreviousSnapshot> {
+    let filter = filter.trim().to_lowercase();
+    snapshots
+        .iter()
+        .filter(|snapshot| {
+            filter.is_empty()
+                || tags.get(&snapshot.name).map_or(false, |tags| {
+                    tags.iter().any(|tag| tag.to_lowercase().contains(&filter))
+                })
+        })
+        .collect()
+}
+
+impl SnapshotList {
+    /// List of already loaded snapshots. `SnapshotList::default()` is the list while loading.
+    pub fn loaded(
+        snapshots: Result<Vec<PreviousSnapshot>, String>,
+        tags: BTreeMap<String, Vec<String>>,
+        pinned: BTreeSet<String>,
+    ) -> Self {
+        let mut list = Self {
+            tags,
+            pinned,
+            ..Default::default()
+        };
+        let _ = list.update(SnapshotListMessage::Loaded(snapshots));
+        list
+    }
+    fn n_pages(&self) -> usize {
+        let n = self
+            .snapshots
+            .as_ref()
+            .map(|s| filtered(s, &self.tags, &self.filter).len())
+            .unwrap_or(0);
+        ((n + SNAPSHOTS_PER_PAGE - 1) / SNAPSHOTS_PER_PAGE).max(1)
+    }
+    pub fn view(&mut self) -> Element<'_, SnapshotListMessage> {
+        let snapshots = match (&self.snapshots, &self.error) {
+            (_, Some(error)) => {
+                return Row::new()
+                    .spacing(20)
+                    .push(
+                        Text::new(format!("Error listing snapshots: {}", error))
+                            .color(Color::from_rgb(0.5, 0.0, 0.0)),
+                    )
+                    .push(refresh_button(&mut self.s_refresh))
+                    .into()
+            }
+            (None, None) => return Text::new("Loading snapshots...").into(),
+            (Some(snapshots), None) => snapshots,
+        };
+        if snapshots.is_empty() {
+            return Row::new()
+                .spacing(20)
+                .push(Text::new("No snapshots yet"))
+                .push(refresh_button(&mut self.s_refresh))
+                .into();
+        }
+        let n_pages = self.n_pages();
+        let shown = filtered(snapshots, &self.tags, &self.filter);
+        let start = self.page * SNAPSHOTS_PER_PAGE;
+        let end = (start + SNAPSHOTS_PER_PAGE).min(shown.len());
+        let mut column = Column::new().spacing(4).push(
+            TextInput::new(
+                &mut self.s_filter,
+                "Filter by tag",
+                &self.filter,
+                SnapshotListMessage::SetFilter,
+            )
+            .style(style::TextInput)
+            .size(TEXT_SIZE - 2)
+            .width(Length::Units(250)),
+        );
+        if shown.is_empty() {
+            column = column.push(Text::new("No snapshots with this tag").size(TEXT_SIZE - 2));
+        }
+        // Only one snapshot is edited at a time
+        let mut rename = self.rename.as_mut();
+        let mut tagging = self.tagging.as_mut();
+        for (snapshot, state) in zip_list(&shown[start..end], &mut self.s_rows) {
+            let mut row = Row::new().spacing(20).push(
+                Text::new(snapshot.timestamp.format("%Y-%m-%d %H:%M:%S").to_string())
+                    .size(TEXT_SIZE - 2),
+            );
+            let editing = match rename {
+                Some(ref r) if r.snapshot == snapshot.name => rename.take(),
+                _ => None,
+            };
+            if let Some(rename) = editing {
+                column = column.push(
+                    row.push(
+                        TextInput::new(
+                            &mut rename.s_label,
+                            "Label",
+                            &rename.label,
+                            SnapshotListMessage::SetLabel,
+                        )
+                        .on_submit(SnapshotListMessage::Rename)
+                        .style(style::TextInput)
+                        .size(TEXT_SIZE - 2),
+                    )
+                    .push(
+                        Button::new(&mut rename.s_confirm, Text::new("OK").size(TEXT_SIZE - 6))
+                            .padding(2)
+                            .style(style::Button::Primary)
+                            .on_press(SnapshotListMessage::Rename),
+                    )
+                    .push(
+                        Button::new(
+                            &mut rename.s_cancel,
+                            Text::new("CANCEL").size(TEXT_SIZE - 6),
+                        )
+                        .padding(2)
+                        .style(style::Button::Text)
+                        .on_press(SnapshotListMessage::CancelRename),
+                    ),
+                );
+                continue;
+            }
+            let editing = match tagging {
+                Some(ref t) if t.snapshot == snapshot.name => tagging.take(),
+                _ => None,
+            };
+            if let Some(tagging) = editing {
+                column = column.push(
+                    row.push(
+                        TextInput::new(
+                            &mut tagging.s_text,
+                            "Tags, separated by commas",
+                            &tagging.text,
+                            SnapshotListMessage::SetTags,
+                        )
+                        .on_submit(SnapshotListMessage::SaveTags)
+                        .style(style::TextInput)
+                        .size(TEXT_SIZE - 2),
+                    )
+                    .push(
+                        Button::new(&mut tagging.s_confirm, Text::new("OK").size(TEXT_SIZE - 6))
+                            .padding(2)
+                            .style(style::Button::Primary)
+                            .on_press(SnapshotListMessage::SaveTags),
+                    )
+                    .push(
+                        Button::new(
+                            &mut tagging.s_cancel,
+                            Text::new("CANCEL").size(TEXT_SIZE - 6),
+                        )
+                        .padding(2)
+                        .style(style::Button::Text)
+                        .on_press(SnapshotListMessage::CancelTagging),
+                    ),
+                );
+                continue;
+            }
+            let pinned = self.pinned.contains(&snapshot.name);
+            if pinned {
+                row = row.push(
+                    Text::new("PINNED")
+                        .size(TEXT_SIZE - 6)
+                        .color(Color::from_rgb(0.9, 0.6, 0.2)),
+                );
+            }
+            if let Some(ref label) = snapshot.label {
+                row = row.push(Text::new(label).size(TEXT_SIZE - 2));
+            }
+            if let Some(tags) = self.tags.get(&snapshot.name) {
+                row = row.push(
+                    Text::new(tags.join(", "))
+                        .size(TEXT_SIZE - 2)
+                        .color(Color::from_rgb(0.4, 0.6, 0.9)),
+                );
+            }
+            let mut verify_button =
+                Button::new(&mut state.s_verify, Text::new("VERIFY").size(TEXT_SIZE - 6))
+                    .padding(2)
+                    .style(style::Button::Text);
+            if !matches!(self.verify.get(&snapshot.name), Some(None)) {
+                verify_button =
+                    verify_button.on_press(SnapshotListMessage::Verify(snapshot.name.clone()));
+            }
+            row = row
+                .push(
+                    Button::new(
+                        &mut state.s_restore,
+                        Text::new("RESTORE").size(TEXT_SIZE - 6),
+                    )
+                    .padding(2)
+                    .style(style::Button::Text)
+                    .on_press(SnapshotListMessage::Restore(snapshot.name.clone())),
+                )
+                .push(
+                    Button::new(&mut state.s_export, Text::new("EXPORT").size(TEXT_SIZE - 6))
+                        .padding(2)
+                        .style(style::Button::Text)
+                        .on_press(SnapshotListMessage::ExportTar(snapshot.name.clone())),
+                )
+                .push(
+                    Button::new(&mut state.s_rename, Text::new("RENAME").size(TEXT_SIZE - 6))
+                        .padding(2)
+                        .style(style::Button::Text)
+                        .on_press(SnapshotListMessage::StartRename(snapshot.name.clone())),
+                )
+                .push(
+                    Button::new(&mut state.s_tags, Text::new("TAGS").size(TEXT_SIZE - 6))
+                        .padding(2)
+                        .style(style::Button::Text)
+                        .on_press(SnapshotListMessage::StartTagging(snapshot.name.clone())),
+                )
+                .push(
+                    Button::new(
+                        &mut state.s_pin,
+                        Text::new(if pinned { "UNPIN" } else { "PIN" }).size(TEXT_SIZE - 6),
+                    )
+                    .padding(2)
+                    .style(style::Button::Text)
+                    .on_press(SnapshotListMessage::TogglePin(snapshot.name.clone())),
+                )
+                .push(verify_button);
+            row = match self.verify.get(&snapshot.name) {
+                Some(None) => row.push(Text::new("Verifying...").size(TEXT_SIZE - 4)),
+                Some(Some(Ok(()))) => row.push(
+                    Text::new("Verified")
+                        .size(TEXT_SIZE - 4)
+                        .color(Color::from_rgb(0.0, 0.5, 0.0)),
+                ),
+                // Corrupted chunks, or a checksum mismatch: the data in the repo changed since the
+                // backup
+                Some(Some(Err(error))) => row.push(
+                    Text::new(error.as_str())
+                        .size(TEXT_SIZE - 2)
+                        .color(Color::from_rgb(0.8, 0.0, 0.0)),
+                ),
+                None => match self.failed_verifications.get(&snapshot.name) {
+                    Some(error) => row.push(
+                        Text::new(format!(
+                            "Could not be read back after the backup: {}",
+                            error
+                        ))
+                        .size(TEXT_SIZE - 2)
+                        .color(Color::from_rgb(0.8, 0.0, 0.0)),
+                    ),
+                    None => row,
+                },
+            };
+            column = column.push(row);
+        }
+        if let Some(ref error) = self.rename_error {
+            column = column.push(
+                Text::new(format!("Error renaming snapshot: {}", error))
+                    .color(Color::from_rgb(0.5, 0.0, 0.0)),
+            );
+        }
+
+        let mut prev = Button::new(&mut self.s_prev, Text::new("PREV").size(TEXT_SIZE - 4))
+            .padding(4)
+            .style(style::Button::Text);
+        if self.page > 0 {
+            prev = prev.on_press(SnapshotListMessage::PrevPage);
+        }
+        let mut next = Button::new(&mut self.s_next, Text::new("NEXT").size(TEXT_SIZE - 4))
+            .padding(4)
+            .style(style::Button::Text);
+        if self.page + 1 < n_pages {
+            next = next.on_press(SnapshotListMessage::NextPage);
+        }
+        column
+            .push(
+                Row::new()
+                    .spacing(10)
+                    .push(prev)
+                    .push(Text::new(format!("Page {} of {}", self.page + 1, n_pages)))
+                    .push(next)
+                    .push(refresh_button(&mut self.s_refresh)),
+            )
+            .into()
+    }
+    pub fn update(&mut self, message: SnapshotListMessage) -> Command<SnapshotListMessage> {
+        match message {
+            SnapshotListMessage::Loaded(Ok(snapshots)) => {
+                self.snapshots = Some(snapshots);
+                self.page = 0;
+                self.error = None;
+            }
+            SnapshotListMessage::Loaded(Err(error)) => self.error = Some(error),
+            SnapshotListMessage::NextPage => {
+                self.page = (self.page + 1).min(self.n_pages() - 1);
+            }
+            SnapshotListMessage::PrevPage => self.page = self.page.saturating_sub(1),
+            SnapshotListMessage::StartRename(snapshot) => {
+                let label = self
+                    .snapshots
+                    .iter()
+                    .flatten()
+                    .find(|s| s.name == snapshot)
+                    .and_then(|s| s.label.clone())
+                    .unwrap_or_default();
+                self.rename = Some(Rename {
+                    snapshot,
+                    label,
+                    ..Default::default()
+                });
+                self.rename_error = None;
+                self.tagging = None;
+            }
+            SnapshotListMessage::SetLabel(label) => {
+                if let Some(ref mut rename) = self.rename {
+                    rename.label = label;
+                }
+            }
+            SnapshotListMessage::CancelRename => self.rename = None,
+            SnapshotListMessage::Renamed(Ok(_)) => {
+                self.rename = None;
+                self.rename_error = None;
+            }
+            SnapshotListMessage::Renamed(Err(error)) => self.rename_error = Some(error),
+            SnapshotListMessage::Verify(name) => {
+                self.verify.insert(name, None);
+            }
+            SnapshotListMessage::Verified(name, result) => {
+                self.verify.insert(name, Some(result));
+            }
+            SnapshotListMessage::StartTagging(snapshot) => {
+                let text = self
+                    .tags
+                    .get(&snapshot)
+                    .map(|tags| tags.join(", "))
+                    .unwrap_or_default();
+                self.tagging = Some(Tagging {
+                    snapshot,
+                    text,
+                    ..Default::default()
+                });
+                self.rename = None;
+            }
+            SnapshotListMessage::SetTags(text) => {
+                if let Some(ref mut tagging) = self.tagging {
+                    tagging.text = text;
+                }
+            }
+            SnapshotListMessage::CancelTagging | SnapshotListMessage::SaveTags => {
+                self.tagging = None
+            }
+            SnapshotListMessage::SetFilter(filter) => {
+                self.filter = filter;
+                self.page = 0;
+            }
+            SnapshotListMessage::Restore(_) | SnapshotListMessage::ExportTar(_) => (),
+            SnapshotListMessage::Rename => (),
+            SnapshotListMessage::Refresh => (),
+            SnapshotListMessage::TogglePin(_) => (),
+        }
+        Command::none()
+    }
+}
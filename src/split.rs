@@ -0,0 +1,229 @@
+//! Create one target per subdirectory of a folder, e.g. one per user under `/home`
+use super::*;
+
+#[derive(Debug, Clone)]
+pub enum SplitMessage {
+    Dir(path::Message),
+    /// Subdirectories of the chosen folder
+    Listed(Result<Vec<PathBuf>, String>),
+    SetIncluded(usize, bool),
+    SetName(usize, String),
+
+    // Meant for outside
+    /// Add the included targets to the repo
+    Confirm,
+    Back,
+}
+
+/// A target about to be created, backing up one subdirectory
+#[derive(Default)]
+pub struct Proposed {
+    pub path: PathBuf,
+    pub name: String,
+    pub included: bool,
+
+    s_name: text_input::State,
+}
+
+#[derive(Default)]
+pub struct Split {
+    pub dir: Option<PathBuf>,
+    /// `None` until a folder is chosen and listed
+    pub proposed: Option<Vec<Proposed>>,
+    pub error: Option<String>,
+
+    s_dir: FilePicker,
+    s_confirm: button::State,
+    s_back: button::State,
+    s_scrollable: scrollable::State,
+}
+
+/// Sorted subdirectories of `dir`, without following symlinks
+pub async fn list_subdirs_async(dir: PathBuf) -> Result<Vec<PathBuf>, String> {
+    tokio::task::spawn_blocking(move || {
+        let result: std::io::Result<Vec<PathBuf>> = try {
+            let mut dirs = Vec::new();
+            for entry in std::fs::read_dir(&dir)? {
+                let entry = entry?;
+                if entry.file_type()?.is_dir() {
+                    dirs.push(entry.path());
+                }
+            }
+            dirs.sort();
+            dirs
+        };
+        result.map_err(|e| format!("Cannot list {}: {}", dir.display(), e))
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// `name`, or `name-2`, `name-3`... if it is taken already
+pub fn unique_name(name: &str, taken: &[String]) -> String {
+    if !taken.iter().any(|t| t == name) {
+        return name.to_string();
+    }
+    (2..)
+        .map(|n| format!("{}-{}", name, n))
+        .find(|candidate| !taken.contains(candidate))
+        .unwrap()
+}
+
+impl Split {
+    /// Targets of the included subdirectories, named uniquely among `existing` and each other
+    pub fn targets(&self, repo_id: Uuid, existing: &[Target]) -> Vec<Target> {
+        let mut taken: Vec<String> = existing.iter().map(|t| t.name.clone()).collect();
+        let mut targets = Vec::new();
+        for proposed in self.proposed.iter().flatten().filter(|p| p.included) {
+            let name = unique_name(proposed.name.trim(), &taken);
+            taken.push(name.clone());
+            targets.push(Target {
+                id: Uuid::new_v4(),
+                repo: repo_id,
+                name,
+                sources: vec![Source {
+                    path: Some(proposed.path.clone()),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            });
+        }
+        targets
+    }
+    pub fn view(&mut self) -> Element<'_, SplitMessage> {
+        let mut column = Column::new()
+            .padding(20)
+            .spacing(20)
+            .push(h3("Split a folder into targets"))
+            .push(
+                Text::new(
+                    "One target is created per subfolder, so that each can be backed up on its \
+                     own schedule. Review the names before adding them.",
+                )
+                .size(TEXT_SIZE - 4),
+            )
+            .push(
+                Row::new().spacing(8).push(Text::new("Folder:")).push(
+                    self.s_dir
+                        .view(self.dir.as_deref(), TEXT_SIZE)
+                        .map(SplitMessage::Dir),
+                ),
+            );
+
+        let mut n_included = 0;
+        if let Some(ref mut proposed) = self.proposed {
+            if proposed.is_empty() {
+                column = column.push(Text::new("The folder has no subfolders"));
+            }
+            let mut list = Column::new().spacing(4);
+            for (i, p) in proposed.iter_mut().enumerate() {
+                if p.included {
+                    n_included += 1;
+                }
+                list = list.push(
+                    Row::new()
+                        .spacing(10)
+                        .push(
+                            Checkbox::new(p.included, "", move |included| {
+                                SplitMessage::SetIncluded(i, included)
+                            })
+                            .size(TEXT_SIZE - 4),
+                        )
+                        .push(
+                            TextInput::new(&mut p.s_name, "Target name", &p.name, move |name| {
+                                SplitMessage::SetName(i, name)
+                            })
+                            .style(style::TextInput)
+                            .size(TEXT_SIZE - 2)
+                            .width(Length::Units(250)),
+                        )
+                        .push(Text::new(p.path.display().to_string()).size(TEXT_SIZE - 4)),
+                );
+            }
+            column = column.push(
+                Scrollable::new(&mut self.s_scrollable)
+                    .push(list)
+                    .height(Length::Units(400)),
+            );
+        }
+        if let Some(ref error) = self.error {
+            column = column
+                .push(Text::new(format!("Error: {}", error)).color(Color::from_rgb(0.5, 0.0, 0.0)));
+        }
+        let mut confirm = Button::new(
+            &mut self.s_confirm,
+            Text::new(format!("ADD {} TARGETS", n_included)).size(TEXT_SIZE - 4),
+        )
+        .padding(8)
+        .style(style::Button::Primary);
+        if n_included > 0 {
+            confirm = confirm.on_press(SplitMessage::Confirm);
+        }
+        column = column.push(
+            Row::new()
+                .spacing(10)
+                .push(
+                    Button::new(&mut self.s_back, Text::new("BACK").size(TEXT_SIZE - 4))
+                        .padding(8)
+                        .style(style::Button::Text)
+                        .on_press(SplitMessage::Back),
+                )
+                .push(confirm),
+        );
+        Container::new(column)
+            .style(style::DialogContainer)
+            .width(Length::Fill)
+            .max_width(1000)
+            .height(Length::Shrink)
+            .into()
+    }
+    pub fn update(&mut self, message: SplitMessage) -> Command<SplitMessage> {
+        match message {
+            SplitMessage::Dir(msg) => {
+                let path = match msg {
+                    path::Message::Path(ref path) => Some(path.clone()),
+                    _ => None,
+                };
+                let mut command = self.s_dir.update(msg).map(SplitMessage::Dir);
+                if let Some(path) = path {
+                    self.dir = Some(path.clone());
+                    self.proposed = None;
+                    self.error = None;
+                    command = Command::batch(vec![
+                        command,
+                        Command::perform(list_subdirs_async(path), SplitMessage::Listed),
+                    ]);
+                }
+                return command;
+            }
+            SplitMessage::Listed(Ok(dirs)) => {
+                self.proposed = Some(
+                    dirs.into_iter()
+                        .map(|path| Proposed {
+                            name: path
+                                .file_name()
+                                .map(|name| name.to_string_lossy().into_owned())
+                                .unwrap_or_default(),
+                            path,
+                            included: true,
+                            s_name: Default::default(),
+                        })
+                        .collect(),
+                )
+            }
+            SplitMessage::Listed(Err(e)) => self.error = Some(e),
+            SplitMessage::SetIncluded(i, included) => {
+                if let Some(p) = self.proposed.iter_mut().flatten().nth(i) {
+                    p.included = included;
+                }
+            }
+            SplitMessage::SetName(i, name) => {
+                if let Some(p) = self.proposed.iter_mut().flatten().nth(i) {
+                    p.name = name;
+                }
+            }
+            SplitMessage::Confirm | SplitMessage::Back => (),
+        }
+        Command::none()
+    }
+}
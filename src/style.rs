@@ -131,6 +131,18 @@ impl container::StyleSheet for DialogContainer {
         }
     }
 }
+pub struct Tooltip;
+impl container::StyleSheet for Tooltip {
+    fn style(&self) -> container::Style {
+        container::Style {
+            text_color: Some(Color::WHITE),
+            background: Some(Background::Color(Color::from_rgb(0.25, 0.25, 0.25))),
+            border_radius: 6.0,
+            border_width: 0.0,
+            border_color: Color::TRANSPARENT,
+        }
+    }
+}
 pub struct AppContainer;
 
 impl container::StyleSheet for AppContainer {
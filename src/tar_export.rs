@@ -0,0 +1,187 @@
+//! Export a snapshot to a plain tar file, for use without bup or rdedup
+use super::*;
+use backup::RestoreProgress;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, Mutex,
+};
+
+#[derive(Debug, Clone)]
+pub enum TarExportMessage {
+    Dest(path::Message),
+    SetGzip(bool),
+    /// Refresh the shown progress of a running export
+    Progress,
+    Finished(Result<PathBuf, String>),
+    /// Cancel the running export
+    Cancel,
+
+    // Meant for outside
+    /// Export button pressed
+    Start,
+    /// Back button pressed
+    Back,
+}
+
+/// Writing one snapshot as a tar file into a folder of choice
+#[derive(Default)]
+pub struct TarExport {
+    pub snapshot: String,
+    pub dest: Option<PathBuf>,
+    pub gzip: bool,
+    /// Written by the exporting thread
+    progress: Arc<Mutex<RestoreProgress>>,
+    /// Copy of `progress`, refreshed on `TarExportMessage::Progress`
+    shown: RestoreProgress,
+    cancel: Arc<AtomicBool>,
+    running: bool,
+    result: Option<Result<PathBuf, String>>,
+
+    s_dest: FilePicker,
+    s_start: button::State,
+    s_cancel: button::State,
+    s_back: button::State,
+}
+impl TarExport {
+    pub fn new(snapshot: String) -> Self {
+        Self {
+            snapshot,
+            ..Default::default()
+        }
+    }
+    pub fn is_running(&self) -> bool {
+        self.running
+    }
+    /// Start exporting in the background
+    pub fn start(
+        &mut self,
+        repo: Repo,
+        passphrase: String,
+        log: Logger,
+    ) -> Command<TarExportMessage> {
+        let dest = match self.dest {
+            Some(ref dest) => dest.clone(),
+            None => {
+                self.result = Some(Err("Choose a folder to export to".to_string()));
+                return Command::none();
+            }
+        };
+        self.progress = Default::default();
+        self.shown = Default::default();
+        self.cancel = Default::default();
+        self.running = true;
+        self.result = None;
+        Command::perform(
+            backup::export_tar_async(
+                repo,
+                self.snapshot.clone(),
+                dest,
+                self.gzip,
+                passphrase,
+                self.progress.clone(),
+                self.cancel.clone(),
+                log,
+            ),
+            TarExportMessage::Finished,
+        )
+    }
+    pub fn view(&mut self) -> Element<'_, TarExportMessage> {
+        let mut column = Column::new()
+            .padding(20)
+            .spacing(20)
+            .push(h3(format!("Export {}", self.snapshot)))
+            .push(
+                Row::new()
+                    .spacing(8)
+                    .push(Text::new("Export to folder:"))
+                    .push(
+                        self.s_dest
+                            .view(self.dest.as_deref(), TEXT_SIZE)
+                            .map(TarExportMessage::Dest),
+                    ),
+            )
+            .push(
+                Checkbox::new(self.gzip, "Compress with gzip", TarExportMessage::SetGzip)
+                    .size(TEXT_SIZE - 4)
+                    .text_size(TEXT_SIZE - 4),
+            )
+            .push(
+                Text::new(
+                    "The exported file is not encrypted: anyone who can read it can read the \
+                     backed up files. Keep it somewhere safe, and delete it when it is no longer \
+                     needed.",
+                )
+                .size(TEXT_SIZE - 4)
+                .color(Color::from_rgb(0.9, 0.6, 0.2)),
+            );
+
+        if self.running {
+            column = column
+                .push(Text::new(format!(
+                    "{} written",
+                    format_bytes(self.shown.bytes)
+                )))
+                .push(
+                    Button::new(&mut self.s_cancel, Text::new("CANCEL").size(TEXT_SIZE - 4))
+                        .padding(8)
+                        .style(style::Button::Text)
+                        .on_press(TarExportMessage::Cancel),
+                );
+        } else {
+            column = column.push(
+                Row::new()
+                    .spacing(10)
+                    .push(
+                        Button::new(&mut self.s_back, Text::new("BACK").size(TEXT_SIZE - 4))
+                            .padding(8)
+                            .style(style::Button::Text)
+                            .on_press(TarExportMessage::Back),
+                    )
+                    .push(
+                        Button::new(&mut self.s_start, Text::new("EXPORT").size(TEXT_SIZE - 4))
+                            .padding(8)
+                            .style(style::Button::Primary)
+                            .on_press(TarExportMessage::Start),
+                    ),
+            );
+        }
+        match self.result {
+            Some(Ok(ref file)) => {
+                column = column.push(Text::new(format!("Exported to {}", file.display())))
+            }
+            Some(Err(ref error)) => {
+                column = column.push(
+                    Text::new(format!("Error: {}", error)).color(Color::from_rgb(0.5, 0.0, 0.0)),
+                )
+            }
+            None => (),
+        }
+        Container::new(column)
+            .style(style::DialogContainer)
+            .width(Length::Fill)
+            .max_width(1000)
+            .height(Length::Shrink)
+            .into()
+    }
+    pub fn update(&mut self, message: TarExportMessage) -> Command<TarExportMessage> {
+        match message {
+            TarExportMessage::Dest(msg) => {
+                if let path::Message::Path(ref path) = msg {
+                    self.dest = Some(path.clone());
+                }
+                return self.s_dest.update(msg).map(TarExportMessage::Dest);
+            }
+            TarExportMessage::SetGzip(gzip) => self.gzip = gzip,
+            TarExportMessage::Progress => self.shown = self.progress.lock().unwrap().clone(),
+            TarExportMessage::Finished(result) => {
+                self.shown = self.progress.lock().unwrap().clone();
+                self.running = false;
+                self.result = Some(result);
+            }
+            TarExportMessage::Cancel => self.cancel.store(true, Ordering::Relaxed),
+            TarExportMessage::Start => (),
+            TarExportMessage::Back => (),
+        }
+        Command::none()
+    }
+}
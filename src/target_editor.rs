@@ -1,66 +1,914 @@
 use super::*;
 
+/// Below this window width, the editor stacks its rows into a single column
+pub const NARROW_WIDTH: u32 = 600;
+/// Source and exclude lists longer than this are collapsed to their count until shown, and then
+/// scroll within `LONG_LIST_HEIGHT`, so that the editor stays responsive for large targets
+const LONG_LIST: usize = 20;
+const LONG_LIST_HEIGHT: u32 = 400;
+
 #[derive(Debug, Clone)]
 pub enum TargetEditorMessage {
     SetName(String),
+    /// Set `Target::group`; empty for none
+    SetGroup(String),
+    SetDescription(String),
 
     NewSource,
+    /// A file or folder was dropped onto the window
+    Dropped(PathBuf),
     Source(usize, path::Message),
+    SetOneFileSystem(usize, bool),
     DelSource(usize),
+    /// Show or hide a long list of sources (see `LONG_LIST`)
+    ToggleSources,
 
     NewExclude,
     SetExclude(usize, String),
     DelExclude(usize),
+    /// Show or hide a long list of excludes
+    ToggleExcludes,
+    PreviewExclude(usize),
+    /// Preview of the exclude with this index and pattern
+    ExcludePreviewed(usize, String, Result<exclude::ExcludePreview, String>),
+    /// Read a newline separated list of excludes from the clipboard
+    PasteExcludes,
+    ExcludesPasted(Option<String>),
+    /// Add the valid pasted excludes; replacing the existing ones if `true`
+    ApplyPastedExcludes(bool),
+    CancelPastedExcludes,
+    SetExcludeHidden(bool),
+    SetExcludePreset(ExcludePreset, bool),
+
+    SetPreHook(String),
+    SetPostHook(String),
+
+    NewDuplication,
+    /// Add a duplication that runs a program (`DuplicationKind::Command`)
+    NewCommandDuplication,
+    /// Add a duplication to a host over SSH (`DuplicationKind::Sftp`)
+    NewRemoteDuplication,
+    DelDuplication(usize),
+    SetInterval(usize, Opt<Duration>),
+    /// Add a disk to the duplication with this index
+    NewDisk(usize),
+    /// Disk `.1` of duplication `.0`
+    Disk(usize, usize, path::Message),
+    DelDisk(usize, usize),
+    /// Program of the command duplication with this index
+    SetProgram(usize, String),
+    /// Arguments of the command duplication with this index, separated by spaces
+    SetArgs(usize, String),
+    /// Field of the SSH duplication with this index
+    SetRemote(usize, RemoteField, String),
+    SetWindow(usize, bool),
+    SetWindowStart(usize, String),
+    SetWindowEnd(usize, String),
+    /// Check that the disks of the duplication with this index can be written to
+    TestDuplication(usize),
+    /// Results per disk, for the disks that were tested
+    DuplicationTested(usize, Vec<PathBuf>, Vec<Result<(), String>>),
+
+    SetMaxFiles(String),
+    /// Limit of the total size, in GiB
+    SetMaxGib(String),
+
+    SetPreCompress(Opt<Option<Compression>>),
+    SetTarFormat(TarFormat),
+    SetHandleSparse(bool),
+    SetPreviewBackups(bool),
+
+    ToggleCommand,
+    /// Edit of the read-only command field; ignored
+    CommandEdited(String),
+    CopyCommand,
+
+    /// Stay in the editor after asking to leave with unsaved changes
+    KeepEditing,
+
+    SetMoveTo(Opt<Uuid>),
+    SetTimerInterval(timer::TimerInterval),
+    /// What installing the system timer did
+    TimerInstalled(Result<String, String>),
 
     // Meant for outside
     /// Save button pressed
     Save,
-    /// Cancel button pressed
+    /// Save, but keep editing
+    Apply,
+    /// Cancel button pressed. Leaves the editor only if there are no unsaved changes.
+    Cancel,
+    /// Leave the editor (see `TargetEditor::leaving`) and throw away the changes
+    Discard,
+    /// Save the target into the repo of `TargetEditor::move_to` instead
+    MoveToRepo,
+    /// Back up the saved target with a system timer of `TargetEditor::timer_interval`
+    InstallTimer,
+}
+impl TargetEditorMessage {
+    /// Whether the message changes the target
+    fn is_edit(&self) -> bool {
+        match self {
+            TargetEditorMessage::Source(_, msg) | TargetEditorMessage::Disk(_, _, msg) => {
+                matches!(msg, path::Message::Path(_))
+            }
+            TargetEditorMessage::ToggleSources
+            | TargetEditorMessage::ToggleExcludes
+            | TargetEditorMessage::PreviewExclude(_)
+            | TargetEditorMessage::ExcludePreviewed(..)
+            | TargetEditorMessage::PasteExcludes
+            | TargetEditorMessage::ExcludesPasted(_)
+            | TargetEditorMessage::CancelPastedExcludes
+            | TargetEditorMessage::TestDuplication(_)
+            | TargetEditorMessage::DuplicationTested(..)
+            | TargetEditorMessage::ToggleCommand
+            | TargetEditorMessage::CommandEdited(_)
+            | TargetEditorMessage::CopyCommand
+            | TargetEditorMessage::KeepEditing
+            | TargetEditorMessage::SetMoveTo(_)
+            | TargetEditorMessage::MoveToRepo
+            | TargetEditorMessage::SetTimerInterval(_)
+            | TargetEditorMessage::TimerInstalled(_)
+            | TargetEditorMessage::InstallTimer
+            | TargetEditorMessage::Save
+            | TargetEditorMessage::Apply
+            | TargetEditorMessage::Cancel
+            | TargetEditorMessage::Discard => false,
+            _ => true,
+        }
+    }
+}
+
+/// Field of a `DuplicationKind::Sftp`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RemoteField {
+    Host,
+    User,
+    Path,
+    /// Empty for the default port
+    Port,
+}
+
+/// What the user tried to do with unsaved changes in the editor
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Leave {
     Cancel,
+    Quit,
 }
 
 #[derive(Default)]
 pub struct TargetEditor {
     pub target: Target,
     pub error: Option<String>,
+    /// Whether `target` was changed since the editor was opened
+    pub dirty: bool,
+    /// Set when asking whether to discard unsaved changes
+    pub leaving: Option<Leave>,
+    s_keep_editing: button::State,
+    s_discard: button::State,
 
     s_name: text_input::State,
+    s_group: text_input::State,
+    s_description: text_input::State,
+    /// One per suggested group
+    s_group_suggestions: Vec<button::State>,
     s_new_source: button::State,
     s_new_exclude: button::State,
     s_save_button: button::State,
+    s_apply_button: button::State,
     s_cancel_button: button::State,
+    s_pre_hook: text_input::State,
+    s_post_hook: text_input::State,
+    s_pre_compress: pick_list::State<Opt<Option<Compression>>>,
+    s_tar_format: pick_list::State<TarFormat>,
+    /// Typed limits; applied to the target once they parse. Empty means no limit.
+    max_files: String,
+    max_gib: String,
+    s_max_files: text_input::State,
+    s_max_gib: text_input::State,
+    /// Whether the `tar` commands are shown
+    show_command: bool,
+    s_show_command: button::State,
+    s_copy_command: button::State,
+    s_command: Vec<text_input::State>,
 
     s_exclude: Vec<text_input::State>,
     s_delete_exclude_button: Vec<button::State>,
+    s_preview_exclude_button: Vec<button::State>,
+    /// Index of the previewed exclude, and the preview. `None` preview while scanning.
+    exclude_preview: Option<(usize, Option<Result<exclude::ExcludePreview, String>>)>,
+    s_paste_excludes: button::State,
+    /// Excludes pasted from the clipboard, waiting to be applied
+    pasted_excludes: Option<PastedExcludes>,
+    /// Number of excludes added by the last paste
+    pasted_count: Option<usize>,
 
     s_source: Vec<FilePicker>,
     s_delete_source_button: Vec<button::State>,
+    /// Whether a long list of sources is shown, rather than just counted
+    show_sources: bool,
+    s_toggle_sources: button::State,
+    s_sources_scrollable: scrollable::State,
+    show_excludes: bool,
+    s_toggle_excludes: button::State,
+    s_excludes_scrollable: scrollable::State,
+
+    s_new_duplication: button::State,
+    s_new_command_duplication: button::State,
+    s_new_remote_duplication: button::State,
+    s_duplication: Vec<DuplicationState>,
+
+    /// Repos the target can be moved to. Empty for new targets.
+    pub destinations: Vec<Opt<Uuid>>,
+    /// `RepoConfig::repo_excludes` of the repo of the target, added to its excludes in backups
+    pub repo_excludes: Vec<String>,
+    /// Whether the repo of the target compresses chunks, if known (see `rdedup::compresses`)
+    pub repo_compresses: Option<bool>,
+    /// Groups of the other targets of the repo, suggested when typing a group
+    pub groups: Vec<String>,
+    pub move_to: Option<Opt<Uuid>>,
+    s_move_to: pick_list::State<Opt<Uuid>>,
+    s_move: button::State,
+
+    /// Whether the target is saved in the config, which system timers back up. False for new
+    /// targets.
+    pub existing: bool,
+    pub timer_interval: timer::TimerInterval,
+    /// `None` while installing
+    timer_result: Option<Option<Result<String, String>>>,
+    s_timer_interval: pick_list::State<timer::TimerInterval>,
+    s_install_timer: button::State,
 
     s_scrollable: scrollable::State,
 }
-impl TargetEditor {
-    pub fn new_target(repo_id: Uuid) -> Self {
+
+/// The group input, with the existing groups matching what is typed as suggestions
+fn view_group<'a>(
+    group: Option<&str>,
+    groups: &[String],
+    s_group: &'a mut text_input::State,
+    s_suggestions: &'a mut Vec<button::State>,
+) -> Element<'a, TargetEditorMessage> {
+    let group = group.unwrap_or("");
+    let typed = group.trim().to_lowercase();
+    let suggestions: Vec<String> = groups
+        .iter()
+        .filter(|other| other.as_str() != group && other.to_lowercase().contains(&typed))
+        .take(5)
+        .cloned()
+        .collect();
+    let row = Row::new().spacing(8).push(Text::new("Group:")).push(
+        TextInput::new(s_group, "None", group, TargetEditorMessage::SetGroup)
+            .style(style::TextInput)
+            .size(TEXT_SIZE)
+            .width(Length::Units(200)),
+    );
+    zip_list(suggestions, s_suggestions)
+        .fold(row, |row, (suggestion, state)| {
+            row.push(
+                Button::new(state, Text::new(suggestion.as_str()).size(TEXT_SIZE - 4))
+                    .padding(4)
+                    .style(style::Button::Text)
+                    .on_press(TargetEditorMessage::SetGroup(suggestion)),
+            )
+        })
+        .push(help(
+            "Targets of a group are listed together in the Overview, under a header that \
+             collapses them.",
+        ))
+        .into()
+}
+
+fn pre_compress_option(compression: Option<Compression>) -> Opt<Option<Compression>> {
+    Opt {
+        name: match compression {
+            Some(compression) => compression.to_string(),
+            None => "Off".to_string(),
+        },
+        value: compression,
+    }
+}
+
+/// `items` of a list of `len` `noun`s; for a long list, behind a count with a toggle and in a
+/// scrollable of capped height. `items` may be left empty while the list is hidden.
+fn view_long_list<'a>(
+    items: Column<'a, TargetEditorMessage>,
+    len: usize,
+    noun: &str,
+    shown: bool,
+    toggle: TargetEditorMessage,
+    s_toggle: &'a mut button::State,
+    s_scrollable: &'a mut scrollable::State,
+) -> Element<'a, TargetEditorMessage> {
+    if len <= LONG_LIST {
+        return items.into();
+    }
+    let mut column = Column::new().spacing(4).push(
+        Row::new()
+            .spacing(8)
+            .push(Text::new(format!("{} {}", len, noun)).size(TEXT_SIZE - 4))
+            .push(
+                Button::new(
+                    s_toggle,
+                    Text::new(if shown { "HIDE" } else { "SHOW" }).size(TEXT_SIZE - 6),
+                )
+                .padding(2)
+                .style(style::Button::Text)
+                .on_press(toggle),
+            ),
+    );
+    if shown {
+        column = column.push(
+            Scrollable::new(s_scrollable)
+                .max_height(LONG_LIST_HEIGHT)
+                .push(items),
+        );
+    }
+    column.into()
+}
+
+fn view_exclude_preview<'a>(
+    preview: &Option<Result<exclude::ExcludePreview, String>>,
+) -> Element<'a, TargetEditorMessage> {
+    let preview = match preview {
+        None => return Text::new("Scanning...").size(TEXT_SIZE - 4).into(),
+        Some(Err(error)) => {
+            return Text::new(error)
+                .size(TEXT_SIZE - 4)
+                .color(Color::from_rgb(0.5, 0.0, 0.0))
+                .into()
+        }
+        Some(Ok(preview)) => preview,
+    };
+    let summary = if preview.capped {
+        format!(
+            "Matches {} of the first {} files scanned",
+            preview.count, preview.scanned
+        )
+    } else if preview.count == 0 {
+        "Matches nothing".to_string()
+    } else {
+        format!("Matches {} paths", preview.count)
+    };
+    preview
+        .sample
+        .iter()
+        .fold(
+            Column::new()
+                .padding(4)
+                .push(Text::new(summary).size(TEXT_SIZE - 4)),
+            |column, path| column.push(Text::new(path.display().to_string()).size(TEXT_SIZE - 6)),
+        )
+        .into()
+}
+
+/// Lines pasted as excludes, each validated
+#[derive(Default)]
+struct PastedExcludes {
+    lines: Vec<(String, Result<(), String>)>,
+    s_append: button::State,
+    s_replace: button::State,
+    s_cancel: button::State,
+}
+impl PastedExcludes {
+    /// One exclude per line, like a `.gitignore`. Empty lines and `#` comments are skipped.
+    fn parse(text: &str) -> Self {
         Self {
+            lines: text
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                .map(|line| (line.to_string(), exclude::validate(line)))
+                .collect(),
+            ..Default::default()
+        }
+    }
+    fn valid(&self) -> impl Iterator<Item = &String> {
+        self.lines
+            .iter()
+            .filter(|(_, valid)| valid.is_ok())
+            .map(|(line, _)| line)
+    }
+    fn view(&mut self) -> Element<'_, TargetEditorMessage> {
+        let n_valid = self.valid().count();
+        let mut column = Column::new().spacing(4).padding(4).push(
+            Text::new(if self.lines.is_empty() {
+                "The clipboard has no excludes. Copy a list with one pattern per line.".to_string()
+            } else {
+                format!("{} excludes in the clipboard", self.lines.len())
+            })
+            .size(TEXT_SIZE - 4),
+        );
+        for (_, valid) in &self.lines {
+            if let Err(error) = valid {
+                column = column.push(
+                    Text::new(format!("Skipped: {}", error))
+                        .size(TEXT_SIZE - 4)
+                        .color(Color::from_rgb(0.8, 0.2, 0.2)),
+                );
+            }
+        }
+        let mut append = Button::new(
+            &mut self.s_append,
+            Text::new(format!("ADD {}", n_valid)).size(TEXT_SIZE - 4),
+        )
+        .padding(4)
+        .style(style::Button::Primary);
+        let mut replace = Button::new(
+            &mut self.s_replace,
+            Text::new("REPLACE ALL").size(TEXT_SIZE - 4),
+        )
+        .padding(4)
+        .style(style::Button::Text);
+        if n_valid > 0 {
+            append = append.on_press(TargetEditorMessage::ApplyPastedExcludes(false));
+            replace = replace.on_press(TargetEditorMessage::ApplyPastedExcludes(true));
+        }
+        column
+            .push(
+                Row::new()
+                    .spacing(10)
+                    .push(
+                        Button::new(&mut self.s_cancel, Text::new("CANCEL").size(TEXT_SIZE - 4))
+                            .padding(4)
+                            .style(style::Button::Text)
+                            .on_press(TargetEditorMessage::CancelPastedExcludes),
+                    )
+                    .push(replace)
+                    .push(append),
+            )
+            .into()
+    }
+}
+
+/// UI state of one `Duplication`
+#[derive(Default)]
+struct DuplicationState {
+    s_interval: pick_list::State<Opt<Duration>>,
+    s_new_disk: button::State,
+    s_delete: button::State,
+    /// Picker and delete button per disk
+    s_disks: Vec<(FilePicker, button::State)>,
+    /// Typed arguments of a command; split on spaces into its arguments
+    args: String,
+    s_program: text_input::State,
+    s_args: text_input::State,
+    /// Typed port of an SSH duplication; applied once it parses. Empty for the default port.
+    port: String,
+    s_host: text_input::State,
+    s_user: text_input::State,
+    s_path: text_input::State,
+    s_port: text_input::State,
+    /// Typed window times; applied to the window once they parse
+    window_start: String,
+    window_end: String,
+    s_window_start: text_input::State,
+    s_window_end: text_input::State,
+    s_test: button::State,
+    /// Result of testing each disk, `None` while testing. Cleared when the disks change.
+    test: Option<Option<Vec<Result<(), String>>>>,
+}
+impl DuplicationState {
+    fn new(duplication: &Duplication) -> Self {
+        let window = duplication.window.unwrap_or_default();
+        Self {
+            s_disks: vec![Default::default(); duplication.kind.paths().len()],
+            args: match duplication.kind {
+                DuplicationKind::Command { ref args, .. } => args
+                    .iter()
+                    .map(|arg| quote_arg(arg))
+                    .collect::<Vec<_>>()
+                    .join(" "),
+                _ => String::new(),
+            },
+            port: match duplication.kind {
+                DuplicationKind::Sftp {
+                    port: Some(port), ..
+                } => port.to_string(),
+                _ => String::new(),
+            },
+            window_start: window.start.format(WINDOW_TIME_FORMAT).to_string(),
+            window_end: window.end.format(WINDOW_TIME_FORMAT).to_string(),
+            ..Default::default()
+        }
+    }
+}
+
+const GIB: f64 = (1u64 << 30) as f64;
+fn parse_max_files(text: &str) -> Option<Option<u64>> {
+    match text.trim() {
+        "" => Some(None),
+        text => text.parse().ok().map(Some),
+    }
+}
+fn parse_max_gib(text: &str) -> Option<Option<u64>> {
+    match text.trim() {
+        "" => Some(None),
+        text => match text.parse::<f64>() {
+            Ok(gib) if gib >= 0.0 => Some(Some((gib * GIB) as u64)),
+            _ => None,
+        },
+    }
+}
+
+const WINDOW_TIME_FORMAT: &str = "%H:%M";
+fn parse_window_time(text: &str) -> Option<NaiveTime> {
+    NaiveTime::parse_from_str(text.trim(), WINDOW_TIME_FORMAT).ok()
+}
+
+const INTERVALS: [(&str, u64); 3] = [
+    ("Hourly", 60 * 60),
+    ("Daily", 24 * 60 * 60),
+    ("Weekly", 7 * 24 * 60 * 60),
+];
+fn interval_option(interval: Duration) -> Opt<Duration> {
+    Opt {
+        name: match INTERVALS
+            .iter()
+            .find(|(_, secs)| *secs == interval.as_secs())
+        {
+            Some((name, _)) => name.to_string(),
+            None => match chrono::Duration::from_std(interval) {
+                Ok(interval) => format!("Every {}", format_duration(interval)),
+                Err(_) => "Never".to_string(),
+            },
+        },
+        value: interval,
+    }
+}
+
+/// Input of `field` of the SSH duplication with index `i`
+fn remote_input<'a>(
+    i: usize,
+    field: RemoteField,
+    state: &'a mut text_input::State,
+    placeholder: &str,
+    value: &str,
+) -> TextInput<'a, TargetEditorMessage> {
+    TextInput::new(state, placeholder, value, move |text| {
+        TargetEditorMessage::SetRemote(i, field, text)
+    })
+    .style(style::TextInput)
+    .size(TEXT_SIZE - 4)
+}
+
+fn view_duplication<'a>(
+    i: usize,
+    duplication: &Duplication,
+    state: &'a mut DuplicationState,
+) -> Element<'a, TargetEditorMessage> {
+    let mut intervals = INTERVALS
+        .iter()
+        .map(|(_, secs)| interval_option(Duration::from_secs(*secs)))
+        .collect::<Vec<_>>();
+    let selected = interval_option(duplication.interval);
+    if !intervals.contains(&selected) {
+        intervals.push(selected.clone());
+    }
+    let mut header = Row::new()
+        .spacing(8)
+        .push(
+            PickList::new(
+                &mut state.s_interval,
+                intervals,
+                Some(selected),
+                move |option| TargetEditorMessage::SetInterval(i, option),
+            )
+            .style(style::Dropdown),
+        )
+        .push(help(
+            "Time between copies of the repo. A copy that is due while the app is closed runs \
+             when it is opened next.",
+        ));
+    let command = match duplication.kind {
+        DuplicationKind::Command { ref program, .. } => Some(program),
+        _ => None,
+    };
+    let remote = matches!(duplication.kind, DuplicationKind::Sftp { .. });
+    if command.is_none() && !remote {
+        header = header.push(
+            Button::new(
+                &mut state.s_new_disk,
+                Text::new("ADD DISK").size(TEXT_SIZE - 4),
+            )
+            .padding(4)
+            .style(style::Button::Text)
+            .on_press(TargetEditorMessage::NewDisk(i)),
+        );
+    }
+    if command.is_none() {
+        header = header.push({
+            let mut test = Button::new(&mut state.s_test, Text::new("TEST").size(TEXT_SIZE - 4))
+                .padding(4)
+                .style(style::Button::Text);
+            if !matches!(state.test, Some(None)) {
+                test = test.on_press(TargetEditorMessage::TestDuplication(i));
+            }
+            test
+        });
+    }
+    header = header.push(
+        Button::new(&mut state.s_delete, Icon::Delete.text())
+            .on_press(TargetEditorMessage::DelDuplication(i))
+            .padding(0)
+            .style(style::Button::Icon {
+                hover_color: Color::from_rgb(0.7, 0.2, 0.2),
+            }),
+    );
+    let paths = duplication.kind.paths();
+    let rotation = paths.len() > 1;
+    let mut column = Column::new().spacing(4).push(header);
+    if let Some(program) = command {
+        column = column.push(
+            Row::new()
+                .spacing(8)
+                .push(Text::new("Program:").size(TEXT_SIZE - 4))
+                .push(
+                    TextInput::new(&mut state.s_program, "rclone", program, move |text| {
+                        TargetEditorMessage::SetProgram(i, text)
+                    })
+                    .style(style::TextInput)
+                    .size(TEXT_SIZE - 4),
+                )
+                .push(Text::new("Arguments:").size(TEXT_SIZE - 4))
+                .push(
+                    TextInput::new(
+                        &mut state.s_args,
+                        "sync $BUP_REPO_HOME remote:bup",
+                        &state.args,
+                        move |text| TargetEditorMessage::SetArgs(i, text),
+                    )
+                    .style(style::TextInput)
+                    .size(TEXT_SIZE - 4),
+                )
+                .push(help(
+                    "Runs the program to copy the repo elsewhere, e.g. with rclone. Arguments \
+                         are split at spaces outside quotes, and $BUP_REPO_HOME, $BUP_TARGET and \
+                         $BUP_SNAPSHOT (the latest snapshot) in them are replaced; the program \
+                         also gets these in its environment. There is no other shell expansion. \
+                         Its output goes to the log, and failure is retried.",
+                )),
+        );
+    }
+    if let DuplicationKind::Sftp {
+        ref host,
+        ref user,
+        ref path,
+        ..
+    } = duplication.kind
+    {
+        let mut row = Row::new()
+            .spacing(8)
+            .push(remote_input(
+                i,
+                RemoteField::User,
+                &mut state.s_user,
+                "User",
+                user,
+            ))
+            .push(Text::new("@").size(TEXT_SIZE - 4))
+            .push(remote_input(
+                i,
+                RemoteField::Host,
+                &mut state.s_host,
+                "Host",
+                host,
+            ))
+            .push(Text::new(":").size(TEXT_SIZE - 4))
+            .push(remote_input(
+                i,
+                RemoteField::Path,
+                &mut state.s_path,
+                "Folder",
+                path,
+            ))
+            .push(Text::new("Port:").size(TEXT_SIZE - 4))
+            .push(
+                remote_input(i, RemoteField::Port, &mut state.s_port, "22", &state.port)
+                    .width(Length::Units(70)),
+            )
+            .push(help(
+                "Copies the repo into a folder of the same name in the folder on the host, with \
+                 rsync over SSH; both need to be installed on both ends. Logs in with your SSH \
+                 keys or agent, never with a password, so set up key login first. An empty \
+                 user or port uses your SSH config.",
+            ));
+        match state.test {
+            Some(Some(ref results)) => match results.first() {
+                Some(Ok(())) => {
+                    row = row.push(
+                        Text::new("OK")
+                            .size(TEXT_SIZE - 4)
+                            .color(Color::from_rgb(0.0, 0.5, 0.0)),
+                    )
+                }
+                Some(Err(error)) => {
+                    row = row.push(
+                        Text::new(error.as_str())
+                            .size(TEXT_SIZE - 4)
+                            .color(Color::from_rgb(0.8, 0.2, 0.2)),
+                    )
+                }
+                None => (),
+            },
+            _ => (),
+        }
+        column = column.push(row);
+    }
+    if let Some(None) = state.test {
+        column = column.push(Text::new("Testing...").size(TEXT_SIZE - 4));
+    }
+    let test = match state.test {
+        Some(Some(ref results)) => &results[..],
+        _ => &[],
+    };
+    for (k, (path, (picker, s_delete))) in paths.into_iter().zip(&mut state.s_disks).enumerate() {
+        let path = Some(path.as_path()).filter(|path| !path.as_os_str().is_empty());
+        let mut row = Row::new().spacing(8).push(
+            picker
+                .view(path, TEXT_SIZE)
+                .map(move |msg| TargetEditorMessage::Disk(i, k, msg)),
+        );
+        if rotation {
+            row = row.push(
+                Button::new(s_delete, Icon::Delete.text())
+                    .on_press(TargetEditorMessage::DelDisk(i, k))
+                    .padding(0)
+                    .style(style::Button::Icon {
+                        hover_color: Color::from_rgb(0.7, 0.2, 0.2),
+                    }),
+            );
+        }
+        match test.get(k) {
+            Some(Ok(())) => {
+                row = row.push(
+                    Text::new("OK")
+                        .size(TEXT_SIZE - 4)
+                        .color(Color::from_rgb(0.0, 0.5, 0.0)),
+                )
+            }
+            Some(Err(error)) => {
+                row = row.push(
+                    Text::new(error.as_str())
+                        .size(TEXT_SIZE - 4)
+                        .color(Color::from_rgb(0.8, 0.2, 0.2)),
+                )
+            }
+            None => (),
+        }
+        column = column.push(row);
+    }
+    if rotation {
+        column = column.push(
+            Text::new("Each run copies to one mounted disk, alternating when several are mounted.")
+                .size(TEXT_SIZE - 4),
+        );
+    }
+    let mut window = Row::new().spacing(8).push(
+        Checkbox::new(
+            duplication.window.is_some(),
+            "Only start between",
+            move |b| TargetEditorMessage::SetWindow(i, b),
+        )
+        .size(TEXT_SIZE - 4)
+        .text_size(TEXT_SIZE - 4),
+    );
+    if duplication.window.is_some() {
+        window = window
+            .push(
+                TextInput::new(
+                    &mut state.s_window_start,
+                    "01:00",
+                    &state.window_start,
+                    move |text| TargetEditorMessage::SetWindowStart(i, text),
+                )
+                .style(style::TextInput)
+                .size(TEXT_SIZE - 4)
+                .width(Length::Units(60)),
+            )
+            .push(Text::new("and").size(TEXT_SIZE - 4))
+            .push(
+                TextInput::new(
+                    &mut state.s_window_end,
+                    "06:00",
+                    &state.window_end,
+                    move |text| TargetEditorMessage::SetWindowEnd(i, text),
+                )
+                .style(style::TextInput)
+                .size(TEXT_SIZE - 4)
+                .width(Length::Units(60)),
+            )
+            .push(help(
+                "Local time, as HH:MM. A copy that is due outside of these hours waits for the \
+                 next opening. The end may be after midnight, like 22:00 to 06:00.",
+            ));
+        if parse_window_time(&state.window_start).is_none()
+            || parse_window_time(&state.window_end).is_none()
+        {
+            window = window.push(
+                Text::new("Use HH:MM, like 23:30")
+                    .size(TEXT_SIZE - 4)
+                    .color(Color::from_rgb(0.8, 0.2, 0.2)),
+            );
+        }
+    }
+    column.push(window).into()
+}
+
+fn view_leave_confirm<'a>(
+    leaving: Leave,
+    s_keep_editing: &'a mut button::State,
+    s_discard: &'a mut button::State,
+) -> Container<'a, TargetEditorMessage> {
+    let question = match leaving {
+        Leave::Cancel => "Discard changes?",
+        Leave::Quit => "Discard changes and quit?",
+    };
+    let keep_editing = Button::new(
+        s_keep_editing,
+        Text::new("KEEP EDITING").size(TEXT_SIZE - 4),
+    )
+    .padding(8)
+    .style(style::Button::Text)
+    .on_press(TargetEditorMessage::KeepEditing);
+    let discard = Button::new(s_discard, Text::new("DISCARD").size(TEXT_SIZE - 4))
+        .padding(8)
+        .style(style::Button::Primary)
+        .on_press(TargetEditorMessage::Discard);
+    Container::new(
+        Column::new()
+            .spacing(10)
+            .push(Text::new(question))
+            .push(Row::new().spacing(10).push(keep_editing).push(discard)),
+    )
+    .width(Length::Fill)
+    .align_x(Horizontal::Right)
+}
+
+impl TargetEditor {
+    /// A new target, starting out with `excludes` (see `Config::default_excludes`)
+    pub fn new_target(repo_id: Uuid, excludes: Vec<String>) -> Self {
+        let mut editor = Self {
             target: Target {
+                id: Uuid::new_v4(),
                 repo: repo_id,
+                excludes,
                 ..Default::default()
             },
             ..Default::default()
-        }
+        };
+        editor.reconcile_state();
+        editor
     }
     pub fn with_target(target: Target) -> Self {
-        Self {
-            // Review; One must manually make sure that the lists of states have the same length as
-            // thet lists of values (or other state lists)
-            s_exclude: vec![Default::default(); target.excludes.len()],
-            s_delete_exclude_button: vec![Default::default(); target.excludes.len()],
-            s_source: vec![Default::default(); target.sources.len()],
-            s_delete_source_button: vec![Default::default(); target.sources.len()],
+        let mut editor = Self {
+            max_files: target
+                .max_files
+                .map(|max| max.to_string())
+                .unwrap_or_default(),
+            max_gib: target
+                .max_total_bytes
+                .map(|max| (max as f64 / GIB).to_string())
+                .unwrap_or_default(),
             target,
+            existing: true,
             ..Default::default()
+        };
+        editor.reconcile_state();
+        editor
+    }
+    /// Make the lists of widget state as long as the lists they are the state of. The state is
+    /// `izip!`ed with the values, so an item with missing state would not show. Updates keep them
+    /// in sync already; this catches the ones that forget to.
+    fn reconcile_state(&mut self) {
+        let n_sources = self.target.sources.len();
+        self.s_source.resize_with(n_sources, Default::default);
+        self.s_delete_source_button
+            .resize_with(n_sources, Default::default);
+        let n_excludes = self.target.excludes.len();
+        self.s_exclude.resize_with(n_excludes, Default::default);
+        self.s_delete_exclude_button
+            .resize_with(n_excludes, Default::default);
+        self.s_preview_exclude_button
+            .resize_with(n_excludes, Default::default);
+        let duplications = &self.target.duplication;
+        self.s_duplication.truncate(duplications.len());
+        let n_states = self.s_duplication.len();
+        self.s_duplication
+            .extend(duplications[n_states..].iter().map(DuplicationState::new));
+        for (duplication, state) in duplications.iter().zip(&mut self.s_duplication) {
+            state
+                .s_disks
+                .resize_with(duplication.kind.paths().len(), Default::default);
         }
     }
-    pub fn view(&mut self) -> Element<'_, TargetEditorMessage> {
+    /// `narrow`: stack rows vertically, for narrow windows
+    pub fn view(&mut self, narrow: bool) -> Element<'_, TargetEditorMessage> {
+        self.reconcile_state();
+        let exclude_presets = &self.target.exclude_presets;
+        let exclude_preview = &self.exclude_preview;
+        let n_excludes = self.target.excludes.len();
+        let excludes_collapsed = n_excludes > LONG_LIST && !self.show_excludes;
         let mut x = Column::new()
             .padding(20)
             .spacing(20)
@@ -77,10 +925,26 @@ impl TargetEditor {
                     .size(H3_SIZE),
                 ),
             )
+            .push(view_group(
+                self.target.group.as_deref(),
+                &self.groups,
+                &mut self.s_group,
+                &mut self.s_group_suggestions,
+            ))
+            .push(
+                TextInput::new(
+                    &mut self.s_description,
+                    "Description, e.g. what is backed up and why",
+                    &self.target.description,
+                    TargetEditorMessage::SetDescription,
+                )
+                .style(style::TextInput)
+                .size(TEXT_SIZE),
+            )
             // Sources
             .push(
                 Container::new({
-                    let mut col = Column::new().push(
+                    let col = Column::new().push(
                         Row::new().spacing(20).push(h3("Sources")).push(
                             // TODO: icon button
                             Button::new(&mut self.s_new_source, Icon::New.text())
@@ -91,31 +955,54 @@ impl TargetEditor {
                                 .on_press(TargetEditorMessage::NewSource),
                         ),
                     );
+                    let n_sources = self.target.sources.len();
+                    let collapsed = n_sources > LONG_LIST && !self.show_sources;
+                    let mut items = Column::new();
                     for (i, (source, del_button, file_picker)) in izip!(
                         &self.target.sources,
                         &mut self.s_delete_source_button,
                         &mut self.s_source
                     )
                     .enumerate()
+                    .take_while(|_| !collapsed)
                     {
-                        col = col.push(
-                            Row::new()
-                                .push(
-                                    file_picker
-                                        .view(source.as_ref().map(|x| x.as_path()), TEXT_SIZE)
-                                        .map(move |msg| TargetEditorMessage::Source(i, msg)),
+                        let picker = file_picker
+                            .view(source.path.as_deref(), TEXT_SIZE)
+                            .map(move |msg| TargetEditorMessage::Source(i, msg));
+                        let options = Row::new()
+                            .spacing(8)
+                            .push(
+                                Checkbox::new(
+                                    source.one_file_system,
+                                    "One file system",
+                                    move |b| TargetEditorMessage::SetOneFileSystem(i, b),
                                 )
-                                .push(
-                                    Button::new(del_button, Icon::Delete.text())
-                                        .on_press(TargetEditorMessage::DelSource(i))
-                                        .padding(0)
-                                        .style(style::Button::Icon {
-                                            hover_color: Color::from_rgb(0.7, 0.2, 0.2),
-                                        }),
-                                ),
-                        );
+                                .size(TEXT_SIZE - 4)
+                                .text_size(TEXT_SIZE - 4),
+                            )
+                            .push(
+                                Button::new(del_button, Icon::Delete.text())
+                                    .on_press(TargetEditorMessage::DelSource(i))
+                                    .padding(0)
+                                    .style(style::Button::Icon {
+                                        hover_color: Color::from_rgb(0.7, 0.2, 0.2),
+                                    }),
+                            );
+                        items = if narrow {
+                            items.push(Column::new().spacing(4).push(picker).push(options))
+                        } else {
+                            items.push(Row::new().push(picker).push(options))
+                        };
                     }
-                    col
+                    col.push(view_long_list(
+                        items,
+                        n_sources,
+                        "sources",
+                        self.show_sources,
+                        TargetEditorMessage::ToggleSources,
+                        &mut self.s_toggle_sources,
+                        &mut self.s_sources_scrollable,
+                    ))
                 })
                 .width(Length::FillPortion(1)),
             )
@@ -124,26 +1011,55 @@ impl TargetEditor {
                 Container::new(
                     Column::new()
                         .push(
-                            Row::new().spacing(20).push(h3("Excludes")).push(
-                                Button::new(&mut self.s_new_exclude, Icon::New.text())
-                                    .style(style::Button::Icon {
-                                        hover_color: Color::WHITE,
-                                    })
-                                    .padding(BUTTON_PAD)
-                                    .on_press(TargetEditorMessage::NewExclude),
-                            ),
+                            Row::new()
+                                .spacing(20)
+                                .push(h3("Excludes"))
+                                .push(
+                                    Button::new(&mut self.s_new_exclude, Icon::New.text())
+                                        .style(style::Button::Icon {
+                                            hover_color: Color::WHITE,
+                                        })
+                                        .padding(BUTTON_PAD)
+                                        .on_press(TargetEditorMessage::NewExclude),
+                                )
+                                .push(
+                                    Button::new(
+                                        &mut self.s_paste_excludes,
+                                        Text::new("PASTE LIST").size(TEXT_SIZE - 6),
+                                    )
+                                    .padding(2)
+                                    .style(style::Button::Text)
+                                    .on_press(TargetEditorMessage::PasteExcludes),
+                                )
+                                .push(help(
+                                    "Patterns as for tar --exclude. * and ? are wildcards, and * \
+                                     also matches /. A pattern matches anywhere in the path, so \
+                                     \"node_modules\" excludes every node_modules folder and \
+                                     \"*.tmp\" every .tmp file.",
+                                )),
                         )
-                        .push(
+                        .push(match (&mut self.pasted_excludes, self.pasted_count) {
+                            (Some(pasted), _) => pasted.view(),
+                            (None, Some(count)) => {
+                                Text::new(format!("Added {} excludes", count))
+                                    .size(TEXT_SIZE - 4)
+                                    .into()
+                            }
+                            (None, None) => Column::new().into(),
+                        })
+                        .push(view_long_list(
                             self.target
                                 .excludes
                                 .iter_mut()
                                 .zip(self.s_exclude.iter_mut())
                                 .zip(self.s_delete_exclude_button.iter_mut())
+                                .zip(self.s_preview_exclude_button.iter_mut())
                                 .enumerate()
+                                .take_while(|_| !excludes_collapsed)
                                 .fold(
                                     Column::new(),
-                                    |column, (i, ((exclude, state), del_button))| {
-                                        column.push(
+                                    |column, (i, (((exclude, state), del_button), preview_button))| {
+                                        let column = column.push(
                                             Row::new()
                                                 .push(
                                                     TextInput::new(
@@ -157,6 +1073,17 @@ impl TargetEditor {
                                                     .style(style::TextInput)
                                                     .size(TEXT_SIZE),
                                                 )
+                                                .push(
+                                                    Button::new(
+                                                        preview_button,
+                                                        Text::new("PREVIEW").size(TEXT_SIZE - 6),
+                                                    )
+                                                    .on_press(TargetEditorMessage::PreviewExclude(
+                                                        i,
+                                                    ))
+                                                    .padding(2)
+                                                    .style(style::Button::Text),
+                                                )
                                                 .push(
                                                     Button::new(del_button, Icon::Delete.text())
                                                         .on_press(TargetEditorMessage::DelExclude(
@@ -169,39 +1096,452 @@ impl TargetEditor {
                                                             ),
                                                         }),
                                                 ),
-                                        )
+                                        );
+                                        match exclude_preview {
+                                            Some((j, preview)) if *j == i => {
+                                                column.push(view_exclude_preview(preview))
+                                            }
+                                            _ => column,
+                                        }
                                     },
                                 ),
-                        ),
+                            n_excludes,
+                            "excludes",
+                            self.show_excludes,
+                            TargetEditorMessage::ToggleExcludes,
+                            &mut self.s_toggle_excludes,
+                            &mut self.s_excludes_scrollable,
+                        ))
+                        .push(
+                            Checkbox::new(
+                                self.target.exclude_hidden,
+                                "Exclude hidden files",
+                                TargetEditorMessage::SetExcludeHidden,
+                            )
+                            .size(TEXT_SIZE)
+                            .text_size(TEXT_SIZE),
+                        )
+                        .push(ExcludePreset::ALL.iter().fold(
+                            Column::new().spacing(4),
+                            |column, &preset| {
+                                column.push(
+                                    Checkbox::new(
+                                        exclude_presets.contains(&preset),
+                                        if preset.supported(*backup::TAR) {
+                                            preset.to_string()
+                                        } else {
+                                            format!("{} (needs GNU tar)", preset)
+                                        },
+                                        move |b| TargetEditorMessage::SetExcludePreset(preset, b),
+                                    )
+                                    .size(TEXT_SIZE - 4)
+                                    .text_size(TEXT_SIZE - 4),
+                                )
+                            },
+                        )),
                 )
                 .width(Length::FillPortion(1)),
             )
+            // Hooks
             .push(
-                Container::new(
+                Column::new()
+                    .push(h3("Hooks"))
+                    .push(
+                        TextInput::new(
+                            &mut self.s_pre_hook,
+                            "Command to run before backup",
+                            self.target.pre_hook.as_deref().unwrap_or(""),
+                            TargetEditorMessage::SetPreHook,
+                        )
+                        .style(style::TextInput)
+                        .size(TEXT_SIZE),
+                    )
+                    .push(
+                        TextInput::new(
+                            &mut self.s_post_hook,
+                            "Command to run after backup",
+                            self.target.post_hook.as_deref().unwrap_or(""),
+                            TargetEditorMessage::SetPostHook,
+                        )
+                        .style(style::TextInput)
+                        .size(TEXT_SIZE),
+                    ),
+            )
+            // Duplication
+            .push(
+                self.target
+                    .duplication
+                    .iter()
+                    .zip(self.s_duplication.iter_mut())
+                    .enumerate()
+                    .fold(
+                        Column::new().spacing(8).push(
+                            Row::new().spacing(20).push(h3("Duplication")).push(
+                                Button::new(&mut self.s_new_duplication, Icon::New.text())
+                                    .style(style::Button::Icon {
+                                        hover_color: Color::WHITE,
+                                    })
+                                    .padding(BUTTON_PAD)
+                                    .on_press(TargetEditorMessage::NewDuplication),
+                            ).push(
+                                Button::new(
+                                    &mut self.s_new_command_duplication,
+                                    Text::new("ADD COMMAND").size(TEXT_SIZE - 4),
+                                )
+                                .padding(4)
+                                .style(style::Button::Text)
+                                .on_press(TargetEditorMessage::NewCommandDuplication),
+                            ).push(
+                                Button::new(
+                                    &mut self.s_new_remote_duplication,
+                                    Text::new("ADD SSH REMOTE").size(TEXT_SIZE - 4),
+                                )
+                                .padding(4)
+                                .style(style::Button::Text)
+                                .on_press(TargetEditorMessage::NewRemoteDuplication),
+                            ),
+                        ),
+                        |column, (i, (duplication, state))| {
+                            column.push(view_duplication(i, duplication, state))
+                        },
+                    ),
+            )
+            // Limits
+            .push({
+                let mut column = Column::new()
+                    .spacing(4)
+                    .push(h3("Limits"))
+                    .push(
+                        Text::new(
+                            "Ask before backing up more than this, to catch a wrong source. \
+                             Leave empty for no limit.",
+                        )
+                        .size(TEXT_SIZE - 4),
+                    )
+                    .push(
+                        Row::new()
+                            .spacing(8)
+                            .push(Text::new("Files:"))
+                            .push(
+                                TextInput::new(
+                                    &mut self.s_max_files,
+                                    "No limit",
+                                    &self.max_files,
+                                    TargetEditorMessage::SetMaxFiles,
+                                )
+                                .style(style::TextInput)
+                                .size(TEXT_SIZE)
+                                .width(Length::Units(120)),
+                            )
+                            .push(Text::new("Size (GiB):"))
+                            .push(
+                                TextInput::new(
+                                    &mut self.s_max_gib,
+                                    "No limit",
+                                    &self.max_gib,
+                                    TargetEditorMessage::SetMaxGib,
+                                )
+                                .style(style::TextInput)
+                                .size(TEXT_SIZE)
+                                .width(Length::Units(120)),
+                            ),
+                    )
+                    .push(
+                        Checkbox::new(
+                            self.target.preview_backups,
+                            "Preview every backup",
+                            TargetEditorMessage::SetPreviewBackups,
+                        )
+                        .size(TEXT_SIZE)
+                        .text_size(TEXT_SIZE),
+                    )
+                    .push(
+                        Text::new(
+                            "The first backup of a target is always previewed: it shows how many \
+                             files it would archive, and waits for confirmation.",
+                        )
+                        .size(TEXT_SIZE - 4),
+                    );
+                if parse_max_files(&self.max_files).is_none()
+                    || parse_max_gib(&self.max_gib).is_none()
+                {
+                    column = column.push(
+                        Text::new("Limits should be numbers")
+                            .size(TEXT_SIZE - 4)
+                            .color(Color::from_rgb(0.8, 0.2, 0.2)),
+                    );
+                }
+                column
+            })
+            // Archive
+            .push({
+                let mut column = Column::new().spacing(4).push(h3("Archive")).push(
                     Row::new()
-                        .spacing(10)
+                        .spacing(8)
+                        .push(Text::new("Compress archive before deduplication:"))
                         .push(
-                            Button::new(
-                                &mut self.s_cancel_button,
-                                Text::new("CANCEL").size(TEXT_SIZE - 4),
+                            PickList::new(
+                                &mut self.s_pre_compress,
+                                std::iter::once(None)
+                                    .chain(Compression::ALL.iter().copied().map(Some))
+                                    .map(pre_compress_option)
+                                    .collect::<Vec<_>>(),
+                                Some(pre_compress_option(self.target.pre_compress)),
+                                TargetEditorMessage::SetPreCompress,
                             )
-                            .padding(8)
-                            .style(style::Button::Text)
-                            .on_press(TargetEditorMessage::Cancel),
+                            .style(style::Dropdown),
+                        ),
+                );
+                column = column.push(
+                    Row::new()
+                        .spacing(8)
+                        .push(Text::new("Archive format:"))
+                        .push(
+                            PickList::new(
+                                &mut self.s_tar_format,
+                                &TarFormat::ALL[..],
+                                Some(self.target.tar_format),
+                                TargetEditorMessage::SetTarFormat,
+                            )
+                            .style(style::Dropdown),
+                        ),
+                );
+                column = column.push(
+                    Row::new()
+                        .spacing(8)
+                        .push(
+                            Checkbox::new(
+                                self.target.handle_sparse,
+                                "Handle sparse files",
+                                TargetEditorMessage::SetHandleSparse,
+                            )
+                            .size(TEXT_SIZE)
+                            .text_size(TEXT_SIZE),
+                        )
+                        .push(help(
+                            "Stores the holes of sparse files, like VM images and databases, as \
+                             holes instead of zeros. bup restores them as sparse files; other \
+                             tools restoring the archive need a tar that supports sparse files \
+                             (GNU tar or bsdtar), or write the zeros out in full.",
+                        )),
+                );
+                if self.target.tar_format != TarFormat::Posix {
+                    column = column.push(
+                        Text::new(match self.target.tar_format {
+                            TarFormat::Ustar => {
+                                "ustar fails on long paths and files over 8 GiB, and drops \
+                                 extended attributes."
+                            }
+                            _ => {
+                                "Restoring with a tar other than GNU tar or bsdtar may lose long \
+                                 paths."
+                            }
+                        })
+                        .size(TEXT_SIZE - 4)
+                        .color(Color::from_rgb(0.9, 0.6, 0.2)),
+                    );
+                }
+                if self.target.pre_compress.is_some() && self.repo_compresses == Some(true) {
+                    column = column.push(
+                        Text::new(
+                            "The repo compresses chunks already; compressing twice wastes CPU and \
+                             compressed data deduplicates worse.",
+                        )
+                        .size(TEXT_SIZE - 4)
+                        .color(Color::from_rgb(0.9, 0.6, 0.2)),
+                    );
+                }
+                column
+            })
+            // Command
+            .push({
+                let mut column = Column::new().spacing(4).push(
+                    Button::new(
+                        &mut self.s_show_command,
+                        Text::new(if self.show_command {
+                            "HIDE COMMAND"
+                        } else {
+                            "SHOW COMMAND"
+                        })
+                        .size(TEXT_SIZE - 4),
+                    )
+                    .padding(4)
+                    .style(style::Button::Text)
+                    .on_press(TargetEditorMessage::ToggleCommand),
+                );
+                if self.show_command {
+                    let target = backup::with_repo_excludes(&self.target, &self.repo_excludes);
+                    let commands = backup::tar_commands(&target);
+                    if commands.is_empty() {
+                        column = column.push(Text::new("No sources").size(TEXT_SIZE - 4));
+                    }
+                    let from_target: Vec<&str> = target
+                        .excludes
+                        .iter()
+                        .filter(|exclude| !self.repo_excludes.contains(*exclude))
+                        .map(String::as_str)
+                        .collect();
+                    let from_repo: Vec<&str> =
+                        self.repo_excludes.iter().map(String::as_str).collect();
+                    for (origin, excludes) in [("repo", from_repo), ("target", from_target)] {
+                        if !excludes.is_empty() {
+                            column = column.push(
+                                Text::new(format!(
+                                    "Excludes from the {}: {}",
+                                    origin,
+                                    excludes.join(", ")
+                                ))
+                                .size(TEXT_SIZE - 4),
+                            );
+                        }
+                    }
+                    // Text inputs, so that the text can be selected
+                    for (command, state) in zip_list(commands, &mut self.s_command) {
+                        column = column.push(
+                            TextInput::new(state, "", &command, TargetEditorMessage::CommandEdited)
+                                .style(style::TextInput)
+                                .size(TEXT_SIZE - 4),
+                        );
+                    }
+                    column = column.push(
+                        Button::new(
+                            &mut self.s_copy_command,
+                            Text::new("COPY").size(TEXT_SIZE - 4),
                         )
+                        .padding(4)
+                        .style(style::Button::Text)
+                        .on_press(TargetEditorMessage::CopyCommand),
+                    );
+                }
+                column
+            })
+            // Move to another repo
+            .push({
+                let mut column = Column::new().spacing(4);
+                if !self.destinations.is_empty() {
+                    let mut move_button =
+                        Button::new(&mut self.s_move, Text::new("MOVE").size(TEXT_SIZE - 4))
+                            .padding(4)
+                            .style(style::Button::Text);
+                    if self.move_to.is_some() {
+                        move_button = move_button.on_press(TargetEditorMessage::MoveToRepo);
+                    }
+                    column = column
+                        .push(h3("Repo"))
                         .push(
-                            Button::new(
-                                &mut self.s_save_button,
-                                Text::new("SAVE").size(TEXT_SIZE - 4),
+                            Row::new()
+                                .spacing(8)
+                                .push(Text::new("Move to repo:"))
+                                .push(
+                                    PickList::new(
+                                        &mut self.s_move_to,
+                                        &self.destinations[..],
+                                        self.move_to.clone(),
+                                        TargetEditorMessage::SetMoveTo,
+                                    )
+                                    .style(style::Dropdown),
+                                )
+                                .push(move_button),
+                        )
+                        .push(
+                            Text::new(
+                                "Existing snapshots stay in the current repo; they aren't moved \
+                                 along. Moving also saves the other changes.",
+                            )
+                            .size(TEXT_SIZE - 4)
+                            .color(Color::from_rgb(0.9, 0.6, 0.2)),
+                        );
+                }
+                column
+            })
+            // System timer
+            .push({
+                let mut column = Column::new().spacing(4);
+                if self.existing {
+                    let mut install = Button::new(
+                        &mut self.s_install_timer,
+                        Text::new("INSTALL AS SYSTEM TIMER").size(TEXT_SIZE - 4),
+                    )
+                    .padding(4)
+                    .style(style::Button::Text);
+                    if !matches!(self.timer_result, Some(None)) {
+                        install = install.on_press(TargetEditorMessage::InstallTimer);
+                    }
+                    column = column.push(h3("System timer")).push(
+                        Row::new()
+                            .spacing(8)
+                            .push(Text::new("Back up"))
+                            .push(
+                                PickList::new(
+                                    &mut self.s_timer_interval,
+                                    &timer::TimerInterval::ALL[..],
+                                    Some(self.timer_interval),
+                                    TargetEditorMessage::SetTimerInterval,
+                                )
+                                .style(style::Dropdown),
                             )
-                            .padding(8)
-                            .style(style::Button::Primary)
-                            .on_press(TargetEditorMessage::Save),
+                            .push(install)
+                            .push(help(
+                                "Backs up the saved target with systemd (Linux) or launchd \
+                                 (macOS), also while bup is closed. The passphrase has to be \
+                                 remembered in the keyring.",
+                            )),
+                    );
+                    column = match self.timer_result {
+                        Some(None) => column.push(Text::new("Installing...").size(TEXT_SIZE - 4)),
+                        Some(Some(Ok(ref message))) => {
+                            column.push(Text::new(message.as_str()).size(TEXT_SIZE - 4))
+                        }
+                        Some(Some(Err(ref error))) => column.push(
+                            Text::new(format!("Error: {}", error))
+                                .size(TEXT_SIZE - 4)
+                                .color(Color::from_rgb(0.5, 0.0, 0.0)),
                         ),
+                        None => column,
+                    };
+                }
+                column
+            })
+            .push(if let Some(leaving) = self.leaving {
+                view_leave_confirm(leaving, &mut self.s_keep_editing, &mut self.s_discard)
+            } else {
+                let cancel = Button::new(
+                    &mut self.s_cancel_button,
+                    Text::new("CANCEL").size(TEXT_SIZE - 4),
                 )
-                .width(Length::Fill)
-                .align_x(Horizontal::Right),
-            );
+                .padding(8)
+                .style(style::Button::Text)
+                .on_press(TargetEditorMessage::Cancel);
+                let save = Button::new(
+                    &mut self.s_save_button,
+                    Text::new("SAVE").size(TEXT_SIZE - 4),
+                )
+                .padding(8)
+                .style(style::Button::Primary)
+                .on_press(TargetEditorMessage::Save);
+                let apply = Button::new(
+                    &mut self.s_apply_button,
+                    Text::new("APPLY").size(TEXT_SIZE - 4),
+                )
+                .padding(8)
+                .style(style::Button::Text)
+                .on_press(TargetEditorMessage::Apply);
+                if narrow {
+                    // Full width, so that they are easy to hit
+                    Container::new(
+                        Column::new()
+                            .spacing(10)
+                            .push(save.width(Length::Fill))
+                            .push(apply.width(Length::Fill))
+                            .push(cancel.width(Length::Fill)),
+                    )
+                    .width(Length::Fill)
+                } else {
+                    Container::new(Row::new().spacing(10).push(cancel).push(apply).push(save))
+                        .width(Length::Fill)
+                        .align_x(Horizontal::Right)
+                }
+            });
         if let Some(ref error) = self.error {
             x = x.push(Text::new(error).color(Color::from_rgb(0.5, 0.0, 0.0)))
         }
@@ -213,44 +1553,453 @@ impl TargetEditor {
         let x = Scrollable::new(&mut self.s_scrollable).push(x);
         x.into()
     }
+    fn push_source(&mut self, source: Source) {
+        self.target.sources.push(source);
+        self.s_delete_source_button.push(Default::default());
+        self.s_source.push(Default::default());
+    }
     pub fn update(&mut self, message: TargetEditorMessage) -> Command<TargetEditorMessage> {
+        self.reconcile_state();
+        if message.is_edit() {
+            self.dirty = true;
+        }
         match message {
             TargetEditorMessage::SetName(name) => self.target.name = name,
+            TargetEditorMessage::SetGroup(group) => {
+                self.target.group = Some(group).filter(|group| !group.trim().is_empty())
+            }
+            TargetEditorMessage::SetDescription(description) => {
+                self.target.description = description
+            }
             TargetEditorMessage::NewSource => {
-                self.target.sources.push(Default::default());
-                self.s_delete_source_button.push(Default::default());
-                // Review; I forgot once to put the following line here
-                // Makes the UI malfunction due to how I izip! the iterators
-                self.s_source.push(Default::default());
+                self.push_source(Default::default());
+                self.show_sources = true;
+            }
+            TargetEditorMessage::Dropped(path) => {
+                if path.is_dir() {
+                    self.push_source(Source {
+                        path: Some(path),
+                        ..Default::default()
+                    });
+                    self.error = None;
+                } else {
+                    self.error = Some(format!(
+                        "{} is not a folder. Sources should be folders.",
+                        path.display()
+                    ));
+                }
             }
             TargetEditorMessage::Source(i, msg) => {
                 if let path::Message::Path(ref path) = msg {
-                    self.target.sources[i] = Some(path.clone());
+                    self.target.sources[i].path = Some(path.clone());
                 }
                 return self.s_source[i]
                     .update(msg)
                     .map(move |msg| TargetEditorMessage::Source(i, msg));
             }
+            TargetEditorMessage::SetOneFileSystem(i, one_file_system) => {
+                self.target.sources[i].one_file_system = one_file_system
+            }
             TargetEditorMessage::DelSource(i) => {
                 self.target.sources.remove(i);
+                self.s_source.remove(i);
+                self.s_delete_source_button.remove(i);
             }
+            TargetEditorMessage::ToggleSources => self.show_sources = !self.show_sources,
+            TargetEditorMessage::ToggleExcludes => self.show_excludes = !self.show_excludes,
             TargetEditorMessage::NewExclude => {
+                self.show_excludes = true;
                 self.target.excludes.push(Default::default());
                 self.s_exclude.push(Default::default());
                 self.s_delete_exclude_button.push(Default::default());
+                self.s_preview_exclude_button.push(Default::default());
+            }
+            TargetEditorMessage::SetExclude(i, exclude) => {
+                self.target.excludes[i] = exclude;
+                if matches!(self.exclude_preview, Some((j, _)) if j == i) {
+                    self.exclude_preview = None;
+                }
             }
-            TargetEditorMessage::SetExclude(i, exclude) => self.target.excludes[i] = exclude,
             TargetEditorMessage::DelExclude(i) => {
                 self.target.excludes.remove(i);
+                self.s_exclude.remove(i);
+                self.s_delete_exclude_button.remove(i);
+                self.s_preview_exclude_button.remove(i);
+                self.exclude_preview = None;
+            }
+            TargetEditorMessage::PasteExcludes => {
+                self.pasted_count = None;
+                return iced::clipboard::read(TargetEditorMessage::ExcludesPasted);
+            }
+            TargetEditorMessage::ExcludesPasted(text) => {
+                self.pasted_excludes = Some(PastedExcludes::parse(&text.unwrap_or_default()))
+            }
+            TargetEditorMessage::ApplyPastedExcludes(replace) => {
+                if let Some(pasted) = self.pasted_excludes.take() {
+                    if replace {
+                        self.target.excludes.clear();
+                    }
+                    let mut count = 0;
+                    for line in pasted.valid() {
+                        if !self.target.excludes.contains(line) {
+                            self.target.excludes.push(line.clone());
+                            count += 1;
+                        }
+                    }
+                    let n = self.target.excludes.len();
+                    self.s_exclude.resize(n, Default::default());
+                    self.s_delete_exclude_button.resize(n, Default::default());
+                    self.s_preview_exclude_button.resize(n, Default::default());
+                    self.exclude_preview = None;
+                    self.pasted_count = Some(count);
+                }
+            }
+            TargetEditorMessage::CancelPastedExcludes => self.pasted_excludes = None,
+            TargetEditorMessage::PreviewExclude(i) => {
+                let sources = self
+                    .target
+                    .sources
+                    .iter()
+                    .filter_map(|source| source.path.clone())
+                    .collect();
+                let pattern = self.target.excludes[i].clone();
+                self.exclude_preview = Some((i, None));
+                return Command::perform(
+                    exclude::preview_async(sources, pattern.clone()),
+                    move |result| TargetEditorMessage::ExcludePreviewed(i, pattern, result),
+                );
+            }
+            TargetEditorMessage::ExcludePreviewed(i, pattern, result) => {
+                // Drop the result if the pattern changed meanwhile
+                let current = matches!(self.exclude_preview, Some((j, None)) if j == i);
+                if current && self.target.excludes.get(i) == Some(&pattern) {
+                    self.exclude_preview = Some((i, Some(result)));
+                }
+            }
+            TargetEditorMessage::SetExcludeHidden(exclude_hidden) => {
+                self.target.exclude_hidden = exclude_hidden
+            }
+            TargetEditorMessage::SetExcludePreset(preset, enabled) => {
+                self.target.exclude_presets.retain(|p| *p != preset);
+                if enabled {
+                    self.target.exclude_presets.push(preset);
+                }
+            }
+            TargetEditorMessage::SetPreHook(hook) => {
+                self.target.pre_hook = Some(hook).filter(|hook| !hook.is_empty())
+            }
+            TargetEditorMessage::SetPostHook(hook) => {
+                self.target.post_hook = Some(hook).filter(|hook| !hook.is_empty())
+            }
+            TargetEditorMessage::NewDuplication => {
+                let duplication = Duplication::new(
+                    Duration::from_secs(INTERVALS[1].1),
+                    DuplicationKind::Disk {
+                        path: PathBuf::new(),
+                    },
+                );
+                self.s_duplication.push(DuplicationState::new(&duplication));
+                self.target.duplication.push(duplication);
+            }
+            TargetEditorMessage::NewCommandDuplication => {
+                let duplication = Duplication::new(
+                    Duration::from_secs(INTERVALS[1].1),
+                    DuplicationKind::Command {
+                        program: String::new(),
+                        args: Vec::new(),
+                    },
+                );
+                self.s_duplication.push(DuplicationState::new(&duplication));
+                self.target.duplication.push(duplication);
             }
-            TargetEditorMessage::Save => {
+            TargetEditorMessage::NewRemoteDuplication => {
+                let duplication = Duplication::new(
+                    Duration::from_secs(INTERVALS[1].1),
+                    DuplicationKind::Sftp {
+                        host: String::new(),
+                        user: String::new(),
+                        path: String::new(),
+                        port: None,
+                    },
+                );
+                self.s_duplication.push(DuplicationState::new(&duplication));
+                self.target.duplication.push(duplication);
+            }
+            TargetEditorMessage::DelDuplication(i) => {
+                self.target.duplication.remove(i);
+                self.s_duplication.remove(i);
+            }
+            TargetEditorMessage::SetInterval(i, option) => {
+                self.target.duplication[i].interval = option.value
+            }
+            TargetEditorMessage::SetWindow(i, enabled) => {
+                let state = &self.s_duplication[i];
+                self.target.duplication[i].window = if enabled {
+                    let default = TimeWindow::default();
+                    Some(TimeWindow {
+                        start: parse_window_time(&state.window_start).unwrap_or(default.start),
+                        end: parse_window_time(&state.window_end).unwrap_or(default.end),
+                    })
+                } else {
+                    None
+                };
+            }
+            TargetEditorMessage::SetWindowStart(i, text) => {
+                if let (Some(time), Some(window)) = (
+                    parse_window_time(&text),
+                    &mut self.target.duplication[i].window,
+                ) {
+                    window.start = time;
+                }
+                self.s_duplication[i].window_start = text;
+            }
+            TargetEditorMessage::SetWindowEnd(i, text) => {
+                if let (Some(time), Some(window)) = (
+                    parse_window_time(&text),
+                    &mut self.target.duplication[i].window,
+                ) {
+                    window.end = time;
+                }
+                self.s_duplication[i].window_end = text;
+            }
+            TargetEditorMessage::NewDisk(i) => {
+                self.target.duplication[i].kind.push_path(PathBuf::new());
+                self.s_duplication[i].s_disks.push(Default::default());
+                self.s_duplication[i].test = None;
+            }
+            TargetEditorMessage::Disk(i, k, msg) => {
+                if let path::Message::Path(ref path) = msg {
+                    *self.target.duplication[i].kind.paths_mut()[k] = path.clone();
+                    self.s_duplication[i].test = None;
+                }
+                return self.s_duplication[i].s_disks[k]
+                    .0
+                    .update(msg)
+                    .map(move |msg| TargetEditorMessage::Disk(i, k, msg));
+            }
+            TargetEditorMessage::SetProgram(i, text) => {
+                if let DuplicationKind::Command {
+                    ref mut program, ..
+                } = self.target.duplication[i].kind
+                {
+                    *program = text;
+                }
+            }
+            TargetEditorMessage::SetArgs(i, text) => {
+                if let DuplicationKind::Command { ref mut args, .. } =
+                    self.target.duplication[i].kind
+                {
+                    // Applied once the quotes are balanced
+                    if let Ok(split) = split_args(&text) {
+                        *args = split;
+                    }
+                }
+                self.s_duplication[i].args = text;
+            }
+            TargetEditorMessage::SetRemote(i, field, text) => {
+                if let DuplicationKind::Sftp {
+                    ref mut host,
+                    ref mut user,
+                    ref mut path,
+                    ref mut port,
+                } = self.target.duplication[i].kind
+                {
+                    match field {
+                        RemoteField::Host => *host = text.trim().to_string(),
+                        RemoteField::User => *user = text.trim().to_string(),
+                        RemoteField::Path => *path = text,
+                        RemoteField::Port => {
+                            match text.trim() {
+                                "" => *port = None,
+                                typed => {
+                                    if let Ok(typed) = typed.parse() {
+                                        *port = Some(typed);
+                                    }
+                                }
+                            }
+                            self.s_duplication[i].port = text;
+                        }
+                    }
+                }
+                self.s_duplication[i].test = None;
+            }
+            TargetEditorMessage::DelDisk(i, k) => {
+                self.target.duplication[i].kind.remove_path(k);
+                self.s_duplication[i].s_disks.remove(k);
+                self.s_duplication[i].test = None;
+            }
+            TargetEditorMessage::TestDuplication(i)
+                if matches!(
+                    self.target.duplication[i].kind,
+                    DuplicationKind::Sftp { .. }
+                ) =>
+            {
+                self.s_duplication[i].test = Some(None);
+                return Command::perform(
+                    duplicate::test_remote_async(self.target.duplication[i].kind.clone()),
+                    move |result| {
+                        TargetEditorMessage::DuplicationTested(i, Vec::new(), vec![result])
+                    },
+                );
+            }
+            TargetEditorMessage::TestDuplication(i) => {
+                let disks: Vec<PathBuf> = self.target.duplication[i]
+                    .kind
+                    .paths()
+                    .into_iter()
+                    .cloned()
+                    .collect();
+                self.s_duplication[i].test = Some(None);
+                return Command::perform(
+                    duplicate::test_disks_async(disks.clone()),
+                    move |results| {
+                        TargetEditorMessage::DuplicationTested(i, disks.clone(), results)
+                    },
+                );
+            }
+            TargetEditorMessage::DuplicationTested(i, disks, results) => {
+                // Drop the results if the disks changed meanwhile
+                let current = self.target.duplication.get(i).map_or(false, |duplication| {
+                    duplication.kind.paths().into_iter().eq(disks.iter())
+                });
+                if current && matches!(self.s_duplication[i].test, Some(None)) {
+                    self.s_duplication[i].test = Some(Some(results));
+                }
+            }
+            TargetEditorMessage::SetMaxFiles(text) => {
+                if let Some(max) = parse_max_files(&text) {
+                    self.target.max_files = max;
+                }
+                self.max_files = text;
+            }
+            TargetEditorMessage::SetMaxGib(text) => {
+                if let Some(max) = parse_max_gib(&text) {
+                    self.target.max_total_bytes = max;
+                }
+                self.max_gib = text;
+            }
+            TargetEditorMessage::SetPreCompress(option) => self.target.pre_compress = option.value,
+            TargetEditorMessage::SetTarFormat(format) => self.target.tar_format = format,
+            TargetEditorMessage::SetPreviewBackups(preview) => {
+                self.target.preview_backups = preview
+            }
+            TargetEditorMessage::SetHandleSparse(handle_sparse) => {
+                self.target.handle_sparse = handle_sparse
+            }
+            TargetEditorMessage::ToggleCommand => self.show_command = !self.show_command,
+            TargetEditorMessage::CommandEdited(_) => (),
+            TargetEditorMessage::CopyCommand => {
+                let target = backup::with_repo_excludes(&self.target, &self.repo_excludes);
+                return iced::clipboard::write(backup::tar_commands(&target).join("\n"));
+            }
+            TargetEditorMessage::Save | TargetEditorMessage::Apply => {
                 // Show eventual error message
                 if let Err(error) = verify_target(&self.target) {
-                    self.error = Some(error);
+                    self.error = Some(error.to_string());
                 }
             }
-            TargetEditorMessage::Cancel => (),
+            TargetEditorMessage::KeepEditing => self.leaving = None,
+            TargetEditorMessage::SetMoveTo(repo) => self.move_to = Some(repo),
+            TargetEditorMessage::SetTimerInterval(interval) => self.timer_interval = interval,
+            TargetEditorMessage::TimerInstalled(result) => self.timer_result = Some(Some(result)),
+            TargetEditorMessage::InstallTimer => self.timer_result = Some(None),
+            TargetEditorMessage::Cancel => {
+                if self.dirty {
+                    self.leaving = Some(Leave::Cancel);
+                }
+            }
+            TargetEditorMessage::Discard | TargetEditorMessage::MoveToRepo => (),
         }
         Command::none()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn source(path: &str) -> Source {
+        Source {
+            path: Some(PathBuf::from(path)),
+            ..Default::default()
+        }
+    }
+
+    fn disk(path: &str) -> Duplication {
+        Duplication::new(
+            Duration::from_secs(3600),
+            DuplicationKind::Disk {
+                path: PathBuf::from(path),
+            },
+        )
+    }
+
+    /// Every list of widget state is as long as the list it is the state of
+    fn assert_reconciled(editor: &TargetEditor) {
+        let (n_sources, n_excludes) = (editor.target.sources.len(), editor.target.excludes.len());
+        assert_eq!(editor.s_source.len(), n_sources);
+        assert_eq!(editor.s_delete_source_button.len(), n_sources);
+        assert_eq!(editor.s_exclude.len(), n_excludes);
+        assert_eq!(editor.s_delete_exclude_button.len(), n_excludes);
+        assert_eq!(editor.s_preview_exclude_button.len(), n_excludes);
+        assert_eq!(editor.s_duplication.len(), editor.target.duplication.len());
+        for (duplication, state) in editor.target.duplication.iter().zip(&editor.s_duplication) {
+            assert_eq!(state.s_disks.len(), duplication.kind.paths().len());
+        }
+    }
+
+    #[test]
+    fn view_after_adding_directly() {
+        let mut editor = TargetEditor::with_target(Default::default());
+        editor.target.sources = vec![source("/a"), source("/b")];
+        editor.target.excludes = vec!["*.tmp".to_string(), "cache".to_string()];
+        editor.target.duplication = vec![disk("/mnt/a")];
+        editor.view(false);
+        assert_reconciled(&editor);
+    }
+
+    #[test]
+    fn update_after_removing_directly() {
+        let mut editor = TargetEditor::with_target(Target {
+            sources: vec![source("/a"), source("/b"), source("/c")],
+            excludes: vec!["*.tmp".to_string(), "cache".to_string()],
+            duplication: vec![disk("/mnt/a"), disk("/mnt/b")],
+            ..Default::default()
+        });
+        assert_reconciled(&editor);
+        editor.target.sources.truncate(1);
+        editor.target.excludes.clear();
+        editor.target.duplication.remove(0);
+        let _ = editor.update(TargetEditorMessage::SetName("renamed".to_string()));
+        assert_reconciled(&editor);
+        editor.view(true);
+        assert_reconciled(&editor);
+    }
+
+    #[test]
+    fn disks_added_to_a_duplication_directly() {
+        let mut editor = TargetEditor::with_target(Target {
+            duplication: vec![disk("/mnt/a")],
+            ..Default::default()
+        });
+        editor.target.duplication[0]
+            .kind
+            .push_path(PathBuf::from("/mnt/b"));
+        editor.view(false);
+        assert_reconciled(&editor);
+        assert_eq!(editor.s_duplication[0].s_disks.len(), 2);
+    }
+
+    #[test]
+    fn long_lists() {
+        let mut editor = TargetEditor::with_target(Default::default());
+        editor.target.sources = (0..LONG_LIST * 2)
+            .map(|i| source(&format!("/source{}", i)))
+            .collect();
+        editor.target.excludes = (0..LONG_LIST * 2).map(|i| i.to_string()).collect();
+        let _ = editor.update(TargetEditorMessage::ToggleSources);
+        let _ = editor.update(TargetEditorMessage::ToggleExcludes);
+        editor.view(false);
+        assert_reconciled(&editor);
+    }
+}
@@ -0,0 +1,186 @@
+//! Scheduling backups with the timers of the system (systemd on Linux, launchd on macOS), which
+//! run `bup --backup` while the GUI is closed
+use crate::*;
+
+/// How often a system timer backs up
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TimerInterval {
+    Hourly,
+    Daily,
+    Weekly,
+}
+impl TimerInterval {
+    pub const ALL: [TimerInterval; 3] = [
+        TimerInterval::Hourly,
+        TimerInterval::Daily,
+        TimerInterval::Weekly,
+    ];
+    #[cfg(target_os = "linux")]
+    fn on_calendar(self) -> &'static str {
+        match self {
+            TimerInterval::Hourly => "hourly",
+            TimerInterval::Daily => "daily",
+            TimerInterval::Weekly => "weekly",
+        }
+    }
+    #[cfg(target_os = "macos")]
+    fn seconds(self) -> u64 {
+        match self {
+            TimerInterval::Hourly => 60 * 60,
+            TimerInterval::Daily => 24 * 60 * 60,
+            TimerInterval::Weekly => 7 * 24 * 60 * 60,
+        }
+    }
+}
+impl Default for TimerInterval {
+    fn default() -> Self {
+        TimerInterval::Daily
+    }
+}
+impl std::fmt::Display for TimerInterval {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            TimerInterval::Hourly => write!(f, "Hourly"),
+            TimerInterval::Daily => write!(f, "Daily"),
+            TimerInterval::Weekly => write!(f, "Weekly"),
+        }
+    }
+}
+
+/// The command line of `bup --backup` for `target`, keeping the data dir of this process
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn backup_args(target: &Target) -> anyhow::Result<Vec<String>> {
+    let exe = std::env::current_exe().context("Finding the bup executable")?;
+    let mut args = vec![exe.display().to_string()];
+    if let Some(dir) = std::env::var_os(DATA_DIR_VAR).filter(|dir| !dir.is_empty()) {
+        args.push("--data-dir".to_string());
+        args.push(Path::new(&dir).display().to_string());
+    }
+    args.push("--backup".to_string());
+    args.push(target.id.to_string());
+    Ok(args)
+}
+
+/// Write the unit files of a systemd user timer backing up `target`, and enable it. Returns what
+/// was done, including the command to enable it by hand if enabling failed.
+#[cfg(target_os = "linux")]
+pub fn install(target: &Target, interval: TimerInterval) -> anyhow::Result<String> {
+    let dir = directories_next::BaseDirs::new()
+        .context("No home directory")?
+        .config_dir()
+        .join("systemd/user");
+    std::fs::create_dir_all(&dir).with_context(|| format!("Creating {}", dir.display()))?;
+    let unit = format!("bup-{}", target.id);
+    // systemd splits `ExecStart` like a shell, so arguments with spaces are quoted, and expands
+    // `%` specifiers
+    let exec = backup_args(target)?
+        .iter()
+        .map(|arg| {
+            let arg = arg
+                .replace('\\', "\\\\")
+                .replace('"', "\\\"")
+                .replace('%', "%%");
+            format!("\"{}\"", arg)
+        })
+        .collect::<Vec<_>>()
+        .join(" ");
+    let service = format!(
+        "[Unit]\nDescription=bup backup of {}\n\n[Service]\nType=oneshot\nExecStart={}\n",
+        target.name, exec
+    );
+    let timer = format!(
+        "[Unit]\nDescription=bup backup of {} ({})\n\n[Timer]\nOnCalendar={}\nPersistent=true\n\n\
+         [Install]\nWantedBy=timers.target\n",
+        target.name,
+        interval,
+        interval.on_calendar()
+    );
+    let service_path = dir.join(format!("{}.service", unit));
+    let timer_path = dir.join(format!("{}.timer", unit));
+    std::fs::write(&service_path, service)
+        .with_context(|| format!("Writing {}", service_path.display()))?;
+    std::fs::write(&timer_path, timer)
+        .with_context(|| format!("Writing {}", timer_path.display()))?;
+
+    let enable = format!("systemctl --user enable --now {}.timer", unit);
+    let enabled: anyhow::Result<()> = try {
+        let reload = std::process::Command::new("systemctl")
+            .args(&["--user", "daemon-reload"])
+            .status()?;
+        let status = std::process::Command::new("systemctl")
+            .args(&["--user", "enable", "--now"])
+            .arg(format!("{}.timer", unit))
+            .status()?;
+        if !reload.success() || !status.success() {
+            Err(anyhow::anyhow!("systemctl failed"))?;
+        }
+    };
+    Ok(match enabled {
+        Ok(()) => format!("Installed and enabled {}", timer_path.display()),
+        Err(_) => format!(
+            "Installed {}, but could not enable it. Enable it with: {}",
+            timer_path.display(),
+            enable
+        ),
+    })
+}
+
+/// Write a launchd agent backing up `target`, and load it. Returns what was done, including the
+/// command to load it by hand if loading failed.
+#[cfg(target_os = "macos")]
+pub fn install(target: &Target, interval: TimerInterval) -> anyhow::Result<String> {
+    let dir = directories_next::BaseDirs::new()
+        .context("No home directory")?
+        .home_dir()
+        .join("Library/LaunchAgents");
+    std::fs::create_dir_all(&dir).with_context(|| format!("Creating {}", dir.display()))?;
+    let label = format!("com.bup.backup.{}", target.id);
+    let escape = |s: &str| {
+        s.replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+    };
+    let args: String = backup_args(target)?
+        .iter()
+        .map(|arg| format!("        <string>{}</string>\n", escape(arg)))
+        .collect();
+    let plist = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <!DOCTYPE plist PUBLIC \"-//Apple//DTD PLIST 1.0//EN\" \
+         \"http://www.apple.com/DTDs/PropertyList-1.0.dtd\">\n\
+         <plist version=\"1.0\">\n<dict>\n    <key>Label</key>\n    <string>{}</string>\n    \
+         <key>ProgramArguments</key>\n    <array>\n{}    </array>\n    \
+         <key>StartInterval</key>\n    <integer>{}</integer>\n</dict>\n</plist>\n",
+        label,
+        args,
+        interval.seconds()
+    );
+    let path = dir.join(format!("{}.plist", label));
+    std::fs::write(&path, plist).with_context(|| format!("Writing {}", path.display()))?;
+
+    let load = format!("launchctl load -w {}", path.display());
+    let loaded = std::process::Command::new("launchctl")
+        .args(&["load", "-w"])
+        .arg(&path)
+        .status();
+    Ok(match loaded {
+        Ok(status) if status.success() => format!("Installed and loaded {}", path.display()),
+        _ => format!(
+            "Installed {}, but could not load it. Load it with: {}",
+            path.display(),
+            load
+        ),
+    })
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+pub fn install(_target: &Target, _interval: TimerInterval) -> anyhow::Result<String> {
+    anyhow::bail!("System timers are only supported on Linux (systemd) and macOS (launchd)")
+}
+
+pub async fn install_async(target: Target, interval: TimerInterval) -> Result<String, String> {
+    tokio::task::spawn_blocking(move || install(&target, interval))
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| format!("{:#}", e))
+}
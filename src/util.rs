@@ -29,3 +29,199 @@ pub fn h3<T: Into<String>>(text: T) -> Text {
         .color([0.7, 0.7, 0.7])
         .horizontal_alignment(Horizontal::Center)
 }
+
+/// A small "?" that reveals `text` when hovered, to put next to a control that needs explaining
+pub fn help<'a, M: 'a>(text: &str) -> Element<'a, M> {
+    Tooltip::new(
+        Text::new("?").size(TEXT_SIZE - 4).color([0.6, 0.6, 0.6]),
+        text,
+        tooltip::Position::Top,
+    )
+    .style(style::Tooltip)
+    .size(TEXT_SIZE - 6)
+    .padding(8)
+    .gap(4)
+    .into()
+}
+
+/// `content` with `text` shown when hovered, e.g. to say why a button is disabled
+pub fn with_tooltip<'a, M: 'a>(content: impl Into<Element<'a, M>>, text: &str) -> Element<'a, M> {
+    Tooltip::new(content, text, tooltip::Position::Top)
+        .style(style::Tooltip)
+        .size(TEXT_SIZE - 6)
+        .padding(8)
+        .gap(4)
+        .into()
+}
+
+/// Short human readable duration, like "3h 12m"
+pub fn format_duration(duration: chrono::Duration) -> String {
+    let minutes = duration.num_minutes();
+    let (days, hours, minutes) = (minutes / (24 * 60), minutes / 60 % 24, minutes % 60);
+    if days > 0 {
+        format!("{}d {}h", days, hours)
+    } else if hours > 0 {
+        format!("{}h {}m", hours, minutes)
+    } else if minutes > 0 {
+        format!("{}m", minutes)
+    } else {
+        format!("{}s", duration.num_seconds().max(0))
+    }
+}
+
+/// Human readable size, like "1.5 GiB"
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit + 1 < UNITS.len() {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+/// Total size of the files under `path`, like `du`. Doesn't follow symlinks.
+pub fn dir_size(path: &Path) -> std::io::Result<u64> {
+    let mut size = 0;
+    for entry in std::fs::read_dir(path)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            size += dir_size(&entry.path())?;
+        } else if file_type.is_file() {
+            size += entry.metadata()?.len();
+        }
+    }
+    Ok(size)
+}
+
+/// Open `path` in the file manager of the desktop
+pub fn open_in_file_manager(path: &Path) -> std::io::Result<()> {
+    let program = if cfg!(target_os = "macos") {
+        "open"
+    } else if cfg!(windows) {
+        "explorer"
+    } else {
+        "xdg-open"
+    };
+    std::process::Command::new(program).arg(path).spawn()?;
+    Ok(())
+}
+
+/// Split typed arguments on whitespace, like a shell without expansion: single quotes keep
+/// everything in them, double quotes everything but `\"` and `\\`, and a backslash outside of
+/// quotes keeps the next character. Inverse of joining `quote_arg`ed arguments with spaces.
+pub fn split_args(text: &str) -> Result<Vec<String>, String> {
+    let mut args = Vec::new();
+    let mut arg: Option<String> = None;
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => args.extend(arg.take()),
+            '\'' => {
+                let arg = arg.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => arg.push(c),
+                        None => return Err("Unterminated ' quote".to_string()),
+                    }
+                }
+            }
+            '"' => {
+                let arg = arg.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c) if c == '"' || c == '\\' => arg.push(c),
+                            Some(c) => {
+                                arg.push('\\');
+                                arg.push(c);
+                            }
+                            None => return Err("Unterminated \" quote".to_string()),
+                        },
+                        Some(c) => arg.push(c),
+                        None => return Err("Unterminated \" quote".to_string()),
+                    }
+                }
+            }
+            '\\' => match chars.next() {
+                Some(c) => arg.get_or_insert_with(String::new).push(c),
+                None => return Err("Nothing after \\".to_string()),
+            },
+            c => arg.get_or_insert_with(String::new).push(c),
+        }
+    }
+    args.extend(arg);
+    Ok(args)
+}
+
+/// `arg` quoted for `split_args` if needed. Unlike for a shell, `$` needs no quotes.
+pub fn quote_arg(arg: &str) -> String {
+    let safe = |c: char| c.is_ascii_alphanumeric() || "-_=/.,:@+%${}".contains(c);
+    if !arg.is_empty() && arg.chars().all(safe) {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', "'\\''"))
+    }
+}
+
+/// How well `query` matches `text`: `None` unless the characters of `query` appear in `text` in
+/// order, ignoring case. Higher is better; consecutive characters and word starts count more.
+pub fn fuzzy_score(query: &str, text: &str) -> Option<i64> {
+    let text: Vec<char> = text.chars().flat_map(char::to_lowercase).collect();
+    let mut score = 0;
+    let mut pos = 0;
+    let mut last: Option<usize> = None;
+    for q in query
+        .chars()
+        .flat_map(char::to_lowercase)
+        .filter(|c| !c.is_whitespace())
+    {
+        let found = pos + text[pos..].iter().position(|&c| c == q)?;
+        score += 1;
+        if last.map_or(false, |last| last + 1 == found) {
+            score += 5;
+        }
+        if found == 0 || !text[found - 1].is_alphanumeric() {
+            score += 3;
+        }
+        score -= (found - pos) as i64;
+        last = Some(found);
+        pos = found + 1;
+    }
+    Some(score)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_quoted_args() {
+        let split = split_args(r#"sync  "$BUP_REPO_HOME" 'remote:my backups' a\ b "x\"y""#);
+        assert_eq!(
+            split.unwrap(),
+            ["sync", "$BUP_REPO_HOME", "remote:my backups", "a b", "x\"y"]
+        );
+        assert!(split_args("'open").is_err());
+        assert_eq!(split_args("''").unwrap(), [""]);
+    }
+
+    #[test]
+    fn quote_round_trip() {
+        let args = ["plain", "${BUP_TARGET}", "with space", "it's", ""];
+        let text = args
+            .iter()
+            .map(|arg| quote_arg(arg))
+            .collect::<Vec<_>>()
+            .join(" ");
+        assert_eq!(split_args(&text).unwrap(), args);
+    }
+}